@@ -2,10 +2,18 @@
 
 pub mod parse;
 
-use crate::op::{ImageOperation, ListFiles, PrepareImage, ScaleImage};
+use crate::op::{
+    AutoLevels, BakeOrientation, BlurImage, CanvasImage, CollageImage, ColorName, CropImage,
+    DedupeImages, DuotoneImage, ExtractThumb, ImageOperation, ListFiles, LutImage, MaskImage,
+    PrepareImage, QuantizeImage, RunOptions, RunSummary, ScaleImage, SplitImage, StripMetadata,
+    TestChart, TimestampImage, TrimImage, VignetteImage,
+};
+use crate::units::SortOrder;
 use std::error::Error;
 use std::fmt;
+use std::path::PathBuf;
 use std::str::FromStr;
+use std::sync::Arc;
 use structopt::StructOpt;
 
 /// Command-line tool for photo print preparation and other bulk image operations.
@@ -20,16 +28,36 @@ use structopt::StructOpt;
 #[structopt(verbatim_doc_comment)]
 pub struct Cli {
     /// List of input files or patterns. On Unix systems, patterns MUST be quoted!
+    /// Use `-` to read a newline-separated list of files from stdin.
+    /// Brace groups match any of their alternatives, like `*.{jpg,png}`.
+    /// Braces are expanded by pprep, not by the shell, so quoted patterns work on all systems.
+    /// Files matched by multiple patterns are processed once, see `--keep-duplicates`.
     ///
     /// Examples:
     /// `--input "path/to/*.jpg"`
+    /// `--input "path/to/*.{jpg,png}"`
     /// `--input "path/to/*.jpg" "other/path/to/*.jpg"`
     /// `--input image-0001.jpg image-0002.jpg image-0003.jpg`
+    /// `--input -`
     ///
     #[structopt(verbatim_doc_comment)]
     #[structopt(short, long)]
     pub input: Vec<String>,
 
+    /// Text file with a newline-separated list of input files. Used in addition to `--input`.
+    #[structopt(name = "input-file", long, value_name = "path")]
+    pub input_file: Option<String>,
+
+    /// Process files matched by multiple inputs multiple times.
+    /// By default, duplicates are removed, comparing canonical paths.
+    #[structopt(name = "keep-duplicates", long)]
+    pub keep_duplicates: bool,
+
+    /// Sort input files, for reproducible sequence numbers `{n}`. One of `(name|mtime|size)`.
+    /// Files are still processed in parallel. Default: order of `--input`, and of the file system.
+    #[structopt(long, value_name = "order")]
+    pub sort: Option<SortOrder>,
+
     /// Dummy option to end the `--input` list when no other top-level options are used.
     ///
     /// E.g., the following won't work:
@@ -40,10 +68,57 @@ pub struct Cli {
     #[structopt(short, long)]
     pub cmd: bool,
 
-    /// Number of threads for parallel processing. Optional, default: number of processors.
-    #[structopt(short, long)]
+    /// Number of threads for parallel processing, at least 1. Optional, default: number of processors.
+    #[structopt(short, long, parse(try_from_str = parse::parse_threads))]
     pub threads: Option<usize>,
 
+    /// Continue processing after errors, and report all failed files at the end.
+    /// Alias: `--no-fail-fast`.
+    #[structopt(name = "keep-going", short, long, visible_alias = "no-fail-fast")]
+    pub keep_going: bool,
+
+    /// Stop at the first failed file. This is the default behaviour.
+    #[structopt(name = "fail-fast", long, conflicts_with = "keep-going")]
+    pub fail_fast: bool,
+
+    /// Only print planned output paths and sizes, without reading or writing any images.
+    #[structopt(name = "dry-run", long)]
+    pub dry_run: bool,
+
+    /// Skip output files that already exist, instead of overwriting them.
+    #[structopt(name = "no-clobber", long)]
+    pub no_clobber: bool,
+
+    /// Overwrite output files that already exist. This is the default behaviour.
+    #[structopt(long, conflicts_with = "no-clobber")]
+    pub force: bool,
+
+    /// Writes a JSON summary of the run to the given file: processed, skipped and failed counts,
+    /// elapsed time, number of files and bytes per output format, and warnings.
+    #[structopt(name = "summary-json", long, value_name = "path")]
+    pub summary_json: Option<PathBuf>,
+
+    /// Print input and output path, output size and processing time for every file.
+    /// Repeat (`-vv`) for debug logging of per-file decisions.
+    /// The log level can also be set by the environment variable `PPREP_LOG`, e.g. `PPREP_LOG=warn`.
+    #[structopt(short, long, parse(from_occurrences))]
+    pub verbose: u8,
+
+    /// Don't show a progress bar. It is also hidden if the output is not a terminal.
+    #[structopt(short, long)]
+    pub quiet: bool,
+
+    /// Minimum number of files per parallel task. Larger values reduce the overhead
+    /// for many small images, smaller values balance the load for large images.
+    /// Default: automatic.
+    #[structopt(name = "parallel-chunk-size", long, value_name = "n")]
+    pub parallel_chunk_size: Option<usize>,
+
+    /// List all known print formats (cm format and its inch equivalent) and exit.
+    /// Given formats in cm are replaced by their exact inch equivalent for printing.
+    #[structopt(name = "list-formats", long)]
+    pub list_formats: bool,
+
     /// Debug print parsed command line options.
     #[structopt(short, long)]
     pub debug: bool,
@@ -52,9 +127,9 @@ pub struct Cli {
     #[structopt(short, long)]
     pub wait: bool,
 
-    /// Input selection.
+    /// Input selection. Required, except for `--list-formats`.
     #[structopt(subcommand)]
-    pub op: Operation,
+    pub op: Option<Operation>,
 }
 
 /// Image operations
@@ -67,6 +142,72 @@ pub enum Operation {
     List(ListFiles),
     // /// Prepare images for printing.
     Prep(PrepareImage),
+    // /// Reduce images to a limited color palette.
+    Quantize(QuantizeImage),
+    // /// Print the capture time into a corner of images.
+    Timestamp(TimestampImage),
+    // /// Mask images to circles or rounded rectangles.
+    Mask(MaskImage),
+    // /// Darken image edges with a radial vignette.
+    Vignette(VignetteImage),
+    // /// Place input images freely on a page, as described by a layout file.
+    Collage(CollageImage),
+    // /// Stretch or equalize image contrast, e.g. for faded scans.
+    Levels(AutoLevels),
+    // /// Apply a 3D color lookup table (LUT), e.g. for film looks.
+    Lut(LutImage),
+    // /// Create a full-page calibration chart, without input images.
+    Chart(TestChart),
+    // /// Gaussian blur images.
+    Blur(BlurImage),
+    // /// Find near-duplicate images by perceptual hashing.
+    Dedupe(DedupeImages),
+    // /// Crop images to a fixed rectangle.
+    Crop(CropImage),
+    // /// Map image brightness to a gradient between two colors, e.g. for sepia prints.
+    Duotone(DuotoneImage),
+    // /// Rotate and flip images according to their EXIF orientation, e.g. to normalize a library.
+    Orient(BakeOrientation),
+    // /// Remove uniform borders, e.g. from scans.
+    Trim(TrimImage),
+    // /// Extract embedded EXIF thumbnails, e.g. for fast previews of camera files.
+    Thumb(ExtractThumb),
+    // /// Remove all metadata (EXIF, XMP, GPS location, ...), e.g. before sharing images.
+    Strip(StripMetadata),
+    // /// Split large images into overlapping pages, e.g. to print posters on multiple sheets.
+    Split(SplitImage),
+    // /// Print the nearest named color for each given color, without input images.
+    ColorName(ColorName),
+    // /// Extend images to an aspect ratio or size by adding bars, without scaling, e.g. for slideshows.
+    Canvas(CanvasImage),
+}
+
+impl Cli {
+    /// Returns the execution options given on the command line.
+    pub fn run_options(&self) -> RunOptions {
+        RunOptions {
+            keep_going: self.keep_going,
+            dry_run: self.dry_run,
+            no_clobber: self.no_clobber,
+            verbose: self.verbose > 0,
+            quiet: self.quiet,
+            chunk_size: self.parallel_chunk_size,
+            sort: self.sort.clone(),
+            summary: self
+                .summary_json
+                .as_ref()
+                .map(|_| Arc::new(RunSummary::default())),
+        }
+    }
+
+    /// Configures the global thread pool if `--threads` is given.
+    /// Does nothing if the pool was already configured, see [`set_thread_count`](crate::set_thread_count).
+    pub fn build_thread_pool(&self) -> Result<(), Box<dyn Error>> {
+        if let Some(threads) = self.threads {
+            crate::set_thread_count(threads)?;
+        }
+        Ok(())
+    }
 }
 
 impl Operation {
@@ -76,6 +217,25 @@ impl Operation {
             Operation::Scale(sc) => sc,
             Operation::List(ls) => ls,
             Operation::Prep(pr) => pr,
+            Operation::Quantize(qu) => qu,
+            Operation::Timestamp(ts) => ts,
+            Operation::Mask(ma) => ma,
+            Operation::Vignette(vi) => vi,
+            Operation::Collage(co) => co,
+            Operation::Levels(le) => le,
+            Operation::Lut(lu) => lu,
+            Operation::Chart(ch) => ch,
+            Operation::Blur(bl) => bl,
+            Operation::Dedupe(de) => de,
+            Operation::Crop(cr) => cr,
+            Operation::Duotone(du) => du,
+            Operation::Orient(or) => or,
+            Operation::Trim(tr) => tr,
+            Operation::Thumb(th) => th,
+            Operation::Strip(st) => st,
+            Operation::Split(sp) => sp,
+            Operation::ColorName(cn) => cn,
+            Operation::Canvas(ca) => ca,
         }
     }
 }
@@ -83,24 +243,70 @@ impl Operation {
 impl FromStr for Cli {
     type Err = ParseCliError;
 
-    /// Parses a string into a Cli.
+    /// Parses the content of a command file into a Cli, without the program name.
+    /// See [`split_args`] for the syntax.
     fn from_str(str: &str) -> Result<Self, Self::Err> {
-        let quote_parts: Vec<_> = str.split('"').collect();
-        let mut args: Vec<String> = vec![];
-        for (i, part) in quote_parts.iter().enumerate() {
-            let part = part.trim();
-            if i % 2 == 0 {
-                args.extend(
-                    part.split(' ')
-                        .map(|s| s.trim().to_string())
-                        .filter(|s| !s.is_empty()),
-                );
-            } else {
-                args.push(part.to_string());
+        let args = split_args(str)?;
+        Ok(Cli::from_iter(
+            std::iter::once("pprep".to_string()).chain(args),
+        ))
+    }
+}
+
+/// Splits the content of a command file into arguments.
+///
+/// * Arguments are separated by whitespace, including line breaks.
+/// * Arguments in double quotes can contain whitespace and line breaks.
+/// * `#` at the start of a line starts a comment until the end of the line.
+///   After an argument, `#` followed by whitespace starts a comment.
+///   Other `#`, like in `#ff0000`, are part of arguments.
+/// * A single `\` or `^` at the end of a line is ignored, for line continuations as in shell scripts.
+pub fn split_args(str: &str) -> Result<Vec<String>, ParseCliError> {
+    let chars: Vec<char> = str.chars().collect();
+    // Only whitespace between `from` and the end of the line, or the start of the line and `to`
+    let line_end = |from: usize| {
+        chars[from..]
+            .iter()
+            .take_while(|c| **c != '\n')
+            .all(|c| c.is_whitespace())
+    };
+    let line_start = |to: usize| {
+        chars[..to]
+            .iter()
+            .rev()
+            .take_while(|c| **c != '\n')
+            .all(|c| c.is_whitespace())
+    };
+
+    let mut args = vec![];
+    let mut arg: Option<String> = None;
+    let mut i = 0;
+    while i < chars.len() {
+        match chars[i] {
+            '"' => {
+                let end = chars[i + 1..]
+                    .iter()
+                    .position(|c| *c == '"')
+                    .ok_or_else(|| ParseCliError("Unterminated quote in arguments".to_string()))?;
+                let quoted: String = chars[i + 1..i + 1 + end].iter().collect();
+                arg.get_or_insert_with(String::new).push_str(&quoted);
+                i += end + 1;
+            }
+            '#' if arg.is_none()
+                && (line_start(i) || chars.get(i + 1).is_none_or(|c| c.is_whitespace())) =>
+            {
+                while i + 1 < chars.len() && chars[i + 1] != '\n' {
+                    i += 1;
+                }
             }
+            '\\' | '^' if arg.is_none() && line_end(i + 1) => {}
+            c if c.is_whitespace() => args.extend(arg.take()),
+            c => arg.get_or_insert_with(String::new).push(c),
         }
-        Ok(Cli::from_iter(args.iter()))
+        i += 1;
     }
+    args.extend(arg);
+    Ok(args)
 }
 
 /// Error type for failed parsing command line argument.
@@ -118,3 +324,112 @@ impl fmt::Display for ParseCliError {
         self.0.fmt(f)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::cli::Cli;
+    use crate::util::test_dir;
+    use crate::util::InputFile;
+    use image::DynamicImage;
+    use structopt::StructOpt;
+
+    #[test]
+    fn split_args() {
+        let file = concat!(
+            "#Comment line\n",
+            "--input \"path with/spaces/*.png\" \"other path.jpg\" \\\n",
+            "  --debug # trailing comment\r\n",
+            "prep ^\n",
+            "  --exif \"{F/2},\n{Exp}\" --bg #ff0000 --output out#1.png\n",
+        );
+        let args = super::split_args(file).unwrap();
+        assert_eq!(
+            args,
+            vec![
+                "--input",
+                "path with/spaces/*.png",
+                "other path.jpg",
+                "--debug",
+                "prep",
+                "--exif",
+                "{F/2},\n{Exp}",
+                "--bg",
+                "#ff0000",
+                "--output",
+                "out#1.png",
+            ]
+        );
+        // Quotes within arguments, and empty quoted arguments
+        let args = super::split_args("--exif=\"{F} {ISO}\" \"\"").unwrap();
+        assert_eq!(args, vec!["--exif={F} {ISO}", ""]);
+        assert!(super::split_args("--input \"open").is_err());
+    }
+
+    #[test]
+    fn from_str() {
+        let cli: Cli = "--input \"a b.png\" # input\n--threads 2\nlist\n"
+            .parse()
+            .unwrap();
+        assert_eq!(cli.input, vec!["a b.png"]);
+        assert_eq!(cli.threads, Some(2));
+    }
+
+    #[test]
+    fn threads() {
+        let args = ["pprep", "--threads", "2", "list"];
+        assert_eq!(Cli::from_iter_safe(&args).unwrap().threads, Some(2));
+
+        let args = ["pprep", "--threads", "0", "list"];
+        assert!(Cli::from_iter_safe(&args).is_err());
+        assert!(crate::set_thread_count(0).is_err());
+    }
+
+    #[test]
+    fn fail_fast() {
+        let args = ["pprep", "--fail-fast", "list"];
+        assert!(!Cli::from_iter_safe(&args).unwrap().run_options().keep_going);
+        let args = ["pprep", "--fail-fast", "--no-fail-fast", "list"];
+        assert!(Cli::from_iter_safe(&args).is_err());
+
+        let dir = test_dir("fail-fast");
+        let mut files = vec![InputFile::from_path(dir.join("missing.png"))];
+        for i in 0..2 {
+            let path = dir.join(format!("in-{}.png", i));
+            DynamicImage::new_rgb8(20, 10).save(&path).unwrap();
+            files.push(InputFile::from_path(path));
+        }
+        let output = dir.join("out-*.png");
+        let cli = Cli::from_iter(&[
+            "pprep",
+            "--no-fail-fast",
+            "scale",
+            "--output",
+            output.to_str().unwrap(),
+            "--scale",
+            "50%",
+        ]);
+        let result = cli
+            .op
+            .as_ref()
+            .unwrap()
+            .get_op()
+            .execute(&files, &cli.run_options());
+        let written = (0..2)
+            .filter(|i| dir.join(format!("out-in-{}.png", i)).exists())
+            .count();
+
+        assert!(result.is_err());
+        assert_eq!(written, 2);
+    }
+
+    #[test]
+    fn list_formats() {
+        let cli = Cli::from_iter_safe(&["pprep", "--list-formats"]).unwrap();
+        assert!(cli.list_formats);
+        assert!(cli.op.is_none());
+
+        let cli = Cli::from_iter_safe(&["pprep", "list"]).unwrap();
+        assert!(!cli.list_formats);
+        assert!(cli.op.is_some());
+    }
+}
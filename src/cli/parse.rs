@@ -1,19 +1,137 @@
 //! String parsing for command line options from external crates.
-use crate::ParseEnumError;
+use crate::{OperationParametersError, ParseEnumError};
 use image::imageops::FilterType;
+use std::error::Error;
 
 /// Parse a string to a FilterType.
-/// Accepts `nearest|linear|cubic|gauss|lanczos`
+/// Accepts `nearest|triangle|catmullrom|gaussian|lanczos3`,
+/// and the aliases `box`, `linear|bilinear`, `cubic|bicubic`, `gauss` and `lanczos`.
+/// The `image` crate has no box filter, so `box` maps to `nearest`, which point-samples
+/// without averaging. For 2x2 box averaging, see `--incremental` of `scale` and `prep`.
 pub fn parse_filter_type(str: &str) -> Result<FilterType, ParseEnumError> {
     match str {
-        "nearest" => Ok(FilterType::Nearest),
-        "linear" => Ok(FilterType::Triangle),
-        "cubic" => Ok(FilterType::CatmullRom),
-        "gauss" => Ok(FilterType::CatmullRom),
-        "lanczos" => Ok(FilterType::Lanczos3),
+        "nearest" | "box" => Ok(FilterType::Nearest),
+        "triangle" | "linear" | "bilinear" => Ok(FilterType::Triangle),
+        "catmullrom" | "cubic" | "bicubic" => Ok(FilterType::CatmullRom),
+        "gaussian" | "gauss" => Ok(FilterType::Gaussian),
+        "lanczos3" | "lanczos" => Ok(FilterType::Lanczos3),
         _ => Err(ParseEnumError(format!(
-            "`{}` is not a valid filter type. Must be one of `(nearest|linear|cubic|gauss|lanczos)`",
+            "`{}` is not a valid filter type. Must be one of `(nearest|triangle|catmullrom|gaussian|lanczos3)`, \
+            or an alias `(box|linear|bilinear|cubic|bicubic|gauss|lanczos)`",
             str
         ))),
     }
 }
+
+/// Parse a string to a PNG compression type.
+/// Accepts `fast|default|best`
+pub fn parse_png_compression(str: &str) -> Result<png::Compression, ParseEnumError> {
+    match str {
+        "fast" => Ok(png::Compression::Fast),
+        "default" => Ok(png::Compression::Default),
+        "best" => Ok(png::Compression::Best),
+        _ => Err(ParseEnumError(format!(
+            "`{}` is not a valid PNG compression. Must be one of `(fast|default|best)`",
+            str
+        ))),
+    }
+}
+
+/// Parse a string to a number of threads.
+/// Accepts positive integers.
+pub fn parse_threads(str: &str) -> Result<usize, Box<dyn Error>> {
+    let threads: usize = str.parse()?;
+    if threads == 0 {
+        return Err(Box::new(OperationParametersError(
+            "Number of threads must be at least 1".to_string(),
+        )));
+    }
+    Ok(threads)
+}
+
+/// Parse a string to an image quality in percent.
+/// Accepts integers from 1 to 100.
+pub fn parse_quality(str: &str) -> Result<u8, Box<dyn Error>> {
+    let quality: u8 = str.parse()?;
+    if !(1..=100).contains(&quality) {
+        return Err(Box::new(OperationParametersError(format!(
+            "Quality must be in the range 1 to 100, got `{}`",
+            str
+        ))));
+    }
+    Ok(quality)
+}
+
+/// Parse a string to a number of bytes.
+/// Accepts plain numbers and the suffixes `k|kB|M|MB` (powers of 1000) and `KiB|MiB` (powers of 1024).
+pub fn parse_bytes(str: &str) -> Result<u64, Box<dyn Error>> {
+    let split = str
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(str.len());
+    let (number, suffix) = str.split_at(split);
+    let factor = match suffix.trim() {
+        "" | "B" => 1.0,
+        "k" | "kB" => 1000.0,
+        "M" | "MB" => 1_000_000.0,
+        "KiB" => 1024.0,
+        "MiB" => 1024.0 * 1024.0,
+        _ => {
+            return Err(Box::new(OperationParametersError(format!(
+                "`{}` is not a valid size in bytes. Unit must be one of `(B|k|kB|M|MB|KiB|MiB)`",
+                str
+            ))))
+        }
+    };
+    let bytes = (number.parse::<f64>()? * factor).round() as u64;
+    if bytes == 0 {
+        return Err(Box::new(OperationParametersError(
+            "Size in bytes must be at least 1".to_string(),
+        )));
+    }
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::cli::parse::{parse_filter_type, parse_quality};
+    use image::imageops::FilterType;
+
+    #[test]
+    fn filter_types() {
+        let expected = [
+            ("nearest", FilterType::Nearest),
+            ("box", FilterType::Nearest),
+            ("triangle", FilterType::Triangle),
+            ("linear", FilterType::Triangle),
+            ("bilinear", FilterType::Triangle),
+            ("catmullrom", FilterType::CatmullRom),
+            ("cubic", FilterType::CatmullRom),
+            ("bicubic", FilterType::CatmullRom),
+            ("gaussian", FilterType::Gaussian),
+            ("gauss", FilterType::Gaussian),
+            ("lanczos3", FilterType::Lanczos3),
+            ("lanczos", FilterType::Lanczos3),
+        ];
+        for (str, filter) in expected.iter() {
+            // `FilterType` does not implement `PartialEq`
+            let parsed = parse_filter_type(str).unwrap();
+            assert_eq!(
+                format!("{:?}", parsed),
+                format!("{:?}", filter),
+                "`{}`",
+                str
+            );
+        }
+        assert!(parse_filter_type("sinc").is_err());
+    }
+
+    #[test]
+    fn quality() {
+        assert_eq!(parse_quality("1").unwrap(), 1);
+        assert_eq!(parse_quality("100").unwrap(), 100);
+        assert!(parse_quality("0").is_err());
+        assert!(parse_quality("101").is_err());
+        assert!(parse_quality("150").is_err());
+        assert!(parse_quality("-5").is_err());
+    }
+}
@@ -15,6 +15,7 @@ use rusttype::Font;
 use std::error::Error;
 use std::fmt;
 use std::process::exit;
+use std::sync::Mutex;
 
 #[derive(RustEmbed)]
 #[folder = "assets/"]
@@ -33,6 +34,32 @@ impl Default for Fonts {
     }
 }
 
+lazy_static! {
+    static ref THREAD_COUNT: Mutex<Option<usize>> = Mutex::new(None);
+}
+
+/// Sets the number of threads of the global thread pool used for parallel processing.
+///
+/// The global pool can be configured only once per process, and must be configured
+/// before any parallel processing. Returns `Ok(true)` if the pool was configured,
+/// and `Ok(false)` without any effect if it was already configured by an earlier call.
+pub fn set_thread_count(threads: usize) -> Result<bool, Box<dyn Error>> {
+    if threads == 0 {
+        return Err(Box::new(OperationParametersError(
+            "Number of threads must be at least 1".to_string(),
+        )));
+    }
+    let mut count = THREAD_COUNT.lock().unwrap();
+    if count.is_some() {
+        return Ok(false);
+    }
+    rayon::ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .build_global()?;
+    *count = Some(threads);
+    Ok(true)
+}
+
 /// Trait to print a message and exit the program.
 /// Implemented for `Result` and `Option`.
 ///
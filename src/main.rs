@@ -1,37 +1,105 @@
 use print_prep::cli::Cli;
-use print_prep::util::PathUtil;
+use print_prep::units::format;
+use print_prep::util::{InputFile, LogUtil, PathUtil};
 use print_prep::ErrorAbort;
 use rayon::prelude::*;
 use std::error::Error;
+use std::io::BufReader;
 use std::process::exit;
 use std::time::Instant;
-use std::{env, fs};
-use structopt::StructOpt;
+use std::{env, fs, io};
+use structopt::{clap, StructOpt};
 
 fn main() {
     let start = Instant::now();
 
-    let cli = parse_args().unwrap();
+    // Logging is not initialized before parsing, so the error is printed directly
+    let (cli, args_file) = match parse_args() {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("Error parsing arguments: {}", e);
+            exit(1);
+        }
+    };
+    LogUtil::init(cli.verbose);
+    if let Some(content) = args_file {
+        log::debug!("Arguments from file: {:?}", content);
+    }
+
+    if cli.list_formats {
+        for format in format::list_formats() {
+            println!("{}", format);
+        }
+        return;
+    }
+    let op = match &cli.op {
+        Some(op) => op.get_op(),
+        None => clap::Error::with_description(
+            "An operation is required, see `pprep --help`.",
+            clap::ErrorKind::MissingSubcommand,
+        )
+        .exit(),
+    };
 
     if cli.debug {
         eprintln!("{:#?}", cli);
     }
 
-    if let Some(threads) = cli.threads {
-        rayon::ThreadPoolBuilder::new()
-            .num_threads(threads)
-            .build_global()
-            .exit("Error building thread pool. Pool already built.");
+    if let Err(e) = cli.build_thread_pool() {
+        exit_on_error(&format!("Error building thread pool: {}", e));
     }
 
-    let files: Vec<_> = cli
+    let mut files: Vec<_> = cli
         .input
         .par_iter()
-        .flat_map(|f| PathUtil::list_files(f).unwrap())
+        .filter(|f| *f != "-")
+        .flat_map(|f| {
+            let root = PathUtil::pattern_root(f);
+            let mut paths: Vec<_> = PathUtil::expand_braces(f)
+                .iter()
+                .flat_map(|pattern| PathUtil::list_files(pattern).unwrap())
+                .collect();
+            // Alternatives of brace groups may overlap
+            paths.sort();
+            paths.dedup();
+            paths
+                .into_iter()
+                .map(move |path| InputFile::new(path, root.clone()))
+                .collect::<Vec<_>>()
+        })
         .collect();
 
-    let op = cli.op.get_op();
-    match op.execute(&files[..]) {
+    if cli.input.iter().any(|f| f == "-") {
+        let stdin = io::stdin();
+        let list = PathUtil::read_list(stdin.lock()).exit("Error reading input files from stdin.");
+        files.extend(list.into_iter().map(InputFile::from_path));
+    }
+
+    if let Some(input_file) = &cli.input_file {
+        let file = fs::File::open(input_file)
+            .exit(&format!("Unable to open input list file {:?}.", input_file));
+        let list = PathUtil::read_list(BufReader::new(file))
+            .exit(&format!("Error reading input list file {:?}.", input_file));
+        files.extend(list.into_iter().map(InputFile::from_path));
+    }
+
+    if !cli.keep_duplicates {
+        PathUtil::dedup_files(&mut files);
+    }
+
+    if let Some(order) = &cli.sort {
+        PathUtil::sort_files(&mut files, order);
+    }
+
+    let options = cli.run_options();
+    let result = op.execute(&files[..], &options);
+
+    if let (Some(path), Some(summary)) = (&cli.summary_json, &options.summary) {
+        fs::write(path, summary.to_json(start.elapsed()))
+            .exit(&format!("Unable to write run summary to {:?}.", path));
+    }
+
+    match result {
         Ok(()) => {}
         Err(e) => {
             exit_on_error(&format!("Error processing images: {:?}", e));
@@ -39,14 +107,16 @@ fn main() {
         }
     };
 
-    eprintln!("Success! Total time: {:?}", start.elapsed());
+    log::info!("Success! Total time: {:?}", start.elapsed());
 
     if cli.wait {
         dont_disappear::any_key_to_continue::default();
     }
 }
 
-fn parse_args() -> Result<Cli, Box<dyn Error>> {
+/// Parses the command line, or a command file given as the only argument.
+/// Returns the content of the command file, if any, to be logged.
+fn parse_args() -> Result<(Cli, Option<String>), Box<dyn Error>> {
     let test = false;
 
     let args: Vec<String> = if test {
@@ -59,19 +129,16 @@ fn parse_args() -> Result<Cli, Box<dyn Error>> {
         env::args().collect()
     };
 
-    let args: Cli = if args.len() == 2 && !args[1].starts_with('-') {
-        let mut content = fs::read_to_string(&args[1])?;
-        println!("{:?}", content);
-        content = "ppa ".to_string() + &content.replace("\r\n", " ").replace("\n", " ");
-        content.parse()?
+    if args.len() == 2 && !args[1].starts_with('-') {
+        let content = fs::read_to_string(&args[1])?;
+        Ok((content.parse()?, Some(content)))
     } else {
-        Cli::from_args()
-    };
-    Ok(args)
+        Ok((Cli::from_args(), None))
+    }
 }
 
 fn exit_on_error(message: &str) {
-    eprintln!("Terminated with ERROR:");
-    eprintln!("{}", message);
+    log::error!("Terminated with ERROR:");
+    log::error!("{}", message);
     exit(1);
 }
@@ -0,0 +1,127 @@
+//! Blur images.
+
+use crate::op::{ImageIoOperation, ImageOperation, OutputOptions, RunOptions};
+use crate::util::InputFile;
+use crate::OperationParametersError;
+use image::{DynamicImage, ImageBuffer, Rgba};
+use std::error::Error;
+use std::path::PathBuf;
+use structopt::StructOpt;
+
+/// Sigma above which blurring is very slow for large images.
+const LARGE_SIGMA: f32 = 50.0;
+
+/// Gaussian blur images.
+#[derive(StructOpt, Debug)]
+pub struct BlurImage {
+    #[structopt(flatten)]
+    pub output: OutputOptions,
+
+    /// Blur radius (standard deviation of the Gaussian) in px. Must be larger than 0.
+    #[structopt(long)]
+    pub sigma: f32,
+}
+
+impl BlurImage {
+    fn check(&self) -> Result<(), Box<dyn Error>> {
+        if self.sigma.is_nan() || self.sigma <= 0.0 {
+            return Err(Box::new(OperationParametersError(format!(
+                "`--sigma` must be larger than 0, got {}!",
+                self.sigma
+            ))));
+        }
+        Ok(())
+    }
+
+    /// Blurs an image with alpha channel. Colors are blurred with premultiplied alpha,
+    /// to avoid dark fringes from transparent pixels. The alpha channel is preserved.
+    fn blur_alpha(image: &DynamicImage, sigma: f32) -> DynamicImage {
+        let rgba = image.to_rgba();
+        let premultiplied = ImageBuffer::from_fn(rgba.width(), rgba.height(), |x, y| {
+            let p = rgba.get_pixel(x, y).0;
+            let a = p[3] as f32 / 255.0;
+            Rgba([p[0] as f32 * a, p[1] as f32 * a, p[2] as f32 * a, a])
+        });
+        let blurred = image::imageops::blur(&premultiplied, sigma);
+        let result = ImageBuffer::from_fn(rgba.width(), rgba.height(), |x, y| {
+            let p = blurred.get_pixel(x, y).0;
+            let alpha = rgba.get_pixel(x, y).0[3];
+            if p[3] <= 0.0 {
+                return Rgba([0, 0, 0, alpha]);
+            }
+            let c = |i: usize| (p[i] / p[3]).round().clamp(0.0, 255.0) as u8;
+            Rgba([c(0), c(1), c(2), alpha])
+        });
+        DynamicImage::ImageRgba8(result)
+    }
+}
+
+impl ImageOperation for BlurImage {
+    fn execute(&self, files: &[InputFile], options: &RunOptions) -> Result<(), Box<dyn Error>> {
+        self.check()?;
+        if self.sigma > LARGE_SIGMA {
+            log::warn!(
+                "Large blur sigma ({}), this may be very slow for large images.",
+                self.sigma
+            );
+        }
+        ImageIoOperation::execute(self, files, options)
+    }
+}
+
+impl ImageIoOperation for BlurImage {
+    fn output(&self) -> &OutputOptions {
+        &self.output
+    }
+
+    fn output_size(&self, width: u32, height: u32) -> Result<(u32, u32), Box<dyn Error>> {
+        self.check()?;
+        Ok((width, height))
+    }
+
+    fn process_image(
+        &self,
+        image: &DynamicImage,
+        _file: &PathBuf,
+    ) -> Result<DynamicImage, Box<dyn Error>> {
+        self.check()?;
+        if image.color().has_alpha() {
+            Ok(Self::blur_alpha(image, self.sigma))
+        } else {
+            Ok(image.blur(self.sigma))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::op::{BlurImage, ImageIoOperation};
+    use image::{DynamicImage, GenericImage, GenericImageView, Rgba};
+    use std::path::PathBuf;
+    use structopt::StructOpt;
+
+    #[test]
+    fn blur_alpha() {
+        let mut image = DynamicImage::new_rgba8(40, 40);
+        for (x, y, _) in DynamicImage::new_rgba8(20, 40).pixels() {
+            let v = if y % 2 == 0 { 255 } else { 0 };
+            image.put_pixel(x, y, Rgba([v, 255, 255, 255]));
+        }
+        let op = BlurImage::from_iter(&["blur", "--output", "out.png", "--sigma", "2"]);
+        let result = op.process_image(&image, &PathBuf::from("in.png")).unwrap();
+
+        assert_eq!(result.color(), image.color());
+        // Transparent areas stay transparent, opaque areas are blurred without dark fringes
+        assert_eq!(result.get_pixel(30, 20).0[3], 0);
+        let edge = result.get_pixel(19, 20).0;
+        assert_eq!(edge[3], 255);
+        assert_eq!(&edge[1..3], &[255, 255]);
+        assert!(edge[0] > 100 && edge[0] < 155);
+    }
+
+    #[test]
+    fn check_sigma() {
+        let op = BlurImage::from_iter(&["blur", "--output", "out.png", "--sigma", "0"]);
+        assert!(op.output_size(10, 10).is_err());
+    }
+}
@@ -0,0 +1,184 @@
+//! Extend images to an aspect ratio or size.
+
+use crate::op::{ImageIoOperation, ImageOperation, OutputOptions, RunOptions};
+use crate::units::color::Color;
+use crate::units::{AspectRatio, FixSize, ScaleMode};
+use crate::util::{CastUtil, ImageUtil, InputFile};
+use crate::OperationParametersError;
+use image::{DynamicImage, GenericImageView};
+use std::error::Error;
+use std::path::PathBuf;
+use structopt::StructOpt;
+
+/// Extend images to an aspect ratio or size by adding bars, without scaling, e.g. for slideshows.
+#[derive(StructOpt, Debug)]
+pub struct CanvasImage {
+    #[structopt(flatten)]
+    pub output: OutputOptions,
+
+    /// Aspect ratio of the canvas, as `width:height`. Examples: `16:9`, `1:1`.
+    #[structopt(
+        short,
+        long,
+        value_name = "w:h",
+        required_unless = "size",
+        conflicts_with = "size"
+    )]
+    pub aspect: Option<AspectRatio>,
+
+    /// Size of the canvas `width/height`. Must not be smaller than the images.
+    /// Examples: `1920px/1080px`, `15cm/10cm`.
+    #[structopt(short, long, value_name = "w/h")]
+    pub size: Option<FixSize>,
+
+    /// Image resolution for sizes not in px. Default `300`.
+    #[structopt(short, long)]
+    pub dpi: Option<f64>,
+
+    /// Fill of the added space. One of `(fill|blur)`.
+    /// `fill` uses `--bg`, `blur` a blurred, enlarged copy of the image. Default: `fill`.
+    #[structopt(short, long)]
+    pub mode: Option<ScaleMode>,
+
+    /// Background color for `--mode fill`. Default `white`.
+    #[structopt(short, long, value_name = "color")]
+    pub bg: Option<Color>,
+}
+
+impl CanvasImage {
+    fn check(&self) -> Result<(), Box<dyn Error>> {
+        if self.size.as_ref().is_some_and(|s| s.is_relative()) {
+            return Err(Box::new(OperationParametersError(
+                "`--size` does not support relative lengths in `%`!".to_string(),
+            )));
+        }
+        match self.mode.as_ref().unwrap_or(&ScaleMode::Fill) {
+            ScaleMode::Fill | ScaleMode::Blur(_) => Ok(()),
+            mode => Err(Box::new(OperationParametersError(format!(
+                "`--mode {}` is not supported for canvas, use `fill` or `blur`!",
+                mode
+            )))),
+        }
+    }
+
+    /// Canvas size for an image size.
+    fn canvas_size(&self, width: u32, height: u32) -> Result<(u32, u32), Box<dyn Error>> {
+        if let Some(aspect) = &self.aspect {
+            return Ok(aspect.enclosing_size(width, height));
+        }
+        let size = self.size.as_ref().unwrap().to_px(self.dpi.unwrap_or(300.0));
+        let canvas = (
+            CastUtil::u32_from_f64(size.width().value().round(), "canvas width"),
+            CastUtil::u32_from_f64(size.height().value().round(), "canvas height"),
+        );
+        if canvas.0 < width || canvas.1 < height {
+            return Err(Box::new(OperationParametersError(format!(
+                "Image of {}x{}px does not fit onto a canvas of {}x{}px",
+                width, height, canvas.0, canvas.1
+            ))));
+        }
+        Ok(canvas)
+    }
+}
+
+impl ImageOperation for CanvasImage {
+    fn execute(&self, files: &[InputFile], options: &RunOptions) -> Result<(), Box<dyn Error>> {
+        self.check()?;
+        ImageIoOperation::execute(self, files, options)
+    }
+}
+
+impl ImageIoOperation for CanvasImage {
+    fn output(&self) -> &OutputOptions {
+        &self.output
+    }
+
+    fn output_size(&self, width: u32, height: u32) -> Result<(u32, u32), Box<dyn Error>> {
+        self.check()?;
+        self.canvas_size(width, height)
+    }
+
+    fn process_image(
+        &self,
+        image: &DynamicImage,
+        _file: &PathBuf,
+    ) -> Result<DynamicImage, Box<dyn Error>> {
+        self.check()?;
+        let (width, height) = self.canvas_size(image.width(), image.height())?;
+        if (width, height) == image.dimensions() {
+            return Ok(image.clone());
+        }
+
+        let mut result = match self.mode.as_ref().unwrap_or(&ScaleMode::Fill) {
+            ScaleMode::Blur(sigma) => ImageUtil::blurred_cover(image, width, height, *sigma),
+            _ => {
+                let mut result = if image.color().has_alpha() {
+                    DynamicImage::new_rgba8(width, height)
+                } else {
+                    DynamicImage::new_rgb8(width, height)
+                };
+                let bg = self.bg.clone().unwrap_or(Color::new(255, 255, 255, 255));
+                ImageUtil::fill_image(&mut result, bg.channels());
+                result
+            }
+        };
+        let x = (width - image.width()) / 2;
+        let y = (height - image.height()) / 2;
+        ImageUtil::composite_over(&mut result, image, x as i64, y as i64, 1.0);
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::op::{CanvasImage, ImageIoOperation};
+    use crate::util::ImageUtil;
+    use image::{DynamicImage, GenericImageView};
+    use std::path::PathBuf;
+    use structopt::StructOpt;
+
+    #[test]
+    fn canvas_aspect() {
+        let op = CanvasImage::from_iter(&[
+            "canvas", "--output", "out.png", "--aspect", "16:9", "--bg", "black",
+        ]);
+        let mut image = DynamicImage::new_rgb8(120, 90);
+        ImageUtil::fill_image(&mut image, &[200, 100, 50, 255]);
+        let result = op.process_image(&image, &PathBuf::from("in.png")).unwrap();
+
+        // Bars left and right, the image is not scaled
+        assert_eq!(result.dimensions(), (160, 90));
+        assert_eq!(result.get_pixel(19, 45).0, [0, 0, 0, 255]);
+        assert_eq!(result.get_pixel(20, 0).0, [200, 100, 50, 255]);
+        assert_eq!(result.get_pixel(139, 89).0, [200, 100, 50, 255]);
+        assert_eq!(result.get_pixel(140, 45).0, [0, 0, 0, 255]);
+
+        let op = CanvasImage::from_iter(&[
+            "canvas", "--output", "out.png", "--aspect", "1:1", "--mode", "blur",
+        ]);
+        let result = op.process_image(&image, &PathBuf::from("in.png")).unwrap();
+        assert_eq!(result.dimensions(), (120, 120));
+        assert_eq!(result.get_pixel(60, 60).0, [200, 100, 50, 255]);
+        // The bars are filled with a blurred copy of the uniform image
+        let bar = result.get_pixel(60, 5).0;
+        assert!(bar[0] >= 195 && bar[1] >= 95 && bar[2] >= 45);
+    }
+
+    #[test]
+    fn canvas_size() {
+        let op =
+            CanvasImage::from_iter(&["canvas", "--output", "out.png", "--size", "200px/100px"]);
+        assert_eq!(op.output_size(120, 90).unwrap(), (200, 100));
+        assert!(op.output_size(120, 120).is_err());
+
+        let op = CanvasImage::from_iter(&[
+            "canvas", "--output", "out.png", "--aspect", "1:1", "--mode", "crop",
+        ]);
+        assert!(op.output_size(120, 90).is_err());
+        assert!(CanvasImage::from_iter_safe(&["canvas", "--output", "out.png"]).is_err());
+        assert!(CanvasImage::from_iter_safe(&[
+            "canvas", "--output", "out.png", "--aspect", "16:0"
+        ])
+        .is_err());
+    }
+}
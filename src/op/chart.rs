@@ -0,0 +1,133 @@
+//! Create calibration charts.
+
+use crate::op::{ImageOperation, OutputOptions, RunOptions};
+use crate::units::{format, FixSize, LengthUnit};
+use crate::util::{ChartUtil, ImageUtil, InputFile};
+use crate::OperationParametersError;
+use image::DynamicImage;
+use std::error::Error;
+use std::path::PathBuf;
+use structopt::StructOpt;
+
+/// Create a full-page calibration chart, without input images.
+#[derive(StructOpt, Debug)]
+pub struct TestChart {
+    #[structopt(flatten)]
+    pub output: OutputOptions,
+
+    /// Print format `width/height`.
+    /// Formats in cm are converted to exact print formats in inches.
+    /// Examples: `15cm/10cm`, `6in/4in`, `6000px/4000px`.
+    ///
+    /// To use an exact size given in cm, use floating point numbers, e.g. `15.0cm/10.0cm`.
+    #[structopt(long, value_name = "w/h")]
+    pub format: FixSize,
+
+    /// Image resolution. Default `300`.
+    #[structopt(short, long)]
+    pub dpi: Option<f64>,
+}
+
+impl TestChart {
+    /// Creates the chart image.
+    fn create(&self) -> Result<DynamicImage, Box<dyn Error>> {
+        if self.format.is_relative() {
+            return Err(Box::new(OperationParametersError(
+                "`--format` does not support relative lengths in `%`!".to_string(),
+            )));
+        }
+        let dpi = self.dpi.unwrap_or(300.0);
+        let format = format::to_print_format(&self.format)?.to(&LengthUnit::Px, dpi);
+        Ok(ChartUtil::test_chart(
+            format.width().value().round() as u32,
+            format.height().value().round() as u32,
+        ))
+    }
+
+    /// The output path. Placeholders are not replaced, as there is no input file.
+    fn out_path(&self) -> PathBuf {
+        let path = PathBuf::from(&self.output.output);
+        let path = match &self.output.output_dir {
+            Some(dir) => dir.join(path),
+            None => path,
+        };
+        match &self.output.output_format {
+            Some(format) => format.apply(&path),
+            None => path,
+        }
+    }
+}
+
+impl ImageOperation for TestChart {
+    fn execute(&self, _files: &[InputFile], options: &RunOptions) -> Result<(), Box<dyn Error>> {
+        let out_path = self.out_path();
+        if options.dry_run {
+            println!("Test chart -> {:?} ({})", out_path, self.format);
+            return Ok(());
+        }
+        if options.no_clobber && out_path.exists() {
+            let warning = format!("Skipping test chart, output file {:?} exists.", out_path);
+            log::info!("{}", warning);
+            if let Some(summary) = &options.summary {
+                summary.add_skipped(warning);
+            }
+            return Ok(());
+        }
+        let chart = self.create()?;
+        let mut save_options = self.output.save_options(!options.no_clobber);
+        save_options.icc = self.output.icc_data()?;
+        ImageUtil::save_image(chart, &out_path, &save_options)?;
+        if let Some(summary) = &options.summary {
+            summary.add_output(&out_path);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::op::{ImageOperation, RunOptions, TestChart};
+    use crate::util::test_dir;
+    use image::GenericImageView;
+    use structopt::StructOpt;
+
+    #[test]
+    fn chart_size() {
+        let op = TestChart::from_iter(&[
+            "chart", "--output", "out.png", "--format", "6in/4in", "--dpi", "100",
+        ]);
+        let chart = op.create().unwrap();
+        assert_eq!((chart.width(), chart.height()), (600, 400));
+    }
+
+    #[test]
+    fn chart_output_options() {
+        let dir = test_dir("chart");
+        let op = TestChart::from_iter(&[
+            "chart",
+            "--output",
+            dir.join("chart").to_str().unwrap(),
+            "--output-format",
+            "jpg",
+            "--format",
+            "6in/4in",
+            "--dpi",
+            "50",
+            "--icc",
+            "srgb",
+        ]);
+        op.execute(&[], &RunOptions::default()).unwrap();
+        let path = dir.join("chart.jpg");
+        let data = std::fs::read(&path).unwrap();
+        assert!(data.windows(12).any(|w| w == b"ICC_PROFILE\0"));
+
+        // Left untouched with `--no-clobber`
+        std::fs::write(&path, b"existing").unwrap();
+        let no_clobber = RunOptions {
+            no_clobber: true,
+            ..RunOptions::default()
+        };
+        op.execute(&[], &no_clobber).unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"existing");
+    }
+}
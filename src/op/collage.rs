@@ -0,0 +1,331 @@
+//! Place multiple images freely on a page.
+
+use crate::cli::parse;
+use crate::op::{ImageOperation, RunOptions};
+use crate::units::color::Color;
+use crate::units::{FixSize, ScaleMode, SortOrder};
+use crate::util::{ImageFormatError, ImageUtil, InputFile, PathUtil, SaveOptions};
+use crate::{OperationParametersError, ParseStructError};
+use image::imageops::FilterType;
+use image::{DynamicImage, GenericImageView};
+use rayon::prelude::*;
+use std::error::Error;
+use std::path::PathBuf;
+use std::str::FromStr;
+use structopt::StructOpt;
+
+/// Place input images freely on a page, as described by a layout file.
+#[derive(StructOpt, Debug)]
+pub struct CollageImage {
+    /// Output path of the collage image. Used to determine output image type.
+    #[structopt(short, long, value_name = "path")]
+    pub output: PathBuf,
+
+    /// Layout file. Each line places an input image:
+    /// `<input-index> <x>/<y> <width>/<height>`, with 1-based index in the order of inputs.
+    /// Inputs are used as given, or sorted by `--sort`.
+    /// Lines starting with `#` are ignored.
+    /// Example: `1 1cm/1cm 8cm/6cm`
+    #[structopt(long, value_name = "path")]
+    pub layout: PathBuf,
+
+    /// Page format `width/height`.
+    /// Examples: `15cm/10cm`, `6in/4in`, `6000px/4000px`.
+    #[structopt(long, value_name = "w/h")]
+    pub format: FixSize,
+
+    /// Image resolution. Default `300`.
+    #[structopt(short, long)]
+    pub dpi: Option<f64>,
+
+    /// Scaling mode for placed images. One of `(keep|stretch|crop|fill|blur|extend)`.
+    /// Default: `crop`.
+    #[structopt(short, long)]
+    pub mode: Option<ScaleMode>,
+
+    /// Filter type for image scaling.
+    /// One of `(nearest|triangle|catmullrom|gaussian|lanczos3)`,
+    /// or an alias: `box` for `nearest` (point sampling, without averaging),
+    /// `linear` and `bilinear` for `triangle`, `cubic` and `bicubic` for `catmullrom`,
+    /// `gauss` for `gaussian`, `lanczos` for `lanczos3`.
+    /// Default: `cubic`.
+    #[structopt(short, long, parse(try_from_str = parse::parse_filter_type))]
+    pub filter: Option<FilterType>,
+
+    /// Background color. Default `white`.
+    #[structopt(short, long, value_name = "color")]
+    pub bg: Option<Color>,
+
+    /// Image quality for JPEG and WebP output in percent, from 1 to 100. Optional, default `95`.
+    #[structopt(short, long, parse(try_from_str = parse::parse_quality))]
+    pub quality: Option<u8>,
+}
+
+/// Placement of an input image in a collage layout.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Placement {
+    /// 1-based index of the input image.
+    pub index: usize,
+    /// Position of the upper left corner.
+    pub position: FixSize,
+    /// Size on the page.
+    pub size: FixSize,
+}
+
+impl FromStr for Placement {
+    type Err = Box<dyn Error>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<_> = s.split_whitespace().collect();
+        if parts.len() != 3 {
+            return Err(Box::new(ParseStructError(format!(
+                "Unexpected layout format in `{}`, expects `<input-index> <x>/<y> <width>/<height>`",
+                s
+            ))));
+        }
+        Ok(Placement {
+            index: parts[0].parse()?,
+            position: parts[1].parse()?,
+            size: parts[2].parse()?,
+        })
+    }
+}
+
+impl CollageImage {
+    /// Parses a layout description, one placement per line.
+    fn parse_layout(layout: &str) -> Result<Vec<Placement>, Box<dyn Error>> {
+        layout
+            .lines()
+            .map(|line| line.trim())
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(|line| line.parse())
+            .collect()
+    }
+
+    /// Inputs in the order referenced by layout indices: sorted by `order` if given,
+    /// or in the order of inputs otherwise.
+    fn ordered(files: &[InputFile], order: Option<&SortOrder>) -> Vec<InputFile> {
+        let mut files = files.to_vec();
+        if let Some(order) = order {
+            PathUtil::sort_files(&mut files, order);
+        }
+        files
+    }
+
+    /// Creates the collage from the input files, in the given order.
+    fn create(
+        &self,
+        files: &[InputFile],
+        layout: &[Placement],
+    ) -> Result<DynamicImage, Box<dyn Error>> {
+        if let Some(p) = layout
+            .iter()
+            .find(|p| p.index == 0 || p.index > files.len())
+        {
+            return Err(Box::new(OperationParametersError(format!(
+                "Layout references input {}, but there are only {} input files!",
+                p.index,
+                files.len()
+            ))));
+        }
+
+        let dpi = self.dpi.unwrap_or(300.0);
+        let mode = self.mode.as_ref().unwrap_or(&ScaleMode::Crop);
+        let filter = self.filter.as_ref().unwrap_or(&FilterType::CatmullRom);
+        let color = self.bg.clone().unwrap_or(Color::new(255, 255, 255, 255));
+
+        let page = self.format.to_px(dpi);
+        let (width, height) = (page.width().value(), page.height().value());
+        let mut result = DynamicImage::new_rgb8(width as u32, height as u32);
+        ImageUtil::fill_image(&mut result, color.channels());
+
+        // Images are loaded in parallel, but placed in the order of the layout
+        let scaled = layout
+            .par_iter()
+            .map(|placement| {
+                let size = placement.size.resolve(width, height).to_px(dpi);
+                let file = &files[placement.index - 1].path;
+                let image = image::open(file)
+                    .map_err(|e| ImageFormatError(format!("Unable to open {:?}: {}", file, e)))?;
+                ImageUtil::scale_image(
+                    &image,
+                    size.width().value() as u32,
+                    size.height().value() as u32,
+                    mode,
+                    filter,
+                    &color,
+                    None,
+                )
+                .map_err(|e| ImageFormatError(format!("Unable to scale {:?}: {}", file, e)))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        for (placement, scaled) in layout.iter().zip(scaled) {
+            let position = placement.position.resolve(width, height).to_px(dpi);
+            let size = placement.size.resolve(width, height).to_px(dpi);
+            // Center the scaled image in its box, for `--mode keep`
+            let x = position.width().value() as i64
+                + (size.width().value() as i64 - scaled.width() as i64) / 2;
+            let y = position.height().value() as i64
+                + (size.height().value() as i64 - scaled.height() as i64) / 2;
+            ImageUtil::composite_over(&mut result, &scaled, x, y, 1.0);
+        }
+        Ok(result)
+    }
+}
+
+impl ImageOperation for CollageImage {
+    fn execute(&self, files: &[InputFile], options: &RunOptions) -> Result<(), Box<dyn Error>> {
+        if self.format.is_relative() {
+            return Err(Box::new(OperationParametersError(
+                "`--format` does not support relative lengths in `%`!".to_string(),
+            )));
+        }
+        let layout = Self::parse_layout(&std::fs::read_to_string(&self.layout)?)?;
+        if options.dry_run {
+            println!(
+                "{} placements of {} inputs -> {:?} ({})",
+                layout.len(),
+                files.len(),
+                self.output,
+                self.format
+            );
+            return Ok(());
+        }
+        if options.no_clobber && self.output.exists() {
+            log::info!("Skipping existing output file {:?}.", self.output);
+            return Ok(());
+        }
+
+        let files = Self::ordered(files, options.sort.as_ref());
+        let result = self.create(&files, &layout)?;
+        let save_options = SaveOptions {
+            quality: self.quality.unwrap_or(95),
+            overwrite: !options.no_clobber,
+            ..SaveOptions::default()
+        };
+        ImageUtil::save_image(result, &self.output, &save_options)?;
+        if let Some(summary) = &options.summary {
+            summary.add_output(&self.output);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::op::collage::{CollageImage, Placement};
+    use crate::op::{ImageOperation, RunOptions};
+    use crate::units::SortOrder;
+    use crate::util::test_dir;
+    use crate::util::{ImageUtil, InputFile};
+    use image::{DynamicImage, GenericImageView};
+    use structopt::StructOpt;
+
+    #[test]
+    fn parse_placement() {
+        let p: Placement = "2 1cm/10% 80px/60px".parse().unwrap();
+        assert_eq!(p.index, 2);
+        assert_eq!(p.position.to_string(), "1cm/10%");
+        assert!("2 1cm/1cm".parse::<Placement>().is_err());
+    }
+
+    #[test]
+    fn collage() {
+        let dir = test_dir("collage");
+        let mut files = vec![];
+        for (i, color) in [[255, 0, 0, 255], [0, 0, 255, 255]].iter().enumerate() {
+            let path = dir.join(format!("{}.png", i));
+            let mut image = DynamicImage::new_rgb8(20, 20);
+            ImageUtil::fill_image(&mut image, color);
+            image.save(&path).unwrap();
+            files.push(InputFile::from_path(path));
+        }
+
+        let op = CollageImage::from_iter(&[
+            "collage",
+            "--output",
+            "out.png",
+            "--layout",
+            "layout.txt",
+            "--format",
+            "100px/50px",
+        ]);
+        let layout = CollageImage::parse_layout(
+            "# overlapping\n1 10px/10px 40px/30px\n\n2 40px/20px 40px/20px\n",
+        )
+        .unwrap();
+        let result = op.create(&files, &layout).unwrap();
+        let out_of_range = CollageImage::parse_layout("3 0px/0px 10px/10px").unwrap();
+        let error = op.create(&files, &out_of_range);
+
+        assert_eq!((result.width(), result.height()), (100, 50));
+        assert_eq!(result.get_pixel(5, 5).0, [255, 255, 255, 255]);
+        assert_eq!(result.get_pixel(20, 20).0, [255, 0, 0, 255]);
+        // Later placements are drawn on top
+        assert_eq!(result.get_pixel(45, 30).0, [0, 0, 255, 255]);
+        assert!(error.is_err());
+    }
+
+    #[test]
+    fn input_order() {
+        let dir = test_dir("collage-order");
+        // Given in reverse alphabetical order
+        let mut files = vec![];
+        for (name, color) in [
+            ("c", [0, 255, 0, 255]),
+            ("b", [0, 0, 255, 255]),
+            ("a", [255, 0, 0, 255]),
+        ]
+        .iter()
+        {
+            let path = dir.join(format!("{}.png", name));
+            let mut image = DynamicImage::new_rgb8(20, 20);
+            ImageUtil::fill_image(&mut image, color);
+            image.save(&path).unwrap();
+            files.push(InputFile::from_path(path));
+        }
+
+        let layout = dir.join("layout.txt");
+        std::fs::write(
+            &layout,
+            "1 0px/0px 10px/10px\n2 10px/0px 10px/10px\n3 20px/0px 10px/10px\n",
+        )
+        .unwrap();
+        let op = CollageImage::from_iter(&[
+            "collage",
+            "--output",
+            dir.join("out.png").to_str().unwrap(),
+            "--layout",
+            layout.to_str().unwrap(),
+            "--format",
+            "30px/10px",
+        ]);
+        let mut results = vec![];
+        for threads in &[1, 4] {
+            let pool = rayon::ThreadPoolBuilder::new()
+                .num_threads(*threads)
+                .build()
+                .unwrap();
+            let ok = pool.install(|| op.execute(&files, &RunOptions::default()).is_ok());
+            assert!(ok);
+            results.push(image::open(&op.output).unwrap());
+            std::fs::remove_file(&op.output).unwrap();
+        }
+        let sorted = RunOptions {
+            sort: Some(SortOrder::Name),
+            ..RunOptions::default()
+        };
+        op.execute(&files, &sorted).unwrap();
+        let sorted = image::open(&op.output).unwrap();
+
+        for result in results {
+            // Without `--sort`, the first given image is in the top left cell
+            assert_eq!(result.get_pixel(5, 5).0, [0, 255, 0, 255]);
+            assert_eq!(result.get_pixel(15, 5).0, [0, 0, 255, 255]);
+            assert_eq!(result.get_pixel(25, 5).0, [255, 0, 0, 255]);
+        }
+        assert_eq!(sorted.get_pixel(5, 5).0, [255, 0, 0, 255]);
+        assert_eq!(sorted.get_pixel(25, 5).0, [0, 255, 0, 255]);
+    }
+}
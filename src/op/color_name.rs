@@ -0,0 +1,50 @@
+//! Look up names of colors.
+
+use crate::op::{ImageOperation, RunOptions};
+use crate::units::color::{Color, COLORS};
+use crate::util::InputFile;
+use std::error::Error;
+use structopt::StructOpt;
+
+/// Print the nearest named color for each given color, without input images.
+#[derive(StructOpt, Debug)]
+pub struct ColorName {
+    /// Colors to look up, e.g. `255/0/0` or `#ff0000`.
+    #[structopt(value_name = "color", required = true)]
+    pub colors: Vec<Color>,
+}
+
+impl ColorName {
+    /// Creates the line printed for a color, like `#fe0101 -> red (#ff0000)`.
+    fn line(color: &Color) -> String {
+        let name = color.nearest_named();
+        format!("{} -> {} ({})", color, name, COLORS[name])
+    }
+}
+
+impl ImageOperation for ColorName {
+    fn execute(&self, _files: &[InputFile], _options: &RunOptions) -> Result<(), Box<dyn Error>> {
+        for color in &self.colors {
+            println!("{}", Self::line(color));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::op::ColorName;
+    use crate::units::color::Color;
+    use structopt::StructOpt;
+
+    #[test]
+    fn color_name() {
+        let op = ColorName::from_iter(&["color-name", "254/1/1", "#ffffff"]);
+        assert_eq!(op.colors.len(), 2);
+        assert_eq!(ColorName::line(&op.colors[0]), "#fe0101 -> red (#ff0000)");
+        assert_eq!(
+            ColorName::line(&Color::new(0, 0, 0, 255)),
+            "#000000 -> black (#000000)"
+        );
+    }
+}
@@ -0,0 +1,144 @@
+//! Crop images to a rectangle.
+
+use crate::op::{edit_log_json, ImageIoOperation, ImageOperation, OutputOptions, RunOptions};
+use crate::units::Geometry;
+use crate::util::InputFile;
+use crate::OperationParametersError;
+use image::{DynamicImage, GenericImageView};
+use std::error::Error;
+use std::path::PathBuf;
+use structopt::StructOpt;
+
+/// Crop images to a fixed rectangle.
+#[derive(StructOpt, Debug)]
+pub struct CropImage {
+    #[structopt(flatten)]
+    pub output: OutputOptions,
+
+    /// Crop rectangle as `x/y/width/height`, measured from the top left corner.
+    /// Components can be given in any length unit, including percent of the image size.
+    /// `.` can be used as placeholder for `x` and `y`, meaning `0`.
+    /// Example: `--rect 10%/10%/80%/80%` for a centered 80% crop.
+    #[structopt(long, value_name = "x/y/w/h")]
+    pub rect: Geometry,
+
+    /// Writes a sidecar file `<output>.edit.json` describing the source size
+    /// and the region of the source that was kept, e.g. to undo crops.
+    #[structopt(name = "write-edit-log", long)]
+    pub write_edit_log: bool,
+
+    /// Image resolution for components not in px or %. Default `300`.
+    #[structopt(short, long)]
+    pub dpi: Option<f64>,
+}
+
+impl CropImage {
+    /// Calculates the crop rectangle in px as `(x, y, width, height)` for an image size.
+    fn crop_rect(&self, width: u32, height: u32) -> Result<(u32, u32, u32, u32), Box<dyn Error>> {
+        let dpi = self.dpi.unwrap_or(300.0);
+        let (w, h) = (width as f64, height as f64);
+        let rect = self.rect.resolve(w, h).to_px(dpi);
+
+        let (x, y) = (rect.x().value(), rect.y().value());
+        let (cw, ch) = (rect.w().value(), rect.h().value());
+        if x < 0.0 || y < 0.0 || cw < 1.0 || ch < 1.0 || x + cw > w || y + ch > h {
+            return Err(Box::new(OperationParametersError(format!(
+                "Crop rectangle {}px/{}px/{}px/{}px is not inside the image ({}x{}px)",
+                x, y, cw, ch, width, height
+            ))));
+        }
+        Ok((x as u32, y as u32, cw as u32, ch as u32))
+    }
+}
+
+impl ImageOperation for CropImage {
+    fn execute(&self, files: &[InputFile], options: &RunOptions) -> Result<(), Box<dyn Error>> {
+        ImageIoOperation::execute(self, files, options)
+    }
+}
+
+impl ImageIoOperation for CropImage {
+    fn output(&self) -> &OutputOptions {
+        &self.output
+    }
+
+    fn output_size(&self, width: u32, height: u32) -> Result<(u32, u32), Box<dyn Error>> {
+        let (_, _, w, h) = self.crop_rect(width, height)?;
+        Ok((w, h))
+    }
+
+    fn edit_log(&self, input: &DynamicImage) -> Option<String> {
+        if !self.write_edit_log {
+            return None;
+        }
+        let (width, height) = input.dimensions();
+        let (x, y, w, h) = self.crop_rect(width, height).ok()?;
+        Some(edit_log_json("crop", (width, height), (x, y, w, h), (w, h)))
+    }
+
+    fn process_image(
+        &self,
+        image: &DynamicImage,
+        _file: &PathBuf,
+    ) -> Result<DynamicImage, Box<dyn Error>> {
+        let (x, y, w, h) = self.crop_rect(image.width(), image.height())?;
+        Ok(image.crop_imm(x, y, w, h))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::op::{CropImage, ImageIoOperation, RunOptions};
+    use crate::util::{test_dir, InputFile};
+    use image::{DynamicImage, GenericImage, GenericImageView, Rgba};
+    use std::path::PathBuf;
+    use structopt::StructOpt;
+
+    #[test]
+    fn crop_percent() {
+        let op =
+            CropImage::from_iter(&["crop", "--output", "out.png", "--rect", "10%/10%/80%/80%"]);
+        let mut image = DynamicImage::new_rgb8(1000, 1000);
+        image.put_pixel(100, 100, Rgba([255, 0, 0, 255]));
+        image.put_pixel(99, 99, Rgba([0, 255, 0, 255]));
+
+        let result = op.process_image(&image, &PathBuf::from("in.png")).unwrap();
+        assert_eq!(result.dimensions(), (800, 800));
+        assert_eq!(result.get_pixel(0, 0), Rgba([255, 0, 0, 255]));
+        assert_eq!(op.output_size(1000, 1000).unwrap(), (800, 800));
+    }
+
+    #[test]
+    fn crop_outside() {
+        let op =
+            CropImage::from_iter(&["crop", "--output", "out.png", "--rect", "50%/0px/60%/100px"]);
+        assert!(op.output_size(1000, 1000).is_err());
+    }
+
+    #[test]
+    fn edit_log() {
+        let dir = test_dir("crop-edit-log");
+        let input = dir.join("in.png");
+        DynamicImage::new_rgb8(1000, 800).save(&input).unwrap();
+
+        let output = dir.join("out.png");
+        let op = CropImage::from_iter(&[
+            "crop",
+            "--output",
+            output.to_str().unwrap(),
+            "--rect",
+            "25%/25%/50%/50%",
+            "--write-edit-log",
+        ]);
+        op.process_file(&InputFile::from_path(input), 1, &RunOptions::default())
+            .unwrap();
+        let log = std::fs::read_to_string(dir.join("out.png.edit.json")).unwrap();
+
+        assert!(log.contains("\"operation\": \"crop\""));
+        assert!(log.contains("\"source\": { \"width\": 1000, \"height\": 800 }"));
+        assert!(
+            log.contains("\"crop\": { \"x\": 250, \"y\": 200, \"width\": 500, \"height\": 400 }")
+        );
+        assert!(log.contains("\"output\": { \"width\": 500, \"height\": 400 }"));
+    }
+}
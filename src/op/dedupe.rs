@@ -0,0 +1,214 @@
+//! Find duplicate images.
+
+use crate::op::{ImageOperation, PathIterOperation, RunOptions};
+use crate::units::color::Color;
+use crate::units::{ImageHash, ScaleMode};
+use crate::util::{ImageUtil, InputFile, PathUtil};
+use crate::OperationParametersError;
+use image::imageops::FilterType;
+use image::DynamicImage;
+use std::error::Error;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use structopt::StructOpt;
+
+/// Find near-duplicate images by perceptual hashing.
+#[derive(StructOpt, Debug)]
+pub struct DedupeImages {
+    /// Perceptual hash algorithm. One of `(dhash|ahash)`. Default: `dhash`.
+    #[structopt(long)]
+    pub hash: Option<ImageHash>,
+
+    /// Maximum number of differing hash bits (of 64) for images to count as duplicates.
+    /// Default: `5`.
+    #[structopt(long)]
+    pub threshold: Option<u32>,
+
+    /// Move duplicates to this directory, keeping the first image of each group
+    /// (in order of paths). If not given, groups are only printed.
+    #[structopt(long, value_name = "path")]
+    pub dir: Option<PathBuf>,
+
+    #[structopt(skip)]
+    hashes: Mutex<Vec<(PathBuf, u64)>>,
+}
+
+impl DedupeImages {
+    /// Computes the perceptual hash of an image.
+    fn image_hash(&self, image: &DynamicImage) -> Result<u64, Box<dyn Error>> {
+        let hash = self.hash.as_ref().unwrap_or(&ImageHash::DHash);
+        let width = if hash == &ImageHash::DHash { 9 } else { 8 };
+        let thumb = ImageUtil::scale_image_simple(
+            image,
+            width,
+            8,
+            &ScaleMode::Stretch,
+            &FilterType::Triangle,
+            &Color::new(255, 255, 255, 255),
+        )?
+        .to_luma();
+
+        let mut bits = 0_u64;
+        match hash {
+            ImageHash::DHash => {
+                for y in 0..8 {
+                    for x in 0..8 {
+                        let brighter = thumb.get_pixel(x + 1, y).0[0] > thumb.get_pixel(x, y).0[0];
+                        bits = (bits << 1) | brighter as u64;
+                    }
+                }
+            }
+            ImageHash::AHash => {
+                let mean = thumb.pixels().map(|p| p.0[0] as u32).sum::<u32>() / 64;
+                for p in thumb.pixels() {
+                    bits = (bits << 1) | (p.0[0] as u32 > mean) as u64;
+                }
+            }
+        }
+        Ok(bits)
+    }
+
+    /// Groups the hashed images by Hamming distance, in order of paths.
+    /// Returns only groups with more than one image.
+    fn groups(&self) -> Vec<Vec<PathBuf>> {
+        let threshold = self.threshold.unwrap_or(5);
+        let mut hashes = self.hashes.lock().unwrap().clone();
+        hashes.sort();
+
+        // Union-find over all pairs within the threshold
+        let mut parent: Vec<usize> = (0..hashes.len()).collect();
+        fn find(parent: &mut [usize], i: usize) -> usize {
+            let mut root = i;
+            while parent[root] != root {
+                root = parent[root];
+            }
+            parent[i] = root;
+            root
+        }
+        for i in 0..hashes.len() {
+            for j in (i + 1)..hashes.len() {
+                if (hashes[i].1 ^ hashes[j].1).count_ones() <= threshold {
+                    let (a, b) = (find(&mut parent, i), find(&mut parent, j));
+                    parent[a.max(b)] = a.min(b);
+                }
+            }
+        }
+
+        let mut groups: Vec<Vec<PathBuf>> = vec![vec![]; hashes.len()];
+        for (i, (path, _)) in hashes.into_iter().enumerate() {
+            let root = find(&mut parent, i);
+            groups[root].push(path);
+        }
+        groups.retain(|g| g.len() > 1);
+        groups
+    }
+
+    /// Moves a duplicate into `dir`.
+    fn move_file(path: &PathBuf, dir: &PathBuf) -> Result<(), Box<dyn Error>> {
+        let name = PathUtil::name(path).ok_or_else(|| {
+            OperationParametersError(format!("Unable to determine file name of {:?}", path))
+        })?;
+        let target = dir.join(name);
+        if target.exists() {
+            return Err(Box::new(OperationParametersError(format!(
+                "Unable to move {:?}, target {:?} exists",
+                path, target
+            ))));
+        }
+        std::fs::create_dir_all(dir)?;
+        if std::fs::rename(path, &target).is_err() {
+            // E.g. across file systems
+            std::fs::copy(path, &target)?;
+            std::fs::remove_file(path)?;
+        }
+        Ok(())
+    }
+}
+
+impl ImageOperation for DedupeImages {
+    fn execute(&self, files: &[InputFile], options: &RunOptions) -> Result<(), Box<dyn Error>> {
+        // Hash in parallel, compare afterwards
+        PathIterOperation::execute(self, files, options)?;
+
+        let groups = self.groups();
+        for group in &groups {
+            println!("Duplicates:");
+            for (i, path) in group.iter().enumerate() {
+                let keep = if i == 0 { "keep" } else { "dupe" };
+                println!("    {} {:?}", keep, path);
+                if let (true, Some(dir)) = (i > 0, &self.dir) {
+                    if !options.dry_run {
+                        Self::move_file(path, dir)?;
+                    }
+                }
+            }
+        }
+        let duplicates: usize = groups.iter().map(|g| g.len() - 1).sum();
+        println!(
+            "Found {} duplicates in {} groups.",
+            duplicates,
+            groups.len()
+        );
+        Ok(())
+    }
+}
+
+impl PathIterOperation for DedupeImages {
+    fn process_path(&self, path: &PathBuf) -> Result<(), Box<dyn Error>> {
+        let image = image::open(path)?;
+        let hash = self.image_hash(&image)?;
+        self.hashes.lock().unwrap().push((path.clone(), hash));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::op::{DedupeImages, ImageOperation, RunOptions};
+    use crate::util::test_dir;
+    use crate::util::InputFile;
+    use image::{DynamicImage, GenericImage, GenericImageView, Rgba};
+    use structopt::StructOpt;
+
+    fn gradient(width: u32, height: u32, flip: bool, noise: bool) -> DynamicImage {
+        let mut image = DynamicImage::new_rgb8(width, height);
+        for (x, y, _) in image.clone().pixels() {
+            let mut v = ((x + y) * 255 / (width + height)) as i32;
+            if flip {
+                v = 255 - v;
+            }
+            if noise && (x * 7 + y * 13) % 5 == 0 {
+                v = (v + 6).min(255);
+            }
+            image.put_pixel(x, y, Rgba([v as u8, v as u8, v as u8, 255]));
+        }
+        image
+    }
+
+    #[test]
+    fn dedupe() {
+        let dir = test_dir("dedupe");
+        let dupes = dir.join("dupes");
+        let images = [
+            ("a.png", gradient(64, 48, false, false)),
+            ("b.png", gradient(128, 96, false, true)),
+            ("c.png", gradient(64, 48, true, false)),
+        ];
+        let mut files = vec![];
+        for (name, image) in images.iter() {
+            let path = dir.join(name);
+            image.save(&path).unwrap();
+            files.push(InputFile::from_path(path));
+        }
+
+        let op = DedupeImages::from_iter(&["dedupe", "--dir", dupes.to_str().unwrap()]);
+        op.execute(&files, &RunOptions::default()).unwrap();
+        let groups = op.groups();
+        let moved = dupes.join("b.png").exists();
+        let kept = dir.join("a.png").exists() && dir.join("c.png").exists();
+
+        assert_eq!(groups, vec![vec![dir.join("a.png"), dir.join("b.png")]]);
+        assert!(moved);
+        assert!(kept);
+    }
+}
@@ -0,0 +1,156 @@
+//! Duotone and sepia toning.
+
+use crate::op::{ImageIoOperation, ImageOperation, OutputOptions, RunOptions};
+use crate::units::color::Color;
+use crate::units::DuotonePreset;
+use crate::util::InputFile;
+use crate::OperationParametersError;
+use image::{DynamicImage, GenericImageView, ImageBuffer, Rgb, Rgba};
+use std::error::Error;
+use std::path::PathBuf;
+use structopt::StructOpt;
+
+/// Map image brightness to a gradient between two colors, e.g. for sepia prints.
+#[derive(StructOpt, Debug)]
+pub struct DuotoneImage {
+    #[structopt(flatten)]
+    pub output: OutputOptions,
+
+    /// Color preset. One of `(sepia)`. Can be combined with `--dark` or `--light` to override one color.
+    #[structopt(short, long)]
+    pub preset: Option<DuotonePreset>,
+
+    /// Color for shadows, i.e. black. Required without `--preset`.
+    #[structopt(long)]
+    pub dark: Option<Color>,
+
+    /// Color for highlights, i.e. white. Required without `--preset`.
+    #[structopt(long)]
+    pub light: Option<Color>,
+}
+
+impl DuotoneImage {
+    /// The colors for shadows and highlights.
+    fn colors(&self) -> Result<(Color, Color), Box<dyn Error>> {
+        let (dark, light) = match &self.preset {
+            Some(preset) => preset.colors(),
+            None => match (&self.dark, &self.light) {
+                (Some(dark), Some(light)) => (dark.clone(), light.clone()),
+                _ => {
+                    return Err(Box::new(OperationParametersError(
+                        "Requires `--preset`, or both `--dark` and `--light`!".to_string(),
+                    )))
+                }
+            },
+        };
+        Ok((
+            self.dark.clone().unwrap_or(dark),
+            self.light.clone().unwrap_or(light),
+        ))
+    }
+
+    /// Creates the mapping from luminance to color, per channel.
+    fn lut(dark: &Color, light: &Color) -> [[u8; 3]; 256] {
+        let mut lut = [[0; 3]; 256];
+        let (dark, light) = (dark.channels(), light.channels());
+        for (v, entry) in lut.iter_mut().enumerate() {
+            let t = v as f64 / 255.0;
+            for c in 0..3 {
+                let value = dark[c] as f64 + t * (light[c] as f64 - dark[c] as f64);
+                entry[c] = value.round() as u8;
+            }
+        }
+        lut
+    }
+}
+
+impl ImageOperation for DuotoneImage {
+    fn execute(&self, files: &[InputFile], options: &RunOptions) -> Result<(), Box<dyn Error>> {
+        self.colors()?;
+        ImageIoOperation::execute(self, files, options)
+    }
+}
+
+impl ImageIoOperation for DuotoneImage {
+    fn output(&self) -> &OutputOptions {
+        &self.output
+    }
+
+    fn output_size(&self, width: u32, height: u32) -> Result<(u32, u32), Box<dyn Error>> {
+        self.colors()?;
+        Ok((width, height))
+    }
+
+    fn process_image(
+        &self,
+        image: &DynamicImage,
+        _file: &PathBuf,
+    ) -> Result<DynamicImage, Box<dyn Error>> {
+        let (dark, light) = self.colors()?;
+        let lut = Self::lut(&dark, &light);
+        let luma = image.to_luma();
+
+        let result = if image.color().has_alpha() {
+            let rgba = image.to_rgba();
+            DynamicImage::ImageRgba8(ImageBuffer::from_fn(
+                image.width(),
+                image.height(),
+                |x, y| {
+                    let [r, g, b] = lut[luma.get_pixel(x, y).0[0] as usize];
+                    Rgba([r, g, b, rgba.get_pixel(x, y).0[3]])
+                },
+            ))
+        } else {
+            DynamicImage::ImageRgb8(ImageBuffer::from_fn(
+                image.width(),
+                image.height(),
+                |x, y| Rgb(lut[luma.get_pixel(x, y).0[0] as usize]),
+            ))
+        };
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::op::{DuotoneImage, ImageIoOperation};
+    use image::{DynamicImage, GenericImage, GenericImageView, Rgba};
+    use std::path::PathBuf;
+    use structopt::StructOpt;
+
+    #[test]
+    fn endpoints() {
+        let op = DuotoneImage::from_iter(&[
+            "duotone",
+            "--output",
+            "out.png",
+            "--dark",
+            "10/20/30",
+            "--light",
+            "200/150/100",
+        ]);
+        let mut image = DynamicImage::new_rgba8(3, 1);
+        image.put_pixel(0, 0, Rgba([0, 0, 0, 255]));
+        image.put_pixel(1, 0, Rgba([255, 255, 255, 100]));
+        image.put_pixel(2, 0, Rgba([128, 128, 128, 255]));
+
+        let result = op.process_image(&image, &PathBuf::from("in.png")).unwrap();
+        assert_eq!(result.get_pixel(0, 0), Rgba([10, 20, 30, 255]));
+        assert_eq!(result.get_pixel(1, 0), Rgba([200, 150, 100, 100]));
+        assert_eq!(result.get_pixel(2, 0), Rgba([105, 85, 65, 255]));
+    }
+
+    #[test]
+    fn preset() {
+        let op = DuotoneImage::from_iter(&["duotone", "--output", "out.png", "--preset", "sepia"]);
+        let mut image = DynamicImage::new_rgb8(2, 1);
+        image.put_pixel(1, 0, Rgba([255, 255, 255, 255]));
+
+        let result = op.process_image(&image, &PathBuf::from("in.png")).unwrap();
+        assert_eq!(result.get_pixel(0, 0), Rgba([40, 26, 13, 255]));
+        assert_eq!(result.get_pixel(1, 0), Rgba([255, 240, 205, 255]));
+
+        let op = DuotoneImage::from_iter(&["duotone", "--output", "out.png", "--dark", "black"]);
+        assert!(op.output_size(2, 1).is_err());
+    }
+}
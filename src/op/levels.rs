@@ -0,0 +1,253 @@
+//! Automatic tone correction.
+
+use crate::op::{ImageIoOperation, ImageOperation, OutputOptions, RunOptions};
+use crate::units::LevelsMode;
+use crate::util::InputFile;
+use crate::OperationParametersError;
+use image::DynamicImage;
+use std::error::Error;
+use std::path::PathBuf;
+use structopt::StructOpt;
+
+/// Stretch or equalize image contrast, e.g. for faded scans.
+#[derive(StructOpt, Debug)]
+pub struct AutoLevels {
+    #[structopt(flatten)]
+    pub output: OutputOptions,
+
+    /// Tone correction mode. One of `(stretch|equalize)`.
+    /// `stretch` maps the darkest and brightest values to the full range,
+    /// `equalize` applies histogram equalization.
+    /// Default: `stretch`.
+    #[structopt(short, long)]
+    pub mode: Option<LevelsMode>,
+
+    /// Derive a single correction from the luminance and apply it to all color channels,
+    /// instead of correcting each channel individually. Preserves the color balance.
+    #[structopt(long)]
+    pub luminance: bool,
+
+    /// Percentage of pixels to ignore at both ends of the histogram for `--mode stretch`,
+    /// to make the stretch robust against outliers. Default: `0`.
+    #[structopt(long, value_name = "percent")]
+    pub clip: Option<f64>,
+}
+
+type Histogram = [u64; 256];
+type Lut = [u8; 256];
+
+impl AutoLevels {
+    fn check(&self) -> Result<(), Box<dyn Error>> {
+        if let Some(clip) = self.clip {
+            if self.mode.as_ref().unwrap_or(&LevelsMode::Stretch) != &LevelsMode::Stretch {
+                return Err(Box::new(OperationParametersError(
+                    "`--clip` requires `--mode stretch`!".to_string(),
+                )));
+            }
+            if !(0.0..50.0).contains(&clip) {
+                return Err(Box::new(OperationParametersError(format!(
+                    "`--clip` must be in range 0-50, got {}!",
+                    clip
+                ))));
+            }
+        }
+        Ok(())
+    }
+
+    /// Corrects the color channels of an interleaved 8 bit buffer in place.
+    /// Channels beyond `colors`, i.e. alpha, are left untouched.
+    fn correct(&self, buffer: &mut [u8], channels: usize, colors: usize) {
+        let luts: Vec<Lut> = if self.luminance && colors == 3 {
+            let mut hist = [0; 256];
+            for pixel in buffer.chunks_exact(channels) {
+                let luma =
+                    0.299 * pixel[0] as f64 + 0.587 * pixel[1] as f64 + 0.114 * pixel[2] as f64;
+                hist[luma.round() as usize] += 1;
+            }
+            vec![self.lut(&hist); colors]
+        } else {
+            let mut hists = vec![[0; 256]; colors];
+            for pixel in buffer.chunks_exact(channels) {
+                for (hist, value) in hists.iter_mut().zip(pixel) {
+                    hist[*value as usize] += 1;
+                }
+            }
+            hists.iter().map(|hist| self.lut(hist)).collect()
+        };
+
+        for pixel in buffer.chunks_exact_mut(channels) {
+            for (value, lut) in pixel.iter_mut().zip(&luts) {
+                *value = lut[*value as usize];
+            }
+        }
+    }
+
+    /// Creates the value mapping for a histogram.
+    fn lut(&self, hist: &Histogram) -> Lut {
+        let total: u64 = hist.iter().sum();
+        let mut lut = [0; 256];
+        for (i, v) in lut.iter_mut().enumerate() {
+            *v = i as u8;
+        }
+        if total == 0 {
+            return lut;
+        }
+
+        match self.mode.as_ref().unwrap_or(&LevelsMode::Stretch) {
+            LevelsMode::Stretch => {
+                let clip = (self.clip.unwrap_or(0.0) / 100.0 * total as f64) as u64;
+                let lower = Self::percentile(hist.iter(), clip);
+                let upper = 255 - Self::percentile(hist.iter().rev(), clip);
+                if upper > lower {
+                    let range = (upper - lower) as f64;
+                    for (i, v) in lut.iter_mut().enumerate() {
+                        let value = (i as f64 - lower as f64) / range * 255.0;
+                        *v = value.round().clamp(0.0, 255.0) as u8;
+                    }
+                }
+            }
+            LevelsMode::Equalize => {
+                let mut cdf = [0; 256];
+                let mut sum = 0;
+                for (c, h) in cdf.iter_mut().zip(hist.iter()) {
+                    sum += h;
+                    *c = sum;
+                }
+                let cdf_min = *cdf.iter().find(|c| **c > 0).unwrap();
+                if total > cdf_min {
+                    let range = (total - cdf_min) as f64;
+                    for (v, c) in lut.iter_mut().zip(cdf.iter()) {
+                        let value = c.saturating_sub(cdf_min) as f64 / range * 255.0;
+                        *v = value.round() as u8;
+                    }
+                }
+            }
+        }
+        lut
+    }
+
+    /// Index of the first histogram bin where the cumulative count exceeds `count`.
+    fn percentile<'a>(hist: impl Iterator<Item = &'a u64>, count: u64) -> usize {
+        let mut sum = 0;
+        for (i, h) in hist.enumerate() {
+            sum += h;
+            if sum > count {
+                return i;
+            }
+        }
+        255
+    }
+}
+
+impl ImageOperation for AutoLevels {
+    fn execute(&self, files: &[InputFile], options: &RunOptions) -> Result<(), Box<dyn Error>> {
+        ImageIoOperation::execute(self, files, options)
+    }
+}
+
+impl ImageIoOperation for AutoLevels {
+    fn output(&self) -> &OutputOptions {
+        &self.output
+    }
+
+    fn output_size(&self, width: u32, height: u32) -> Result<(u32, u32), Box<dyn Error>> {
+        self.check()?;
+        Ok((width, height))
+    }
+
+    fn process_image(
+        &self,
+        image: &DynamicImage,
+        _file: &PathBuf,
+    ) -> Result<DynamicImage, Box<dyn Error>> {
+        self.check()?;
+
+        let mut result = match image {
+            DynamicImage::ImageLuma8(_)
+            | DynamicImage::ImageLumaA8(_)
+            | DynamicImage::ImageRgb8(_)
+            | DynamicImage::ImageRgba8(_) => image.clone(),
+            _ => DynamicImage::ImageRgba8(image.to_rgba()),
+        };
+        match &mut result {
+            DynamicImage::ImageLuma8(img) => self.correct(img, 1, 1),
+            DynamicImage::ImageLumaA8(img) => self.correct(img, 2, 1),
+            DynamicImage::ImageRgb8(img) => self.correct(img, 3, 3),
+            DynamicImage::ImageRgba8(img) => self.correct(img, 4, 3),
+            _ => unreachable!(),
+        }
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::op::{AutoLevels, ImageIoOperation};
+    use image::{DynamicImage, GenericImage, GenericImageView, Rgba};
+    use std::path::PathBuf;
+    use structopt::StructOpt;
+
+    fn faded(image: &mut DynamicImage) {
+        for (x, y, _) in image.clone().pixels() {
+            let v = 100 + (x * 50 / image.width()) as u8;
+            image.put_pixel(x, y, Rgba([v, v / 2 + 50, v, 128]));
+        }
+    }
+
+    #[test]
+    fn stretch() {
+        let op = AutoLevels::from_iter(&["levels", "--output", "out.png"]);
+        for image in &mut [
+            DynamicImage::new_luma8(100, 10),
+            DynamicImage::new_rgba8(100, 10),
+        ] {
+            faded(image);
+            let result = op.process_image(image, &PathBuf::from("in.png")).unwrap();
+
+            assert_eq!(result.color(), image.color());
+            assert_eq!(result.get_pixel(0, 0).0[0], 0);
+            assert_eq!(result.get_pixel(99, 0).0[0], 255);
+            assert_eq!(result.get_pixel(99, 0).0[3], image.get_pixel(99, 0).0[3]);
+        }
+    }
+
+    #[test]
+    fn stretch_clip_luminance() {
+        let mut image = DynamicImage::new_rgb8(100, 10);
+        faded(&mut image);
+        image.put_pixel(0, 0, Rgba([0, 0, 0, 255]));
+
+        let op = AutoLevels::from_iter(&["levels", "--output", "out.png", "--luminance"]);
+        let result = op.process_image(&image, &PathBuf::from("in.png")).unwrap();
+        // The dark outlier prevents the stretch
+        assert!(result.get_pixel(1, 0).0[0] > 90);
+
+        let op = AutoLevels::from_iter(&[
+            "levels",
+            "--output",
+            "out.png",
+            "--luminance",
+            "--clip",
+            "1",
+        ]);
+        let result = op.process_image(&image, &PathBuf::from("in.png")).unwrap();
+        let (low, high) = (result.get_pixel(1, 0).0, result.get_pixel(99, 0).0);
+        assert!(low[0] < 10);
+        // Same mapping for all channels, so green is not stretched to the full range
+        assert_eq!(high[0], 255);
+        assert!(high[1] < 200);
+    }
+
+    #[test]
+    fn equalize() {
+        let op = AutoLevels::from_iter(&["levels", "--output", "out.png", "--mode", "equalize"]);
+        let mut image = DynamicImage::new_rgb8(100, 10);
+        faded(&mut image);
+        let result = op.process_image(&image, &PathBuf::from("in.png")).unwrap();
+
+        assert_eq!(result.get_pixel(0, 0).0[0], 0);
+        assert_eq!(result.get_pixel(99, 0).0[0], 255);
+        let mid = result.get_pixel(50, 0).0[0];
+        assert!(mid > 115 && mid < 140);
+    }
+}
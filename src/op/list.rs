@@ -1,12 +1,17 @@
 //! List files.
 
-use crate::op::{ImageOperation, PathIterOperation};
-use crate::util::PathUtil;
+use crate::op::{ImageOperation, PathIterOperation, RunOptions, RunSummary};
+use crate::units::ListFormat;
+use crate::util::{ImageUtil, InputFile, PathUtil};
 use path_absolutize::*;
 use std::error::Error;
 use std::path::PathBuf;
+use std::sync::Mutex;
 use structopt::StructOpt;
 
+/// EXIF fields printed by `--info`, as abbreviations.
+const INFO_EXIF_FIELDS: [&str; 6] = ["Mod", "Date", "F", "F/2", "Exp", "ISO"];
+
 /// List files found by input pattern.
 #[derive(StructOpt, Debug)]
 pub struct ListFiles {
@@ -16,16 +21,268 @@ pub struct ListFiles {
     /// Prints the absolute path.
     #[structopt(short, long)]
     pub absolute: bool,
+    /// Prints the total number of files after listing.
+    #[structopt(short, long)]
+    pub count: bool,
+    /// Don't list the individual files. Use together with `--count`.
+    #[structopt(short, long)]
+    pub quiet: bool,
+    /// With `--count`, also prints the total size in bytes
+    /// and the minimum, maximum and mean image dimensions.
+    #[structopt(long)]
+    pub details: bool,
+    /// Prints image size, color type, file size and key EXIF fields for each file.
+    #[structopt(short, long, conflicts_with = "format")]
+    pub info: bool,
+    /// Output format. One of `(text|json|csv)`. Default: `text`.
+    /// `json` and `csv` list name, path and absolute path of each file, and ignore `--path` and `--absolute`.
+    #[structopt(long)]
+    pub format: Option<ListFormat>,
+    /// For `--format json` and `csv`, also lists image width and height in px, and file size in bytes.
+    #[structopt(name = "with-dimensions", long)]
+    pub with_dimensions: bool,
+
+    #[structopt(skip)]
+    stats: Mutex<ListStats>,
+    #[structopt(skip)]
+    entries: Mutex<Vec<ListEntry>>,
+}
+
+/// A listed file, for structured output.
+#[derive(Debug)]
+struct ListEntry {
+    name: String,
+    path: String,
+    absolute: String,
+    size: Option<(u32, u32)>,
+    bytes: Option<u64>,
+}
+
+impl ListEntry {
+    fn new(path: &PathBuf, with_dimensions: bool) -> Result<Self, Box<dyn Error>> {
+        let (size, bytes) = if with_dimensions {
+            (
+                image::image_dimensions(path).ok(),
+                Some(std::fs::metadata(path)?.len()),
+            )
+        } else {
+            (None, None)
+        };
+        Ok(ListEntry {
+            name: PathUtil::name(path).unwrap_or_default(),
+            path: path.to_string_lossy().to_string(),
+            absolute: path.absolutize()?.to_string_lossy().to_string(),
+            size,
+            bytes,
+        })
+    }
+
+    fn to_json(&self, with_dimensions: bool) -> String {
+        let mut json = format!(
+            "{{\"name\": {}, \"path\": {}, \"absolute\": {}",
+            RunSummary::json_string(&self.name),
+            RunSummary::json_string(&self.path),
+            RunSummary::json_string(&self.absolute),
+        );
+        if with_dimensions {
+            let (width, height) = match self.size {
+                Some((w, h)) => (w.to_string(), h.to_string()),
+                None => ("null".to_string(), "null".to_string()),
+            };
+            json.push_str(&format!(
+                ", \"width\": {}, \"height\": {}, \"size_bytes\": {}",
+                width,
+                height,
+                self.bytes.map_or("null".to_string(), |b| b.to_string()),
+            ));
+        }
+        json.push('}');
+        json
+    }
+
+    fn to_csv(&self, with_dimensions: bool) -> String {
+        let mut fields = vec![
+            Self::csv_field(&self.name),
+            Self::csv_field(&self.path),
+            Self::csv_field(&self.absolute),
+        ];
+        if with_dimensions {
+            let (width, height) = match self.size {
+                Some((w, h)) => (w.to_string(), h.to_string()),
+                None => (String::new(), String::new()),
+            };
+            fields.push(width);
+            fields.push(height);
+            fields.push(self.bytes.map(|b| b.to_string()).unwrap_or_default());
+        }
+        fields.join(",")
+    }
+
+    /// Quotes a CSV field if required.
+    fn csv_field(str: &str) -> String {
+        if str.contains([',', '"', '\n', '\r']) {
+            format!("\"{}\"", str.replace('"', "\"\""))
+        } else {
+            str.to_string()
+        }
+    }
+}
+
+/// Totals collected while listing files.
+#[derive(Debug, Default)]
+struct ListStats {
+    files: usize,
+    bytes: u64,
+    images: usize,
+    min_size: Option<(u32, u32)>,
+    max_size: Option<(u32, u32)>,
+    sum_size: (u64, u64),
+}
+
+impl ListStats {
+    fn add_image(&mut self, width: u32, height: u32) {
+        self.images += 1;
+        self.min_size = Some(
+            self.min_size
+                .map_or((width, height), |(w, h)| (w.min(width), h.min(height))),
+        );
+        self.max_size = Some(
+            self.max_size
+                .map_or((width, height), |(w, h)| (w.max(width), h.max(height))),
+        );
+        self.sum_size.0 += u64::from(width);
+        self.sum_size.1 += u64::from(height);
+    }
+}
+
+impl ListFiles {
+    /// Creates the summary of all listed files.
+    fn summary(&self) -> String {
+        let stats = self.stats.lock().unwrap();
+        let mut summary = format!("Total: {} files", stats.files);
+        if self.details {
+            summary.push_str(&format!(", {} bytes", stats.bytes));
+            if let (Some(min), Some(max)) = (stats.min_size, stats.max_size) {
+                let n = stats.images as u64;
+                summary.push_str(&format!(
+                    "\nImage size ({} images): min {}x{}px, max {}x{}px, mean {}x{}px",
+                    stats.images,
+                    min.0,
+                    min.1,
+                    max.0,
+                    max.1,
+                    stats.sum_size.0 / n,
+                    stats.sum_size.1 / n,
+                ));
+            }
+        }
+        summary
+    }
+
+    /// Creates the info for a file: image size, color type, file size and key EXIF fields.
+    /// Only reads the image header where possible.
+    fn info(path: &PathBuf) -> String {
+        let (width, height, color) = match ImageUtil::image_header(path) {
+            Ok(header) => header,
+            Err(e) => return format!("not an image ({})", e),
+        };
+        let mut info = format!("{}x{}px, {:?}", width, height, color);
+        if let Ok(meta) = std::fs::metadata(path) {
+            info.push_str(&format!(", {} bytes", meta.len()));
+        }
+        if let Ok(exif) = ImageUtil::get_exif_map(path) {
+            for key in INFO_EXIF_FIELDS.iter() {
+                if let Some(value) = exif.get(*key) {
+                    info.push_str(&format!(", {}: {}", key, value));
+                }
+            }
+        }
+        info
+    }
+
+    /// Creates the structured output of all listed files, sorted by path.
+    /// Returns `None` for `--format text`.
+    fn structured(&self) -> Option<String> {
+        let mut entries = self.entries.lock().unwrap();
+        entries.sort_by(|a, b| a.path.cmp(&b.path));
+        let dims = self.with_dimensions;
+        match self.format.as_ref().unwrap_or(&ListFormat::Text) {
+            ListFormat::Text => None,
+            ListFormat::Json => {
+                if entries.is_empty() {
+                    return Some("[]".to_string());
+                }
+                let lines: Vec<_> = entries
+                    .iter()
+                    .map(|e| format!("  {}", e.to_json(dims)))
+                    .collect();
+                Some(format!("[\n{}\n]", lines.join(",\n")))
+            }
+            ListFormat::Csv => {
+                let mut lines = vec![if dims {
+                    "name,path,absolute,width,height,size_bytes".to_string()
+                } else {
+                    "name,path,absolute".to_string()
+                }];
+                lines.extend(entries.iter().map(|e| e.to_csv(dims)));
+                Some(lines.join("\n"))
+            }
+        }
+    }
 }
 
 impl ImageOperation for ListFiles {
-    fn execute(&self, files: &[PathBuf]) -> Result<(), Box<dyn Error>> {
-        PathIterOperation::execute(self, &files)
+    fn execute(&self, files: &[InputFile], options: &RunOptions) -> Result<(), Box<dyn Error>> {
+        PathIterOperation::execute(self, files, options)?;
+        match self.structured() {
+            Some(output) => {
+                if !self.quiet {
+                    println!("{}", output);
+                }
+                // Keep stdout parsable
+                if self.count {
+                    eprintln!("{}", self.summary());
+                }
+            }
+            None => {
+                if self.count {
+                    println!("{}", self.summary());
+                }
+            }
+        }
+        Ok(())
     }
 }
 
 impl PathIterOperation for ListFiles {
     fn process_path(&self, path: &PathBuf) -> Result<(), Box<dyn Error>> {
+        if self.count {
+            let bytes = if self.details {
+                std::fs::metadata(path)?.len()
+            } else {
+                0
+            };
+            let dims = if self.details {
+                image::image_dimensions(path).ok()
+            } else {
+                None
+            };
+            let mut stats = self.stats.lock().unwrap();
+            stats.files += 1;
+            stats.bytes += bytes;
+            if let Some((width, height)) = dims {
+                stats.add_image(width, height);
+            }
+        }
+        if self.quiet {
+            return Ok(());
+        }
+        if self.format.as_ref().is_some_and(|f| f != &ListFormat::Text) {
+            let entry = ListEntry::new(path, self.with_dimensions)?;
+            self.entries.lock().unwrap().push(entry);
+            return Ok(());
+        }
+
         let p = if self.path || self.absolute {
             if self.absolute {
                 path.absolutize()
@@ -38,8 +295,75 @@ impl PathIterOperation for ListFiles {
             PathUtil::name(&path)
         };
         if let Some(p) = p {
-            println!("{}", p.replace("\\\\", "\\").replace("\"", ""));
+            let p = p.replace("\\\\", "\\").replace("\"", "");
+            if self.info {
+                println!("{}: {}", p, Self::info(path));
+            } else {
+                println!("{}", p);
+            }
         }
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::op::{ImageOperation, ListFiles, RunOptions};
+    use crate::util::{InputFile, PathUtil};
+    use std::path::PathBuf;
+    use structopt::StructOpt;
+
+    #[test]
+    fn count() {
+        let files: Vec<_> = PathUtil::list_files("test_data/*")
+            .unwrap()
+            .into_iter()
+            .map(InputFile::from_path)
+            .collect();
+        let op = ListFiles::from_iter(&["list", "--count", "--quiet", "--details"]);
+        op.execute(&files, &RunOptions::default()).unwrap();
+
+        let summary = op.summary();
+        assert!(summary.starts_with(&format!("Total: {} files, ", files.len())));
+        assert_eq!(op.stats.lock().unwrap().files, files.len());
+        assert!(summary.contains("Image size"));
+    }
+
+    #[test]
+    fn structured() {
+        let files: Vec<_> = PathUtil::list_files("test_data/*.jpg")
+            .unwrap()
+            .into_iter()
+            .map(InputFile::from_path)
+            .collect();
+        let op = ListFiles::from_iter(&["list", "--format", "json", "--with-dimensions"]);
+        op.execute(&files, &RunOptions::default()).unwrap();
+        let json = op.structured().unwrap();
+        assert!(json.starts_with("[\n  {\"name\": "));
+        assert!(json.ends_with("}\n]"));
+        assert_eq!(json.matches("\"width\": ").count(), files.len());
+        assert_eq!(json.matches("\"size_bytes\": ").count(), files.len());
+
+        let op = ListFiles::from_iter(&["list", "--format", "csv"]);
+        op.execute(&files, &RunOptions::default()).unwrap();
+        let csv = op.structured().unwrap();
+        assert_eq!(csv.lines().count(), files.len() + 1);
+        assert_eq!(csv.lines().next().unwrap(), "name,path,absolute");
+
+        let op = ListFiles::from_iter(&["list", "--format", "json"]);
+        op.execute(&[], &RunOptions::default()).unwrap();
+        assert_eq!(op.structured().unwrap(), "[]");
+        assert!(ListFiles::from_iter(&["list"]).structured().is_none());
+    }
+
+    #[test]
+    fn info() {
+        let info = ListFiles::info(&PathBuf::from("test_data/tree-exif.jpg"));
+        assert!(info.starts_with("1440x720px, Rgb8, "));
+        assert!(info.contains(" bytes"));
+        assert!(info.contains("Exp: "));
+
+        let info = ListFiles::info(&PathBuf::from("test_data/test-pattern.svg"));
+        assert!(info.starts_with("not an image"));
+    }
+}
@@ -0,0 +1,284 @@
+//! Color grading by 3D lookup tables.
+
+use crate::op::{ImageIoOperation, ImageOperation, OutputOptions, RunOptions};
+use crate::util::InputFile;
+use crate::ParseStructError;
+use image::{DynamicImage, Pixel};
+use std::error::Error;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use structopt::StructOpt;
+
+/// Apply a 3D color lookup table (LUT), e.g. for film looks.
+#[derive(StructOpt, Debug)]
+pub struct LutImage {
+    #[structopt(flatten)]
+    pub output: OutputOptions,
+
+    /// 3D LUT file in `.cube` format.
+    #[structopt(long, value_name = "path")]
+    pub lut: PathBuf,
+
+    #[structopt(skip)]
+    table: Mutex<Option<Arc<CubeLut>>>,
+}
+
+/// A 3D lookup table, as read from a `.cube` file.
+#[derive(Debug, PartialEq, Clone)]
+pub struct CubeLut {
+    size: usize,
+    domain_min: [f32; 3],
+    domain_max: [f32; 3],
+    /// Output colors, with red changing fastest.
+    table: Vec<[f32; 3]>,
+}
+
+impl CubeLut {
+    /// Maps a color through the table, using trilinear interpolation.
+    pub fn apply(&self, color: [f32; 3]) -> [f32; 3] {
+        let max_index = (self.size - 1) as f32;
+        let mut lower = [0; 3];
+        let mut frac = [0.0; 3];
+        for i in 0..3 {
+            let t = (color[i] - self.domain_min[i]) / (self.domain_max[i] - self.domain_min[i]);
+            let pos = t.clamp(0.0, 1.0) * max_index;
+            lower[i] = (pos.floor() as usize).min(self.size - 2);
+            frac[i] = pos - lower[i] as f32;
+        }
+
+        let mut result = [0.0; 3];
+        for corner in 0..8 {
+            let mut weight = 1.0;
+            let mut index = [0; 3];
+            for i in 0..3 {
+                let upper = (corner >> i) & 1 == 1;
+                index[i] = lower[i] + upper as usize;
+                weight *= if upper { frac[i] } else { 1.0 - frac[i] };
+            }
+            if weight > 0.0 {
+                let value = self.get(index[0], index[1], index[2]);
+                for i in 0..3 {
+                    result[i] += weight * value[i];
+                }
+            }
+        }
+        result
+    }
+
+    fn get(&self, r: usize, g: usize, b: usize) -> &[f32; 3] {
+        &self.table[r + self.size * (g + self.size * b)]
+    }
+
+    fn parse_triple(values: &[&str], line: &str) -> Result<[f32; 3], Box<dyn Error>> {
+        if values.len() != 3 {
+            return Err(Box::new(ParseStructError(format!(
+                "Expected three values in LUT line `{}`",
+                line
+            ))));
+        }
+        Ok([values[0].parse()?, values[1].parse()?, values[2].parse()?])
+    }
+}
+
+impl FromStr for CubeLut {
+    type Err = Box<dyn Error>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut size = None;
+        let mut domain_min = [0.0; 3];
+        let mut domain_max = [1.0; 3];
+        let mut table = vec![];
+
+        for line in s.lines().map(|l| l.trim()) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let parts: Vec<_> = line.split_whitespace().collect();
+            match parts[0] {
+                "TITLE" => {}
+                "LUT_3D_SIZE" => size = Some(line["LUT_3D_SIZE".len()..].trim().parse()?),
+                "DOMAIN_MIN" => domain_min = Self::parse_triple(&parts[1..], line)?,
+                "DOMAIN_MAX" => domain_max = Self::parse_triple(&parts[1..], line)?,
+                "LUT_1D_SIZE" => {
+                    return Err(Box::new(ParseStructError(
+                        "1D LUTs are not supported, expects a 3D LUT".to_string(),
+                    )))
+                }
+                _ => table.push(Self::parse_triple(&parts, line)?),
+            }
+        }
+
+        let size: usize =
+            size.ok_or_else(|| ParseStructError("Missing `LUT_3D_SIZE` in LUT file".to_string()))?;
+        if !(2..=256).contains(&size) {
+            return Err(Box::new(ParseStructError(format!(
+                "LUT size must be in range 2-256, got {}",
+                size
+            ))));
+        }
+        if table.len() != size.pow(3) {
+            return Err(Box::new(ParseStructError(format!(
+                "Expected {} entries for LUT size {}, got {}",
+                size.pow(3),
+                size,
+                table.len()
+            ))));
+        }
+        if (0..3).any(|i| domain_min[i] >= domain_max[i]) {
+            return Err(Box::new(ParseStructError(format!(
+                "Invalid LUT domain: `DOMAIN_MIN` {:?} must be less than `DOMAIN_MAX` {:?}",
+                domain_min, domain_max
+            ))));
+        }
+
+        Ok(CubeLut {
+            size,
+            domain_min,
+            domain_max,
+            table,
+        })
+    }
+}
+
+impl LutImage {
+    /// The LUT, read on first use.
+    fn table(&self) -> Result<Arc<CubeLut>, Box<dyn Error>> {
+        let mut table = self.table.lock().unwrap();
+        if table.is_none() {
+            *table = Some(Arc::new(std::fs::read_to_string(&self.lut)?.parse()?));
+        }
+        Ok(table.as_ref().unwrap().clone())
+    }
+
+    fn map_pixel<P: Pixel<Subpixel = u8>>(lut: &CubeLut, pixel: &mut P) {
+        let channels = pixel.channels_mut();
+        let color = [
+            channels[0] as f32 / 255.0,
+            channels[1] as f32 / 255.0,
+            channels[2] as f32 / 255.0,
+        ];
+        let mapped = lut.apply(color);
+        for i in 0..3 {
+            channels[i] = (mapped[i] * 255.0).round().clamp(0.0, 255.0) as u8;
+        }
+    }
+}
+
+impl ImageOperation for LutImage {
+    fn execute(&self, files: &[InputFile], options: &RunOptions) -> Result<(), Box<dyn Error>> {
+        ImageIoOperation::execute(self, files, options)
+    }
+}
+
+impl ImageIoOperation for LutImage {
+    fn output(&self) -> &OutputOptions {
+        &self.output
+    }
+
+    fn output_size(&self, width: u32, height: u32) -> Result<(u32, u32), Box<dyn Error>> {
+        Ok((width, height))
+    }
+
+    fn process_image(
+        &self,
+        image: &DynamicImage,
+        _file: &PathBuf,
+    ) -> Result<DynamicImage, Box<dyn Error>> {
+        let lut = self.table()?;
+        if image.color().has_alpha() {
+            let mut result = image.to_rgba();
+            result
+                .pixels_mut()
+                .for_each(|pixel| Self::map_pixel(&lut, pixel));
+            Ok(DynamicImage::ImageRgba8(result))
+        } else {
+            let mut result = image.to_rgb();
+            result
+                .pixels_mut()
+                .for_each(|pixel| Self::map_pixel(&lut, pixel));
+            Ok(DynamicImage::ImageRgb8(result))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::op::lut::CubeLut;
+    use crate::op::{ImageIoOperation, LutImage};
+    use crate::util::test_dir;
+    use image::{DynamicImage, GenericImage, GenericImageView, Rgba};
+    use std::path::PathBuf;
+    use structopt::StructOpt;
+
+    fn cube(size: usize, f: impl Fn([f32; 3]) -> [f32; 3]) -> String {
+        let mut cube = format!("TITLE \"test\"\n# comment\nLUT_3D_SIZE {}\n", size);
+        let max = (size - 1) as f32;
+        for b in 0..size {
+            for g in 0..size {
+                for r in 0..size {
+                    let c = f([r as f32 / max, g as f32 / max, b as f32 / max]);
+                    cube.push_str(&format!("{} {} {}\n", c[0], c[1], c[2]));
+                }
+            }
+        }
+        cube
+    }
+
+    fn apply(cube: &str, image: &DynamicImage) -> DynamicImage {
+        let dir = test_dir("lut");
+        let path = dir.join(format!("{}.cube", cube.len()));
+        std::fs::write(&path, cube).unwrap();
+        let op = LutImage::from_iter(&[
+            "lut",
+            "--output",
+            "out.png",
+            "--lut",
+            path.to_str().unwrap(),
+        ]);
+        let result = op.process_image(image, &PathBuf::from("in.png"));
+        std::fs::remove_file(&path).unwrap();
+        result.unwrap()
+    }
+
+    fn gradient() -> DynamicImage {
+        let mut image = DynamicImage::new_rgba8(32, 32);
+        for (x, y, _) in image.clone().pixels() {
+            image.put_pixel(
+                x,
+                y,
+                Rgba([(x * 8) as u8, (y * 8) as u8, 255 - (x * 8) as u8, 200]),
+            );
+        }
+        image
+    }
+
+    #[test]
+    fn identity() {
+        let image = gradient();
+        let result = apply(&cube(17, |c| c), &image);
+        assert_eq!(result.to_bytes(), image.to_bytes());
+    }
+
+    #[test]
+    fn shift() {
+        let image = gradient();
+        let result = apply(&cube(5, |c| [0.5 + 0.5 * c[0], c[1], 1.0 - c[2]]), &image);
+        for (x, y, pixel) in result.pixels() {
+            let orig = image.get_pixel(x, y).0;
+            let expected = 127.5 + orig[0] as f32 / 2.0;
+            assert!((pixel.0[0] as f32 - expected).abs() <= 0.5);
+            assert_eq!(pixel.0[1..], [orig[1], 255 - orig[2], orig[3]]);
+        }
+    }
+
+    #[test]
+    fn validate() {
+        assert!(cube(2, |c| c).parse::<CubeLut>().is_ok());
+        assert!(cube(1, |c| c).parse::<CubeLut>().is_err());
+        let truncated = cube(3, |c| c).replace("LUT_3D_SIZE 3", "LUT_3D_SIZE 4");
+        assert!(truncated.parse::<CubeLut>().is_err());
+        let domain = format!("DOMAIN_MIN 0 0 1\nDOMAIN_MAX 1 1 1\n{}", cube(2, |c| c));
+        assert!(domain.parse::<CubeLut>().is_err());
+    }
+}
@@ -0,0 +1,161 @@
+//! Mask images to circles or rounded rectangles.
+
+use crate::op::{ImageIoOperation, ImageOperation, OutputOptions, RunOptions};
+use crate::units::{Length, MaskShape};
+use crate::util::InputFile;
+use crate::OperationParametersError;
+use image::{DynamicImage, GenericImageView};
+use std::error::Error;
+use std::path::PathBuf;
+use structopt::StructOpt;
+
+/// Mask images to circles or rounded rectangles, with transparent surroundings.
+/// Output must be in a format supporting transparency, like PNG or WebP.
+#[derive(StructOpt, Debug)]
+pub struct MaskImage {
+    #[structopt(flatten)]
+    pub output: OutputOptions,
+
+    /// Mask shape. One of `(circle|rounded)`.
+    #[structopt(long)]
+    pub shape: MaskShape,
+
+    /// Corner radius for `rounded`, required.
+    /// Circle radius for `circle`, default: half the smaller image dimension.
+    #[structopt(long, value_name = "length")]
+    pub radius: Option<Length>,
+
+    /// Image resolution for size not in px. Default `300`.
+    #[structopt(short, long)]
+    pub dpi: Option<f64>,
+}
+
+impl MaskImage {
+    fn check(&self) -> Result<(), Box<dyn Error>> {
+        if self.shape == MaskShape::Rounded && self.radius.is_none() {
+            return Err(Box::new(OperationParametersError(
+                "`--shape rounded` requires `--radius`!".to_string(),
+            )));
+        }
+        if self.radius.as_ref().is_some_and(|r| r.is_relative()) {
+            return Err(Box::new(OperationParametersError(
+                "`--radius` does not support relative lengths in `%`!".to_string(),
+            )));
+        }
+        Ok(())
+    }
+
+    /// Signed distance of a point from the mask edge, negative inside.
+    fn distance(&self, x: f64, y: f64, width: f64, height: f64, radius: f64) -> f64 {
+        let (dx, dy) = ((x - width / 2.0).abs(), (y - height / 2.0).abs());
+        match self.shape {
+            MaskShape::Circle => (dx * dx + dy * dy).sqrt() - radius,
+            MaskShape::Rounded => {
+                let qx = dx - (width / 2.0 - radius);
+                let qy = dy - (height / 2.0 - radius);
+                let outside = (qx.max(0.0).powi(2) + qy.max(0.0).powi(2)).sqrt();
+                outside + qx.max(qy).min(0.0) - radius
+            }
+        }
+    }
+}
+
+impl ImageOperation for MaskImage {
+    fn execute(&self, files: &[InputFile], options: &RunOptions) -> Result<(), Box<dyn Error>> {
+        ImageIoOperation::execute(self, files, options)
+    }
+}
+
+impl ImageIoOperation for MaskImage {
+    fn output(&self) -> &OutputOptions {
+        &self.output
+    }
+
+    fn output_size(&self, width: u32, height: u32) -> Result<(u32, u32), Box<dyn Error>> {
+        self.check()?;
+        Ok((width, height))
+    }
+
+    fn requires_alpha(&self) -> bool {
+        true
+    }
+
+    fn process_image(
+        &self,
+        image: &DynamicImage,
+        _file: &PathBuf,
+    ) -> Result<DynamicImage, Box<dyn Error>> {
+        self.check()?;
+
+        let dpi = self.dpi.unwrap_or(300.0);
+        let (width, height) = (image.width() as f64, image.height() as f64);
+        let max_radius = width.min(height) / 2.0;
+        let radius = self
+            .radius
+            .as_ref()
+            .map_or(max_radius, |r| r.to_px(dpi).value())
+            .min(max_radius)
+            .max(0.0);
+
+        let mut result = image.to_rgba();
+        for (x, y, pixel) in result.enumerate_pixels_mut() {
+            let dist = self.distance(x as f64 + 0.5, y as f64 + 0.5, width, height, radius);
+            // Anti-aliasing: linear coverage over one pixel across the edge
+            let coverage = (0.5 - dist).clamp(0.0, 1.0);
+            pixel.0[3] = (pixel.0[3] as f64 * coverage).round() as u8;
+        }
+
+        Ok(DynamicImage::ImageRgba8(result))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::op::{ImageIoOperation, MaskImage};
+    use crate::util::InputFile;
+    use image::{DynamicImage, GenericImageView};
+    use std::path::PathBuf;
+    use structopt::StructOpt;
+
+    #[test]
+    fn mask_circle() {
+        let op = MaskImage::from_iter(&["mask", "--output", "out.png", "--shape", "circle"]);
+        let image = DynamicImage::new_rgb8(100, 60);
+        let result = op.process_image(&image, &PathBuf::from("in.jpg")).unwrap();
+
+        assert!(result.as_rgba8().is_some());
+        assert_eq!(result.get_pixel(50, 30).0[3], 255);
+        assert_eq!(result.get_pixel(0, 0).0[3], 0);
+        assert_eq!(result.get_pixel(15, 30).0[3], 0);
+        let edge = result.get_pixel(20, 30).0[3];
+        assert!(edge > 0 && edge < 255);
+    }
+
+    #[test]
+    fn mask_rounded() {
+        let op = MaskImage::from_iter(&[
+            "mask", "--output", "out.png", "--shape", "rounded", "--radius", "10px",
+        ]);
+        let image = DynamicImage::new_rgb8(100, 60);
+        let result = op.process_image(&image, &PathBuf::from("in.jpg")).unwrap();
+
+        assert_eq!(result.get_pixel(0, 0).0[3], 0);
+        assert_eq!(result.get_pixel(50, 0).0[3], 255);
+        assert_eq!(result.get_pixel(10, 10).0[3], 255);
+    }
+
+    #[test]
+    fn refuse_jpeg() {
+        let op = MaskImage::from_iter(&["mask", "--output", "out.jpg", "--shape", "circle"]);
+        assert!(op
+            .resolve_out_path(
+                &InputFile::from_path(PathBuf::from("in.png")),
+                1,
+                0,
+                (100, 100),
+                100,
+                100
+            )
+            .is_err());
+    }
+}
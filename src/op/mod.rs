@@ -1,111 +1,1036 @@
 //! `print-prep` operations
-use crate::util::{ImageFormatError, ImageUtil, PathUtil};
-use image::DynamicImage;
-use indicatif::ProgressBar;
+use crate::units::SortOrder;
+use crate::util::{ExtMapping, ImageFormatError, ImageUtil, InputFile, PathUtil};
+use image::{DynamicImage, GenericImageView};
+use indicatif::{ProgressBar, ProgressStyle};
+use path_absolutize::Absolutize;
 use rayon::prelude::*;
+use std::collections::HashMap;
 use std::error::Error;
+use std::io::{self, Write};
 use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
+mod blur;
+mod canvas;
+mod chart;
+mod collage;
+mod color_name;
+mod crop;
+mod dedupe;
+mod duotone;
+mod levels;
 mod list;
+mod lut;
+mod mask;
+mod orient;
+mod output;
 mod prep;
+mod quantize;
 mod scale;
+mod split;
+mod strip;
+mod summary;
+mod thumb;
+mod timestamp;
+mod trim;
+mod vignette;
 
+pub use blur::BlurImage;
+pub use canvas::CanvasImage;
+pub use chart::TestChart;
+pub use collage::CollageImage;
+pub use color_name::ColorName;
+pub use crop::CropImage;
+pub use dedupe::DedupeImages;
+pub use duotone::DuotoneImage;
+pub use levels::AutoLevels;
 pub use list::ListFiles;
+pub use lut::LutImage;
+pub use mask::MaskImage;
+pub use orient::BakeOrientation;
+pub use output::OutputOptions;
 pub use prep::PrepareImage;
+pub use quantize::QuantizeImage;
 pub use scale::ScaleImage;
+pub use split::SplitImage;
+pub use strip::StripMetadata;
+pub use summary::RunSummary;
+pub use thumb::ExtractThumb;
+pub use timestamp::TimestampImage;
+pub use trim::TrimImage;
+pub use vignette::VignetteImage;
+
+/// Options controlling the execution of operations, independent of the operation itself.
+#[derive(Debug, Default, Clone)]
+pub struct RunOptions {
+    /// Continue processing remaining files after a failure, and report all errors at the end.
+    pub keep_going: bool,
+    /// Only report planned outputs, without reading or writing images.
+    pub dry_run: bool,
+    /// Skip existing output files instead of overwriting them.
+    pub no_clobber: bool,
+    /// Print input and output path, size and processing time for every file.
+    pub verbose: bool,
+    /// Don't show a progress bar.
+    pub quiet: bool,
+    /// Minimum number of files per parallel task. Automatic if `None`.
+    pub chunk_size: Option<usize>,
+    /// Order of input files given by `--sort`, for operations combining multiple inputs.
+    pub sort: Option<SortOrder>,
+    /// Collects totals of the run, for `--summary-json`.
+    pub summary: Option<Arc<RunSummary>>,
+}
+
+/// An output file written by an `ImageIoOperation`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProcessedFile {
+    /// Path of the output file.
+    pub output: PathBuf,
+    /// Output image width in px.
+    pub width: u32,
+    /// Output image height in px.
+    pub height: u32,
+}
+
+impl ProcessedFile {
+    /// Creates the line printed for a processed file with `--verbose`.
+    pub fn verbose_line(&self, input: &PathBuf, elapsed: Duration) -> String {
+        format!(
+            "{:?} -> {:?} ({}x{}px, took {}ms)",
+            input,
+            self.output,
+            self.width,
+            self.height,
+            elapsed.as_millis()
+        )
+    }
+}
 
 /// Super-trait for all image operations.
 pub trait ImageOperation {
-    fn execute(&self, files: &[PathBuf]) -> Result<(), Box<dyn Error>>;
+    fn execute(&self, files: &[InputFile], options: &RunOptions) -> Result<(), Box<dyn Error>>;
 }
 
 /// Trait for image operations that produce one output image per input image.
 pub trait ImageIoOperation: ImageOperation + Send + Sync {
-    fn output(&self) -> &str;
-    fn quality(&self) -> &Option<u8>;
+    fn output(&self) -> &OutputOptions;
+    /// Calculates the output image size from the input image size, without processing the image.
+    fn output_size(&self, width: u32, height: u32) -> Result<(u32, u32), Box<dyn Error>>;
+    /// Whether `output_size` is exact, i.e. does not depend on the image content. Default: `true`.
+    fn exact_output_size(&self) -> bool {
+        true
+    }
+    /// Optional description of the computed layout of a variant for an input image size, for dry runs.
+    fn layout_info(&self, _width: u32, _height: u32, _variant: usize) -> Option<String> {
+        None
+    }
+    /// Number of output images for an input image size, e.g. one per print format. Default: `1`.
+    fn variants(&self, _width: u32, _height: u32) -> usize {
+        1
+    }
+    /// Output path tokens of a variant for an input image size, e.g. `{format}`.
+    fn variant_tokens(
+        &self,
+        _width: u32,
+        _height: u32,
+        _variant: usize,
+    ) -> HashMap<&'static str, String> {
+        HashMap::new()
+    }
+    /// Calculates the output image size of a variant. Default: `output_size`.
+    fn variant_size(
+        &self,
+        width: u32,
+        height: u32,
+        _variant: usize,
+    ) -> Result<(u32, u32), Box<dyn Error>> {
+        self.output_size(width, height)
+    }
+    /// Processes the image for a variant. Default: `process_image`.
+    fn process_variant(
+        &self,
+        image: &DynamicImage,
+        file: &PathBuf,
+        _variant: usize,
+    ) -> Result<DynamicImage, Box<dyn Error>> {
+        self.process_image(image, file)
+    }
+    /// Optional JSON description of the edit applied to an input image,
+    /// written to `<output>.edit.json` for non-destructive workflows. See `edit_log_json`.
+    fn edit_log(&self, _input: &DynamicImage) -> Option<String> {
+        None
+    }
+    /// Optional EXIF data (TIFF structure) to embed into JPEG outputs of an input file,
+    /// for the given output image size.
+    fn exif(&self, _file: &PathBuf, _width: u32, _height: u32) -> Option<Vec<u8>> {
+        None
+    }
+    /// Whether the output contains transparency, and thus can't be saved as JPEG.
+    fn requires_alpha(&self) -> bool {
+        false
+    }
     fn process_image(
         &self,
         image: &DynamicImage,
         file: &PathBuf,
     ) -> Result<DynamicImage, Box<dyn Error>>;
-    fn execute(&self, files: &[PathBuf]) -> Result<(), Box<dyn Error>> {
-        let bar = ProgressBar::new(files.len() as u64);
-        files
-            .par_iter()
-            .map(|file: &PathBuf| {
-                bar.inc(1);
+    /// Optional sidecar file of a variant for an input image size, as output path pattern
+    /// and content, e.g. `--svg-guides`. Written next to the image, with the same output rules.
+    fn sidecar(
+        &self,
+        _width: u32,
+        _height: u32,
+        _variant: usize,
+    ) -> Result<Option<(&str, String)>, Box<dyn Error>> {
+        Ok(None)
+    }
+    /// Resolves the path of an output pattern for a variant of an input file,
+    /// applying the path tokens, `--output-dir` and `--preserve-tree`.
+    #[allow(clippy::too_many_arguments)]
+    fn pattern_out_path(
+        &self,
+        pattern: &str,
+        ext_map: &[ExtMapping],
+        file: &InputFile,
+        index: usize,
+        variant: usize,
+        in_size: (u32, u32),
+        width: u32,
+        height: u32,
+    ) -> Result<PathBuf, ImageFormatError> {
+        let output = self.output();
+        let mut tokens = out_path_tokens(pattern, &file.path, index, width, height);
+        tokens.extend(self.variant_tokens(in_size.0, in_size.1, variant));
+        let root = if output.preserve_tree || output.output_dir.is_some() {
+            Some(&file.root)
+        } else {
+            None
+        };
+        let path = match PathUtil::out_path(&file.path, pattern, &tokens, ext_map, root) {
+            Some(p) => p,
+            None => {
+                return Err(ImageFormatError(format!(
+                    "Unable to generate output file name from {:?}",
+                    pattern
+                )))
+            }
+        };
+        Ok(match &output.output_dir {
+            Some(dir) => dir.join(path),
+            None => path,
+        })
+    }
+    /// Resolves the output path of a variant of an input file,
+    /// from the input image size `in_size` and the output image size.
+    fn resolve_out_path(
+        &self,
+        file: &InputFile,
+        index: usize,
+        variant: usize,
+        in_size: (u32, u32),
+        width: u32,
+        height: u32,
+    ) -> Result<PathBuf, ImageFormatError> {
+        let output = self.output();
+        let path = match self.mapped_out_path(file)? {
+            Some(path) => path,
+            None => self.pattern_out_path(
+                &output.output,
+                &output.map_ext,
+                file,
+                index,
+                variant,
+                in_size,
+                width,
+                height,
+            )?,
+        };
+        let path = match &output.output_format {
+            Some(format) => format.apply(&path),
+            None => path,
+        };
+        if self.requires_alpha() {
+            let ext = PathUtil::extension(&path).unwrap_or_default();
+            if ext == "jpg" || ext == "jpeg" {
+                return Err(ImageFormatError(format!(
+                    "Unable to save transparent image to {:?}: JPEG does not support transparency, use PNG or WebP",
+                    path
+                )));
+            }
+        }
+        Ok(path)
+    }
+    /// The output path given for a file by `--map-csv`, if any.
+    fn mapped_out_path(&self, file: &InputFile) -> Result<Option<PathBuf>, ImageFormatError> {
+        let map = match self.output().path_map() {
+            Ok(Some(map)) => map,
+            Ok(None) => return Ok(None),
+            Err(e) => {
+                return Err(ImageFormatError(format!(
+                    "Unable to read output mapping {:?}: {}",
+                    self.output().map_csv,
+                    e
+                )))
+            }
+        };
+        Ok(file
+            .path
+            .absolutize()
+            .ok()
+            .and_then(|path| map.get(&*path).cloned()))
+    }
+    fn plan_file(&self, input_file: &InputFile, index: usize) -> Result<(), ImageFormatError> {
+        let file = &input_file.path;
+        let (in_width, in_height) = match image::image_dimensions(file) {
+            Ok(d) => d,
+            Err(e) => {
+                return Err(ImageFormatError(format!(
+                    "Unable to read image {:?} ({:?})",
+                    file, e
+                )));
+            }
+        };
+        for variant in 0..self.variants(in_width, in_height) {
+            let (width, height) = match self.variant_size(in_width, in_height, variant) {
+                Ok(s) => s,
+                Err(e) => {
+                    return Err(ImageFormatError(format!(
+                        "Unable to process image {:?}: {:?}",
+                        file,
+                        e.to_string()
+                    )));
+                }
+            };
+            let out_path = self.resolve_out_path(
+                input_file,
+                index,
+                variant,
+                (in_width, in_height),
+                width,
+                height,
+            )?;
 
-                let out_path = match PathUtil::out_path(file, &self.output()) {
-                    Some(p) => p,
-                    None => {
-                        return Err(ImageFormatError(format!(
-                            "Unable to generate output file name from {:?}",
-                            self.output()
-                        )));
-                    }
-                };
-
-                let input = match image::open(file) {
-                    Ok(i) => i,
-                    Err(e) => {
-                        return Err(ImageFormatError(format!(
-                            "Unable to read image {:?} ({:?})",
-                            file, e
-                        )));
-                    }
-                };
-
-                let output = match self.process_image(&input, &file) {
-                    Ok(o) => o,
-                    Err(e) => {
-                        return Err(ImageFormatError(format!(
-                            "Unable to process image {:?}: {:?}",
-                            file,
-                            e.to_string()
-                        )));
-                    }
-                };
-
-                match ImageUtil::save_image(output, &out_path, self.quality().unwrap_or(95)) {
-                    Ok(_) => {}
-                    Err(e) => {
-                        return Err(ImageFormatError(format!(
-                            "Unable to save image to {:?}: {:?}",
-                            out_path,
-                            e.to_string()
-                        )));
+            let mut message = format!("{:?} -> {:?} ({}x{}px)", file, out_path, width, height);
+            if let Some(layout) = self.layout_info(in_width, in_height, variant) {
+                message.push_str(&format!("\n    {}", layout));
+            }
+            println!("{}", message);
+        }
+        Ok(())
+    }
+    /// The output paths of all variants of a file, if they can be resolved without
+    /// processing the image and all of them exist already.
+    fn existing_outputs(&self, input_file: &InputFile, index: usize) -> Option<Vec<PathBuf>> {
+        if !self.exact_output_size() {
+            return None;
+        }
+        let (in_width, in_height) = image::image_dimensions(&input_file.path).ok()?;
+        let mut paths = Vec::new();
+        for variant in 0..self.variants(in_width, in_height) {
+            let (width, height) = self.variant_size(in_width, in_height, variant).ok()?;
+            let path = self
+                .resolve_out_path(
+                    input_file,
+                    index,
+                    variant,
+                    (in_width, in_height),
+                    width,
+                    height,
+                )
+                .ok()?;
+            if !path.exists() {
+                return None;
+            }
+            paths.push(path);
+        }
+        Some(paths)
+    }
+    /// Processes a single file. Returns the written outputs, one per variant that was not skipped.
+    fn process_file(
+        &self,
+        input_file: &InputFile,
+        index: usize,
+        options: &RunOptions,
+    ) -> Result<Vec<ProcessedFile>, ImageFormatError> {
+        let file = &input_file.path;
+        // Skip before decoding if all outputs exist already
+        if options.no_clobber {
+            if let Some(paths) = self.existing_outputs(input_file, index) {
+                for out_path in paths {
+                    let warning =
+                        format!("Skipping {:?}, output file {:?} exists.", file, out_path);
+                    log::info!("{}", warning);
+                    if let Some(summary) = &options.summary {
+                        summary.add_skipped(warning);
                     }
-                };
+                }
+                return Ok(vec![]);
+            }
+        }
+        // Palette and grayscale images are converted, for a consistent pixel format
+        let input = match image::open(file) {
+            Ok(i) => ImageUtil::normalize_color(i),
+            Err(e) => {
+                return Err(ImageFormatError(format!(
+                    "Unable to read image {:?} ({:?})",
+                    file, e
+                )));
+            }
+        };
 
-                Ok(())
-            })
-            .collect::<Result<(), ImageFormatError>>()?;
-        bar.finish_and_clear();
+        let mut processed = Vec::new();
+        for variant in 0..self.variants(input.width(), input.height()) {
+            if let Some(p) =
+                self.process_variant_file(&input, input_file, index, variant, options)?
+            {
+                processed.push(p);
+            }
+        }
+        Ok(processed)
+    }
+    /// Processes and saves a variant of a loaded input image.
+    /// Returns the written output, or `None` if the output was skipped.
+    fn process_variant_file(
+        &self,
+        input: &DynamicImage,
+        input_file: &InputFile,
+        index: usize,
+        variant: usize,
+        options: &RunOptions,
+    ) -> Result<Option<ProcessedFile>, ImageFormatError> {
+        let file = &input_file.path;
+        let (in_width, in_height) = input.dimensions();
+        let output = match self.process_variant(input, file, variant) {
+            Ok(o) => o,
+            Err(e) => {
+                return Err(ImageFormatError(format!(
+                    "Unable to process image {:?}: {:?}",
+                    file,
+                    e.to_string()
+                )));
+            }
+        };
+
+        let (out_width, out_height) = output.dimensions();
+        let out_path = self.resolve_out_path(
+            input_file,
+            index,
+            variant,
+            (in_width, in_height),
+            out_width,
+            out_height,
+        )?;
+
+        if options.no_clobber && out_path.exists() {
+            let warning = format!("Skipping {:?}, output file {:?} exists.", file, out_path);
+            log::info!("{}", warning);
+            if let Some(summary) = &options.summary {
+                summary.add_skipped(warning);
+            }
+            return Ok(None);
+        }
+
+        if out_path.exists() {
+            log::debug!("Overwriting output file {:?}.", out_path);
+        }
+        log::debug!(
+            "Writing {:?} ({}x{}px) to {:?} ({}x{}px).",
+            file,
+            in_width,
+            in_height,
+            out_path,
+            out_width,
+            out_height
+        );
+
+        let mut save_options = self.output().save_options(!options.no_clobber);
+        save_options.exif = self.exif(file, out_width, out_height);
+        if self.output().embed_name {
+            save_options.original_name = PathUtil::name(file);
+        }
+        save_options.icc = match self.output().icc_data() {
+            Ok(icc) => icc,
+            Err(e) => {
+                return Err(ImageFormatError(format!(
+                    "Unable to load ICC profile: {:?}",
+                    e.to_string()
+                )))
+            }
+        };
+        match ImageUtil::save_image(output, &out_path, &save_options) {
+            Ok(_) => {}
+            Err(e) => {
+                return Err(ImageFormatError(format!(
+                    "Unable to save image to {:?}: {:?}",
+                    out_path,
+                    e.to_string()
+                )));
+            }
+        };
+        if let Some(summary) = &options.summary {
+            summary.add_output(&out_path);
+        }
+
+        self.write_sidecar(input_file, index, variant, (in_width, in_height), options)?;
+
+        if let Some(log) = self.edit_log(input) {
+            let mut log_path = out_path.clone().into_os_string();
+            log_path.push(".edit.json");
+            if let Err(e) = std::fs::write(&log_path, log) {
+                return Err(ImageFormatError(format!(
+                    "Unable to write edit log to {:?}: {:?}",
+                    log_path,
+                    e.to_string()
+                )));
+            }
+        }
+
+        Ok(Some(ProcessedFile {
+            output: out_path,
+            width: out_width,
+            height: out_height,
+        }))
+    }
+    /// Writes the sidecar file of a variant, if any, unless it exists and `--no-clobber` is given.
+    fn write_sidecar(
+        &self,
+        input_file: &InputFile,
+        index: usize,
+        variant: usize,
+        in_size: (u32, u32),
+        options: &RunOptions,
+    ) -> Result<(), ImageFormatError> {
+        let file = &input_file.path;
+        let (pattern, content) = match self.sidecar(in_size.0, in_size.1, variant) {
+            Ok(Some(sidecar)) => sidecar,
+            Ok(None) => return Ok(()),
+            Err(e) => {
+                return Err(ImageFormatError(format!(
+                    "Unable to process image {:?}: {:?}",
+                    file,
+                    e.to_string()
+                )))
+            }
+        };
+        let (width, height) = match self.variant_size(in_size.0, in_size.1, variant) {
+            Ok(size) => size,
+            Err(e) => {
+                return Err(ImageFormatError(format!(
+                    "Unable to process image {:?}: {:?}",
+                    file,
+                    e.to_string()
+                )))
+            }
+        };
+        let path = self.pattern_out_path(
+            pattern,
+            &[],
+            input_file,
+            index,
+            variant,
+            in_size,
+            width,
+            height,
+        )?;
+        if options.no_clobber && path.exists() {
+            log::info!("Skipping sidecar file {:?}, file exists.", path);
+            return Ok(());
+        }
+        let result = match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => std::fs::create_dir_all(parent),
+            _ => Ok(()),
+        };
+        if let Err(e) = result.and_then(|_| std::fs::write(&path, content)) {
+            return Err(ImageFormatError(format!(
+                "Unable to write sidecar file {:?}: {:?}",
+                path,
+                e.to_string()
+            )));
+        }
         Ok(())
     }
+    fn execute(&self, files: &[InputFile], options: &RunOptions) -> Result<(), Box<dyn Error>> {
+        // With an output directory, all files share the same input root
+        let rooted: Vec<_>;
+        let files = if self.output().output_dir.is_some() {
+            let root = self.output().input_root.clone().unwrap_or_else(|| {
+                let paths: Vec<_> = files.iter().map(|f| f.path.clone()).collect();
+                PathUtil::common_root(&paths)
+            });
+            rooted = files
+                .iter()
+                .map(|f| InputFile::new(f.path.clone(), root.clone()))
+                .collect();
+            &rooted[..]
+        } else {
+            files
+        };
+        let bar = progress_bar(files.len(), options);
+        let chunk_size = options.chunk_size.unwrap_or(1).max(1);
+        // Sequence numbers follow the order of the input files, independent of parallel processing
+        let run = |(index, file): (usize, &InputFile)| {
+            let index = index + 1;
+            bar.set_message(&file.path.to_string_lossy());
+            if options.dry_run {
+                self.plan_file(file, index)?;
+                bar.inc(1);
+                return Ok(());
+            }
+            let start = Instant::now();
+            let processed = self.process_file(file, index, options)?;
+            bar.inc(1);
+            if options.verbose {
+                // Lock stdout to keep lines intact under parallel processing
+                let stdout = io::stdout();
+                let mut out = stdout.lock();
+                for processed in processed {
+                    let _ = writeln!(
+                        out,
+                        "{}",
+                        processed.verbose_line(&file.path, start.elapsed())
+                    );
+                }
+            }
+            Ok(())
+        };
+        if options.keep_going {
+            let errors: Vec<_> = files
+                .par_iter()
+                .enumerate()
+                .with_min_len(chunk_size)
+                .map(run)
+                .filter_map(|result| result.err())
+                .collect();
+            bar.finish_and_clear();
+            if let Some(summary) = &options.summary {
+                summary.add_errors(&errors);
+            }
+            report_errors(files.len(), errors)
+        } else {
+            let result = files
+                .par_iter()
+                .enumerate()
+                .with_min_len(chunk_size)
+                .map(run)
+                .collect::<Result<(), ImageFormatError>>();
+            bar.finish_and_clear();
+            if let (Err(e), Some(summary)) = (&result, &options.summary) {
+                summary.add_errors(std::slice::from_ref(e));
+            }
+            Ok(result?)
+        }
+    }
 }
 
 /// Trait for image operations that do something for every supplied path.
 pub trait PathIterOperation: ImageOperation + Send + Sync {
     fn process_path(&self, path: &PathBuf) -> Result<(), Box<dyn Error>>;
-    fn execute(&self, files: &[PathBuf]) -> Result<(), Box<dyn Error>> {
-        files
-            .par_iter()
-            .map(|file: &PathBuf| {
-                match self.process_path(&file) {
-                    Ok(_) => {}
-                    Err(e) => {
-                        return Err(ImageFormatError(format!(
-                            "Unable to process path {:?}: {:?}",
-                            file,
-                            e.to_string()
-                        )));
-                    }
-                }
+    fn process_file(&self, file: &PathBuf) -> Result<(), ImageFormatError> {
+        match self.process_path(file) {
+            Ok(_) => Ok(()),
+            Err(e) => Err(ImageFormatError(format!(
+                "Unable to process path {:?}: {:?}",
+                file,
+                e.to_string()
+            ))),
+        }
+    }
+    fn execute(&self, files: &[InputFile], options: &RunOptions) -> Result<(), Box<dyn Error>> {
+        if options.keep_going {
+            let errors: Vec<_> = files
+                .par_iter()
+                .map(|file: &InputFile| self.process_file(&file.path))
+                .filter_map(|result| result.err())
+                .collect();
+            report_errors(files.len(), errors)
+        } else {
+            files
+                .par_iter()
+                .map(|file: &InputFile| self.process_file(&file.path))
+                .collect::<Result<(), ImageFormatError>>()?;
+            Ok(())
+        }
+    }
+}
 
-                Ok(())
-            })
-            .collect::<Result<(), ImageFormatError>>()?;
+/// Creates a progress bar showing the current file, throughput and ETA.
+/// Hidden for `--quiet`, and by `indicatif` if stderr is not a terminal.
+fn progress_bar(len: usize, options: &RunOptions) -> ProgressBar {
+    if options.quiet {
+        return ProgressBar::hidden();
+    }
+    let bar = ProgressBar::new(len as u64);
+    bar.set_style(
+        ProgressStyle::default_bar()
+            .template("{bar:30} {pos}/{len} ({per_sec}, ETA {eta}) {wide_msg}")
+            .progress_chars("=> "),
+    );
+    bar
+}
+
+/// Creates the tokens for output path patterns, in addition to `*`, `{stem}` and `{ext}`:
+///
+/// * `{n}`: zero-padded sequence number, in order of the input files
+/// * `{w}`, `{h}`: output image dimensions in pixels
+/// * `{date}`: EXIF `DateTimeOriginal`, as `YYYY-MM-DD_hh-mm-ss`, or `nodate` if not available
+fn out_path_tokens(
+    pattern: &str,
+    file: &PathBuf,
+    index: usize,
+    width: u32,
+    height: u32,
+) -> HashMap<&'static str, String> {
+    let mut tokens = HashMap::new();
+    tokens.insert("n", format!("{:04}", index));
+    tokens.insert("w", width.to_string());
+    tokens.insert("h", height.to_string());
+    if pattern.contains("{date}") {
+        let date = ImageUtil::get_exif_map(file)
+            .ok()
+            .and_then(|exif| exif.get("DateTimeOriginal").cloned())
+            .map_or("nodate".to_string(), |date| {
+                date.replace(":", "-").replace(" ", "_")
+            });
+        tokens.insert("date", date);
+    }
+    tokens
+}
+
+/// Creates the JSON edit log of a source image size, the kept region `(x, y, width, height)`
+/// of the source, and the output image size.
+pub(crate) fn edit_log_json(
+    operation: &str,
+    source: (u32, u32),
+    crop: (u32, u32, u32, u32),
+    output: (u32, u32),
+) -> String {
+    format!(
+        concat!(
+            "{{\n",
+            "  \"operation\": \"{}\",\n",
+            "  \"source\": {{ \"width\": {}, \"height\": {} }},\n",
+            "  \"crop\": {{ \"x\": {}, \"y\": {}, \"width\": {}, \"height\": {} }},\n",
+            "  \"output\": {{ \"width\": {}, \"height\": {} }}\n",
+            "}}\n"
+        ),
+        operation, source.0, source.1, crop.0, crop.1, crop.2, crop.3, output.0, output.1
+    )
+}
+
+/// Prints all collected per-file errors and a summary.
+/// Returns an error if any file failed.
+fn report_errors(total: usize, errors: Vec<ImageFormatError>) -> Result<(), Box<dyn Error>> {
+    for e in errors.iter() {
+        log::error!("{}", e);
+    }
+    log::info!(
+        "Processed {} files: {} succeeded, {} failed.",
+        total,
+        total - errors.len(),
+        errors.len()
+    );
+    if errors.is_empty() {
         Ok(())
+    } else {
+        Err(Box::new(ImageFormatError(format!(
+            "{} of {} files failed",
+            errors.len(),
+            total
+        ))))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::op::{ImageIoOperation, ImageOperation, RunOptions, RunSummary, ScaleImage};
+    use crate::util::test_dir;
+    use crate::util::{ImageUtil, InputFile};
+    use image::{DynamicImage, GenericImage, GenericImageView, Rgba};
+    use std::sync::Arc;
+    use std::time::Duration;
+    use structopt::StructOpt;
+
+    #[test]
+    fn progress_bar() {
+        let options = RunOptions {
+            quiet: true,
+            ..RunOptions::default()
+        };
+        let bar = super::progress_bar(3, &options);
+        assert!(bar.is_hidden());
+        bar.inc(1);
+        assert_eq!(bar.position(), 1);
+        bar.finish_and_clear();
+    }
+
+    #[test]
+    fn chunk_size() {
+        let dir = test_dir("chunk-size");
+        let inputs: Vec<_> = (0..20)
+            .map(|i| {
+                let path = dir.join(format!("in-{}.png", i));
+                DynamicImage::new_rgb8(4, 4).save(&path).unwrap();
+                InputFile::from_path(path)
+            })
+            .collect();
+
+        let output = dir.join("{chunk}-{n}.png");
+        for chunk_size in &[1, 8] {
+            let output = output
+                .to_str()
+                .unwrap()
+                .replace("{chunk}", &chunk_size.to_string());
+            let op = ScaleImage::from_iter(&["scale", "--output", &output, "--scale", "50%"]);
+            let options = RunOptions {
+                quiet: true,
+                chunk_size: Some(*chunk_size),
+                ..RunOptions::default()
+            };
+            ImageOperation::execute(&op, &inputs, &options).unwrap();
+        }
+        let missing: Vec<_> = [1, 8]
+            .iter()
+            .flat_map(|chunk| (1..=20).map(move |n| format!("{}-{:04}.png", chunk, n)))
+            .filter(|name| !dir.join(name).exists())
+            .collect();
+
+        assert!(missing.is_empty(), "Missing outputs: {:?}", missing);
+    }
+
+    #[test]
+    fn max_bytes_icc() {
+        let dir = test_dir("max-bytes-icc");
+        let input = InputFile::from_path(dir.join("in.png"));
+        let mut image = DynamicImage::new_rgb8(200, 200);
+        for (x, y, _) in image.clone().pixels() {
+            let v = ((x * 7919 + y * 104729) % 251) as u8;
+            image.put_pixel(x, y, Rgba([v, v.wrapping_mul(3), 255 - v, 255]));
+        }
+        image.save(&input.path).unwrap();
+
+        let output = dir.join("out.jpg");
+        let op = ScaleImage::from_iter(&[
+            "scale",
+            "--output",
+            output.to_str().unwrap(),
+            "--scale",
+            "100%",
+            "--icc",
+            "srgb",
+            "--max-bytes",
+            "20000",
+            "--min-quality",
+            "5",
+        ]);
+        op.process_file(&input, 1, &RunOptions::default()).unwrap();
+        let data = std::fs::read(&output).unwrap();
+
+        // The ICC profile is inserted after encoding, but must count towards the limit
+        assert!(data.windows(12).any(|w| w == b"ICC_PROFILE\0"));
+        assert!(data.len() <= 20_000, "{} bytes", data.len());
+        assert!(data.len() > 15_000, "{} bytes", data.len());
+    }
+
+    #[test]
+    fn no_clobber() {
+        let dir = test_dir("no-clobber");
+        let input = InputFile::from_path(dir.join("in.png"));
+        DynamicImage::new_rgb8(20, 10).save(&input.path).unwrap();
+        // Corrupt the image data, but keep the header readable
+        let mut data = std::fs::read(&input.path).unwrap();
+        let end = data.len() - 12;
+        for b in &mut data[end - 8..end] {
+            *b = !*b;
+        }
+        std::fs::write(&input.path, data).unwrap();
+
+        let output = dir.join("out.png");
+        std::fs::write(&output, b"existing").unwrap();
+
+        let op = ScaleImage::from_iter(&[
+            "scale",
+            "--output",
+            output.to_str().unwrap(),
+            "--scale",
+            "50%",
+        ]);
+        let summary = Arc::new(RunSummary::default());
+        let options = RunOptions {
+            no_clobber: true,
+            summary: Some(summary.clone()),
+            ..RunOptions::default()
+        };
+        // Skipped before decoding the input
+        let processed = op.process_file(&input, 1, &options).unwrap();
+
+        assert!(processed.is_empty());
+        assert_eq!(std::fs::read(&output).unwrap(), b"existing");
+        assert_eq!(summary.skipped(), 1);
+        assert_eq!(summary.processed(), 0);
+        assert!(op.process_file(&input, 1, &RunOptions::default()).is_err());
+    }
+
+    #[test]
+    fn output_format() {
+        let dir = test_dir("output-format");
+        let input = InputFile::from_path(dir.join("in.png"));
+        DynamicImage::new_rgb8(20, 10).save(&input.path).unwrap();
+
+        let mut outputs = vec![];
+        for (output, format) in &[("{stem}-out", "jpg"), ("{stem}-out.jpg", "png")] {
+            let output = dir.join(output);
+            let op = ScaleImage::from_iter(&[
+                "scale",
+                "--output",
+                output.to_str().unwrap(),
+                "--output-format",
+                format,
+                "--scale",
+                "50%",
+            ]);
+            let processed = op
+                .process_file(&input, 1, &RunOptions::default())
+                .unwrap()
+                .remove(0);
+            let data = std::fs::read(&processed.output).unwrap();
+            outputs.push((processed.output, data));
+        }
+
+        // Extension appended to extension-less outputs
+        assert_eq!(outputs[0].0, dir.join("in-out.jpg"));
+        assert_eq!(&outputs[0].1[..2], &[0xFF, 0xD8]);
+        // Extension replaced if it disagrees
+        assert_eq!(outputs[1].0, dir.join("in-out.png"));
+        assert_eq!(&outputs[1].1[1..4], b"PNG");
+    }
+
+    #[test]
+    fn verbose_line() {
+        let dir = test_dir("verbose");
+        let input = dir.join("in.png");
+        DynamicImage::new_rgb8(20, 10).save(&input).unwrap();
+
+        let output = dir.join("out.png");
+        let op = ScaleImage::from_iter(&[
+            "scale",
+            "--output",
+            output.to_str().unwrap(),
+            "--scale",
+            "50%",
+        ]);
+        let processed = op
+            .process_file(
+                &InputFile::from_path(input.clone()),
+                1,
+                &RunOptions::default(),
+            )
+            .unwrap()
+            .remove(0);
+
+        assert_eq!(processed.output, output);
+        let line = processed.verbose_line(&input, Duration::from_millis(42));
+        assert_eq!(
+            line,
+            format!("{:?} -> {:?} (10x5px, took 42ms)", input, output)
+        );
+    }
+
+    #[test]
+    fn indexed_input() {
+        let dir = test_dir("indexed");
+        let input = dir.join("in.png");
+
+        // 2x1 indexed PNG: opaque red and fully transparent blue
+        let file = std::io::BufWriter::new(std::fs::File::create(&input).unwrap());
+        let mut encoder = png::Encoder::new(file, 2, 1);
+        encoder.set_color(png::ColorType::Indexed);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header().unwrap();
+        writer
+            .write_chunk(*b"PLTE", &[255, 0, 0, 0, 0, 255])
+            .unwrap();
+        writer.write_chunk(*b"tRNS", &[255, 0]).unwrap();
+        writer.write_image_data(&[0, 1]).unwrap();
+        drop(writer);
+
+        let output = dir.join("out.png");
+        let op = ScaleImage::from_iter(&[
+            "scale",
+            "--output",
+            output.to_str().unwrap(),
+            "--scale",
+            "200%",
+            "--filter",
+            "nearest",
+        ]);
+        op.process_file(&InputFile::from_path(input), 1, &RunOptions::default())
+            .unwrap();
+        let result = image::open(&output).unwrap();
+
+        assert_eq!(result.color(), image::ColorType::Rgba8);
+        assert_eq!(result.get_pixel(0, 0).0, [255, 0, 0, 255]);
+        assert_eq!(result.get_pixel(3, 1).0[3], 0);
+    }
+
+    #[test]
+    fn map_csv() {
+        let dir = test_dir("map-csv");
+        let inputs: Vec<_> = ["a.png", "b.png", "c.png"]
+            .iter()
+            .map(|name| {
+                let path = dir.join(name);
+                DynamicImage::new_rgb8(4, 4).save(&path).unwrap();
+                InputFile::from_path(path)
+            })
+            .collect();
+
+        let csv = dir.join("map.csv");
+        let first = dir.join("custom").join("first.jpg");
+        let second = dir.join("second.png");
+        std::fs::create_dir_all(first.parent().unwrap()).unwrap();
+        std::fs::write(
+            &csv,
+            format!(
+                "source,target\n{},{}\n\n\"{}\",\"{}\"\n",
+                inputs[0].path.to_str().unwrap(),
+                first.to_str().unwrap(),
+                inputs[1].path.to_str().unwrap(),
+                second.to_str().unwrap(),
+            ),
+        )
+        .unwrap();
+
+        let op = ScaleImage::from_iter(&[
+            "scale",
+            "--output",
+            dir.join("*-out.png").to_str().unwrap(),
+            "--map-csv",
+            csv.to_str().unwrap(),
+            "--scale",
+            "50%",
+        ]);
+        ImageIoOperation::execute(&op, &inputs, &RunOptions::default()).unwrap();
+
+        let exists = [
+            first.is_file(),
+            second.is_file(),
+            dir.join("c-out.png").is_file(),
+        ];
+        let unmapped = dir.join("a-out.png").is_file();
+
+        assert_eq!(exists, [true, true, true]);
+        assert!(!unmapped);
+    }
+
+    #[test]
+    fn normalize_color() {
+        let gray = ImageUtil::normalize_color(DynamicImage::new_luma8(2, 2));
+        assert_eq!(gray.color(), image::ColorType::Rgb8);
+        let gray_alpha = ImageUtil::normalize_color(DynamicImage::new_luma_a8(2, 2));
+        assert_eq!(gray_alpha.color(), image::ColorType::Rgba8);
+        let gray16 = ImageUtil::normalize_color(DynamicImage::new_luma16(2, 2));
+        assert_eq!(gray16.color(), image::ColorType::Rgb16);
     }
 }
@@ -0,0 +1,136 @@
+//! Bake EXIF orientation into pixels.
+
+use crate::op::{ImageIoOperation, ImageOperation, OutputOptions, RunOptions};
+use crate::util::{ImageUtil, InputFile};
+use image::DynamicImage;
+use std::error::Error;
+use std::path::PathBuf;
+use structopt::StructOpt;
+
+/// Rotate and flip images according to their EXIF orientation, e.g. to normalize a library.
+#[derive(StructOpt, Debug)]
+pub struct BakeOrientation {
+    #[structopt(flatten)]
+    pub output: OutputOptions,
+
+    /// Keep the EXIF data for JPEG output, with the orientation reset to `1` (normal).
+    /// Without this option, EXIF data is removed.
+    #[structopt(name = "keep-exif", long)]
+    pub keep_exif: bool,
+}
+
+impl ImageOperation for BakeOrientation {
+    fn execute(&self, files: &[InputFile], options: &RunOptions) -> Result<(), Box<dyn Error>> {
+        ImageIoOperation::execute(self, files, options)
+    }
+}
+
+impl ImageIoOperation for BakeOrientation {
+    fn output(&self) -> &OutputOptions {
+        &self.output
+    }
+
+    fn output_size(&self, width: u32, height: u32) -> Result<(u32, u32), Box<dyn Error>> {
+        // The orientation is not known without the file, assume unchanged
+        Ok((width, height))
+    }
+
+    fn exact_output_size(&self) -> bool {
+        false
+    }
+
+    fn exif(&self, file: &PathBuf, width: u32, height: u32) -> Option<Vec<u8>> {
+        if !self.keep_exif {
+            return None;
+        }
+        let exif = ImageUtil::get_exif(file).ok()?;
+        match ImageUtil::exif_reoriented(&exif, width, height) {
+            Ok(data) => Some(data),
+            Err(e) => {
+                log::warn!("Unable to copy EXIF data of {:?}: {}", file, e);
+                None
+            }
+        }
+    }
+
+    fn process_image(
+        &self,
+        image: &DynamicImage,
+        file: &PathBuf,
+    ) -> Result<DynamicImage, Box<dyn Error>> {
+        let orientation = ImageUtil::exif_orientation(file);
+        Ok(ImageUtil::apply_orientation(image.clone(), orientation))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::op::{BakeOrientation, ImageOperation, RunOptions};
+    use crate::util::test_dir;
+    use crate::util::{ImageUtil, InputFile, SaveOptions};
+    use exif::{Field, In, Tag, Value};
+    use image::{DynamicImage, GenericImage, GenericImageView, Rgba};
+    use structopt::StructOpt;
+
+    #[test]
+    fn bake_orientation() {
+        let dir = test_dir("orient");
+        let input = dir.join("in.jpg");
+
+        // Orientation-6 fixture: stored 40x20 with a red top-left corner,
+        // displayed rotated by 90° clockwise
+        let mut image = DynamicImage::new_rgb8(40, 20);
+        ImageUtil::fill_image(&mut image, &[255, 255, 255, 255]);
+        for x in 0..8 {
+            for y in 0..8 {
+                image.put_pixel(x, y, Rgba([255, 0, 0, 255]));
+            }
+        }
+        let fields = [
+            Field {
+                tag: Tag::Orientation,
+                ifd_num: In::PRIMARY,
+                value: Value::Short(vec![6]),
+            },
+            Field {
+                tag: Tag::Make,
+                ifd_num: In::PRIMARY,
+                value: Value::Ascii(vec![b"print-prep".to_vec()]),
+            },
+        ];
+        let mut writer = exif::experimental::Writer::new();
+        for field in &fields {
+            writer.push_field(field);
+        }
+        let mut exif = std::io::Cursor::new(Vec::new());
+        writer.write(&mut exif, false).unwrap();
+        let options = SaveOptions {
+            exif: Some(exif.into_inner()),
+            ..SaveOptions::default()
+        };
+        ImageUtil::save_image(image, &input, &options).unwrap();
+        assert_eq!(ImageUtil::exif_orientation(&input), 6);
+
+        let output = dir.join("out-*.jpg");
+        let op = BakeOrientation::from_iter(&[
+            "orient",
+            "--output",
+            output.to_str().unwrap(),
+            "--keep-exif",
+        ]);
+        op.execute(&[InputFile::from_path(input)], &RunOptions::default())
+            .unwrap();
+
+        let out_path = dir.join("out-in.jpg");
+        let result = image::open(&out_path).unwrap();
+        let exif = ImageUtil::get_exif(&out_path).unwrap();
+
+        assert_eq!(result.dimensions(), (20, 40));
+        // The red corner is now at the top right
+        assert!(result.get_pixel(17, 2).0[1] < 50);
+        assert!(result.get_pixel(2, 2).0[1] > 200);
+        let orientation = exif.get_field(Tag::Orientation, In::PRIMARY).unwrap();
+        assert_eq!(orientation.value.get_uint(0), Some(1));
+        assert!(exif.get_field(Tag::Make, In::PRIMARY).is_some());
+    }
+}
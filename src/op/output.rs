@@ -0,0 +1,182 @@
+//! Output options shared by image operations.
+
+use crate::cli::parse;
+use crate::units::{IccProfile, OutputFormat};
+use crate::util::{ExtMapping, PathUtil, SaveOptions};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use structopt::StructOpt;
+
+/// Output paths by absolute input path.
+type PathMap = HashMap<PathBuf, PathBuf>;
+
+// Output options of operations that produce one output image per input image.
+// No doc comment here, as it would replace the help text of the flattening operation.
+#[derive(StructOpt, Debug)]
+pub struct OutputOptions {
+    /// Output path. Use `*` as placeholder for the original base file name.
+    /// Used to determine output image type. On Unix systems, this MUST be quoted!
+    ///
+    /// Further placeholders:
+    /// `{stem}` original base file name, `{ext}` original extension,
+    /// `{n}` zero-padded sequence number (see `--sort`), `{w}`/`{h}` output size in px,
+    /// `{date}` EXIF date taken. Unknown placeholders are left unchanged.
+    ///
+    /// Examples:
+    /// --output "path/to/*-out.jpg"
+    /// --output "path/to/{date}_{n}.jpg"
+    ///
+    #[structopt(verbatim_doc_comment)]
+    #[structopt(short, long)]
+    pub output: String,
+
+    /// Output image format, overriding the extension of `--output`. One of `(jpg|png|webp|tiff|bmp)`.
+    /// The extension of output paths is replaced, or appended if there is none.
+    #[structopt(name = "output-format", long, value_name = "format")]
+    pub output_format: Option<OutputFormat>,
+
+    /// Image quality for JPEG and WebP output in percent, from 1 to 100. Optional, default `95`.
+    #[structopt(short, long, parse(try_from_str = parse::parse_quality))]
+    pub quality: Option<u8>,
+
+    /// Insert JPEG restart markers every n MCU rows (8px each), to limit the spread of corruption.
+    /// Disabled by default.
+    #[structopt(name = "jpeg-restart", long, value_name = "n")]
+    pub jpeg_restart: Option<u16>,
+
+    /// Maximum file size for JPEG output, e.g. `2MB`, `500k` or `1MiB`.
+    /// Reduces the quality down to `--min-quality` to meet it, and fails if it is still too large.
+    #[structopt(name = "max-bytes", long, value_name = "size", parse(try_from_str = parse::parse_bytes))]
+    pub max_bytes: Option<u64>,
+
+    /// Lowest JPEG quality to try for `--max-bytes`, from 1 to 100. Default: `30`.
+    #[structopt(
+        name = "min-quality",
+        long,
+        value_name = "quality",
+        requires = "max-bytes",
+        parse(try_from_str = parse::parse_quality)
+    )]
+    pub min_quality: Option<u8>,
+
+    /// Downscale images that exceed `--max-bytes` even at `--min-quality`, instead of failing.
+    #[structopt(name = "max-bytes-downscale", long, requires = "max-bytes")]
+    pub max_bytes_downscale: bool,
+
+    /// Estimate the quality for `--max-bytes` on a downscaled copy, instead of repeated full encodes,
+    /// and encode with optimized Huffman tables.
+    #[structopt(name = "two-pass-jpeg", long, requires = "max-bytes")]
+    pub two_pass_jpeg: bool,
+
+    /// ICC color profile to embed into JPEG, PNG and TIFF output.
+    /// A path to an ICC file, or `srgb` for the bundled sRGB profile.
+    #[structopt(long, value_name = "path|srgb")]
+    pub icc: Option<IccProfile>,
+
+    /// Embed the input file name into JPEG and PNG output, as comment or text chunk `OriginalName`.
+    #[structopt(name = "embed-name", long)]
+    pub embed_name: bool,
+
+    /// Use lossless compression for WebP output.
+    #[structopt(long)]
+    pub lossless: bool,
+
+    /// Compression for PNG output. One of `(fast|default|best)`. Default: `default`.
+    #[structopt(name = "png-compression", long, parse(try_from_str = parse::parse_png_compression))]
+    pub png_compression: Option<png::Compression>,
+
+    /// Output extension depending on the input extension, overriding the extension of `--output`.
+    /// Can be given multiple times. Example: `--map-ext tiff=jpg`
+    #[structopt(name = "map-ext", long, value_name = "from=to", number_of_values = 1)]
+    pub map_ext: Vec<ExtMapping>,
+
+    /// Preserve the directory structure of input files below the input root,
+    /// i.e. the leading part of the `--input` pattern without wildcards.
+    #[structopt(name = "preserve-tree", long)]
+    pub preserve_tree: bool,
+
+    /// Output directory. Outputs mirror the directory structure of the inputs below `--input-root`,
+    /// with `--output` relative to the respective sub-directory.
+    #[structopt(
+        name = "output-dir",
+        long,
+        value_name = "path",
+        conflicts_with = "preserve-tree"
+    )]
+    pub output_dir: Option<PathBuf>,
+
+    /// Input root directory for `--output-dir`.
+    /// Default: the longest common directory of all input files.
+    #[structopt(
+        name = "input-root",
+        long,
+        value_name = "path",
+        requires = "output-dir"
+    )]
+    pub input_root: Option<PathBuf>,
+
+    /// CSV file with `source,target` rows, mapping input files to output paths.
+    /// Inputs not listed in the file use the `--output` pattern.
+    #[structopt(name = "map-csv", long, value_name = "file")]
+    pub map_csv: Option<PathBuf>,
+
+    #[structopt(skip)]
+    icc_data: Mutex<Option<Arc<Vec<u8>>>>,
+
+    #[structopt(skip)]
+    path_map: Mutex<Option<Arc<PathMap>>>,
+}
+
+impl OutputOptions {
+    /// Creates the options for saving images.
+    pub fn save_options(&self, overwrite: bool) -> SaveOptions {
+        SaveOptions {
+            quality: self.quality.unwrap_or(95),
+            overwrite,
+            lossless: self.lossless,
+            png_compression: self
+                .png_compression
+                .clone()
+                .unwrap_or(png::Compression::Default),
+            jpeg_restart: self.jpeg_restart,
+            max_bytes: self.max_bytes,
+            min_quality: self.min_quality.unwrap_or(30),
+            max_bytes_downscale: self.max_bytes_downscale,
+            two_pass_jpeg: self.two_pass_jpeg,
+            exif: None,
+            icc: None,
+            original_name: None,
+        }
+    }
+
+    /// The data of the ICC profile given by `--icc`, loaded on first use.
+    pub fn icc_data(&self) -> Result<Option<Arc<Vec<u8>>>, Box<dyn Error>> {
+        let icc = match &self.icc {
+            Some(icc) => icc,
+            None => return Ok(None),
+        };
+        let mut data = self.icc_data.lock().unwrap();
+        if data.is_none() {
+            *data = Some(Arc::new(icc.data()?));
+        }
+        Ok(data.clone())
+    }
+
+    /// The output paths mapped by `--map-csv`, by absolute input path, loaded on first use.
+    pub fn path_map(&self) -> Result<Option<Arc<PathMap>>, Box<dyn Error>> {
+        let file = match &self.map_csv {
+            Some(file) => file,
+            None => return Ok(None),
+        };
+        let mut map = self.path_map.lock().unwrap();
+        if map.is_none() {
+            let reader = BufReader::new(File::open(file)?);
+            *map = Some(Arc::new(PathUtil::read_path_map(reader)?));
+        }
+        Ok(map.clone())
+    }
+}
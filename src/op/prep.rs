@@ -1,19 +1,25 @@
 //! Prepare images for printing.
 
 use crate::cli::parse;
-use crate::op::{ImageIoOperation, ImageOperation};
+use crate::op::{ImageIoOperation, ImageOperation, OutputOptions, RunOptions};
 use crate::units::color::Color;
-use crate::units::{format, FreeSize, Length, LengthUnit, ScaleMode};
-use crate::units::{Borders, FixSize};
-use crate::util::ImageUtil;
+use crate::units::format::PageFormat;
+use crate::units::{format, Downscale, FreeSize, Length, LengthUnit, ScaleMode};
+use crate::units::{Borders, CropMarks, FixSize, Overlay, OverlayPosition, Stroke, TextPosition};
+use crate::util::{CastUtil, ChartUtil, ImageUtil, InputFile, PathUtil};
+use crate::OperationParametersError;
 use image::imageops::FilterType;
-use image::{DynamicImage, GenericImage, GenericImageView, Rgba};
+use image::{DynamicImage, GenericImage, GenericImageView, Rgba, RgbaImage};
 use imageproc::rect::Rect;
 use std::collections::HashMap;
 use std::error::Error;
 use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
 use structopt::StructOpt;
 
+/// Effective image resolution below which a warning is printed, without `--min-dpi`.
+const WARN_DPI: f64 = 150.0;
+
 /// Prepare images for printing (add cut marks, 'mats', test patterns, EXIF information, ...).
 ///
 /// <pre>
@@ -36,53 +42,96 @@ use structopt::StructOpt;
 ///     |____|______________________________|____|
 /// </pre>
 #[doc(test(ignore))]
-#[structopt(verbatim_doc_comment)]
 #[allow(dead_code)]
 #[derive(StructOpt, Debug)]
+#[structopt(verbatim_doc_comment)]
 pub struct PrepareImage {
-    /// Output path. Use `*` as placeholder for the original base file name.
-    /// Used to determine output image type. On Unix systems, this MUST be quoted!
-    ///
-    /// Examples:
-    /// --output "path/to/*-out.jpg"
-    ///
-    #[structopt(verbatim_doc_comment)]
-    #[structopt(short, long)]
-    pub output: String,
-
-    /// Image quality for JPEG output in percent. Optional, default `95`.
-    #[structopt(short, long)]
-    pub quality: Option<u8>,
+    #[structopt(flatten)]
+    pub output: OutputOptions,
 
     /// Image resolution. Default `300`.
     #[structopt(short, long)]
     pub dpi: Option<f64>,
 
+    /// Minimum effective resolution of the printed image, i.e. source pixels per inch of print.
+    /// Images below fail. Without this option, a warning is printed below `150`.
+    /// Alias: `--min-ppi`.
+    #[structopt(name = "min-dpi", long, alias = "min-ppi", value_name = "dpi")]
+    pub min_dpi: Option<f64>,
+
     /// Cut marks with offset. Format <line-width>/<offset>. Use alternative to `--cut-frame`.
     #[structopt(name = "cut-marks", long, value_name = "w/off")]
     pub cut_marks: Option<FreeSize>,
 
+    /// Corner crop marks in the printer's style: short marks on the extensions of the trim edges,
+    /// starting at an offset outside each corner. Format <length>/<offset>/<gap>.
+    /// With a non-zero gap, a second pair of marks is drawn `gap` further out, e.g. for bleed.
+    /// Use alternative to `--cut-marks`. Example: `--crop-marks 5mm/3mm`.
+    #[structopt(name = "crop-marks", long, value_name = "len/off/gap")]
+    pub crop_marks: Option<CropMarks>,
+
     /// Cut frame. Format <line-width>/<extend>. Use alternative to `--cut-marks`.
     #[structopt(name = "cut-frame", long, value_name = "w/off")]
     pub cut_frame: Option<FreeSize>,
 
+    /// Short marks at the middle of each cut edge, e.g. for cutters with edge guides.
+    /// Marks extend outwards from the cut edge. Format <length>/<line-width>. Default: `3mm/1px`.
+    #[structopt(name = "edge-center-marks", long, value_name = "len/w")]
+    pub edge_center_marks: Option<FreeSize>,
+
+    /// Writes the layout (page, trim and image rectangles, cut marks) as SVG at the physical
+    /// page size, e.g. for proofing in a vector editor. Written next to the image output,
+    /// placeholders, `--output-dir` and `--no-clobber` as for `--output`.
+    /// Example: `--svg-guides "path/to/*-guides.svg"`.
+    #[structopt(name = "svg-guides", long, value_name = "path")]
+    pub svg_guides: Option<String>,
+
     /// Cut marks, frame and exif color. Default: black.
     #[structopt(long, value_name = "color")]
     pub color: Option<Color>,
 
     /// Filter type for image scaling.
-    /// One of `(nearest|linear|cubic|gauss|lanczos)`.
+    /// One of `(nearest|triangle|catmullrom|gaussian|lanczos3)`,
+    /// or an alias: `box` for `nearest` (point sampling, without averaging),
+    /// `linear` and `bilinear` for `triangle`, `cubic` and `bicubic` for `catmullrom`,
+    /// `gauss` for `gaussian`, `lanczos` for `lanczos3`.
     /// Default: `cubic`.
     #[structopt(short, long, parse(try_from_str = parse::parse_filter_type))]
     pub filter: Option<FilterType>,
 
-    /// Print format `width/height`.
+    /// Print format `width/height`, or `auto`.
     /// Formats in cm are converted to exact print formats in inches.
     /// Examples: `15cm/10cm`, `6in/4in`, `6000px/4000px`.
     ///
     /// To use an exact size given in cm, use floating point numbers, e.g. `15.0cm/10.0cm`.
-    #[structopt(long, value_name = "w/h")]
-    pub format: FixSize,
+    ///
+    /// With `auto`, the format has the aspect ratio of each image, with the size given by `--long-edge`.
+    ///
+    /// Can be given multiple times, to write one output per format.
+    /// Then, `--output` must contain the placeholder `{format}`, e.g. `15cmx10cm`.
+    #[structopt(
+        long,
+        value_name = "w/h",
+        number_of_values = 1,
+        required_unless = "fit-format"
+    )]
+    pub format: Vec<PageFormat>,
+
+    /// Use the known print format with the aspect ratio closest to each image, instead of `--format`.
+    /// The chosen format is printed for each file. See `--list-formats` for the known formats.
+    /// Alias: `--auto-format`.
+    #[structopt(
+        name = "fit-format",
+        long,
+        alias = "auto-format",
+        conflicts_with = "format"
+    )]
+    pub fit_format: bool,
+
+    /// Length of the longer edge of the print format for `--format auto`.
+    /// Example: `6in`.
+    #[structopt(name = "long-edge", long, value_name = "length")]
+    pub long_edge: Option<Length>,
 
     /// Maximum image size, excl. padding.
     #[structopt(name = "image-size", long, value_name = "w/h")]
@@ -93,10 +142,12 @@ pub struct PrepareImage {
     pub framed_size: Option<FixSize>,
 
     /// Padding between image and cut marks.
+    /// Can be given in `%` of the page size.
     #[structopt(long, value_name = "tp/rt/bm/lt")]
     pub padding: Option<Borders>,
 
     /// Minimum margins around cut marks.
+    /// Can be given in `%` of the page size, e.g. `5%`.
     #[structopt(long, value_name = "tp/rt/bm/lt")]
     pub margins: Option<Borders>,
 
@@ -109,92 +160,217 @@ pub struct PrepareImage {
     #[structopt(name = "border-color", long, value_name = "color")]
     pub border_color: Option<Color>,
 
+    /// Mat board around the image, like a passe-partout. Format <width>/<color>.
+    /// Drawn inside the padding, over the border. Example: `--mat 2cm/white`.
+    #[structopt(long, value_name = "w/color")]
+    pub mat: Option<Stroke>,
+
+    /// Bevel around the image, like the cut edge of a mat board. Format <width>/<color>.
+    /// Top and left edges use the color, bottom and right edges a darker shade.
+    /// Example: `--mat-bevel 1mm/245/240/230`.
+    #[structopt(name = "mat-bevel", long, value_name = "w/color")]
+    pub mat_bevel: Option<Stroke>,
+
     /// Enable incremental scaling.
-    /// For scaling to small sizes, scales down in multiple steps, to 50% per step, averaging over 2x2 pixels.
+    /// For scaling to small sizes, scales down in multiple steps, to 50% per step, see `--downscale`.
     #[structopt(long)]
     pub incremental: bool,
 
+    /// Half-size steps for `--incremental`. One of `(box|filtered)`.
+    /// `box` averages over 2x2 pixels, `filtered` uses `--filter` and is sharper.
+    /// Default: `box`.
+    #[structopt(long, requires = "incremental", value_name = "step")]
+    pub downscale: Option<Downscale>,
+
     /// Background color. Default `white`.
     #[structopt(short, long, value_name = "color")]
     pub bg: Option<Color>,
 
+    /// Alpha of the background color, overriding the alpha of `--bg`.
+    /// Values below 255 force an output image with alpha channel, e.g. for a transparent mat.
+    #[structopt(name = "bg-alpha", long, value_name = "0-255")]
+    pub bg_alpha: Option<u8>,
+
     /// Prevents rotation of portrait format images
     /// (or of landscape format images if `--format` is portrait).
     #[structopt(name = "no-rotation", long)]
     pub no_rotation: bool,
 
+    /// Prints the computed layout (in px) for every file, for debugging.
+    #[structopt(name = "verbose-geometry", long)]
+    pub verbose_geometry: bool,
+
     /// Prints exif data. Formatting string.
     /// Example: --exif "{F/2}, {Exp}, ISO {ISO}, {F}"
+    /// Dates can be formatted like `{Date:%Y-%m-%d}`, with `%Y`, `%y`, `%m`, `%b`, `%d`, `%H`, `%M`, `%S`.
     /// Common abbreviations:
     /// `F/2`, `Exp`, `ISO`, `F`, `Bias`, `Date`, `Mod`.
     /// Further, all official exif tags.
+    /// Computed fields: `MP` (megapixels), `Aspect` (aspect ratio, e.g. `3:2`),
+    /// `mtime` (file modification time).
+    /// Fallbacks like `{Date|DateTimeDigitized|mtime}` use the first available field.
     #[structopt(long, value_name = "format")]
     pub exif: Option<String>,
 
     /// Size of exif font, in arbitrary units. Default: `12px`.
+    /// Reduced if the text does not fit onto the canvas.
     #[structopt(name = "exif-size", long, value_name = "size")]
     pub exif_size: Option<Length>,
 
+    /// Position of the exif text. One of `(below|above|corner)`.
+    /// `corner` places the text inside the image, in the bottom left corner.
+    /// Default: `below`.
+    #[structopt(name = "exif-position", long, value_name = "pos")]
+    pub exif_position: Option<TextPosition>,
+
+    /// Background color of a box behind the exif text, for legibility. Default: none.
+    #[structopt(name = "exif-bg", long, value_name = "color")]
+    pub exif_bg: Option<Color>,
+
     /// Prints a print control element, with the given square size and gap.
     /// Format: `<sx>/<gx>/<sy>/<gy>` or `<size>/<gap>`.
     /// Example: `10px/2px/10px/2px`
     #[structopt(name = "test-pattern", long, value_name = "sx/gx/sy/gy")]
     pub test_pattern: Option<Borders>,
 
+    /// Overlay images, composited onto the final canvas in the given order. Repeatable.
+    /// Format: `path@position@opacity`, with `position` one of
+    /// `(top-left|top-right|bottom-left|bottom-right)` or `x/y`, and optional `opacity` in percent.
+    /// Example: `--overlay logo.png@bottom-right@50 --overlay corner.png@1cm/1cm`
+    #[structopt(long, value_name = "path@pos@opacity", number_of_values = 1)]
+    pub overlay: Vec<Overlay>,
+
     #[structopt(skip)]
     fonts: crate::Fonts,
+
+    #[structopt(skip)]
+    overlay_images: Mutex<Option<Arc<Vec<RgbaImage>>>>,
 }
 
 impl ImageOperation for PrepareImage {
-    fn execute(&self, files: &[PathBuf]) -> Result<(), Box<dyn Error>> {
-        ImageIoOperation::execute(self, &files)
+    fn execute(&self, files: &[InputFile], options: &RunOptions) -> Result<(), Box<dyn Error>> {
+        ImageIoOperation::execute(self, files, options)
     }
 }
 
 impl ImageIoOperation for PrepareImage {
-    fn output(&self) -> &str {
+    fn output(&self) -> &OutputOptions {
         &self.output
     }
 
-    fn quality(&self) -> &Option<u8> {
-        &self.quality
+    fn output_size(&self, width: u32, height: u32) -> Result<(u32, u32), Box<dyn Error>> {
+        self.variant_size(width, height, 0)
+    }
+
+    fn variants(&self, _width: u32, _height: u32) -> usize {
+        if self.fit_format {
+            1
+        } else {
+            self.format.len()
+        }
+    }
+
+    fn variant_tokens(
+        &self,
+        width: u32,
+        height: u32,
+        variant: usize,
+    ) -> HashMap<&'static str, String> {
+        let mut tokens = HashMap::new();
+        let format = self
+            .page_format(width, height, variant)
+            .to_string()
+            .replace('/', "x");
+        tokens.insert("format", format);
+        tokens
+    }
+
+    fn variant_size(
+        &self,
+        width: u32,
+        height: u32,
+        variant: usize,
+    ) -> Result<(u32, u32), Box<dyn Error>> {
+        self.check()?;
+        let format = self.page_format(width, height, variant);
+        let (width, height, rotate) = self.canvas_size(&format, width, height)?;
+        self.check_layout(width, height, rotate, self.dpi.unwrap_or(300.0))?;
+        Ok((width, height))
+    }
+
+    fn layout_info(&self, width: u32, height: u32, variant: usize) -> Option<String> {
+        let dpi = self.dpi.unwrap_or(300.0);
+        let format = self.page_format(width, height, variant);
+        let (out_width, out_height, rotate) = self.canvas_size(&format, width, height).ok()?;
+        self.check_layout(out_width, out_height, rotate, dpi).ok()?;
+        let (img, framed, padding, margins) =
+            self.calc_sizes(out_width, out_height, width, height, rotate, dpi);
+        Some(format!(
+            "image: {}, framed: {}, padding: {}, margins: {}",
+            img, framed, padding, margins
+        ))
+    }
+
+    fn sidecar(
+        &self,
+        width: u32,
+        height: u32,
+        variant: usize,
+    ) -> Result<Option<(&str, String)>, Box<dyn Error>> {
+        let pattern = match &self.svg_guides {
+            Some(pattern) => pattern,
+            None => return Ok(None),
+        };
+        let dpi = self.dpi.unwrap_or(300.0);
+        let format = self.page_format(width, height, variant);
+        let (out_width, out_height, rotate) = self.canvas_size(&format, width, height)?;
+        let sizes = self.calc_sizes(out_width, out_height, width, height, rotate, dpi);
+        let image_rect = Self::image_rect(&sizes);
+        let svg = self.svg_guides(out_width, out_height, dpi, image_rect, &sizes.2);
+        Ok(Some((pattern, svg)))
     }
 
     fn process_image(
         &self,
         image: &DynamicImage,
         file: &PathBuf,
+    ) -> Result<DynamicImage, Box<dyn Error>> {
+        self.process_variant(image, file, 0)
+    }
+
+    fn process_variant(
+        &self,
+        image: &DynamicImage,
+        file: &PathBuf,
+        variant: usize,
     ) -> Result<DynamicImage, Box<dyn Error>> {
         self.check()?;
 
         let dpi = self.dpi.unwrap_or(300.0);
         let filter = self.filter.as_ref().unwrap_or(&FilterType::CatmullRom);
-        let color = self.bg.clone().unwrap_or(Color::new(255, 255, 255, 255));
-        let format = format::to_print_format(&self.format)?.to(&LengthUnit::Px, dpi);
-
-        let width = format.width().value().round() as u32;
-        let height = format.height().value().round() as u32;
-
-        let in_is_portrait = image.height() > image.width();
-        let out_is_portrait = height > width;
-        let rotate = !(self.no_rotation || in_is_portrait == out_is_portrait);
-
-        let (width, height) = if rotate {
-            (height, width)
-        } else {
-            (width, height)
-        };
+        let mut color = self.bg.clone().unwrap_or(Color::new(255, 255, 255, 255));
+        if let Some(alpha) = self.bg_alpha {
+            let [r, g, b, _] = *color.channels();
+            color = Color::new(r, g, b, alpha);
+        }
+        let format = self.page_format(image.width(), image.height(), variant);
+        if self.fit_format {
+            log::info!("{:?}: format {}", file, format);
+        }
+        let (width, height, rotate) = self.canvas_size(&format, image.width(), image.height())?;
+        self.check_layout(width, height, rotate, dpi)?;
 
         // Calculates sizes, etc.
-        let (img, _frame, padding, margins) =
-            self.calc_sizes(width, height, image.width(), image.height(), rotate, dpi);
-        let x_img = (margins.left().value() + padding.left().value()) as u32;
-        let y_img = (margins.top().value() + padding.top().value()) as u32;
-        let img_width = img.width().value() as u32;
-        let img_height = img.height().value() as u32;
+        let sizes = self.calc_sizes(width, height, image.width(), image.height(), rotate, dpi);
+        if self.verbose_geometry {
+            println!("{}", Self::geometry(file, width, height, rotate, &sizes));
+        }
+        let (img, _frame, padding, _margins) = &sizes;
+        let (x_img, y_img, img_width, img_height) = Self::image_rect(&sizes);
+        self.check_resolution(file, image.width(), image.height(), img, dpi)?;
 
         // Create empty image
-        let mut result = if image.color().has_alpha() {
+        let mut result = if image.color().has_alpha() || self.bg_alpha.is_some_and(|a| a < 255) {
             DynamicImage::new_rgba8(width, height)
         } else {
             DynamicImage::new_rgb8(width, height)
@@ -215,6 +391,7 @@ impl ImageIoOperation for PrepareImage {
             dpi,
             rotate,
         );
+        self.draw_mat(&mut result, x_img, y_img, img_width, img_height, dpi);
 
         let color = self
             .color
@@ -226,63 +403,59 @@ impl ImageIoOperation for PrepareImage {
         // Cut marks
         if let Some(m) = &self.cut_marks {
             let marks = m.to_px(dpi);
-            let lw = marks.width().as_ref().map_or(1, |l| l.value() as i32);
+            let lw = marks
+                .width()
+                .as_ref()
+                .map_or(1, |l| CastUtil::i32_from_f64(l.value(), "cut mark width"));
             let lw2 = lw / 2;
-            let offset = marks.height().as_ref().map_or(0, |l| l.value() as i32);
-            let xmin = x_img as i32 - padding.left().value() as i32;
-            let xmax = x_img as i32 + img_width as i32 + padding.right().value() as i32;
-            let ymin = y_img as i32 - padding.top().value() as i32;
-            let ymax = y_img as i32 + img_height as i32 + padding.bottom().value() as i32;
+            let offset = marks
+                .height()
+                .as_ref()
+                .map_or(0, |l| CastUtil::i32_from_f64(l.value(), "cut mark offset"));
+            let (xmin, xmax, ymin, ymax) =
+                Self::cut_bounds(x_img, y_img, img_width, img_height, padding);
 
             // Top left
-            imageproc::drawing::draw_filled_rect_mut(
-                &mut result,
-                Rect::at(0, ymin - lw2).of_size((xmin - offset) as u32, lw as u32),
-                rgba,
-            );
-            imageproc::drawing::draw_filled_rect_mut(
-                &mut result,
-                Rect::at(xmin - lw2, 0).of_size(lw as u32, (ymin - offset) as u32),
-                rgba,
-            );
+            Self::fill_rect(&mut result, 0, ymin - lw2, xmin - offset, lw, rgba);
+            Self::fill_rect(&mut result, xmin - lw2, 0, lw, ymin - offset, rgba);
 
             // Top right
-            imageproc::drawing::draw_filled_rect_mut(
+            Self::fill_rect(
                 &mut result,
-                Rect::at(xmax + offset, ymin - lw2)
-                    .of_size((width as i32 - xmax - offset) as u32, lw as u32),
-                rgba,
-            );
-            imageproc::drawing::draw_filled_rect_mut(
-                &mut result,
-                Rect::at(xmax - lw2, 0).of_size(lw as u32, (ymin - offset) as u32),
+                xmax + offset,
+                ymin - lw2,
+                width as i32 - xmax - offset,
+                lw,
                 rgba,
             );
+            Self::fill_rect(&mut result, xmax - lw2, 0, lw, ymin - offset, rgba);
 
             // Bottom left
-            imageproc::drawing::draw_filled_rect_mut(
+            Self::fill_rect(&mut result, 0, ymax - lw2, xmin - offset, lw, rgba);
+            Self::fill_rect(
                 &mut result,
-                Rect::at(0, ymax - lw2).of_size((xmin - offset) as u32, lw as u32),
-                rgba,
-            );
-            imageproc::drawing::draw_filled_rect_mut(
-                &mut result,
-                Rect::at(xmin - lw2, ymax + offset)
-                    .of_size(lw as u32, (height as i32 - ymax - offset) as u32),
+                xmin - lw2,
+                ymax + offset,
+                lw,
+                height as i32 - ymax - offset,
                 rgba,
             );
 
             // Bottom right
-            imageproc::drawing::draw_filled_rect_mut(
+            Self::fill_rect(
                 &mut result,
-                Rect::at(xmax + offset, ymax - lw2)
-                    .of_size((width as i32 - xmax - offset) as u32, lw as u32),
+                xmax + offset,
+                ymax - lw2,
+                width as i32 - xmax - offset,
+                lw,
                 rgba,
             );
-            imageproc::drawing::draw_filled_rect_mut(
+            Self::fill_rect(
                 &mut result,
-                Rect::at(xmax - lw2, ymax + offset)
-                    .of_size(lw as u32, (height as i32 - ymax - offset) as u32),
+                xmax - lw2,
+                ymax + offset,
+                lw,
+                height as i32 - ymax - offset,
                 rgba,
             );
         }
@@ -290,82 +463,125 @@ impl ImageIoOperation for PrepareImage {
         // Cut frame
         if let Some(f) = &self.cut_frame {
             let frame = f.to_px(dpi);
-            let lw = frame.width().as_ref().map_or(1, |l| l.value() as i32);
+            let lw = frame
+                .width()
+                .as_ref()
+                .map_or(1, |l| CastUtil::i32_from_f64(l.value(), "cut frame width"));
             let lw2 = lw / 2;
-            let offset = frame.height().as_ref().map_or(0, |l| l.value() as i32);
-            let xmin = x_img as i32 - padding.left().value() as i32;
-            let xmax = x_img as i32 + img_width as i32 + padding.right().value() as i32;
-            let ymin = y_img as i32 - padding.top().value() as i32;
-            let ymax = y_img as i32 + img_height as i32 + padding.bottom().value() as i32;
+            let offset = frame
+                .height()
+                .as_ref()
+                .map_or(0, |l| CastUtil::i32_from_f64(l.value(), "cut frame offset"));
+            let (xmin, xmax, ymin, ymax) =
+                Self::cut_bounds(x_img, y_img, img_width, img_height, padding);
 
             // Top
-            imageproc::drawing::draw_filled_rect_mut(
+            Self::fill_rect(
                 &mut result,
-                Rect::at(xmin - offset, ymin - lw2)
-                    .of_size(((xmax - xmin) + 2 * offset) as u32, lw as u32),
+                xmin - offset,
+                ymin - lw2,
+                (xmax - xmin) + 2 * offset,
+                lw,
                 rgba,
             );
 
             // Bottom
-            imageproc::drawing::draw_filled_rect_mut(
+            Self::fill_rect(
                 &mut result,
-                Rect::at(xmin - offset, ymax - lw2)
-                    .of_size(((xmax - xmin) + 2 * offset) as u32, lw as u32),
+                xmin - offset,
+                ymax - lw2,
+                (xmax - xmin) + 2 * offset,
+                lw,
                 rgba,
             );
 
             // Left
-            imageproc::drawing::draw_filled_rect_mut(
+            Self::fill_rect(
                 &mut result,
-                Rect::at(xmin - lw2, ymin - offset)
-                    .of_size(lw as u32, ((ymax - ymin) + 2 * offset) as u32),
+                xmin - lw2,
+                ymin - offset,
+                lw,
+                (ymax - ymin) + 2 * offset,
                 rgba,
             );
 
             // Right
-            imageproc::drawing::draw_filled_rect_mut(
+            Self::fill_rect(
                 &mut result,
-                Rect::at(xmax - lw2, ymin - offset)
-                    .of_size(lw as u32, ((ymax - ymin) + 2 * offset) as u32),
+                xmax - lw2,
+                ymin - offset,
+                lw,
+                (ymax - ymin) + 2 * offset,
                 rgba,
             );
         }
 
-        let pad_distance = Length::mm(2.0).to_px(dpi).value() as u32;
-        // EXIF data
-        if let Some(format) = &self.exif {
-            let exif = ImageUtil::get_exif_map(&file);
-            let font_size = self
-                .exif_size
-                .clone()
-                .unwrap_or_else(|| Length::px(12))
-                .to_px(dpi)
-                .value();
-            if let Ok(exif) = exif {
-                let str = self.exif_string(&format, &exif);
-                imageproc::drawing::draw_text_mut(
+        // Crop marks
+        if let Some(m) = &self.crop_marks {
+            let lw = Self::crop_mark_width(dpi);
+            let bounds = Self::cut_bounds(x_img, y_img, img_width, img_height, &padding);
+            for (x1, y1, x2, y2) in Self::crop_mark_lines(bounds, &m.to_px(dpi)) {
+                Self::fill_rect(
                     &mut result,
+                    x1.min(x2) - lw / 2,
+                    y1.min(y2) - lw / 2,
+                    (x2 - x1).abs() + lw,
+                    (y2 - y1).abs() + lw,
                     rgba,
-                    x_img, //x_img - padding.left().value() as u32 + 5,
-                    y_img + img_height + padding.bottom().value() as u32 + pad_distance,
-                    rusttype::Scale::uniform(font_size as f32),
-                    &self.fonts.default,
-                    &str,
-                )
+                );
             }
         }
 
+        // Edge center marks
+        if let Some(m) = &self.edge_center_marks {
+            let marks = m.to_px(dpi);
+            let len = marks
+                .width()
+                .clone()
+                .unwrap_or_else(|| Length::mm(3.0).to_px(dpi));
+            let len = CastUtil::i32_from_f64(len.value(), "edge mark length");
+            let lw = marks
+                .height()
+                .as_ref()
+                .map_or(1, |l| CastUtil::i32_from_f64(l.value(), "edge mark width"));
+            let lw2 = lw / 2;
+            let (xmin, xmax, ymin, ymax) =
+                Self::cut_bounds(x_img, y_img, img_width, img_height, &padding);
+            let (xmid, ymid) = ((xmin + xmax) / 2, (ymin + ymax) / 2);
+
+            // Top, bottom, left, right
+            Self::fill_rect(&mut result, xmid - lw2, ymin - len, lw, len, rgba);
+            Self::fill_rect(&mut result, xmid - lw2, ymax, lw, len, rgba);
+            Self::fill_rect(&mut result, xmin - len, ymid - lw2, len, lw, rgba);
+            Self::fill_rect(&mut result, xmax, ymid - lw2, len, lw, rgba);
+        }
+
+        let pad_distance = Length::mm(2.0).to_px(dpi).value() as u32;
+        let y_below = y_img
+            + img_height
+            + CastUtil::u32_from_f64(padding.bottom().value(), "bottom padding")
+            + pad_distance;
         // Control element
         if let Some(patt) = &self.test_pattern {
             let borders = patt.to_px(dpi);
-            let mut element = self.create_control_element(&borders);
+            let mut element = ChartUtil::control_element(
+                borders.top().value() as u32,
+                borders.right().value() as u32,
+                borders.bottom().value() as u32,
+                borders.left().value() as u32,
+            );
             //let x = x_img + img_width + padding.right().value() as u32 - 5 - element.width();
-            let x = x_img + img_width - element.width();
-            let y = y_img + img_height + padding.bottom().value() as u32 + pad_distance;
-            if result.height() < y + element.height() {
-                element = element.crop_imm(0, 0, element.width(), result.height() - y);
+            let x = CastUtil::u32_from_i32(
+                (x_img + img_width) as i32 - element.width() as i32,
+                "test pattern x position",
+            );
+            let y = y_below;
+            if y < result.height() {
+                if result.height() < y + element.height() {
+                    element = element.crop_imm(0, 0, element.width(), result.height() - y);
+                }
+                result.copy_from(&element, x, y)?;
             }
-            result.copy_from(&element, x, y)?;
         }
 
         // ***************************************
@@ -373,21 +589,64 @@ impl ImageIoOperation for PrepareImage {
         // ***************************************
         let scaled = ImageUtil::scale_image(
             image,
-            img.width().value() as u32,
-            img.height().value() as u32,
+            img_width,
+            img_height,
             &ScaleMode::Stretch,
             filter,
             &color,
-            self.incremental,
+            ImageUtil::incremental_step(self.incremental, self.downscale.as_ref()),
         )?;
 
         result.copy_from(&scaled, x_img, y_img)?;
 
+        // EXIF data, after the image as it may be drawn onto it
+        if let Some(format) = &self.exif {
+            // Without EXIF data, computed fields and the file modification time are still available
+            let mut exif = ImageUtil::get_exif_map(file).unwrap_or_default();
+            Self::add_computed_fields(&mut exif, image.width(), image.height());
+            if let Ok(mtime) = PathUtil::modified_time(file) {
+                exif.insert("mtime".to_string(), mtime);
+            }
+            let str = self.exif_string(format, &exif);
+            let image_rect = (x_img, y_img, img_width, img_height);
+            self.draw_exif(&mut result, &str, image_rect, padding, dpi);
+        }
+
+        // Overlays
+        let overlay_images = self.overlay_images()?;
+        for (overlay, image) in self.overlay.iter().zip(overlay_images.iter()) {
+            let (x, y) = match &overlay.position {
+                OverlayPosition::Corner(corner) => {
+                    let (x, y) = corner.place(width, height, image.width(), image.height(), 0);
+                    (x as i64, y as i64)
+                }
+                OverlayPosition::At(pos) => {
+                    let pos = pos.resolve(width as f64, height as f64).to_px(dpi);
+                    (pos.width().value() as i64, pos.height().value() as i64)
+                }
+            };
+            ImageUtil::composite_over(&mut result, image, x, y, overlay.opacity / 100.0);
+        }
+
         Ok(result)
     }
 }
 
 impl PrepareImage {
+    /// The overlay images, read on first use.
+    fn overlay_images(&self) -> Result<Arc<Vec<RgbaImage>>, Box<dyn Error>> {
+        let mut images = self.overlay_images.lock().unwrap();
+        if images.is_none() {
+            let loaded = self
+                .overlay
+                .iter()
+                .map(|o| image::open(&o.path).map(|img| img.to_rgba()))
+                .collect::<Result<Vec<_>, _>>()?;
+            *images = Some(Arc::new(loaded));
+        }
+        Ok(images.as_ref().unwrap().clone())
+    }
+
     fn check(&self) -> Result<(), Box<dyn Error>> {
         let mut count = 0;
         for v in [&self.image_size, &self.framed_size].iter() {
@@ -410,6 +669,57 @@ impl PrepareImage {
             ))));
         }
 
+        if self.format.contains(&PageFormat::Auto) != self.long_edge.is_some() {
+            return Err(Box::new(format::PrintFormatError(
+                "`--format auto` requires `--long-edge`, and vice versa".to_string(),
+            )));
+        }
+
+        if self.format.len() > 1 && !self.output.output.contains("{format}") {
+            return Err(Box::new(OperationParametersError(
+                "Multiple `--format` values require the placeholder `{format}` in `--output`!"
+                    .to_string(),
+            )));
+        }
+
+        let absolute_only = [
+            ("format", self.format.iter().any(|f| f.is_relative())),
+            (
+                "long-edge",
+                self.long_edge.as_ref().is_some_and(|l| l.is_relative()),
+            ),
+            (
+                "cut-marks",
+                self.cut_marks.as_ref().is_some_and(|m| m.is_relative()),
+            ),
+            (
+                "cut-frame",
+                self.cut_frame.as_ref().is_some_and(|f| f.is_relative()),
+            ),
+            (
+                "edge-center-marks",
+                self.edge_center_marks
+                    .as_ref()
+                    .is_some_and(|m| m.is_relative()),
+            ),
+            (
+                "exif-size",
+                self.exif_size.as_ref().is_some_and(|l| l.is_relative()),
+            ),
+            (
+                "test-pattern",
+                self.test_pattern.as_ref().is_some_and(|p| p.is_relative()),
+            ),
+        ];
+        for (name, relative) in absolute_only.iter() {
+            if *relative {
+                return Err(Box::new(OperationParametersError(format!(
+                    "`--{}` does not support relative lengths in `%`!",
+                    name
+                ))));
+            }
+        }
+
         if self.framed_size.is_some() && self.margins.is_some() {
             return Err(Box::new(format::PrintFormatError(format!(
                 "Invalid combination of print format options. \
@@ -422,167 +732,359 @@ impl PrepareImage {
         Ok(())
     }
 
+    /// Effective resolution of an image of `width`/`height` px printed at `size` (in px at `dpi`).
+    /// The lower resolution of both axes.
+    fn effective_dpi(width: u32, height: u32, size: &FixSize, dpi: f64) -> f64 {
+        let x = width as f64 * dpi / size.width().value();
+        let y = height as f64 * dpi / size.height().value();
+        x.min(y)
+    }
+
+    /// Warns if the effective resolution is below `WARN_DPI`,
+    /// or fails if it is below `--min-dpi`.
+    fn check_resolution(
+        &self,
+        file: &PathBuf,
+        width: u32,
+        height: u32,
+        size: &FixSize,
+        dpi: f64,
+    ) -> Result<(), Box<dyn Error>> {
+        let effective = Self::effective_dpi(width, height, size, dpi);
+        match self.min_dpi {
+            Some(min_dpi) if effective < min_dpi => {
+                Err(Box::new(OperationParametersError(format!(
+                    "{:?} would be printed at {:.0} dpi, below `--min-dpi` {}",
+                    file, effective, min_dpi
+                ))))
+            }
+            None if effective < WARN_DPI => {
+                log::warn!(
+                    "{:?} is printed at {:.0} dpi, below {} dpi.",
+                    file,
+                    effective,
+                    WARN_DPI
+                );
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Describes the layout calculated by `calc_sizes`, for `--verbose-geometry`.
+    /// Rectangles are given as `x/y/width/height`, borders as `top/right/bottom/left`, all in px.
+    fn geometry(
+        file: &PathBuf,
+        width: u32,
+        height: u32,
+        rotate: bool,
+        sizes: &(FixSize, FixSize, Borders, Borders),
+    ) -> String {
+        let (img, frame, padding, margins) = sizes;
+        let borders = |b: &Borders| {
+            format!(
+                "{}/{}/{}/{}",
+                b.top().value(),
+                b.right().value(),
+                b.bottom().value(),
+                b.left().value()
+            )
+        };
+        let (x, y) = (margins.left().value(), margins.top().value());
+        format!(
+            "{:?}: rotate {}, canvas {}x{}, image {}/{}/{}/{}, framed {}/{}/{}/{}, padding {}, margins {}",
+            file,
+            rotate,
+            width,
+            height,
+            x + padding.left().value(),
+            y + padding.top().value(),
+            img.width().value(),
+            img.height().value(),
+            x,
+            y,
+            frame.width().value(),
+            frame.height().value(),
+            borders(padding),
+            borders(margins),
+        )
+    }
+
+    /// The page format of a variant, or the fitting format for `--fit-format`.
+    fn page_format(&self, image_width: u32, image_height: u32, variant: usize) -> PageFormat {
+        if self.fit_format {
+            PageFormat::Fixed(format::fit_format(image_width, image_height))
+        } else {
+            self.format[variant].clone()
+        }
+    }
+
+    /// Returns the output canvas size in px, and whether the format is rotated
+    /// to match the orientation of the input image.
+    fn canvas_size(
+        &self,
+        format: &PageFormat,
+        image_width: u32,
+        image_height: u32,
+    ) -> Result<(u32, u32, bool), Box<dyn Error>> {
+        let dpi = self.dpi.unwrap_or(300.0);
+        let format = match format {
+            PageFormat::Fixed(size) => format::to_print_format(size)?.to(&LengthUnit::Px, dpi),
+            PageFormat::Auto => {
+                // Already in the image's orientation
+                let long = self.long_edge.as_ref().unwrap().to_px(dpi).value();
+                let (long_side, short_side) = if image_width >= image_height {
+                    (image_width, image_height)
+                } else {
+                    (image_height, image_width)
+                };
+                let short = (long * short_side as f64 / long_side as f64).round();
+                let (width, height) = if image_width >= image_height {
+                    (long, short)
+                } else {
+                    (short, long)
+                };
+                return Ok((width as u32, height as u32, false));
+            }
+        };
+
+        let width = format.width().value().round() as u32;
+        let height = format.height().value().round() as u32;
+
+        let in_is_portrait = image_height > image_width;
+        let out_is_portrait = height > width;
+        let rotate = !(self.no_rotation || in_is_portrait == out_is_portrait);
+
+        if rotate {
+            Ok((height, width, rotate))
+        } else {
+            Ok((width, height, rotate))
+        }
+    }
+
+    /// Checks that the given sizes and borders fit onto the page of `width`/`height` px,
+    /// leaving room for the image. Reports each overflowing dimension and the amount.
+    fn check_layout(
+        &self,
+        width: u32,
+        height: u32,
+        rotate: bool,
+        dpi: f64,
+    ) -> Result<(), Box<dyn Error>> {
+        let (page_width, page_height) = (width as f64, height as f64);
+        let (format_width, format_height) = if rotate {
+            (page_height, page_width)
+        } else {
+            (page_width, page_height)
+        };
+        let size = |s: &Option<FixSize>| {
+            s.as_ref().map(|s| {
+                let s =
+                    Self::rotate_size(s.resolve(format_width, format_height).to_px(dpi), rotate);
+                (s.width().value(), s.height().value())
+            })
+        };
+        let borders = |b: &Option<Borders>| {
+            b.as_ref().map_or((0.0, 0.0), |b| {
+                let b = b.resolve(page_width, page_height).to_px(dpi);
+                (
+                    b.left().value() + b.right().value(),
+                    b.top().value() + b.bottom().value(),
+                )
+            })
+        };
+        let margins = borders(&self.margins);
+        // With a framed size, padding is part of it
+        let (content, padding, what) = match (size(&self.framed_size), size(&self.image_size)) {
+            (Some(framed), _) => (Some(framed), (0.0, 0.0), "framed size and borders"),
+            (None, Some(image)) => (
+                Some(image),
+                borders(&self.padding),
+                "image size and borders",
+            ),
+            (None, None) => (None, borders(&self.padding), "margins and padding"),
+        };
+
+        let mut errors = vec![];
+        let dims = [
+            (
+                "width",
+                page_width,
+                margins.0 + padding.0,
+                content.map(|c| c.0),
+            ),
+            (
+                "height",
+                page_height,
+                margins.1 + padding.1,
+                content.map(|c| c.1),
+            ),
+        ];
+        for (name, page, borders, content) in dims.iter() {
+            let excess = borders + content.unwrap_or(0.0) - page;
+            // Without a given image size, at least one px must be left for the image
+            if excess > 0.0 || (content.is_none() && excess >= 0.0) {
+                errors.push(format!(
+                    "{} by {}px ({:.1}mm)",
+                    name,
+                    excess.round(),
+                    excess / dpi * 25.4
+                ));
+            }
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(Box::new(format::PrintFormatError(format!(
+                "The {} exceed the page of {}x{}px in {}",
+                what,
+                width,
+                height,
+                errors.join(" and ")
+            ))))
+        }
+    }
+
+    /// Adds fields computed from the image size to an exif map:
+    /// `MP` (megapixels) and `Aspect` (reduced aspect ratio, or `<ratio>:1` if not reducible to small numbers).
+    fn add_computed_fields(exif: &mut HashMap<String, String>, width: u32, height: u32) {
+        let mp = (width as f64 * height as f64) / 1_000_000.0;
+        exif.insert("MP".to_string(), format!("{:.1}", mp));
+
+        let mut a = width;
+        let mut b = height;
+        while b != 0 {
+            let t = a % b;
+            a = b;
+            b = t;
+        }
+        let aspect = if a == 0 {
+            "?".to_string()
+        } else if width / a <= 32 && height / a <= 32 {
+            format!("{}:{}", width / a, height / a)
+        } else {
+            format!("{:.2}:1", width as f64 / height as f64)
+        };
+        exif.insert("Aspect".to_string(), aspect);
+    }
+
     fn exif_string(&self, format: &str, exif: &HashMap<String, String>) -> String {
-        let mut str = format.to_string();
+        let mut str = Self::resolve_fallbacks(format, exif);
         for (k, v) in exif.iter() {
             let key = format!("{{{}}}", k);
             str = str.replace(&key, v);
         }
-        str
+        Self::format_dates(&str, exif)
     }
 
-    fn create_control_element(&self, sizes: &Borders) -> DynamicImage {
-        let off_x = sizes.right().value() as i32;
-        let off_y = sizes.left().value() as i32;
-        let sx = sizes.top().value() as u32;
-        let sy = sizes.bottom().value() as u32;
-        let mut image =
-            DynamicImage::new_rgb8(9 * sy + 10 * off_x as u32, 3 * sy + 4 * off_y as u32);
-        ImageUtil::fill_image(&mut image, &[255, 255, 255, 255]);
-
-        // CMY(K)
-        for i in 0..5 {
-            imageproc::drawing::draw_filled_rect_mut(
-                &mut image,
-                Rect::at(off_x + i * (sx as i32 + off_x), off_y).of_size(sx, sy),
-                Rgba([i as u8 * 51, 255, 255, 255]),
-            );
-            imageproc::drawing::draw_filled_rect_mut(
-                &mut image,
-                Rect::at(
-                    off_x + i * (sx as i32 + off_x),
-                    off_y + 1 * (sy as i32 + off_y),
-                )
-                .of_size(sx, sy),
-                Rgba([255, i as u8 * 51, 255, 255]),
-            );
-            imageproc::drawing::draw_filled_rect_mut(
-                &mut image,
-                Rect::at(
-                    off_x + i * (sx as i32 + off_x),
-                    off_y + 2 * (sy as i32 + off_y),
-                )
-                .of_size(sx, sy),
-                Rgba([255, 255, i as u8 * 51, 255]),
-            );
+    /// Replaces placeholders with fallbacks, like `{Date|mtime}` or `{Date|mtime:%Y}`,
+    /// by the first field available. Placeholders without any available field are kept.
+    fn resolve_fallbacks(format: &str, exif: &HashMap<String, String>) -> String {
+        let mut result = String::new();
+        let mut rest = format;
+        while let Some(start) = rest.find('{') {
+            result.push_str(&rest[..start]);
+            let placeholder = &rest[start..];
+            rest = placeholder;
+            let end = match placeholder.find('}') {
+                Some(end) => end,
+                None => break,
+            };
+            let inner = &placeholder[1..end];
+            let (keys, pattern) = match inner.split_once(':') {
+                Some((keys, pattern)) => (keys, Some(pattern)),
+                None => (inner, None),
+            };
+            let key = keys.split('|').find(|key| exif.contains_key(*key));
+            match (key, keys.contains('|')) {
+                (Some(key), true) => {
+                    result.push('{');
+                    result.push_str(key);
+                    if let Some(pattern) = pattern {
+                        result.push(':');
+                        result.push_str(pattern);
+                    }
+                    result.push('}');
+                }
+                _ => result.push_str(&placeholder[..=end]),
+            }
+            rest = &placeholder[end + 1..];
         }
-        // RGB
-        imageproc::drawing::draw_filled_rect_mut(
-            &mut image,
-            Rect::at(off_x + 5 * (sx as i32 + off_x), off_y).of_size(sx, sy),
-            Rgba([255, 0, 0, 255]),
-        );
-        imageproc::drawing::draw_filled_rect_mut(
-            &mut image,
-            Rect::at(
-                off_x + 5 * (sx as i32 + off_x),
-                off_y + 1 * (sy as i32 + off_y),
-            )
-            .of_size(sx, sy),
-            Rgba([0, 255, 0, 255]),
-        );
-        imageproc::drawing::draw_filled_rect_mut(
-            &mut image,
-            Rect::at(
-                off_x + 5 * (sx as i32 + off_x),
-                off_y + 2 * (sy as i32 + off_y),
-            )
-            .of_size(sx, sy),
-            Rgba([0, 0, 255, 255]),
-        );
-        // Greyscale light
-        imageproc::drawing::draw_filled_rect_mut(
-            &mut image,
-            Rect::at(off_x + 6 * (sx as i32 + off_x), off_y).of_size(sx, sy),
-            Rgba([255, 255, 255, 255]),
-        );
-        imageproc::drawing::draw_filled_rect_mut(
-            &mut image,
-            Rect::at(
-                off_x + 6 * (sx as i32 + off_x),
-                off_y + 1 * (sy as i32 + off_y),
-            )
-            .of_size(sx, sy),
-            Rgba([204, 204, 204, 255]),
-        );
-        imageproc::drawing::draw_filled_rect_mut(
-            &mut image,
-            Rect::at(
-                off_x + 6 * (sx as i32 + off_x),
-                off_y + 2 * (sy as i32 + off_y),
-            )
-            .of_size(sx, sy),
-            Rgba([153, 153, 153, 255]),
-        );
-        // Greyscale dark
-        imageproc::drawing::draw_filled_rect_mut(
-            &mut image,
-            Rect::at(off_x + 7 * (sx as i32 + off_x), off_y).of_size(sx, sy),
-            Rgba([102, 102, 102, 255]),
-        );
-        imageproc::drawing::draw_filled_rect_mut(
-            &mut image,
-            Rect::at(
-                off_x + 7 * (sx as i32 + off_x),
-                off_y + 1 * (sy as i32 + off_y),
-            )
-            .of_size(sx, sy),
-            Rgba([51, 51, 51, 255]),
-        );
-        imageproc::drawing::draw_filled_rect_mut(
-            &mut image,
-            Rect::at(
-                off_x + 7 * (sx as i32 + off_x),
-                off_y + 2 * (sy as i32 + off_y),
-            )
-            .of_size(sx, sy),
-            Rgba([0, 0, 0, 255]),
-        );
-        // Vertical pattern
-        for i in 0..(sx / 2) {
-            let x = 2 * i as i32;
-            imageproc::drawing::draw_filled_rect_mut(
-                &mut image,
-                Rect::at(off_x + 8 * (sx as i32 + off_x) + x, off_y).of_size(1, sy),
-                Rgba([0, 0, 0, 255]),
-            );
+        result.push_str(rest);
+        result
+    }
+
+    /// Replaces placeholders with a date format, like `{Date:%Y-%m-%d}`.
+    /// Values that can't be parsed as date are inserted unchanged.
+    fn format_dates(format: &str, exif: &HashMap<String, String>) -> String {
+        let mut result = String::new();
+        let mut rest = format;
+        while let Some(start) = rest.find('{') {
+            result.push_str(&rest[..start]);
+            let placeholder = &rest[start..];
+            rest = placeholder;
+            let end = match placeholder.find('}') {
+                Some(end) => end,
+                None => break,
+            };
+            let inner = &placeholder[1..end];
+            let replacement = inner.split_once(':').and_then(|(key, pattern)| {
+                exif.get(key)
+                    .map(|value| Self::format_date(value, pattern).unwrap_or_else(|| value.clone()))
+            });
+            match replacement {
+                Some(value) => result.push_str(&value),
+                None => result.push_str(&placeholder[..=end]),
+            }
+            rest = &placeholder[end + 1..];
         }
-        // Horizontal pattern
-        for i in 0..(sy / 2) {
-            let y = 2 * i as i32;
-            imageproc::drawing::draw_filled_rect_mut(
-                &mut image,
-                Rect::at(
-                    off_x + 8 * (sx as i32 + off_x),
-                    off_y + 2 * (sy as i32 + off_y) + y,
-                )
-                .of_size(sx, 1),
-                Rgba([0, 0, 0, 255]),
-            );
+        result.push_str(rest);
+        result
+    }
+
+    /// Formats an EXIF date/time like `2023:05:01 12:00:00` with a strftime-like pattern.
+    /// Supports `%Y`, `%y`, `%m`, `%b`, `%d`, `%H`, `%M`, `%S` and `%%`.
+    /// Returns `None` if the value is not a date/time.
+    fn format_date(value: &str, pattern: &str) -> Option<String> {
+        const MONTHS: [&str; 12] = [
+            "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+        ];
+        let parts: Vec<u32> = value
+            .split(|c: char| !c.is_ascii_digit())
+            .filter(|p| !p.is_empty())
+            .map(|p| p.parse().ok())
+            .collect::<Option<_>>()?;
+        if parts.len() != 6 || !(1..=12).contains(&parts[1]) {
+            return None;
         }
-        // Crosshair
-        {
-            let x = off_x + 8 * (sx as i32 + off_x) + sx as i32 / 2;
-            let y = off_y + 1 * (sy as i32 + off_y) + sy as i32 / 2;
-            imageproc::drawing::draw_hollow_circle_mut(
-                &mut image,
-                (x, y),
-                std::cmp::min(sx, sy) as i32 / 3,
-                Rgba([0, 0, 0, 255]),
-            );
-            imageproc::drawing::draw_filled_rect_mut(
-                &mut image,
-                Rect::at(x - sx as i32 / 2, y).of_size(sx, 1),
-                Rgba([0, 0, 0, 255]),
-            );
-            imageproc::drawing::draw_filled_rect_mut(
-                &mut image,
-                Rect::at(x, y - sy as i32 / 2).of_size(1, sy),
-                Rgba([0, 0, 0, 255]),
-            );
+        let [year, month, day, hour, minute, second] =
+            [parts[0], parts[1], parts[2], parts[3], parts[4], parts[5]];
+
+        let mut result = String::new();
+        let mut chars = pattern.chars();
+        while let Some(c) = chars.next() {
+            if c != '%' {
+                result.push(c);
+                continue;
+            }
+            match chars.next() {
+                Some('Y') => result.push_str(&format!("{:04}", year)),
+                Some('y') => result.push_str(&format!("{:02}", year % 100)),
+                Some('m') => result.push_str(&format!("{:02}", month)),
+                Some('b') => result.push_str(MONTHS[month as usize - 1]),
+                Some('d') => result.push_str(&format!("{:02}", day)),
+                Some('H') => result.push_str(&format!("{:02}", hour)),
+                Some('M') => result.push_str(&format!("{:02}", minute)),
+                Some('S') => result.push_str(&format!("{:02}", second)),
+                Some('%') => result.push('%'),
+                Some(other) => {
+                    result.push('%');
+                    result.push(other);
+                }
+                None => result.push('%'),
+            }
         }
-
-        image
+        Some(result)
     }
 
     /// Returns calculated (image, framed, padding, margins).
@@ -595,46 +1097,52 @@ impl PrepareImage {
         rotate: bool,
         dpi: f64,
     ) -> (FixSize, FixSize, Borders, Borders) {
+        // Resolve relative lengths against the page, after rotation is determined.
+        // Sizes are given in the orientation of `--format`, borders in output orientation.
+        let (page_width, page_height) = (width as f64, height as f64);
+        let (format_width, format_height) = if rotate {
+            (page_height, page_width)
+        } else {
+            (page_width, page_height)
+        };
+        let framed_size = self
+            .framed_size
+            .as_ref()
+            .map(|s| s.resolve(format_width, format_height));
+        let image_size = self
+            .image_size
+            .as_ref()
+            .map(|s| s.resolve(format_width, format_height));
+        let padding_borders = self
+            .padding
+            .as_ref()
+            .map(|b| b.resolve(page_width, page_height));
+        let margin_borders = self
+            .margins
+            .as_ref()
+            .map(|b| b.resolve(page_width, page_height));
+
         // Calculate maximum size of image + padding
-        let framed = if let Some(framed) = &self.framed_size {
+        let framed = if let Some(framed) = &framed_size {
             Self::rotate_size(framed.to_px(dpi), rotate)
+        } else if let Some(margins) = &margin_borders {
+            let mar = Self::rotate_borders(margins.to_px(dpi), rotate);
+            FixSize::px(width as i32, height as i32).sub_borders(&mar)
         } else {
-            if let Some(margins) = &self.margins {
-                let mar = Self::rotate_borders(margins.to_px(dpi), rotate);
-                FixSize::px(
-                    width as i32 - mar.right().value() as i32 - mar.left().value() as i32,
-                    height as i32 - mar.top().value() as i32 - mar.bottom().value() as i32,
-                )
-            } else {
-                let img = Self::rotate_size(self.image_size.as_ref().unwrap().to_px(dpi), rotate);
-                let pad = Self::rotate_borders(self.padding.as_ref().unwrap().to_px(dpi), rotate);
-                FixSize::px(
-                    img.width().value() as i32
-                        + pad.right().value() as i32
-                        + pad.left().value() as i32,
-                    img.height().value() as i32
-                        + pad.top().value() as i32
-                        + pad.bottom().value() as i32,
-                )
-            }
+            let img = Self::rotate_size(image_size.as_ref().unwrap().to_px(dpi), rotate);
+            let pad = Self::rotate_borders(padding_borders.as_ref().unwrap().to_px(dpi), rotate);
+            img.add_borders(&pad)
         };
 
         // Calculate maximum size of image (without padding)
-        let image = if let Some(image) = &self.framed_size {
+        let image = if let Some(image) = &framed_size {
             Self::rotate_size(image.to_px(dpi), rotate)
         } else {
-            let pad = Self::rotate_borders(self.padding.as_ref().unwrap().to_px(dpi), rotate);
-            FixSize::px(
-                framed.width().value() as i32
-                    - pad.right().value() as i32
-                    - pad.left().value() as i32,
-                framed.height().value() as i32
-                    - pad.top().value() as i32
-                    - pad.bottom().value() as i32,
-            )
+            let pad = Self::rotate_borders(padding_borders.as_ref().unwrap().to_px(dpi), rotate);
+            framed.sub_borders(&pad)
         };
         // Calculate padding
-        let padding = if let Some(pad) = &self.padding {
+        let padding = if let Some(pad) = &padding_borders {
             Self::rotate_borders(pad.to_px(dpi), rotate)
         } else {
             let hor = (framed.width().value() as i32 - image.width().value() as i32) / 2;
@@ -642,36 +1150,14 @@ impl PrepareImage {
             Borders::px(ver, hor, ver, hor)
         };
 
-        // Resize for original aspect ratio
-        let (scaled_width, scaled_height) = {
-            let orig_aspect = img_width as f64 / img_height as f64;
-            let out_aspect = image.width().value() / image.height().value();
-            if orig_aspect >= out_aspect {
-                // wider
-                (
-                    image.width().value().round() as i32,
-                    (image.height().value() * out_aspect / orig_aspect).round() as i32,
-                )
-            } else {
-                // higher
-                (
-                    (image.width().value() * orig_aspect / out_aspect).round() as i32,
-                    image.height().value().round() as i32,
-                )
-            }
-        };
-
-        // Calculate actual size of image
-        let image = FixSize::px(scaled_width, scaled_height);
+        // Calculate actual size of image, resized for original aspect ratio
+        let image = FixSize::px(img_width as i32, img_height as i32).scaled_to_fit(&image);
 
         // Calculate actual size of image + padding
-        let framed = FixSize::px(
-            scaled_width + padding.left().value() as i32 + padding.right().value() as i32,
-            scaled_height + padding.top().value() as i32 + padding.bottom().value() as i32,
-        );
+        let framed = image.add_borders(&padding);
 
         // Calculate actual margine
-        let margins = if let Some(mar_orig) = &self.margins {
+        let margins = if let Some(mar_orig) = &margin_borders {
             let mar = Self::rotate_borders(mar_orig.to_px(dpi), rotate);
             let diff_hor = (mar.right().value() as i32 - mar.left().value() as i32) / 2;
             let diff_ver = (mar.top().value() as i32 - mar.bottom().value() as i32) / 2;
@@ -713,25 +1199,1066 @@ impl PrepareImage {
         dpi: f64,
         rotate: bool,
     ) {
-        if let Some(b) = &self.border {
+        if let Some(b) = self.border.as_ref().filter(|b| !b.is_zero()) {
+            let b = b.resolve(image.width() as f64, image.height() as f64);
             let bor = Self::rotate_borders(b.to_px(dpi), rotate);
             let color = Rgba(
                 self.border_color
                     .as_ref()
                     .map_or([0_u8, 0, 0, 255], |c| *c.channels()),
             );
+            let left = CastUtil::i32_from_f64(bor.left().value(), "left border");
+            let right = CastUtil::i32_from_f64(bor.right().value(), "right border");
+            let top = CastUtil::i32_from_f64(bor.top().value(), "top border");
+            let bottom = CastUtil::i32_from_f64(bor.bottom().value(), "bottom border");
+            Self::fill_rect(
+                image,
+                image_x as i32 - left,
+                image_y as i32 - top,
+                image_width as i32 + left + right,
+                image_height as i32 + top + bottom,
+                color,
+            );
+        }
+    }
+
+    /// Draws the mat and its bevel around the image.
+    fn draw_mat(
+        &self,
+        image: &mut DynamicImage,
+        image_x: u32,
+        image_y: u32,
+        image_width: u32,
+        image_height: u32,
+        dpi: f64,
+    ) {
+        let rect = (
+            image_x as i32,
+            image_y as i32,
+            image_width as i32,
+            image_height as i32,
+        );
+        if let Some(mat) = &self.mat {
+            let w = CastUtil::i32_from_f64(mat.width.to_px(dpi).value().round(), "mat width");
+            Self::fill_rect(
+                image,
+                rect.0 - w,
+                rect.1 - w,
+                rect.2 + 2 * w,
+                rect.3 + 2 * w,
+                Rgba(*mat.color.channels()),
+            );
+        }
+        if let Some(bevel) = &self.mat_bevel {
+            let w = CastUtil::i32_from_f64(bevel.width.to_px(dpi).value().round(), "bevel width");
+            let [r, g, b, a] = *bevel.color.channels();
+            let shade = |v: u8| (v as f64 * 0.7).round() as u8;
+            let dark = Rgba([shade(r), shade(g), shade(b), a]);
+            Self::draw_bevel(image, rect, w, Rgba([r, g, b, a]), dark);
+        }
+    }
+
+    /// Draws a bevel of width `w` around the rectangle `(x, y, width, height)`,
+    /// with `light` top and left edges, and `dark` bottom and right edges.
+    /// Edges meet at 45° in the corners, as the light comes from the top left.
+    fn draw_bevel(
+        image: &mut DynamicImage,
+        rect: (i32, i32, i32, i32),
+        w: i32,
+        light: Rgba<u8>,
+        dark: Rgba<u8>,
+    ) {
+        let (x, y, width, height) = rect;
+        let (x_end, y_end) = (x + width - 1, y + height - 1);
+        for py in (y - w).max(0)..(y_end + w + 1).min(image.height() as i32) {
+            for px in (x - w).max(0)..(x_end + w + 1).min(image.width() as i32) {
+                // Distances outside of each edge
+                let (left, right, top, bottom) = (x - px, px - x_end, y - py, py - y_end);
+                if left.max(right).max(top).max(bottom) <= 0 {
+                    continue;
+                }
+                // The edge with the larger distance wins, ties go to the light edges
+                let color = if top.max(left) >= bottom.max(right) {
+                    light
+                } else {
+                    dark
+                };
+                image.put_pixel(px as u32, py as u32, color);
+            }
+        }
+    }
+
+    /// Draws the exif text at `--exif-position` relative to the image rectangle `(x, y, width, height)`,
+    /// with an optional background box.
+    fn draw_exif(
+        &self,
+        image: &mut DynamicImage,
+        text: &str,
+        image_rect: (u32, u32, u32, u32),
+        padding: &Borders,
+        dpi: f64,
+    ) {
+        let color = self
+            .color
+            .as_ref()
+            .map_or([0_u8, 0, 0, 255], |c| *c.channels());
+        let (x, y, scale, (text_width, text_height)) = self.exif_placement(
+            text,
+            (image.width(), image.height()),
+            image_rect,
+            padding,
+            dpi,
+        );
+        if let Some(bg) = &self.exif_bg {
+            let margin = (text_height / 5) as i32;
+            Self::fill_rect(
+                image,
+                x as i32 - margin,
+                y as i32 - margin,
+                text_width as i32 + 2 * margin,
+                text_height as i32 + 2 * margin,
+                Rgba(*bg.channels()),
+            );
+        }
+        imageproc::drawing::draw_text_mut(
+            image,
+            Rgba(color),
+            x,
+            y,
+            scale,
+            &self.fonts.default,
+            text,
+        );
+    }
+
+    /// Calculates position, font scale and size of the exif text.
+    /// The font is scaled down if the text is wider than the canvas,
+    /// and the position is shifted to keep the text on the canvas.
+    fn exif_placement(
+        &self,
+        text: &str,
+        canvas: (u32, u32),
+        image_rect: (u32, u32, u32, u32),
+        padding: &Borders,
+        dpi: f64,
+    ) -> (u32, u32, rusttype::Scale, (u32, u32)) {
+        let (canvas_width, canvas_height) = canvas;
+        let (x_img, y_img, img_width, img_height) = image_rect;
+        let gap = Length::mm(2.0).to_px(dpi).value() as u32;
+        let font_size = self
+            .exif_size
+            .clone()
+            .unwrap_or_else(|| Length::px(12))
+            .to_px(dpi)
+            .value() as f32;
+
+        let position = self.exif_position.as_ref().unwrap_or(&TextPosition::Below);
+        let max_width = match position {
+            TextPosition::Corner => img_width.saturating_sub(2 * gap),
+            _ => canvas_width,
+        };
+        let mut scale = rusttype::Scale::uniform(font_size);
+        let mut size = ImageUtil::text_size(&self.fonts.default, scale, text);
+        if size.0 > max_width && max_width > 0 {
+            scale = rusttype::Scale::uniform(font_size * max_width as f32 / size.0 as f32);
+            size = ImageUtil::text_size(&self.fonts.default, scale, text);
+        }
+        let (text_width, text_height) = size;
+
+        let (x, y) = match position {
+            TextPosition::Below => (
+                x_img,
+                y_img
+                    + img_height
+                    + CastUtil::u32_from_f64(padding.bottom().value(), "bottom padding")
+                    + gap,
+            ),
+            TextPosition::Above => (
+                x_img,
+                y_img
+                    .saturating_sub(CastUtil::u32_from_f64(padding.top().value(), "top padding"))
+                    .saturating_sub(gap + text_height),
+            ),
+            TextPosition::Corner => (
+                x_img + gap,
+                (y_img + img_height).saturating_sub(gap + text_height),
+            ),
+        };
+        (
+            x.min(canvas_width.saturating_sub(text_width)),
+            y.min(canvas_height.saturating_sub(text_height)),
+            scale,
+            size,
+        )
+    }
+
+    /// The image rectangle `(x, y, width, height)` in px, from the sizes of `calc_sizes`.
+    fn image_rect(sizes: &(FixSize, FixSize, Borders, Borders)) -> (u32, u32, u32, u32) {
+        let (img, _frame, padding, margins) = sizes;
+        let x_img = CastUtil::u32_from_f64(
+            margins.left().value() + padding.left().value(),
+            "image x position",
+        );
+        let y_img = CastUtil::u32_from_f64(
+            margins.top().value() + padding.top().value(),
+            "image y position",
+        );
+        let img_width = CastUtil::u32_from_f64(img.width().value(), "image width");
+        let img_height = CastUtil::u32_from_f64(img.height().value(), "image height");
+        (x_img, y_img, img_width, img_height)
+    }
+
+    /// Creates an SVG of the layout for `--svg-guides`, with the page size in inches
+    /// and coordinates in px. `image` is the image rectangle `(x, y, width, height)`.
+    fn svg_guides(
+        &self,
+        width: u32,
+        height: u32,
+        dpi: f64,
+        image: (u32, u32, u32, u32),
+        padding: &Borders,
+    ) -> String {
+        let (x_img, y_img, img_width, img_height) = image;
+        let (xmin, xmax, ymin, ymax) =
+            Self::cut_bounds(x_img, y_img, img_width, img_height, padding);
+        let rect = |id: &str, x: i32, y: i32, w: i32, h: i32, color: &str| {
+            format!(
+                "  <rect id=\"{}\" x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"none\" stroke=\"{}\"/>\n",
+                id, x, y, w, h, color
+            )
+        };
+        let mut svg = format!(
+            concat!(
+                "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+                "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{:.4}in\" height=\"{:.4}in\" viewBox=\"0 0 {} {}\">\n"
+            ),
+            width as f64 / dpi,
+            height as f64 / dpi,
+            width,
+            height
+        );
+        svg.push_str(&rect("page", 0, 0, width as i32, height as i32, "gray"));
+        svg.push_str(&rect("trim", xmin, ymin, xmax - xmin, ymax - ymin, "red"));
+        svg.push_str(&rect(
+            "image",
+            x_img as i32,
+            y_img as i32,
+            img_width as i32,
+            img_height as i32,
+            "blue",
+        ));
+        if let Some(m) = &self.cut_marks {
+            let marks = m.to_px(dpi);
+            let lw = marks.width().as_ref().map_or(1.0, |l| l.value());
+            let offset = marks
+                .height()
+                .as_ref()
+                .map_or(0, |l| CastUtil::i32_from_f64(l.value(), "cut mark offset"));
+            let (w, h) = (width as i32, height as i32);
+            // Same segments as the raster cut marks: from the page edge to the offset
+            let lines = [
+                (0, ymin, xmin - offset, ymin),
+                (xmin, 0, xmin, ymin - offset),
+                (xmax + offset, ymin, w, ymin),
+                (xmax, 0, xmax, ymin - offset),
+                (0, ymax, xmin - offset, ymax),
+                (xmin, ymax + offset, xmin, h),
+                (xmax + offset, ymax, w, ymax),
+                (xmax, ymax + offset, xmax, h),
+            ];
+            for (x1, y1, x2, y2) in lines.iter() {
+                if x2 > x1 || y2 > y1 {
+                    svg.push_str(&format!(
+                        "  <line class=\"cut-mark\" x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\" stroke=\"black\" stroke-width=\"{}\"/>\n",
+                        x1, y1, x2, y2, lw
+                    ));
+                }
+            }
+        }
+        if let Some(m) = &self.crop_marks {
+            let bounds = (xmin, xmax, ymin, ymax);
+            for (x1, y1, x2, y2) in Self::crop_mark_lines(bounds, &m.to_px(dpi)) {
+                svg.push_str(&format!(
+                    "  <line class=\"crop-mark\" x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\" stroke=\"black\" stroke-width=\"{}\"/>\n",
+                    x1, y1, x2, y2, Self::crop_mark_width(dpi)
+                ));
+            }
+        }
+        svg.push_str("</svg>\n");
+        svg
+    }
+
+    /// Returns the bounds of the cut area (`xmin`, `xmax`, `ymin`, `ymax`), i.e. the image plus padding.
+    fn cut_bounds(
+        x_img: u32,
+        y_img: u32,
+        img_width: u32,
+        img_height: u32,
+        padding: &Borders,
+    ) -> (i32, i32, i32, i32) {
+        let left = CastUtil::i32_from_f64(padding.left().value(), "left padding");
+        let right = CastUtil::i32_from_f64(padding.right().value(), "right padding");
+        let top = CastUtil::i32_from_f64(padding.top().value(), "top padding");
+        let bottom = CastUtil::i32_from_f64(padding.bottom().value(), "bottom padding");
+        (
+            x_img as i32 - left,
+            x_img as i32 + img_width as i32 + right,
+            y_img as i32 - top,
+            y_img as i32 + img_height as i32 + bottom,
+        )
+    }
+
+    /// Line width of crop marks in px: 0.1mm, at least 1px.
+    fn crop_mark_width(dpi: f64) -> i32 {
+        CastUtil::i32_from_f64(
+            Length::mm(0.1).to_px(dpi).value().round().max(1.0),
+            "crop mark width",
+        )
+    }
+
+    /// Line segments `(x1, y1, x2, y2)` of corner crop marks around the trim box `(xmin, xmax, ymin, ymax)`.
+    fn crop_mark_lines(
+        bounds: (i32, i32, i32, i32),
+        marks: &CropMarks,
+    ) -> Vec<(i32, i32, i32, i32)> {
+        let (xmin, xmax, ymin, ymax) = bounds;
+        let length = CastUtil::i32_from_f64(marks.length.value(), "crop mark length");
+        let offset = CastUtil::i32_from_f64(marks.offset.value(), "crop mark offset");
+        let gap = CastUtil::i32_from_f64(marks.gap.value(), "crop mark gap");
+        let distances: &[i32] = if gap > 0 { &[0, gap] } else { &[0] };
+
+        let mut lines = vec![];
+        // Corners with the outward direction
+        for &(x, dx) in &[(xmin, -1), (xmax, 1)] {
+            for &(y, dy) in &[(ymin, -1), (ymax, 1)] {
+                for d in distances {
+                    let (xd, yd) = (x + dx * d, y + dy * d);
+                    // Horizontal mark, on the extension of the top or bottom edge
+                    lines.push((x + dx * offset, yd, x + dx * (offset + length), yd));
+                    // Vertical mark, on the extension of the left or right edge
+                    lines.push((xd, y + dy * offset, xd, y + dy * (offset + length)));
+                }
+            }
+        }
+        lines
+    }
+
+    /// Draws a filled rectangle, clipped to the image bounds.
+    /// Rectangles with zero or negative extent after clipping are not drawn,
+    /// e.g. cut mark segments in layouts where the offset exceeds the margins.
+    pub(crate) fn fill_rect(
+        image: &mut DynamicImage,
+        x: i32,
+        y: i32,
+        width: i32,
+        height: i32,
+        color: Rgba<u8>,
+    ) {
+        let (x0, y0) = (x.max(0) as i64, y.max(0) as i64);
+        let x1 = (x as i64 + width as i64).min(image.width() as i64);
+        let y1 = (y as i64 + height as i64).min(image.height() as i64);
+        if x1 > x0 && y1 > y0 {
             imageproc::drawing::draw_filled_rect_mut(
                 image,
-                Rect::at(
-                    image_x as i32 - bor.left().value() as i32,
-                    image_y as i32 - bor.top().value() as i32,
-                )
-                .of_size(
-                    image_width + bor.left().value() as u32 + bor.right().value() as u32,
-                    image_height + bor.top().value() as u32 + bor.bottom().value() as u32,
-                ),
+                Rect::at(x0 as i32, y0 as i32).of_size((x1 - x0) as u32, (y1 - y0) as u32),
                 color,
             );
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::op::{ImageIoOperation, ImageOperation, PrepareImage, RunOptions};
+    use crate::units::Borders;
+    use crate::util::test_dir;
+    use crate::util::{ImageUtil, InputFile};
+    use image::{DynamicImage, GenericImageView};
+    use std::collections::HashMap;
+    use std::path::PathBuf;
+    use structopt::StructOpt;
+
+    #[test]
+    fn computed_fields() {
+        let op = PrepareImage::from_iter(&["prep", "--output", "out.png", "--format", "6in/4in"]);
+        let mut exif = HashMap::new();
+        PrepareImage::add_computed_fields(&mut exif, 6000, 4000);
+
+        assert_eq!(op.exif_string("{MP} MP, {Aspect}", &exif), "24.0 MP, 3:2");
+    }
+
+    #[test]
+    fn computed_fields_without_exif() {
+        let dir = test_dir("computed-fields");
+        let input = dir.join("in.png");
+        DynamicImage::new_rgb8(300, 200).save(&input).unwrap();
+        let image = image::open(&input).unwrap();
+
+        let args = [
+            "prep",
+            "--output",
+            "out.png",
+            "--format",
+            "6in/4in",
+            "--dpi",
+            "100",
+            "--padding",
+            "10px",
+            "--margins",
+            "40px",
+        ];
+        let plain = PrepareImage::from_iter(&args)
+            .process_image(&image, &input)
+            .unwrap();
+        let op = PrepareImage::from_iter(args.iter().chain(&["--exif", "{MP} MP, {Aspect}"]));
+        let result = op.process_image(&image, &input).unwrap();
+
+        // Computed from the pixel size, so drawn without EXIF data
+        assert!(ImageUtil::get_exif_map(&input).is_err());
+        assert_ne!(result.to_bytes(), plain.to_bytes());
+    }
+
+    #[test]
+    fn exif_dates() {
+        let op = PrepareImage::from_iter(&["prep", "--output", "out.png", "--format", "6in/4in"]);
+        let mut exif = HashMap::new();
+        exif.insert("Date".to_string(), "2023:05:01 12:03:09".to_string());
+        exif.insert("Mod".to_string(), "Camera".to_string());
+
+        assert_eq!(op.exif_string("{Date:%Y-%m-%d}", &exif), "2023-05-01");
+        assert_eq!(
+            op.exif_string("{Mod}, {Date:%d %b %y, %H:%M:%S}", &exif),
+            "Camera, 01 May 23, 12:03:09"
+        );
+        assert_eq!(op.exif_string("{Date}", &exif), "2023:05:01 12:03:09");
+        assert_eq!(op.exif_string("{Other:%Y}", &exif), "{Other:%Y}");
+        assert_eq!(PrepareImage::format_dates("at {Date", &exif), "at {Date");
+
+        exif.insert("Date".to_string(), "unknown".to_string());
+        assert_eq!(op.exif_string("{Date:%Y}", &exif), "unknown");
+    }
+
+    #[test]
+    fn exif_fallbacks() {
+        let op = PrepareImage::from_iter(&["prep", "--output", "out.png", "--format", "6in/4in"]);
+        let mut exif = HashMap::new();
+        exif.insert("mtime".to_string(), "2024-02-03 04:05:06".to_string());
+
+        assert_eq!(op.exif_string("{Date|mtime}", &exif), "2024-02-03 04:05:06");
+        assert_eq!(op.exif_string("{Date|mtime:%d.%m.%Y}", &exif), "03.02.2024");
+        assert_eq!(op.exif_string("{Date|Other}", &exif), "{Date|Other}");
+        assert_eq!(op.exif_string("at {Date|mtime", &exif), "at {Date|mtime");
+
+        exif.insert(
+            "DateTimeDigitized".to_string(),
+            "2023:05:01 12:03:09".to_string(),
+        );
+        assert_eq!(
+            op.exif_string("{Date|DateTimeDigitized|mtime:%Y}", &exif),
+            "2023"
+        );
+        exif.insert("Date".to_string(), "2022:01:01 00:00:00".to_string());
+        assert_eq!(op.exif_string("{Date|mtime:%Y}", &exif), "2022");
+    }
+
+    #[test]
+    fn exif_placement() {
+        let args = [
+            "prep",
+            "--output",
+            "out.png",
+            "--format",
+            "100px/100px",
+            "--padding",
+            "0px",
+            "--margins",
+            "20px",
+            "--exif-size",
+            "10px",
+        ];
+        let padding = Borders::px(0, 0, 0, 0);
+        let place = |extra: &[&str], text: &str| {
+            let op = PrepareImage::from_iter(args.iter().chain(extra.iter()));
+            op.exif_placement(text, (100, 100), (20, 20, 60, 60), &padding, 254.0)
+        };
+
+        let (x, y, _, (w, h)) = place(&[], "f/8");
+        assert_eq!((x, y), (20, 100 - h));
+        assert!(w > 0 && h > 0);
+
+        // No space above the image with a 2mm gap, so the text is placed at the top edge
+        let (x, y, _, _) = place(&["--exif-position", "above"], "f/8");
+        assert_eq!((x, y), (20, 0));
+
+        let (x, y, _, (_, h)) = place(&["--exif-position", "corner"], "f/8");
+        assert_eq!((x, y), (40, 80 - 20 - h));
+
+        // Too long texts are scaled down to fit into the canvas
+        let (x, _, scale, (w, _)) = place(&[], &"f/8 ".repeat(20));
+        assert!(scale.x < 10.0);
+        assert!(x + w <= 100);
+    }
+
+    #[test]
+    fn cut_marks_tight_layout() {
+        // Cut mark offset larger than the margins results in negative mark lengths.
+        let op = PrepareImage::from_iter(&[
+            "prep",
+            "--output",
+            "out.png",
+            "--format",
+            "100px/100px",
+            "--padding",
+            "0px",
+            "--margins",
+            "20px",
+            "--cut-marks",
+            "2px/30px",
+        ]);
+        let image = DynamicImage::new_rgb8(60, 60);
+        let result = op.process_image(&image, &PathBuf::from("in.png")).unwrap();
+
+        assert_eq!(result.width(), 100);
+        assert_eq!(result.height(), 100);
+        assert_eq!(result.get_pixel(5, 20).0, [255, 255, 255, 255]);
+        assert_eq!(result.get_pixel(95, 20).0, [255, 255, 255, 255]);
+    }
+
+    #[test]
+    fn cut_marks_large_offsets() {
+        // Line widths and offsets far beyond the canvas must neither panic nor draw garbage.
+        for (marks, frame) in [("400px/5000px", "5px/1px"), ("2px/1px", "300px/100000px")].iter() {
+            let op = PrepareImage::from_iter(&[
+                "prep",
+                "--output",
+                "out.png",
+                "--format",
+                "100px/100px",
+                "--padding",
+                "0px",
+                "--margins",
+                "2px",
+                "--cut-marks",
+                marks,
+                "--cut-frame",
+                frame,
+                "--edge-center-marks",
+                "100000px/5000px",
+            ]);
+            let image = DynamicImage::new_rgb8(90, 90);
+            let result = op.process_image(&image, &PathBuf::from("in.png")).unwrap();
+            assert_eq!(result.dimensions(), (100, 100));
+        }
+    }
+
+    #[test]
+    fn edge_center_marks() {
+        let op = PrepareImage::from_iter(&[
+            "prep",
+            "--output",
+            "out.png",
+            "--format",
+            "100px/100px",
+            "--padding",
+            "0px",
+            "--margins",
+            "20px",
+            "--edge-center-marks",
+            "10px/2px",
+        ]);
+        let image = DynamicImage::new_rgb8(60, 60);
+        let result = op.process_image(&image, &PathBuf::from("in.png")).unwrap();
+
+        let black = [0, 0, 0, 255];
+        let white = [255, 255, 255, 255];
+        // Midpoints of the trim edges at 20px and 80px
+        assert_eq!(result.get_pixel(50, 15).0, black);
+        assert_eq!(result.get_pixel(50, 85).0, black);
+        assert_eq!(result.get_pixel(15, 50).0, black);
+        assert_eq!(result.get_pixel(85, 50).0, black);
+        // Marks are short and narrow
+        assert_eq!(result.get_pixel(50, 5).0, white);
+        assert_eq!(result.get_pixel(45, 15).0, white);
+        assert_eq!(result.get_pixel(15, 45).0, white);
+    }
+
+    #[test]
+    fn mat_bevel() {
+        let op = PrepareImage::from_iter(&[
+            "prep",
+            "--output",
+            "out.png",
+            "--format",
+            "100px/100px",
+            "--padding",
+            "20px",
+            "--margins",
+            "10px",
+            "--mat",
+            "15px/200",
+            "--mat-bevel",
+            "5px/100/150/200",
+        ]);
+        let image = DynamicImage::new_rgb8(40, 40);
+        let result = op.process_image(&image, &PathBuf::from("in.png")).unwrap();
+
+        let light = [100, 150, 200, 255];
+        let dark = [70, 105, 140, 255];
+        // Image at 30-70px, mat from 15px, bevel from 25px
+        assert_eq!(result.get_pixel(50, 50).0, [0, 0, 0, 255]);
+        assert_eq!(result.get_pixel(50, 12).0, [255, 255, 255, 255]);
+        assert_eq!(result.get_pixel(50, 18).0, [200, 200, 200, 255]);
+        assert_eq!(result.get_pixel(50, 27).0, light);
+        assert_eq!(result.get_pixel(27, 50).0, light);
+        assert_eq!(result.get_pixel(50, 72).0, dark);
+        assert_eq!(result.get_pixel(72, 50).0, dark);
+        // 45° corner at the top right
+        assert_eq!(result.get_pixel(72, 26).0, light);
+        assert_eq!(result.get_pixel(72, 27).0, light);
+        assert_eq!(result.get_pixel(73, 27).0, dark);
+    }
+
+    #[test]
+    fn crop_marks() {
+        let op = PrepareImage::from_iter(&[
+            "prep",
+            "--output",
+            "out.png",
+            "--format",
+            "120px/100px",
+            "--padding",
+            "6px",
+            "--margins",
+            "25px",
+            "--crop-marks",
+            "12px/4px/6px",
+        ]);
+        let mut image = DynamicImage::new_rgb8(60, 40);
+        ImageUtil::fill_image(&mut image, &[120, 160, 200, 255]);
+        let result = op.process_image(&image, &PathBuf::from("in.png")).unwrap();
+
+        // Visual regression against a reviewed rendering
+        let reference = image::open("test_data/reference/prep-crop-marks.png").unwrap();
+        assert_eq!(result.dimensions(), reference.dimensions());
+        assert!(
+            result.to_rgb().into_raw() == reference.to_rgb().into_raw(),
+            "Crop marks differ from test_data/reference/prep-crop-marks.png"
+        );
+    }
+
+    #[test]
+    fn relative_margins() {
+        let op = PrepareImage::from_iter(&[
+            "prep",
+            "--output",
+            "out.png",
+            "--format",
+            "600px/400px",
+            "--padding",
+            "0px",
+            "--margins",
+            "10%",
+        ]);
+        let (img, _, _, margins) = op.calc_sizes(600, 400, 300, 200, false, 300.0);
+        assert_eq!(img.to_string(), "480px/320px");
+        assert_eq!(margins.to_string(), "40px/60px/40px/60px");
+
+        // Rotated for portrait input: margins relative to the rotated page
+        let (width, height, rotate) = op.canvas_size(&op.format[0], 200, 300).unwrap();
+        assert!(rotate);
+        let (img, _, _, margins) = op.calc_sizes(width, height, 200, 300, rotate, 300.0);
+        assert_eq!(img.to_string(), "320px/480px");
+        assert_eq!(margins.to_string(), "60px/40px/60px/40px");
+    }
+
+    #[test]
+    fn relative_without_reference() {
+        let op = PrepareImage::from_iter(&[
+            "prep",
+            "--output",
+            "out.png",
+            "--format",
+            "600px/400px",
+            "--padding",
+            "0px",
+            "--margins",
+            "10%",
+            "--exif-size",
+            "5%",
+        ]);
+        assert!(op.output_size(300, 200).is_err());
+    }
+
+    #[test]
+    fn bg_alpha() {
+        let op = PrepareImage::from_iter(&[
+            "prep",
+            "--output",
+            "out.png",
+            "--format",
+            "200px/100px",
+            "--padding",
+            "0px",
+            "--margins",
+            "10px",
+            "--bg",
+            "red",
+            "--bg-alpha",
+            "64",
+        ]);
+        let input = DynamicImage::new_rgb8(180, 80);
+        let result = op.process_image(&input, &PathBuf::from("in.png")).unwrap();
+
+        assert!(result.color().has_alpha());
+        assert_eq!(result.get_pixel(2, 2).0[3], 64);
+        assert_eq!(result.get_pixel(2, 2).0[0], 255);
+        assert_eq!(result.get_pixel(100, 50).0[3], 255);
+    }
+
+    #[test]
+    fn overlays() {
+        let dir = test_dir("overlays");
+        let red = dir.join("red.png");
+        let blue = dir.join("blue.png");
+        let mut image = DynamicImage::new_rgba8(20, 10);
+        ImageUtil::fill_image(&mut image, &[255, 0, 0, 255]);
+        image.save(&red).unwrap();
+        ImageUtil::fill_image(&mut image, &[0, 0, 255, 255]);
+        image.save(&blue).unwrap();
+
+        let op = PrepareImage::from_iter(&[
+            "prep",
+            "--output",
+            "out.png",
+            "--format",
+            "200px/100px",
+            "--padding",
+            "0px",
+            "--margins",
+            "0px",
+            "--overlay",
+            &format!("{}@bottom-right", red.to_str().unwrap()),
+            "--overlay",
+            &format!("{}@10px/20px@50", blue.to_str().unwrap()),
+        ]);
+        let mut input = DynamicImage::new_rgb8(200, 100);
+        ImageUtil::fill_image(&mut input, &[255, 255, 255, 255]);
+        let result = op.process_image(&input, &PathBuf::from("in.png"));
+        let result = result.unwrap();
+
+        assert_eq!(result.get_pixel(190, 95).0, [255, 0, 0, 255]);
+        assert_eq!(result.get_pixel(175, 85).0, [255, 255, 255, 255]);
+        assert_eq!(result.get_pixel(15, 25).0, [128, 128, 255, 255]);
+        assert_eq!(result.get_pixel(5, 25).0, [255, 255, 255, 255]);
+    }
+
+    #[test]
+    fn verbose_geometry() {
+        let op = PrepareImage::from_iter(&[
+            "prep",
+            "--output",
+            "out.png",
+            "--format",
+            "600px/400px",
+            "--padding",
+            "10px",
+            "--margins",
+            "20px/30px/40px/50px",
+            "--verbose-geometry",
+        ]);
+        let (width, height, rotate) = op.canvas_size(&op.format[0], 300, 400).unwrap();
+        let sizes = op.calc_sizes(width, height, 300, 400, rotate, 300.0);
+        let report =
+            PrepareImage::geometry(&PathBuf::from("in.png"), width, height, rotate, &sizes);
+        assert!(report.starts_with("\"in.png\": rotate true, canvas 400x600, image "));
+
+        let value = |key: &str| -> Vec<f64> {
+            let start = report.find(&format!("{} ", key)).unwrap() + key.len() + 1;
+            report[start..]
+                .split(&[',', ' '][..])
+                .next()
+                .unwrap()
+                .split(&['/', 'x'][..])
+                .map(|v| v.parse().unwrap())
+                .collect()
+        };
+        let (image, padding, margins) = (value("image"), value("padding"), value("margins"));
+        let sum_x = margins[3] + padding[3] + image[2] + padding[1] + margins[1];
+        let sum_y = margins[0] + padding[0] + image[3] + padding[2] + margins[2];
+        assert_eq!((sum_x, sum_y), (400.0, 600.0));
+        assert_eq!(&value("canvas"), &[400.0, 600.0]);
+    }
+
+    #[test]
+    fn svg_guides() {
+        let dir = test_dir("svg");
+        let input = dir.join("in").join("in.png");
+        std::fs::create_dir_all(input.parent().unwrap()).unwrap();
+        DynamicImage::new_rgb8(300, 200).save(&input).unwrap();
+        let input = InputFile::from_path(input);
+
+        let op = |output_dir: &str, output: &str| {
+            PrepareImage::from_iter(&[
+                "prep",
+                "--output",
+                output,
+                "--output-dir",
+                dir.join(output_dir).to_str().unwrap(),
+                "--format",
+                "6in/4in",
+                "--dpi",
+                "100",
+                "--padding",
+                "10px",
+                "--margins",
+                "20px",
+                "--cut-marks",
+                "2px/5px",
+                "--svg-guides",
+                "*-guides.svg",
+            ])
+        };
+        op("out", "*.png")
+            .process_file(&input, 1, &RunOptions::default())
+            .unwrap();
+        let svg_path = dir.join("out").join("in-guides.svg");
+        let svg = std::fs::read_to_string(&svg_path).unwrap();
+
+        // Page 600x400px, image 510x340px centered, trim including 10px padding
+        assert!(dir.join("out").join("in.png").exists());
+        assert!(svg.contains("width=\"6.0000in\" height=\"4.0000in\" viewBox=\"0 0 600 400\""));
+        assert!(svg.contains("<rect id=\"image\" x=\"45\" y=\"30\" width=\"510\" height=\"340\""));
+        assert!(svg.contains("<rect id=\"trim\" x=\"35\" y=\"20\" width=\"530\" height=\"360\""));
+        assert_eq!(svg.matches("class=\"cut-mark\"").count(), 8);
+
+        // Existing guides are kept with `--no-clobber`
+        std::fs::remove_file(dir.join("out").join("in.png")).unwrap();
+        std::fs::write(&svg_path, "existing").unwrap();
+        let options = RunOptions {
+            no_clobber: true,
+            ..RunOptions::default()
+        };
+        op("out", "*.png")
+            .process_file(&input, 1, &options)
+            .unwrap();
+        assert!(dir.join("out").join("in.png").exists());
+        assert_eq!(std::fs::read_to_string(&svg_path).unwrap(), "existing");
+
+        // No guides if the image can't be saved, here to a directory blocked by a file
+        std::fs::create_dir_all(dir.join("fail")).unwrap();
+        std::fs::write(dir.join("fail").join("sub"), "").unwrap();
+        assert!(op("fail", "sub/*.png")
+            .process_file(&input, 1, &RunOptions::default())
+            .is_err());
+        assert!(!dir.join("fail").join("in-guides.svg").exists());
+    }
+
+    #[test]
+    fn min_dpi() {
+        let args = [
+            "prep",
+            "--output",
+            "out.png",
+            "--format",
+            "4in/2in",
+            "--dpi",
+            "100",
+            "--padding",
+            "0px",
+            "--margins",
+            "0px",
+        ];
+        // 4in/2in at 100 dpi = 400x200px, but only 200x100px from the source
+        let image = DynamicImage::new_rgb8(200, 100);
+        let op = PrepareImage::from_iter(&args);
+        let size = op.calc_sizes(400, 200, 200, 100, false, 100.0).0;
+        assert_eq!(PrepareImage::effective_dpi(200, 100, &size, 100.0), 50.0);
+        assert!(op.process_image(&image, &PathBuf::from("in.png")).is_ok());
+
+        let op = PrepareImage::from_iter(args.iter().chain(&["--min-dpi", "60"]));
+        let error = op
+            .process_image(&image, &PathBuf::from("in.png"))
+            .err()
+            .unwrap();
+        assert!(error.to_string().contains("printed at 50 dpi"));
+
+        let op = PrepareImage::from_iter(args.iter().chain(&["--min-dpi", "50"]));
+        assert!(op.process_image(&image, &PathBuf::from("in.png")).is_ok());
+    }
+
+    #[test]
+    fn min_ppi() {
+        // 300px printed at 6in are 50 ppi
+        let op = PrepareImage::from_iter(&[
+            "prep",
+            "--output",
+            "out.png",
+            "--format",
+            "6in/4in",
+            "--padding",
+            "0px",
+            "--margins",
+            "0px",
+            "--min-ppi",
+            "150",
+        ]);
+        let image = DynamicImage::new_rgb8(300, 200);
+        let error = op
+            .process_image(&image, &PathBuf::from("in.png"))
+            .err()
+            .unwrap();
+        assert!(error.to_string().contains("printed at 50 dpi"));
+    }
+
+    #[test]
+    fn multiple_formats() {
+        let dir = test_dir("prep-formats");
+        let input = dir.join("in.png");
+        DynamicImage::new_rgb8(300, 200).save(&input).unwrap();
+
+        let args = |output: &str| {
+            vec![
+                "prep".to_string(),
+                "--output".to_string(),
+                dir.join(output).to_str().unwrap().to_string(),
+                "--format".to_string(),
+                "60px/40px".to_string(),
+                "--format".to_string(),
+                "90px/60px".to_string(),
+                "--padding".to_string(),
+                "0px".to_string(),
+                "--margins".to_string(),
+                "5px".to_string(),
+            ]
+        };
+        let op = PrepareImage::from_iter(args("*-{format}.png"));
+        ImageOperation::execute(
+            &op,
+            &[InputFile::from_path(input.clone())],
+            &RunOptions::default(),
+        )
+        .unwrap();
+        let small = image::open(dir.join("in-60pxx40px.png")).map(|i| i.dimensions());
+        let large = image::open(dir.join("in-90pxx60px.png")).map(|i| i.dimensions());
+
+        let op = PrepareImage::from_iter(args("*.png"));
+        assert!(op.output_size(300, 200).is_err());
+
+        assert_eq!(small.unwrap(), (60, 40));
+        assert_eq!(large.unwrap(), (90, 60));
+    }
+
+    #[test]
+    fn layout_overflow() {
+        let op = PrepareImage::from_iter(&[
+            "prep",
+            "--output",
+            "out.png",
+            "--format",
+            "10cm/15cm",
+            "--margins",
+            "10cm",
+            "--padding",
+            "0px",
+        ]);
+        let err = op.output_size(200, 300).unwrap_err().to_string();
+        assert!(err.contains("margins and padding exceed"), "{}", err);
+        assert!(err.contains("width by 1162px (98.4mm)"), "{}", err);
+        assert!(err.contains("height by 562px (47.6mm)"), "{}", err);
+
+        let image = DynamicImage::new_rgb8(200, 300);
+        let file = PathBuf::from("in.png");
+        assert!(op.process_image(&image, &file).is_err());
+
+        let op = PrepareImage::from_iter(&[
+            "prep",
+            "--output",
+            "out.png",
+            "--format",
+            "10cm/15cm",
+            "--image-size",
+            "9cm/14cm",
+            "--padding",
+            "1cm",
+        ]);
+        let err = op.output_size(200, 300).unwrap_err().to_string();
+        assert!(err.contains("image size and borders exceed"), "{}", err);
+        assert!(
+            err.contains("width by 99px") && err.contains("height by 90px"),
+            "{}",
+            err
+        );
+
+        let op = PrepareImage::from_iter(&[
+            "prep",
+            "--output",
+            "out.png",
+            "--format",
+            "10cm/15cm",
+            "--margins",
+            "2cm",
+            "--padding",
+            "1cm",
+        ]);
+        assert!(op.output_size(200, 300).is_ok());
+    }
+
+    #[test]
+    fn fit_format() {
+        let op = PrepareImage::from_iter(&[
+            "prep",
+            "--output",
+            "{stem}-{format}.png",
+            "--fit-format",
+            "--dpi",
+            "100",
+            "--padding",
+            "0px",
+            "--margins",
+            "0px",
+        ]);
+        assert_eq!(op.output_size(300, 200).unwrap(), (600, 400));
+        assert_eq!(op.output_size(200, 300).unwrap(), (400, 600));
+        assert_eq!(op.output_size(400, 300).unwrap(), (950, 700));
+        assert_eq!(op.variant_tokens(300, 200, 0)["format"], "15cmx10cm");
+
+        let args = ["prep", "--output", "out.png", "--format", "6in/4in"];
+        assert!(PrepareImage::from_iter_safe(args.iter().chain(&["--fit-format"])).is_err());
+        assert!(PrepareImage::from_iter_safe(&["prep", "--output", "out.png"]).is_err());
+    }
+
+    #[test]
+    fn format_auto() {
+        let op = PrepareImage::from_iter(&[
+            "prep",
+            "--output",
+            "out.png",
+            "--format",
+            "auto",
+            "--long-edge",
+            "6in",
+            "--padding",
+            "0.5in",
+            "--margins",
+            "0px",
+        ]);
+        assert_eq!(op.output_size(3000, 2000).unwrap(), (1800, 1200));
+        assert_eq!(op.output_size(2000, 3000).unwrap(), (1200, 1800));
+
+        let (img, _, _, _) = op.calc_sizes(1800, 1200, 3000, 2000, false, 300.0);
+        assert_eq!(img.to_string(), "1350px/900px");
+
+        let op = PrepareImage::from_iter(&[
+            "prep",
+            "--output",
+            "out.png",
+            "--format",
+            "auto",
+            "--padding",
+            "0px",
+            "--margins",
+            "0px",
+        ]);
+        assert!(op.output_size(3000, 2000).is_err());
+    }
+}
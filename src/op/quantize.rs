@@ -0,0 +1,138 @@
+//! Reduce images to a limited color palette.
+
+use crate::op::{ImageIoOperation, ImageOperation, OutputOptions, RunOptions};
+use crate::util::{ImageUtil, InputFile};
+use image::DynamicImage;
+use std::error::Error;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use structopt::StructOpt;
+
+/// Reduce images to a limited color palette.
+#[derive(StructOpt, Debug)]
+pub struct QuantizeImage {
+    #[structopt(flatten)]
+    pub output: OutputOptions,
+
+    /// Number of colors of the palette. Default: `16`.
+    #[structopt(long)]
+    pub colors: Option<usize>,
+
+    /// Reference image to derive the palette from, used for all images.
+    /// If not given, the palette is derived from each image individually.
+    #[structopt(name = "palette-from", long, value_name = "path")]
+    pub palette_from: Option<PathBuf>,
+
+    /// Use Floyd-Steinberg dithering, to reduce banding.
+    #[structopt(long)]
+    pub dither: bool,
+
+    #[structopt(skip)]
+    palette: Mutex<Option<Vec<[u8; 3]>>>,
+}
+
+impl QuantizeImage {
+    fn palette(&self, image: &DynamicImage) -> Result<Vec<[u8; 3]>, Box<dyn Error>> {
+        let colors = self.colors.unwrap_or(16);
+        if let Some(path) = &self.palette_from {
+            let mut palette = self.palette.lock().unwrap();
+            if palette.is_none() {
+                let reference = image::open(path)?;
+                *palette = Some(ImageUtil::median_cut_palette(&reference, colors));
+            }
+            Ok(palette.clone().unwrap())
+        } else {
+            Ok(ImageUtil::median_cut_palette(image, colors))
+        }
+    }
+}
+
+impl ImageOperation for QuantizeImage {
+    fn execute(&self, files: &[InputFile], options: &RunOptions) -> Result<(), Box<dyn Error>> {
+        ImageIoOperation::execute(self, files, options)
+    }
+}
+
+impl ImageIoOperation for QuantizeImage {
+    fn output(&self) -> &OutputOptions {
+        &self.output
+    }
+
+    fn output_size(&self, width: u32, height: u32) -> Result<(u32, u32), Box<dyn Error>> {
+        Ok((width, height))
+    }
+
+    fn process_image(
+        &self,
+        image: &DynamicImage,
+        _file: &PathBuf,
+    ) -> Result<DynamicImage, Box<dyn Error>> {
+        let palette = self.palette(image)?;
+        if self.dither {
+            Ok(ImageUtil::dither_floyd_steinberg(image, |c| {
+                ImageUtil::nearest_color(
+                    &palette,
+                    [c[0].round() as u8, c[1].round() as u8, c[2].round() as u8],
+                )
+            }))
+        } else {
+            Ok(ImageUtil::quantize(image, &palette))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::op::{ImageIoOperation, QuantizeImage};
+    use crate::util::test_dir;
+    use crate::util::ImageUtil;
+    use image::{DynamicImage, GenericImage, GenericImageView, Rgba};
+    use std::path::PathBuf;
+    use structopt::StructOpt;
+
+    #[test]
+    fn palette_from() {
+        let dir = test_dir("quantize");
+        let reference_path = dir.join("reference.png");
+
+        let mut reference = DynamicImage::new_rgb8(32, 32);
+        for (x, y, col) in [
+            (0, 0, [255, 0, 0, 255]),
+            (16, 0, [0, 255, 0, 255]),
+            (0, 16, [0, 0, 255, 255]),
+            (16, 16, [255, 255, 0, 255]),
+        ]
+        .iter()
+        {
+            for yy in *y..(y + 16) {
+                for xx in *x..(x + 16) {
+                    reference.put_pixel(xx, yy, Rgba(*col));
+                }
+            }
+        }
+        reference.save(&reference_path).unwrap();
+
+        let op = QuantizeImage::from_iter(&[
+            "quantize",
+            "--output",
+            "out.png",
+            "--colors",
+            "4",
+            "--palette-from",
+            reference_path.to_str().unwrap(),
+        ]);
+
+        let mut image = DynamicImage::new_rgb8(16, 16);
+        for (x, y, pix) in DynamicImage::new_rgb8(16, 16).pixels() {
+            let v = ((x * 16 + y) % 256) as u8;
+            image.put_pixel(x, y, Rgba([v, 255 - v, v / 2, pix.0[3]]));
+        }
+        let result = op.process_image(&image, &PathBuf::from("in.png")).unwrap();
+        let palette = ImageUtil::median_cut_palette(&reference, 4);
+
+        assert_eq!(palette.len(), 4);
+        for (_, _, pix) in result.pixels() {
+            assert!(palette.contains(&[pix.0[0], pix.0[1], pix.0[2]]));
+        }
+    }
+}
@@ -1,10 +1,13 @@
 //! Scale images.
 
 use crate::cli::parse;
-use crate::op::{ImageIoOperation, ImageOperation};
+use crate::op::{edit_log_json, ImageIoOperation, ImageOperation, OutputOptions, RunOptions};
 use crate::units::color::Color;
-use crate::units::{Length, LengthUnit, Scale, ScaleMode, Size};
-use crate::util::ImageUtil;
+use crate::units::{
+    format, Downscale, FixSize, Length, LengthUnit, Orientation, Scale, ScaleMode, ScaleOf, Size,
+    SmartCrop,
+};
+use crate::util::{ImageUtil, InputFile};
 use crate::OperationParametersError;
 use image::imageops::FilterType;
 use image::{DynamicImage, GenericImageView};
@@ -15,47 +18,98 @@ use structopt::StructOpt;
 /// Scale images to absolute or relative size.
 #[derive(StructOpt, Debug)]
 pub struct ScaleImage {
-    /// Output path. Use `*` as placeholder for the original base file name.
-    /// Used to determine output image type. On Unix systems, this MUST be quoted!
-    ///
-    /// Examples:
-    /// --output "path/to/*-out.jpg"
-    ///
-    #[structopt(verbatim_doc_comment)]
-    #[structopt(short, long)]
-    pub output: String,
-
-    /// Image quality for JPEG output in percent. Optional, default `95`.
-    #[structopt(short, long)]
-    pub quality: Option<u8>,
+    #[structopt(flatten)]
+    pub output: OutputOptions,
 
-    /// Output image size. Use either `--size` or `--scale`.
-    /// Examples: `100px/.`, `./15cm`, `8in/6in`.
+    /// Output image size. Use either `--size`, `--scale`, `--long-edge` or `--short-edge`.
+    /// A single value is used for width and height.
+    /// Examples: `100px/.`, `./15cm`, `8in/6in`, `500px`.
     #[structopt(long)]
     pub size: Option<Size>,
 
-    /// Output image scale. Use either `--size` or `--scale`.
+    /// Output image scale. Use either `--size`, `--scale`, `--long-edge` or `--short-edge`.
     /// Examples: `0.5`, `50%`, `20%/10%`.
     #[structopt(long)]
     pub scale: Option<Scale>,
 
+    /// Output length of the longer image edge, independent of orientation. Keeps the aspect ratio.
+    /// Examples: `2000px`, `15cm`.
+    #[structopt(name = "long-edge", long, value_name = "length")]
+    pub long_edge: Option<Length>,
+
+    /// Output length of the shorter image edge, independent of orientation. Keeps the aspect ratio.
+    /// Examples: `1000px`, `10cm`.
+    #[structopt(name = "short-edge", long, value_name = "length")]
+    pub short_edge: Option<Length>,
+
+    /// Reference for `--scale`. One of `(source|format)`.
+    /// With `format`, scaling is relative to `--print-format`.
+    /// Default: `source`.
+    #[structopt(name = "scale-of", long)]
+    pub scale_of: Option<ScaleOf>,
+
+    /// Print format `width/height` used as reference for `--scale-of format`.
+    /// Formats in cm are converted to exact print formats in inches.
+    /// Examples: `15cm/10cm`, `6in/4in`.
+    #[structopt(name = "print-format", long, value_name = "w/h")]
+    pub print_format: Option<FixSize>,
+
+    /// Rotate images by 90° clockwise before scaling if they don't have the given orientation.
+    /// One of `(portrait|landscape)`. Square images are never rotated. Ignores EXIF orientation.
+    #[structopt(name = "rotate-to", long, value_name = "orientation")]
+    pub rotate_to: Option<Orientation>,
+
+    /// Keep the aspect ratio for `--size` with only width or height, and for `--long-edge` and `--short-edge`.
+    /// This is always the case, but is required to confirm that a `--mode` other than `keep` is ignored.
+    #[structopt(name = "lock-aspect", long)]
+    pub lock_aspect: bool,
+
     /// Scaling mode. Must be given when using `--size` with width and height.
-    /// One of `(keep|stretch|crop|fill)`.
+    /// One of `(keep|stretch|crop|fill|blur|extend)`.
     /// Default: `keep`.
     #[structopt(short, long)]
     pub mode: Option<ScaleMode>,
 
+    /// Blur radius (sigma) in px of the padding for `--mode blur`. Default: `25`.
+    #[structopt(name = "blur-sigma", long, value_name = "sigma")]
+    pub blur_sigma: Option<f32>,
+
+    /// Heuristic for the image region to keep with `--mode crop`.
+    /// One of `(center|upper|rule-of-thirds)`. `upper` is suitable for portraits.
+    /// Default: `center`.
+    #[structopt(name = "smart-crop", long, value_name = "heuristic")]
+    pub smart_crop: Option<SmartCrop>,
+
     /// Filter type for image scaling.
-    /// One of `(nearest|linear|cubic|gauss|lanczos)`.
+    /// One of `(nearest|triangle|catmullrom|gaussian|lanczos3)`,
+    /// or an alias: `box` for `nearest` (point sampling, without averaging),
+    /// `linear` and `bilinear` for `triangle`, `cubic` and `bicubic` for `catmullrom`,
+    /// `gauss` for `gaussian`, `lanczos` for `lanczos3`.
     /// Default: `cubic`.
     #[structopt(short, long, parse(try_from_str = parse::parse_filter_type))]
     pub filter: Option<FilterType>,
 
     /// Enable incremental scaling.
-    /// For scaling to small sizes, scales down in multiple steps, to 50% per step, averaging over 2x2 pixels.
+    /// For scaling to small sizes, scales down in multiple steps, to 50% per step, see `--downscale`.
     #[structopt(long)]
     pub incremental: bool,
 
+    /// Half-size steps for `--incremental`. One of `(box|filtered)`.
+    /// `box` averages over 2x2 pixels, `filtered` uses `--filter` and is sharper.
+    /// Default: `box`.
+    #[structopt(long, requires = "incremental", value_name = "step")]
+    pub downscale: Option<Downscale>,
+
+    /// Gaussian pre-blur radius (sigma) in px of the original image, to reduce aliasing.
+    /// Only applied for large downscales, to less than 50% in any direction.
+    #[structopt(long, value_name = "radius")]
+    pub prefilter: Option<f32>,
+
+    /// Writes a sidecar file `<output>.edit.json` describing the source size
+    /// and the region of the source that was kept, e.g. to undo crops.
+    #[structopt(name = "write-edit-log", long)]
+    pub write_edit_log: bool,
+
     /// Image resolution for size not in px. Default `300`.
     #[structopt(short, long)]
     pub dpi: Option<f64>,
@@ -66,9 +120,79 @@ pub struct ScaleImage {
 }
 impl ScaleImage {
     fn check(&self) -> Result<(), Box<dyn Error>> {
-        if self.size.is_some() == self.scale.is_some() {
+        let given = [
+            self.size.is_some(),
+            self.scale.is_some(),
+            self.long_edge.is_some(),
+            self.short_edge.is_some(),
+        ];
+        if given.iter().filter(|g| **g).count() != 1 {
+            return Err(Box::new(OperationParametersError(
+                "Exactly one of `--size`, `--scale`, `--long-edge` and `--short-edge` must be given!"
+                    .to_string(),
+            )));
+        }
+        if self
+            .long_edge
+            .iter()
+            .chain(self.short_edge.iter())
+            .any(|l| l.is_relative())
+        {
+            return Err(Box::new(OperationParametersError(
+                "`--long-edge` and `--short-edge` do not support relative lengths in `%`, use `--scale` instead!"
+                    .to_string(),
+            )));
+        }
+        if self.size.as_ref().is_some_and(|s| s.is_relative()) {
+            return Err(Box::new(OperationParametersError(
+                "`--size` does not support relative lengths in `%`, use `--scale` instead!"
+                    .to_string(),
+            )));
+        }
+        let single_dimension = self.long_edge.is_some()
+            || self.short_edge.is_some()
+            || self
+                .size
+                .as_ref()
+                .is_some_and(|s| s.width().is_none() || s.height().is_none());
+        if self.lock_aspect && !single_dimension {
+            return Err(Box::new(OperationParametersError(
+                "`--lock-aspect` requires `--size` with only width or height, `--long-edge` or `--short-edge`!"
+                    .to_string(),
+            )));
+        }
+        if let Some(mode) = &self.mode {
+            if single_dimension && mode != &ScaleMode::Keep && !self.lock_aspect {
+                return Err(Box::new(OperationParametersError(format!(
+                    "`--mode {}` conflicts with a single-dimension size, which always keeps the aspect ratio. \
+                    Give both width and height, or use `--lock-aspect` to ignore the mode!",
+                    mode
+                ))));
+            }
+        }
+        if self.scale_of == Some(ScaleOf::Format)
+            && (self.scale.is_none() || self.print_format.is_none())
+        {
             return Err(Box::new(OperationParametersError(
-                "Exactly one of `--size` and `--scale` must be given!".to_string(),
+                "`--scale-of format` requires `--scale` and `--print-format`!".to_string(),
+            )));
+        }
+        if let Some(sigma) = self.blur_sigma {
+            if !matches!(self.mode, Some(ScaleMode::Blur(_))) {
+                return Err(Box::new(OperationParametersError(
+                    "`--blur-sigma` requires `--mode blur`!".to_string(),
+                )));
+            }
+            if sigma.is_nan() || sigma <= 0.0 {
+                return Err(Box::new(OperationParametersError(format!(
+                    "`--blur-sigma` must be larger than 0, got {}!",
+                    sigma
+                ))));
+            }
+        }
+        if self.smart_crop.is_some() && self.mode != Some(ScaleMode::Crop) {
+            return Err(Box::new(OperationParametersError(
+                "`--smart-crop` requires `--mode crop`!".to_string(),
             )));
         }
         Ok(())
@@ -76,18 +200,43 @@ impl ScaleImage {
 }
 
 impl ImageOperation for ScaleImage {
-    fn execute(&self, files: &[PathBuf]) -> Result<(), Box<dyn Error>> {
-        ImageIoOperation::execute(self, &files)
+    fn execute(&self, files: &[InputFile], options: &RunOptions) -> Result<(), Box<dyn Error>> {
+        ImageIoOperation::execute(self, files, options)
     }
 }
 
 impl ImageIoOperation for ScaleImage {
-    fn output(&self) -> &str {
+    fn output(&self) -> &OutputOptions {
         &self.output
     }
 
-    fn quality(&self) -> &Option<u8> {
-        &self.quality
+    fn output_size(&self, width: u32, height: u32) -> Result<(u32, u32), Box<dyn Error>> {
+        self.check()?;
+        let (width, height) = self.rotated_size(width, height);
+
+        let (out_width, out_height, mode) = self.target_size(width, height)?;
+        if mode == &ScaleMode::Keep {
+            Ok(ImageUtil::fit_size(width, height, out_width, out_height))
+        } else {
+            Ok((out_width, out_height))
+        }
+    }
+
+    fn edit_log(&self, input: &DynamicImage) -> Option<String> {
+        if !self.write_edit_log {
+            return None;
+        }
+        let (width, height) = input.dimensions();
+        let output = self.output_size(width, height).ok()?;
+        let (width, height) = self.rotated_size(width, height);
+        let (target_width, target_height, mode) = self.target_size(width, height).ok()?;
+        let crop = if mode == &ScaleMode::Crop {
+            let smart_crop = self.smart_crop.as_ref().unwrap_or(&SmartCrop::Center);
+            ImageUtil::crop_rect(width, height, target_width, target_height, smart_crop)
+        } else {
+            (0, 0, width, height)
+        };
+        Some(edit_log_json("scale", (width, height), crop, output))
     }
 
     fn process_image(
@@ -97,44 +246,396 @@ impl ImageIoOperation for ScaleImage {
     ) -> Result<DynamicImage, Box<dyn Error>> {
         self.check()?;
 
-        let dpi = self.dpi.unwrap_or(300.0);
         let filter = self.filter.as_ref().unwrap_or(&FilterType::CatmullRom);
-        let mode = self.mode.as_ref().unwrap_or(&ScaleMode::Keep);
         let color = self.bg.clone().unwrap_or(Color::new(255, 255, 255, 255));
 
+        let rotated;
+        let image = if self.rotate(image.width(), image.height()) {
+            rotated = image.rotate90();
+            &rotated
+        } else {
+            image
+        };
+
+        let (width, height, mode) = self.target_size(image.width(), image.height())?;
+        let cropped;
+        let (image, mode) = match &self.smart_crop {
+            Some(smart_crop) if mode == &ScaleMode::Crop => {
+                cropped = ImageUtil::crop_to_aspect(image, width, height, smart_crop);
+                (&cropped, &ScaleMode::Stretch)
+            }
+            _ => (image, mode),
+        };
+        let blur_mode;
+        let mode = match (mode, self.blur_sigma) {
+            (ScaleMode::Blur(_), Some(sigma)) => {
+                blur_mode = ScaleMode::Blur(sigma);
+                &blur_mode
+            }
+            _ => mode,
+        };
+        match self.prefilter {
+            Some(radius) if 2 * width < image.width() || 2 * height < image.height() => {
+                let blurred = image.blur(radius);
+                ImageUtil::scale_image(
+                    &blurred,
+                    width,
+                    height,
+                    mode,
+                    filter,
+                    &color,
+                    ImageUtil::incremental_step(self.incremental, self.downscale.as_ref()),
+                )
+            }
+            _ => ImageUtil::scale_image(
+                image,
+                width,
+                height,
+                mode,
+                filter,
+                &color,
+                ImageUtil::incremental_step(self.incremental, self.downscale.as_ref()),
+            ),
+        }
+    }
+}
+
+impl ScaleImage {
+    /// Whether an image of the given size is rotated for `--rotate-to`.
+    fn rotate(&self, width: u32, height: u32) -> bool {
+        self.rotate_to
+            .as_ref()
+            .is_some_and(|o| !o.matches(width, height))
+    }
+
+    /// The image size after rotation for `--rotate-to`.
+    fn rotated_size(&self, width: u32, height: u32) -> (u32, u32) {
+        if self.rotate(width, height) {
+            (height, width)
+        } else {
+            (width, height)
+        }
+    }
+
+    /// Calculates the target size in px and the effective scale mode for an input image size.
+    fn target_size(
+        &self,
+        image_width: u32,
+        image_height: u32,
+    ) -> Result<(u32, u32, &ScaleMode), Box<dyn Error>> {
+        let dpi = self.dpi.unwrap_or(300.0);
+        let mode = self.mode.as_ref().unwrap_or(&ScaleMode::Keep);
+
         let size = if let Some(s) = &self.size {
             s.to(&LengthUnit::Px, dpi)
+        } else if let Some(edge) = self.long_edge.as_ref().or(self.short_edge.as_ref()) {
+            let edge = Some(edge.to_px(dpi));
+            let landscape = image_width >= image_height;
+            if landscape == self.long_edge.is_some() {
+                Size::new(edge, None)?
+            } else {
+                Size::new(None, edge)?
+            }
         } else {
+            let (ref_width, ref_height) =
+                if self.scale_of.as_ref().unwrap_or(&ScaleOf::Source) == &ScaleOf::Format {
+                    let format =
+                        format::to_print_format(self.print_format.as_ref().unwrap())?.to_px(dpi);
+                    (
+                        format.width().value() as f32,
+                        format.height().value() as f32,
+                    )
+                } else {
+                    (image_width as f32, image_height as f32)
+                };
             Size::new(
                 Some(Length::px(
-                    (image.width() as f32 * self.scale.as_ref().unwrap().width()).round() as i32,
+                    (ref_width * self.scale.as_ref().unwrap().width()).round() as i32,
                 )),
                 Some(Length::px(
-                    (image.height() as f32 * self.scale.as_ref().unwrap().height()).round() as i32,
+                    (ref_height * self.scale.as_ref().unwrap().height()).round() as i32,
                 )),
             )?
         };
 
+        // Only sizes given explicitly can be zero, computed sizes are clamped to 1px
+        let zero = |l: &Option<Length>| l.as_ref().is_some_and(|l| l.value() <= 0.0);
+        if self.scale.is_none() && (zero(size.width()) || zero(size.height())) {
+            return Err(Box::new(OperationParametersError(
+                "Can't scale to a size of zero".to_string(),
+            )));
+        }
+
         let mut any_missing = false;
         let width = if let Some(w) = size.width() {
             w.value() as u32
         } else {
             any_missing = true;
             let h = size.height().as_ref().unwrap().value();
-            ((h as f64 / image.height() as f64) * image.width() as f64).round() as u32
+            ((h / image_height as f64) * image_width as f64).round() as u32
         };
         let height = if let Some(h) = size.height() {
             h.value() as u32
         } else {
             any_missing = true;
             let w = size.width().as_ref().unwrap().value();
-            ((w as f64 / image.width() as f64) * image.height() as f64).round() as u32
+            ((w / image_width as f64) * image_height as f64).round() as u32
         };
 
         let mode = if any_missing { &ScaleMode::Keep } else { mode };
-        let result =
-            ImageUtil::scale_image(image, width, height, mode, filter, &color, self.incremental);
+        Ok((width.max(1), height.max(1), mode))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::op::{ImageIoOperation, RunOptions, ScaleImage};
+    use crate::util::test_dir;
+    use crate::util::InputFile;
+    use image::{DynamicImage, GenericImage, GenericImageView, Rgba};
+    use std::path::PathBuf;
+    use structopt::StructOpt;
+
+    #[test]
+    fn scale_of_format() {
+        let op = ScaleImage::from_iter(&[
+            "scale",
+            "--output",
+            "out.png",
+            "--scale",
+            "80%",
+            "--scale-of",
+            "format",
+            "--print-format",
+            "6in/4in",
+            "--dpi",
+            "300",
+        ]);
+        let image = DynamicImage::new_rgb8(60, 40);
+        let result = op.process_image(&image, &PathBuf::from("in.png")).unwrap();
+
+        assert_eq!(result.width(), 1440);
+        assert_eq!(result.height(), 960);
+    }
+
+    #[test]
+    fn prefilter() {
+        let mut image = DynamicImage::new_rgb8(300, 300);
+        for (x, y, _) in DynamicImage::new_rgb8(300, 300).pixels() {
+            let v = if x % 3 == 0 { 255 } else { 0 };
+            image.put_pixel(x, y, Rgba([v, v, v, 255]));
+        }
+
+        let variance = |args: &[&str]| {
+            let op = ScaleImage::from_iter(args);
+            let result = op.process_image(&image, &PathBuf::from("in.png")).unwrap();
+            let values: Vec<f64> = result.pixels().map(|(_, _, p)| p.0[0] as f64).collect();
+            let mean = values.iter().sum::<f64>() / values.len() as f64;
+            values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / values.len() as f64
+        };
+
+        let args = [
+            "scale",
+            "--output",
+            "out.png",
+            "--size",
+            "37px/37px",
+            "--filter",
+            "nearest",
+        ];
+        let plain = variance(&args);
+        let filtered = variance(&[&args[..], &["--prefilter", "2"]].concat());
+
+        assert!(filtered < plain);
+    }
+
+    #[test]
+    fn smart_crop_upper() {
+        let mut image = DynamicImage::new_rgb8(100, 300);
+        for (x, y, _) in DynamicImage::new_rgb8(100, 300).pixels() {
+            let v = (y * 255 / 299) as u8;
+            image.put_pixel(x, y, Rgba([v, v, v, 255]));
+        }
+        let top_value = |heuristic: &str| {
+            let op = ScaleImage::from_iter(&[
+                "scale",
+                "--output",
+                "out.png",
+                "--size",
+                "50px/50px",
+                "--mode",
+                "crop",
+                "--smart-crop",
+                heuristic,
+            ]);
+            let result = op.process_image(&image, &PathBuf::from("in.png")).unwrap();
+            assert_eq!((result.width(), result.height()), (50, 50));
+            result.get_pixel(25, 0).0[0]
+        };
+
+        assert!(top_value("upper") < top_value("rule-of-thirds"));
+        assert!(top_value("rule-of-thirds") < top_value("center"));
+    }
+
+    #[test]
+    fn edit_log() {
+        let dir = test_dir("edit-log");
+        let input = dir.join("in.png");
+        DynamicImage::new_rgb8(300, 200).save(&input).unwrap();
+
+        let output = dir.join("out.png");
+        let op = ScaleImage::from_iter(&[
+            "scale",
+            "--output",
+            output.to_str().unwrap(),
+            "--size",
+            "100px/100px",
+            "--mode",
+            "crop",
+            "--write-edit-log",
+        ]);
+        op.process_file(&InputFile::from_path(input), 1, &RunOptions::default())
+            .unwrap();
+        let log = std::fs::read_to_string(dir.join("out.png.edit.json")).unwrap();
+
+        assert!(log.contains("\"source\": { \"width\": 300, \"height\": 200 }"));
+        assert!(log.contains("\"crop\": { \"x\": 50, \"y\": 0, \"width\": 200, \"height\": 200 }"));
+        assert!(log.contains("\"output\": { \"width\": 100, \"height\": 100 }"));
+    }
+
+    #[test]
+    fn long_edge() {
+        let op = ScaleImage::from_iter(&["scale", "--output", "out.png", "--long-edge", "2000"]);
+        assert_eq!(op.output_size(400, 300).unwrap(), (2000, 1500));
+        assert_eq!(op.output_size(300, 400).unwrap(), (1500, 2000));
+
+        let op = ScaleImage::from_iter(&["scale", "--output", "out.png", "--short-edge", "600"]);
+        assert_eq!(op.output_size(400, 300).unwrap(), (800, 600));
+        assert_eq!(op.output_size(300, 400).unwrap(), (600, 800));
+
+        let op = ScaleImage::from_iter(&[
+            "scale",
+            "--output",
+            "out.png",
+            "--long-edge",
+            "2000",
+            "--scale",
+            "50%",
+        ]);
+        assert!(op.output_size(400, 300).is_err());
+    }
+
+    #[test]
+    fn zero_size() {
+        let op = ScaleImage::from_iter(&["scale", "--output", "out.png", "--scale", "1%"]);
+        let image = DynamicImage::new_rgb8(10, 10);
+        assert_eq!(op.output_size(10, 10).unwrap(), (1, 1));
+        let result = op.process_image(&image, &PathBuf::from("in.png")).unwrap();
+        assert_eq!(result.dimensions(), (1, 1));
+
+        // Extreme aspect ratio, the short edge rounds to zero
+        let op = ScaleImage::from_iter(&["scale", "--output", "out.png", "--size", "10px/10px"]);
+        let image = DynamicImage::new_rgb8(1000, 10);
+        assert_eq!(op.output_size(1000, 10).unwrap(), (10, 1));
+        let result = op.process_image(&image, &PathBuf::from("in.png")).unwrap();
+        assert_eq!(result.dimensions(), (10, 1));
+
+        let op = ScaleImage::from_iter(&["scale", "--output", "out.png", "--size", "0px/."]);
+        assert!(op.output_size(10, 10).is_err());
+    }
+
+    #[test]
+    fn rotate_to() {
+        let op = ScaleImage::from_iter(&[
+            "scale",
+            "--output",
+            "out.png",
+            "--scale",
+            "50%",
+            "--rotate-to",
+            "portrait",
+        ]);
+        let mut image = DynamicImage::new_rgb8(40, 20);
+        image.put_pixel(0, 0, Rgba([255, 255, 255, 255]));
+        image.put_pixel(1, 0, Rgba([255, 255, 255, 255]));
+        image.put_pixel(0, 1, Rgba([255, 255, 255, 255]));
+        image.put_pixel(1, 1, Rgba([255, 255, 255, 255]));
+        assert_eq!(op.output_size(40, 20).unwrap(), (10, 20));
+        assert_eq!(op.output_size(20, 40).unwrap(), (10, 20));
+        assert_eq!(op.output_size(30, 30).unwrap(), (15, 15));
+        let result = op.process_image(&image, &PathBuf::from("in.png")).unwrap();
+        assert_eq!(result.dimensions(), (10, 20));
+        // Rotated clockwise, so the top left corner is now top right
+        assert!(result.get_pixel(9, 0).0[0] > 100);
+        assert_eq!(result.get_pixel(0, 0).0[0], 0);
+
+        let op = ScaleImage::from_iter(&[
+            "scale",
+            "--output",
+            "out.png",
+            "--long-edge",
+            "100",
+            "--rotate-to",
+            "landscape",
+        ]);
+        assert_eq!(op.output_size(20, 40).unwrap(), (100, 50));
+        assert_eq!(op.output_size(40, 20).unwrap(), (100, 50));
+        let result = op
+            .process_image(&DynamicImage::new_rgb8(20, 40), &PathBuf::from("in.png"))
+            .unwrap();
+        assert_eq!(result.dimensions(), (100, 50));
+    }
+
+    #[test]
+    fn lock_aspect() {
+        let args = [
+            "scale", "--output", "out.png", "--size", "800px/.", "--mode", "crop",
+        ];
+        let op = ScaleImage::from_iter(&args);
+        let error = op.output_size(400, 300).unwrap_err().to_string();
+        assert!(error.contains("`--mode crop` conflicts with a single-dimension size"));
+        assert!(error.contains("--lock-aspect"));
+
+        let op = ScaleImage::from_iter(args.iter().chain(&["--lock-aspect"]));
+        assert_eq!(op.output_size(400, 300).unwrap(), (800, 600));
+
+        let op = ScaleImage::from_iter(&[
+            "scale",
+            "--output",
+            "out.png",
+            "--size",
+            "800px/600px",
+            "--lock-aspect",
+        ]);
+        assert!(op.output_size(400, 300).is_err());
+    }
+
+    #[test]
+    fn blur_mode() {
+        let op = ScaleImage::from_iter(&[
+            "scale",
+            "--output",
+            "out.png",
+            "--size",
+            "60px/60px",
+            "--mode",
+            "blur",
+            "--blur-sigma",
+            "3",
+        ]);
+        let image = DynamicImage::new_rgb8(120, 80);
+        let result = op.process_image(&image, &PathBuf::from("in.png")).unwrap();
+        assert_eq!((result.width(), result.height()), (60, 60));
 
-        result
+        let op = ScaleImage::from_iter(&[
+            "scale",
+            "--output",
+            "out.png",
+            "--size",
+            "60px/60px",
+            "--blur-sigma",
+            "3",
+        ]);
+        assert!(op.output_size(120, 80).is_err());
     }
 }
@@ -0,0 +1,296 @@
+//! Split images into overlapping tiles.
+
+use crate::op::{ImageIoOperation, ImageOperation, OutputOptions, PrepareImage, RunOptions};
+use crate::units::color::Color;
+use crate::units::{format, FixSize, Length};
+use crate::util::{CastUtil, ImageUtil, InputFile};
+use crate::OperationParametersError;
+use image::{DynamicImage, GenericImage, GenericImageView, Rgba};
+use std::collections::HashMap;
+use std::error::Error;
+use std::path::PathBuf;
+use structopt::StructOpt;
+
+/// Split large images into overlapping pages, e.g. to print posters on multiple sheets.
+/// Images are printed at `--dpi`, i.e. one image pixel per printed pixel.
+#[derive(StructOpt, Debug)]
+pub struct SplitImage {
+    #[structopt(flatten)]
+    pub output: OutputOptions,
+
+    /// Page format `width/height`. Formats in cm are converted to exact print formats in inches.
+    /// Examples: `15cm/10cm`, `6in/4in`, `6000px/4000px`.
+    #[structopt(short, long, value_name = "w/h")]
+    pub format: FixSize,
+
+    /// Image resolution. Default `300`.
+    #[structopt(short, long)]
+    pub dpi: Option<f64>,
+
+    /// Overlap of neighbouring pages, for gluing or cutting. Default: `10mm`.
+    #[structopt(long, value_name = "length")]
+    pub overlap: Option<Length>,
+
+    /// Background color for the unused part of the last row and column. Default `white`.
+    #[structopt(short, long, value_name = "color")]
+    pub bg: Option<Color>,
+
+    /// Draw crosses at the center of overlaps, to align neighbouring pages.
+    #[structopt(name = "registration-marks", long)]
+    pub registration_marks: bool,
+
+    /// Registration mark color. Default: black
+    #[structopt(long, value_name = "color")]
+    pub color: Option<Color>,
+}
+
+impl SplitImage {
+    fn check(&self) -> Result<(), Box<dyn Error>> {
+        if self.format.is_relative() || self.overlap.as_ref().is_some_and(|o| o.is_relative()) {
+            return Err(Box::new(OperationParametersError(
+                "`--format` and `--overlap` can't be relative!".to_string(),
+            )));
+        }
+        let output = &self.output.output;
+        if !output.contains("{row}") || !output.contains("{col}") {
+            return Err(Box::new(OperationParametersError(
+                "`--output` requires the placeholders `{row}` and `{col}`, e.g. `{stem}_r{row}_c{col}.jpg`!"
+                    .to_string(),
+            )));
+        }
+        let (width, height, overlap) = self.tile_size()?;
+        if overlap >= width || overlap >= height {
+            return Err(Box::new(OperationParametersError(format!(
+                "`--overlap` must be smaller than the page, got {}px for {}x{}px",
+                overlap, width, height
+            ))));
+        }
+        Ok(())
+    }
+
+    /// Page width, height and overlap in px.
+    fn tile_size(&self) -> Result<(u32, u32, u32), Box<dyn Error>> {
+        let dpi = self.dpi.unwrap_or(300.0);
+        let format = format::to_print_format(&self.format)?.to_px(dpi);
+        let overlap = self
+            .overlap
+            .clone()
+            .unwrap_or_else(|| Length::mm(10.0))
+            .to_px(dpi);
+        Ok((
+            CastUtil::u32_from_f64(format.width().value().round(), "page width"),
+            CastUtil::u32_from_f64(format.height().value().round(), "page height"),
+            CastUtil::u32_from_f64(overlap.value().round(), "overlap"),
+        ))
+    }
+
+    /// Number of pages to cover `size` px with pages of `tile` px, overlapping by `overlap` px.
+    fn tile_count(size: u32, tile: u32, overlap: u32) -> u32 {
+        if size <= tile {
+            1
+        } else {
+            let step = tile - overlap;
+            1 + (size - tile).div_ceil(step)
+        }
+    }
+
+    /// Number of `(rows, columns)` for an image size.
+    fn grid(&self, width: u32, height: u32) -> Result<(u32, u32), Box<dyn Error>> {
+        let (tile_width, tile_height, overlap) = self.tile_size()?;
+        Ok((
+            Self::tile_count(height, tile_height, overlap),
+            Self::tile_count(width, tile_width, overlap),
+        ))
+    }
+
+    /// Row and column of a variant.
+    fn position(&self, width: u32, height: u32, variant: usize) -> (u32, u32) {
+        let columns = self.grid(width, height).map_or(1, |(_, c)| c) as usize;
+        ((variant / columns) as u32, (variant % columns) as u32)
+    }
+
+    /// Draws a registration cross at `x`/`y`.
+    fn draw_cross(image: &mut DynamicImage, x: i32, y: i32, len: i32, lw: i32, color: Rgba<u8>) {
+        PrepareImage::fill_rect(image, x - len / 2, y - lw / 2, len, lw, color);
+        PrepareImage::fill_rect(image, x - lw / 2, y - len / 2, lw, len, color);
+    }
+}
+
+impl ImageOperation for SplitImage {
+    fn execute(&self, files: &[InputFile], options: &RunOptions) -> Result<(), Box<dyn Error>> {
+        self.check()?;
+        ImageIoOperation::execute(self, files, options)
+    }
+}
+
+impl ImageIoOperation for SplitImage {
+    fn output(&self) -> &OutputOptions {
+        &self.output
+    }
+
+    fn output_size(&self, _width: u32, _height: u32) -> Result<(u32, u32), Box<dyn Error>> {
+        self.check()?;
+        let (width, height, _) = self.tile_size()?;
+        Ok((width, height))
+    }
+
+    fn variants(&self, width: u32, height: u32) -> usize {
+        self.grid(width, height)
+            .map_or(1, |(rows, columns)| (rows * columns) as usize)
+    }
+
+    fn variant_tokens(
+        &self,
+        width: u32,
+        height: u32,
+        variant: usize,
+    ) -> HashMap<&'static str, String> {
+        let (row, col) = self.position(width, height, variant);
+        let mut tokens = HashMap::new();
+        tokens.insert("row", (row + 1).to_string());
+        tokens.insert("col", (col + 1).to_string());
+        tokens
+    }
+
+    fn process_image(
+        &self,
+        image: &DynamicImage,
+        file: &PathBuf,
+    ) -> Result<DynamicImage, Box<dyn Error>> {
+        self.process_variant(image, file, 0)
+    }
+
+    fn process_variant(
+        &self,
+        image: &DynamicImage,
+        _file: &PathBuf,
+        variant: usize,
+    ) -> Result<DynamicImage, Box<dyn Error>> {
+        self.check()?;
+        let (tile_width, tile_height, overlap) = self.tile_size()?;
+        let (rows, columns) = self.grid(image.width(), image.height())?;
+        let (row, col) = self.position(image.width(), image.height(), variant);
+
+        // Copy the covered part of the image, the last row and column are padded
+        let (x, y) = (col * (tile_width - overlap), row * (tile_height - overlap));
+        let width = tile_width.min(image.width() - x);
+        let height = tile_height.min(image.height() - y);
+        let mut result = if image.color().has_alpha() {
+            DynamicImage::new_rgba8(tile_width, tile_height)
+        } else {
+            DynamicImage::new_rgb8(tile_width, tile_height)
+        };
+        let bg = self.bg.clone().unwrap_or(Color::new(255, 255, 255, 255));
+        ImageUtil::fill_image(&mut result, bg.channels());
+        result.copy_from(&image.crop_imm(x, y, width, height), 0, 0)?;
+
+        if self.registration_marks && overlap > 0 {
+            let dpi = self.dpi.unwrap_or(300.0);
+            let color = self.color.clone().unwrap_or(Color::new(0, 0, 0, 255));
+            let rgba = Rgba(*color.channels());
+            let len = CastUtil::i32_from_f64(
+                Length::mm(5.0).to_px(dpi).value().min(overlap as f64),
+                "mark length",
+            );
+            let lw =
+                CastUtil::i32_from_f64(Length::mm(0.2).to_px(dpi).value().max(1.0), "mark width");
+            let (w, h, half) = (tile_width as i32, tile_height as i32, overlap as i32 / 2);
+            // At the center of the overlap with each neighbour
+            if col > 0 {
+                Self::draw_cross(&mut result, half, h / 2, len, lw, rgba);
+            }
+            if col + 1 < columns {
+                Self::draw_cross(&mut result, w - half, h / 2, len, lw, rgba);
+            }
+            if row > 0 {
+                Self::draw_cross(&mut result, w / 2, half, len, lw, rgba);
+            }
+            if row + 1 < rows {
+                Self::draw_cross(&mut result, w / 2, h - half, len, lw, rgba);
+            }
+        }
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::op::{ImageIoOperation, ImageOperation, RunOptions, SplitImage};
+    use crate::util::test_dir;
+    use crate::util::{ImageUtil, InputFile};
+    use image::{DynamicImage, GenericImage, GenericImageView, Rgba};
+    use std::path::PathBuf;
+    use structopt::StructOpt;
+
+    #[test]
+    fn tiles() {
+        let op = SplitImage::from_iter(&[
+            "split",
+            "--output",
+            "out/{stem}_r{row}_c{col}.png",
+            "--format",
+            "100px/80px",
+            "--overlap",
+            "20px",
+        ]);
+        // 3 columns: 0-100, 80-180, 160-260; 2 rows: 0-80, 60-140
+        assert_eq!(op.variants(250, 120), 6);
+        assert_eq!(op.variants(100, 80), 1);
+        assert_eq!(op.variant_tokens(250, 120, 5)["row"], "2");
+        assert_eq!(op.variant_tokens(250, 120, 5)["col"], "3");
+
+        let mut image = DynamicImage::new_rgb8(250, 120);
+        ImageUtil::fill_image(&mut image, &[0, 0, 255, 255]);
+        image.put_pixel(170, 70, Rgba([255, 0, 0, 255]));
+
+        let tile = op
+            .process_variant(&image, &PathBuf::from("in.png"), 5)
+            .unwrap();
+        assert_eq!(tile.dimensions(), (100, 80));
+        assert_eq!(tile.get_pixel(10, 10).0, [255, 0, 0, 255]);
+        // Padding after the end of the image
+        assert_eq!(tile.get_pixel(89, 59).0, [0, 0, 255, 255]);
+        assert_eq!(tile.get_pixel(90, 60).0, [255, 255, 255, 255]);
+
+        let op = SplitImage::from_iter(&[
+            "split",
+            "--output",
+            "out/{stem}.png",
+            "--format",
+            "100px/80px",
+        ]);
+        assert!(op.output_size(250, 120).is_err());
+    }
+
+    #[test]
+    fn registration_marks() {
+        let dir = test_dir("split");
+        let input = dir.join("in.png");
+        let mut image = DynamicImage::new_rgb8(180, 80);
+        ImageUtil::fill_image(&mut image, &[255, 255, 255, 255]);
+        image.save(&input).unwrap();
+
+        let output = dir.join("{stem}_r{row}_c{col}.png");
+        let op = SplitImage::from_iter(&[
+            "split",
+            "--output",
+            output.to_str().unwrap(),
+            "--format",
+            "100px/80px",
+            "--overlap",
+            "20px",
+            "--registration-marks",
+        ]);
+        ImageOperation::execute(&op, &[InputFile::from_path(input)], &RunOptions::default())
+            .unwrap();
+        let left = image::open(dir.join("in_r1_c1.png")).unwrap();
+        let right = image::open(dir.join("in_r1_c2.png")).unwrap();
+        let second_row = dir.join("in_r2_c1.png").exists();
+
+        assert!(!second_row);
+        // Marks at the center of the overlap, at the same position of the image
+        assert_eq!(left.get_pixel(90, 40).0, [0, 0, 0, 255]);
+        assert_eq!(right.get_pixel(10, 40).0, [0, 0, 0, 255]);
+        assert_eq!(left.get_pixel(10, 40).0, [255, 255, 255, 255]);
+    }
+}
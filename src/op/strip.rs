@@ -0,0 +1,139 @@
+//! Remove metadata.
+
+use crate::op::{ImageIoOperation, ImageOperation, OutputOptions, RunOptions};
+use crate::util::{ImageUtil, InputFile};
+use crate::OperationParametersError;
+use image::DynamicImage;
+use std::error::Error;
+use std::path::PathBuf;
+use structopt::StructOpt;
+
+/// Remove all metadata (EXIF, XMP, GPS location, ...), e.g. before sharing images.
+/// The output is guaranteed to carry no metadata, except for an ICC profile given by `--icc`.
+#[derive(StructOpt, Debug)]
+pub struct StripMetadata {
+    #[structopt(flatten)]
+    pub output: OutputOptions,
+
+    /// Rotate and flip images according to their EXIF orientation before removing it,
+    /// so that they are still displayed correctly.
+    #[structopt(name = "auto-orient", long)]
+    pub auto_orient: bool,
+}
+
+impl StripMetadata {
+    fn check(&self) -> Result<(), Box<dyn Error>> {
+        if self.output.embed_name {
+            return Err(Box::new(OperationParametersError(
+                "`--embed-name` can't be used when stripping metadata!".to_string(),
+            )));
+        }
+        Ok(())
+    }
+}
+
+impl ImageOperation for StripMetadata {
+    fn execute(&self, files: &[InputFile], options: &RunOptions) -> Result<(), Box<dyn Error>> {
+        self.check()?;
+        ImageIoOperation::execute(self, files, options)
+    }
+}
+
+impl ImageIoOperation for StripMetadata {
+    fn output(&self) -> &OutputOptions {
+        &self.output
+    }
+
+    fn output_size(&self, width: u32, height: u32) -> Result<(u32, u32), Box<dyn Error>> {
+        // The orientation is not known without the file, assume unchanged
+        self.check()?;
+        Ok((width, height))
+    }
+
+    fn exact_output_size(&self) -> bool {
+        !self.auto_orient
+    }
+
+    fn exif(&self, _file: &PathBuf, _width: u32, _height: u32) -> Option<Vec<u8>> {
+        // Explicitly never write EXIF data, even if the default changes
+        None
+    }
+
+    fn process_image(
+        &self,
+        image: &DynamicImage,
+        file: &PathBuf,
+    ) -> Result<DynamicImage, Box<dyn Error>> {
+        self.check()?;
+        if self.auto_orient {
+            let orientation = ImageUtil::exif_orientation(file);
+            Ok(ImageUtil::apply_orientation(image.clone(), orientation))
+        } else {
+            Ok(image.clone())
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::op::{ImageOperation, RunOptions, StripMetadata};
+    use crate::util::test_dir;
+    use crate::util::{ImageUtil, InputFile, SaveOptions};
+    use exif::{Field, In, Rational, Tag, Value};
+    use image::{DynamicImage, GenericImageView};
+    use structopt::StructOpt;
+
+    #[test]
+    fn strip_metadata() {
+        let dir = test_dir("strip");
+        let input = dir.join("in.jpg");
+
+        let fields = [
+            Field {
+                tag: Tag::Orientation,
+                ifd_num: In::PRIMARY,
+                value: Value::Short(vec![6]),
+            },
+            Field {
+                tag: Tag::GPSLatitude,
+                ifd_num: In::PRIMARY,
+                value: Value::Rational(vec![
+                    Rational::from((52, 1)),
+                    Rational::from((31, 1)),
+                    Rational::from((0, 1)),
+                ]),
+            },
+        ];
+        let mut writer = exif::experimental::Writer::new();
+        for field in &fields {
+            writer.push_field(field);
+        }
+        let mut exif = std::io::Cursor::new(Vec::new());
+        writer.write(&mut exif, false).unwrap();
+        let options = SaveOptions {
+            exif: Some(exif.into_inner()),
+            ..SaveOptions::default()
+        };
+        ImageUtil::save_image(DynamicImage::new_rgb8(40, 20), &input, &options).unwrap();
+        assert!(ImageUtil::get_exif_map(&input)
+            .unwrap()
+            .contains_key("GPSLatitude"));
+
+        let output = dir.join("out-*.jpg");
+        let op = StripMetadata::from_iter(&[
+            "strip",
+            "--output",
+            output.to_str().unwrap(),
+            "--auto-orient",
+        ]);
+        op.execute(&[InputFile::from_path(input)], &RunOptions::default())
+            .unwrap();
+
+        let out_path = dir.join("out-in.jpg");
+        let result = image::open(&out_path).unwrap();
+        let exif = ImageUtil::get_exif_map(&out_path).unwrap_or_default();
+
+        assert_eq!(result.dimensions(), (20, 40));
+        assert!(exif.is_empty());
+    }
+}
@@ -0,0 +1,182 @@
+//! Machine-readable summary of a run.
+
+use crate::util::{ImageFormatError, PathUtil};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Totals collected over all files of a run, for `--summary-json`.
+#[derive(Debug, Default)]
+pub struct RunSummary {
+    totals: Mutex<SummaryTotals>,
+}
+
+#[derive(Debug, Default)]
+struct SummaryTotals {
+    processed: usize,
+    skipped: usize,
+    failed: usize,
+    /// Number of files and bytes per output format.
+    formats: BTreeMap<String, (usize, u64)>,
+    warnings: Vec<String>,
+    errors: Vec<String>,
+}
+
+impl RunSummary {
+    /// Records a successfully written output file.
+    pub fn add_output(&self, path: &PathBuf) {
+        let format = PathUtil::extension(path).unwrap_or_default();
+        let bytes = std::fs::metadata(path).map_or(0, |m| m.len());
+        let mut totals = self.totals.lock().unwrap();
+        totals.processed += 1;
+        let entry = totals.formats.entry(format).or_default();
+        entry.0 += 1;
+        entry.1 += bytes;
+    }
+    /// Records a skipped file, with the reason as warning.
+    pub fn add_skipped(&self, warning: String) {
+        let mut totals = self.totals.lock().unwrap();
+        totals.skipped += 1;
+        totals.warnings.push(warning);
+    }
+    /// Records failed files.
+    pub fn add_errors(&self, errors: &[ImageFormatError]) {
+        let mut totals = self.totals.lock().unwrap();
+        totals.failed += errors.len();
+        totals.errors.extend(errors.iter().map(|e| e.to_string()));
+    }
+    /// Number of successfully processed files.
+    pub fn processed(&self) -> usize {
+        self.totals.lock().unwrap().processed
+    }
+    /// Number of skipped files.
+    pub fn skipped(&self) -> usize {
+        self.totals.lock().unwrap().skipped
+    }
+
+    /// Creates the JSON summary, with the total elapsed time of the run.
+    pub fn to_json(&self, elapsed: Duration) -> String {
+        let totals = self.totals.lock().unwrap();
+        let formats: Vec<_> = totals
+            .formats
+            .iter()
+            .map(|(format, (files, bytes))| {
+                format!(
+                    "    {}: {{ \"files\": {}, \"bytes\": {} }}",
+                    Self::json_string(format),
+                    files,
+                    bytes
+                )
+            })
+            .collect();
+        let list = |values: &[String]| {
+            values
+                .iter()
+                .map(|v| format!("    {}", Self::json_string(v)))
+                .collect::<Vec<_>>()
+        };
+        let block = |entries: Vec<String>, open: &str, close: &str| {
+            if entries.is_empty() {
+                format!("{}{}", open, close)
+            } else {
+                format!("{}\n{}\n  {}", open, entries.join(",\n"), close)
+            }
+        };
+        format!(
+            concat!(
+                "{{\n",
+                "  \"processed\": {},\n",
+                "  \"skipped\": {},\n",
+                "  \"failed\": {},\n",
+                "  \"elapsed_seconds\": {:.3},\n",
+                "  \"formats\": {},\n",
+                "  \"warnings\": {},\n",
+                "  \"errors\": {}\n",
+                "}}\n"
+            ),
+            totals.processed,
+            totals.skipped,
+            totals.failed,
+            elapsed.as_secs_f64(),
+            block(formats, "{", "}"),
+            block(list(&totals.warnings), "[", "]"),
+            block(list(&totals.errors), "[", "]"),
+        )
+    }
+
+    /// Quotes and escapes a string for JSON.
+    pub(crate) fn json_string(str: &str) -> String {
+        let mut result = String::with_capacity(str.len() + 2);
+        result.push('"');
+        for c in str.chars() {
+            match c {
+                '"' => result.push_str("\\\""),
+                '\\' => result.push_str("\\\\"),
+                '\n' => result.push_str("\\n"),
+                '\r' => result.push_str("\\r"),
+                '\t' => result.push_str("\\t"),
+                c if (c as u32) < 0x20 => result.push_str(&format!("\\u{:04x}", c as u32)),
+                c => result.push(c),
+            }
+        }
+        result.push('"');
+        result
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::op::summary::RunSummary;
+    use crate::op::{ImageOperation, RunOptions, ScaleImage};
+    use crate::util::test_dir;
+    use crate::util::InputFile;
+    use image::DynamicImage;
+    use std::sync::Arc;
+    use std::time::Duration;
+    use structopt::StructOpt;
+
+    #[test]
+    fn json_string() {
+        assert_eq!(
+            RunSummary::json_string("C:\\a \"b\"\n"),
+            "\"C:\\\\a \\\"b\\\"\\n\""
+        );
+    }
+
+    #[test]
+    fn summary() {
+        let dir = test_dir("summary");
+        let mut files = vec![];
+        for i in 0..3 {
+            let path = dir.join(format!("in-{}.png", i));
+            DynamicImage::new_rgb8(20, 10).save(&path).unwrap();
+            files.push(InputFile::from_path(path));
+        }
+        files.push(InputFile::from_path(dir.join("missing.png")));
+
+        let output = dir.join("out-*.png");
+        let op = ScaleImage::from_iter(&[
+            "scale",
+            "--output",
+            output.to_str().unwrap(),
+            "--scale",
+            "50%",
+        ]);
+        let summary = Arc::new(RunSummary::default());
+        let options = RunOptions {
+            keep_going: true,
+            summary: Some(summary.clone()),
+            ..RunOptions::default()
+        };
+        let result = op.execute(&files, &options);
+
+        assert!(result.is_err());
+        assert_eq!(summary.processed(), 3);
+        let json = summary.to_json(Duration::from_millis(1500));
+        assert!(json.contains("\"processed\": 3,"));
+        assert!(json.contains("\"failed\": 1,"));
+        assert!(json.contains("\"elapsed_seconds\": 1.500,"));
+        assert!(json.contains("\"png\": { \"files\": 3, \"bytes\": "));
+    }
+}
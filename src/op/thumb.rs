@@ -0,0 +1,160 @@
+//! Extract embedded thumbnails.
+
+use crate::op::{ImageIoOperation, ImageOperation, OutputOptions, RunOptions};
+use crate::util::{ImageUtil, InputFile};
+use crate::OperationParametersError;
+use image::imageops::FilterType;
+use image::{DynamicImage, GenericImageView};
+use std::error::Error;
+use std::path::PathBuf;
+use structopt::StructOpt;
+
+/// Extract embedded EXIF thumbnails, e.g. for fast previews of camera files.
+#[derive(StructOpt, Debug)]
+pub struct ExtractThumb {
+    #[structopt(flatten)]
+    pub output: OutputOptions,
+
+    /// Size of the long edge of generated thumbnails in px, for files without embedded thumbnail.
+    /// Without this option, such files fail.
+    #[structopt(name = "fallback-size", long, value_name = "px")]
+    pub fallback_size: Option<u32>,
+}
+
+impl ExtractThumb {
+    fn check(&self) -> Result<(), Box<dyn Error>> {
+        if self.fallback_size == Some(0) {
+            return Err(Box::new(OperationParametersError(
+                "`--fallback-size` must be larger than 0!".to_string(),
+            )));
+        }
+        Ok(())
+    }
+
+    /// Size of a generated thumbnail for an image size.
+    fn fallback_size(&self, size: u32, width: u32, height: u32) -> (u32, u32) {
+        let (w, h) = ImageUtil::fit_size(width, height, size, size);
+        (w.max(1), h.max(1))
+    }
+}
+
+impl ImageOperation for ExtractThumb {
+    fn execute(&self, files: &[InputFile], options: &RunOptions) -> Result<(), Box<dyn Error>> {
+        self.check()?;
+        ImageIoOperation::execute(self, files, options)
+    }
+}
+
+impl ImageIoOperation for ExtractThumb {
+    fn output(&self) -> &OutputOptions {
+        &self.output
+    }
+
+    fn output_size(&self, width: u32, height: u32) -> Result<(u32, u32), Box<dyn Error>> {
+        // Embedded thumbnails are not known without reading the file, assume the fallback size.
+        self.check()?;
+        Ok(match self.fallback_size {
+            Some(size) => self.fallback_size(size, width, height),
+            None => (width, height),
+        })
+    }
+
+    fn exact_output_size(&self) -> bool {
+        false
+    }
+
+    fn process_image(
+        &self,
+        image: &DynamicImage,
+        file: &PathBuf,
+    ) -> Result<DynamicImage, Box<dyn Error>> {
+        self.check()?;
+        if let Some(data) = ImageUtil::exif_thumbnail(file) {
+            return Ok(ImageUtil::normalize_color(image::load_from_memory(&data)?));
+        }
+        match self.fallback_size {
+            Some(size) => {
+                let (w, h) = self.fallback_size(size, image.width(), image.height());
+                Ok(image.resize_exact(w, h, FilterType::Triangle))
+            }
+            None => Err(Box::new(OperationParametersError(format!(
+                "{:?} has no embedded thumbnail. Use `--fallback-size` to generate one.",
+                file
+            )))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::op::{ExtractThumb, ImageOperation, RunOptions};
+    use crate::util::test_dir;
+    use crate::util::{ImageUtil, InputFile, SaveOptions};
+    use exif::{Field, In, Tag, Value};
+    use image::{DynamicImage, GenericImageView};
+    use structopt::StructOpt;
+
+    #[test]
+    fn extract_thumb() {
+        let dir = test_dir("thumb");
+        let input = dir.join("in.jpg");
+        let plain = dir.join("plain.jpg");
+
+        // Fixture: 200x100 image with an embedded 64x32 thumbnail
+        let mut thumb = Vec::new();
+        image::jpeg::JPEGEncoder::new(&mut thumb)
+            .encode(&[128; 64 * 32 * 3], 64, 32, image::ColorType::Rgb8)
+            .unwrap();
+        let make = Field {
+            tag: Tag::Make,
+            ifd_num: In::PRIMARY,
+            value: Value::Ascii(vec![b"print-prep".to_vec()]),
+        };
+        let mut writer = exif::experimental::Writer::new();
+        writer.push_field(&make);
+        writer.set_jpeg(&thumb, In::THUMBNAIL);
+        let mut exif = std::io::Cursor::new(Vec::new());
+        writer.write(&mut exif, false).unwrap();
+        let options = SaveOptions {
+            exif: Some(exif.into_inner()),
+            ..SaveOptions::default()
+        };
+        ImageUtil::save_image(DynamicImage::new_rgb8(200, 100), &input, &options).unwrap();
+        ImageUtil::save_image(
+            DynamicImage::new_rgb8(200, 100),
+            &plain,
+            &SaveOptions::default(),
+        )
+        .unwrap();
+
+        let output = dir.join("thumb-*.png");
+        let op = ExtractThumb::from_iter(&["thumb", "--output", output.to_str().unwrap()]);
+        op.execute(
+            &[InputFile::from_path(input.clone())],
+            &RunOptions::default(),
+        )
+        .unwrap();
+        let result = image::open(dir.join("thumb-in.png")).unwrap();
+        assert_eq!(result.dimensions(), (64, 32));
+
+        // No embedded thumbnail
+        assert!(op
+            .execute(
+                &[InputFile::from_path(plain.clone())],
+                &RunOptions::default()
+            )
+            .is_err());
+        let op = ExtractThumb::from_iter(&[
+            "thumb",
+            "--output",
+            output.to_str().unwrap(),
+            "--fallback-size",
+            "50",
+        ]);
+        op.execute(&[InputFile::from_path(plain)], &RunOptions::default())
+            .unwrap();
+        let result = image::open(dir.join("thumb-plain.png")).unwrap();
+
+        assert_eq!(result.dimensions(), (50, 25));
+    }
+}
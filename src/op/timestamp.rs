@@ -0,0 +1,175 @@
+//! Print capture timestamps into images.
+
+use crate::op::{ImageIoOperation, ImageOperation, OutputOptions, RunOptions};
+use crate::units::color::Color;
+use crate::units::{Corner, Length};
+use crate::util::{ImageFormatError, ImageUtil, InputFile, PathUtil};
+use crate::OperationParametersError;
+use image::{DynamicImage, GenericImageView, Rgba};
+use std::error::Error;
+use std::path::PathBuf;
+use structopt::StructOpt;
+
+/// Print the capture time into a corner of images.
+#[derive(StructOpt, Debug)]
+pub struct TimestampImage {
+    #[structopt(flatten)]
+    pub output: OutputOptions,
+
+    /// Corner to print the timestamp into.
+    /// One of `(top-left|top-right|bottom-left|bottom-right)`.
+    /// Default: `bottom-right`.
+    #[structopt(short, long, value_name = "corner")]
+    pub position: Option<Corner>,
+
+    /// Size of the timestamp font. Default: `12px`.
+    #[structopt(name = "exif-size", long, value_name = "size")]
+    pub exif_size: Option<Length>,
+
+    /// Distance of the timestamp from the image edges. Default: half the font size.
+    #[structopt(long, value_name = "size")]
+    pub margin: Option<Length>,
+
+    /// Timestamp color. Default: `white`.
+    #[structopt(long, value_name = "color")]
+    pub color: Option<Color>,
+
+    /// Fail for images without EXIF capture date,
+    /// instead of falling back to the file modification time.
+    #[structopt(name = "no-mtime", long)]
+    pub no_mtime: bool,
+
+    /// Image resolution for sizes not in px. Default `300`.
+    #[structopt(short, long)]
+    pub dpi: Option<f64>,
+
+    #[structopt(skip)]
+    fonts: crate::Fonts,
+}
+
+impl TimestampImage {
+    fn check(&self) -> Result<(), Box<dyn Error>> {
+        for (name, length) in &[("exif-size", &self.exif_size), ("margin", &self.margin)] {
+            if length.as_ref().is_some_and(|l| l.is_relative()) {
+                return Err(Box::new(OperationParametersError(format!(
+                    "`--{}` does not support relative lengths in `%`!",
+                    name
+                ))));
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the EXIF capture time of a file, or its modification time as fallback.
+    fn timestamp(&self, file: &PathBuf) -> Result<String, Box<dyn Error>> {
+        let date = ImageUtil::get_exif_map(file)
+            .ok()
+            .and_then(|exif| exif.get("DateTimeOriginal").cloned());
+        match date {
+            Some(date) => Ok(date),
+            None if self.no_mtime => Err(Box::new(ImageFormatError(format!(
+                "No EXIF capture date found in {:?}",
+                file
+            )))),
+            None => Ok(PathUtil::modified_time(file)?),
+        }
+    }
+}
+
+impl ImageOperation for TimestampImage {
+    fn execute(&self, files: &[InputFile], options: &RunOptions) -> Result<(), Box<dyn Error>> {
+        ImageIoOperation::execute(self, files, options)
+    }
+}
+
+impl ImageIoOperation for TimestampImage {
+    fn output(&self) -> &OutputOptions {
+        &self.output
+    }
+
+    fn output_size(&self, width: u32, height: u32) -> Result<(u32, u32), Box<dyn Error>> {
+        self.check()?;
+        Ok((width, height))
+    }
+
+    fn process_image(
+        &self,
+        image: &DynamicImage,
+        file: &PathBuf,
+    ) -> Result<DynamicImage, Box<dyn Error>> {
+        self.check()?;
+
+        let dpi = self.dpi.unwrap_or(300.0);
+        let color = self.color.clone().unwrap_or(Color::new(255, 255, 255, 255));
+        let corner = self.position.as_ref().unwrap_or(&Corner::BottomRight);
+        let font_size = self
+            .exif_size
+            .clone()
+            .unwrap_or_else(|| Length::px(12))
+            .to_px(dpi)
+            .value();
+        let margin = self
+            .margin
+            .as_ref()
+            .map_or(font_size / 2.0, |m| m.to_px(dpi).value());
+
+        let text = self.timestamp(file)?;
+        let scale = rusttype::Scale::uniform(font_size as f32);
+        let (text_width, text_height) = ImageUtil::text_size(&self.fonts.default, scale, &text);
+
+        let mut result = image.clone();
+        let (x, y) = corner.place(
+            image.width(),
+            image.height(),
+            text_width,
+            text_height,
+            margin.round() as u32,
+        );
+        imageproc::drawing::draw_text_mut(
+            &mut result,
+            Rgba(*color.channels()),
+            x,
+            y,
+            scale,
+            &self.fonts.default,
+            &text,
+        );
+
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::op::{ImageIoOperation, TimestampImage};
+    use crate::util::test_dir;
+    use image::{DynamicImage, GenericImageView};
+    use structopt::StructOpt;
+
+    #[test]
+    fn timestamp_corner() {
+        let dir = test_dir("timestamp");
+        let file = dir.join("image.png");
+        std::fs::write(&file, b"").unwrap();
+
+        let op = TimestampImage::from_iter(&[
+            "timestamp",
+            "--output",
+            "out.png",
+            "--position",
+            "bottom-right",
+            "--exif-size",
+            "16px",
+        ]);
+        let image = DynamicImage::new_rgb8(400, 200);
+        let result = op.process_image(&image, &file).unwrap();
+
+        let lit: Vec<(u32, u32)> = result
+            .pixels()
+            .filter(|(_, _, p)| p.0[0] > 0)
+            .map(|(x, y, _)| (x, y))
+            .collect();
+        assert!(!lit.is_empty());
+        assert!(lit.iter().all(|(x, y)| *x >= 200 && *y >= 100));
+    }
+}
@@ -0,0 +1,245 @@
+//! Trim uniform borders.
+
+use crate::op::{edit_log_json, ImageIoOperation, ImageOperation, OutputOptions, RunOptions};
+use crate::units::color::AutoColor;
+use crate::util::InputFile;
+use crate::OperationParametersError;
+use image::{DynamicImage, GenericImageView};
+use std::error::Error;
+use std::path::PathBuf;
+use structopt::StructOpt;
+
+/// Remove uniform borders, e.g. from scans.
+#[derive(StructOpt, Debug)]
+pub struct TrimImage {
+    #[structopt(flatten)]
+    pub output: OutputOptions,
+
+    /// Border color, or `auto` to use the most common color of the corner pixels. Default: `auto`.
+    #[structopt(long, visible_alias = "bg")]
+    pub color: Option<AutoColor>,
+
+    /// Maximum difference of any channel to the border color, in percent. Default: `10`.
+    #[structopt(long, value_name = "0-100")]
+    pub tolerance: Option<f64>,
+
+    /// Writes a sidecar file `<output>.edit.json` describing the source size
+    /// and the region of the source that was kept, e.g. to undo crops.
+    #[structopt(name = "write-edit-log", long)]
+    pub write_edit_log: bool,
+}
+
+impl TrimImage {
+    fn check(&self) -> Result<(), Box<dyn Error>> {
+        if let Some(tolerance) = self.tolerance {
+            if !(0.0..=100.0).contains(&tolerance) {
+                return Err(Box::new(OperationParametersError(format!(
+                    "`--tolerance` must be in range 0-100, got {}!",
+                    tolerance
+                ))));
+            }
+        }
+        Ok(())
+    }
+
+    /// Maximum channel difference in `[0, 255]`.
+    fn max_difference(&self) -> i32 {
+        (self.tolerance.unwrap_or(10.0) / 100.0 * 255.0).round() as i32
+    }
+
+    fn matches(a: &[u8; 4], b: &[u8; 4], max_difference: i32) -> bool {
+        a.iter()
+            .zip(b)
+            .all(|(a, b)| (*a as i32 - *b as i32).abs() <= max_difference)
+    }
+
+    /// The border color. If not given, the corner color matched by most other corners.
+    fn border_color(&self, image: &DynamicImage, max_difference: i32) -> [u8; 4] {
+        if let Some(AutoColor::Color(color)) = &self.color {
+            return *color.channels();
+        }
+        let (w, h) = (image.width() - 1, image.height() - 1);
+        let corners: Vec<_> = [(0, 0), (w, 0), (0, h), (w, h)]
+            .iter()
+            .map(|(x, y)| image.get_pixel(*x, *y).0)
+            .collect();
+        // First maximum, so the top left corner wins ties
+        let mut best = (corners[0], 0);
+        for corner in &corners {
+            let count = corners
+                .iter()
+                .filter(|c| Self::matches(corner, c, max_difference))
+                .count();
+            if count > best.1 {
+                best = (*corner, count);
+            }
+        }
+        best.0
+    }
+
+    /// Bounding box `(x, y, width, height)` of all pixels not matching the border color,
+    /// or `None` if the whole image matches.
+    fn content_box(&self, image: &DynamicImage) -> Option<(u32, u32, u32, u32)> {
+        let max_difference = self.max_difference();
+        let border = self.border_color(image, max_difference);
+
+        let mut bounds: Option<(u32, u32, u32, u32)> = None;
+        for (x, y, pixel) in image.pixels() {
+            if Self::matches(&pixel.0, &border, max_difference) {
+                continue;
+            }
+            bounds = Some(match bounds {
+                None => (x, y, x, y),
+                Some((x0, y0, x1, y1)) => (x0.min(x), y0.min(y), x1.max(x), y1.max(y)),
+            });
+        }
+        bounds.map(|(x0, y0, x1, y1)| (x0, y0, x1 - x0 + 1, y1 - y0 + 1))
+    }
+}
+
+impl ImageOperation for TrimImage {
+    fn execute(&self, files: &[InputFile], options: &RunOptions) -> Result<(), Box<dyn Error>> {
+        self.check()?;
+        ImageIoOperation::execute(self, files, options)
+    }
+}
+
+impl ImageIoOperation for TrimImage {
+    fn output(&self) -> &OutputOptions {
+        &self.output
+    }
+
+    fn output_size(&self, width: u32, height: u32) -> Result<(u32, u32), Box<dyn Error>> {
+        // The content can't be known without reading the image, so this is the maximum size.
+        self.check()?;
+        Ok((width, height))
+    }
+
+    fn exact_output_size(&self) -> bool {
+        false
+    }
+
+    fn edit_log(&self, input: &DynamicImage) -> Option<String> {
+        if !self.write_edit_log {
+            return None;
+        }
+        let (width, height) = input.dimensions();
+        let (x, y, w, h) = self.content_box(input).unwrap_or((0, 0, 1, 1));
+        Some(edit_log_json("trim", (width, height), (x, y, w, h), (w, h)))
+    }
+
+    fn process_image(
+        &self,
+        image: &DynamicImage,
+        file: &PathBuf,
+    ) -> Result<DynamicImage, Box<dyn Error>> {
+        self.check()?;
+        match self.content_box(image) {
+            Some((x, y, w, h)) => Ok(image.crop_imm(x, y, w, h)),
+            None => {
+                log::warn!(
+                    "{:?} consists only of the border color, trimmed to 1x1px.",
+                    file
+                );
+                Ok(image.crop_imm(0, 0, 1, 1))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::op::{ImageIoOperation, RunOptions, TrimImage};
+    use crate::util::{test_dir, ImageUtil, InputFile};
+    use image::{DynamicImage, GenericImage, GenericImageView, Rgba};
+    use std::path::PathBuf;
+    use structopt::StructOpt;
+
+    #[test]
+    fn trim_auto() {
+        let op = TrimImage::from_iter(&["trim", "--output", "out.png"]);
+        let mut image = DynamicImage::new_rgb8(100, 80);
+        ImageUtil::fill_image(&mut image, &[250, 250, 250, 255]);
+        for x in 20..70 {
+            for y in 10..40 {
+                image.put_pixel(x, y, Rgba([40, 80, 120, 255]));
+            }
+        }
+        // Within tolerance of the border color
+        image.put_pixel(5, 5, Rgba([240, 245, 255, 255]));
+
+        let result = op.process_image(&image, &PathBuf::from("in.png")).unwrap();
+        assert_eq!(result.dimensions(), (50, 30));
+        assert_eq!(result.get_pixel(0, 0), Rgba([40, 80, 120, 255]));
+
+        let op = TrimImage::from_iter(&["trim", "--output", "out.png", "--tolerance", "0"]);
+        let result = op.process_image(&image, &PathBuf::from("in.png")).unwrap();
+        assert_eq!(result.dimensions(), (65, 35));
+    }
+
+    #[test]
+    fn trim_bg_auto() {
+        // Uniform dark green border, with a slightly different corner
+        let mut image = DynamicImage::new_rgb8(60, 40);
+        ImageUtil::fill_image(&mut image, &[30, 90, 40, 255]);
+        image.put_pixel(59, 39, Rgba([35, 85, 45, 255]));
+        for x in 10..50 {
+            for y in 5..30 {
+                image.put_pixel(x, y, Rgba([250, 250, 250, 255]));
+            }
+        }
+        let op = TrimImage::from_iter(&["trim", "--output", "out.png", "--bg", "auto"]);
+        let result = op.process_image(&image, &PathBuf::from("in.png")).unwrap();
+        assert_eq!(result.dimensions(), (40, 25));
+        assert_eq!(result.get_pixel(0, 0), Rgba([250, 250, 250, 255]));
+
+        let op = TrimImage::from_iter(&["trim", "--output", "out.png", "--bg", "30/90/40"]);
+        let result = op.process_image(&image, &PathBuf::from("in.png")).unwrap();
+        assert_eq!(result.dimensions(), (40, 25));
+    }
+
+    #[test]
+    fn trim_color() {
+        let mut image = DynamicImage::new_rgb8(100, 80);
+        for x in 0..100 {
+            image.put_pixel(x, 79, Rgba([255, 255, 255, 255]));
+        }
+        let op = TrimImage::from_iter(&["trim", "--output", "out.png", "--color", "white"]);
+        let result = op.process_image(&image, &PathBuf::from("in.png")).unwrap();
+        assert_eq!(result.dimensions(), (100, 79));
+
+        let op = TrimImage::from_iter(&["trim", "--output", "out.png"]);
+        let result = op
+            .process_image(&DynamicImage::new_rgb8(10, 10), &PathBuf::from("in.png"))
+            .unwrap();
+        assert_eq!(result.dimensions(), (1, 1));
+    }
+
+    #[test]
+    fn edit_log() {
+        let dir = test_dir("trim-edit-log");
+        let input = dir.join("in.png");
+        let mut image = DynamicImage::new_rgb8(100, 80);
+        ImageUtil::fill_image(&mut image, &[255, 255, 255, 255]);
+        for x in 20..70 {
+            for y in 10..40 {
+                image.put_pixel(x, y, Rgba([0, 0, 0, 255]));
+            }
+        }
+        image.save(&input).unwrap();
+
+        let output = dir.join("out.png");
+        let op = TrimImage::from_iter(&[
+            "trim",
+            "--output",
+            output.to_str().unwrap(),
+            "--write-edit-log",
+        ]);
+        op.process_file(&InputFile::from_path(input), 1, &RunOptions::default())
+            .unwrap();
+        let log = std::fs::read_to_string(dir.join("out.png.edit.json")).unwrap();
+
+        assert!(log.contains("\"source\": { \"width\": 100, \"height\": 80 }"));
+        assert!(log.contains("\"crop\": { \"x\": 20, \"y\": 10, \"width\": 50, \"height\": 30 }"));
+    }
+}
@@ -0,0 +1,134 @@
+//! Darken image edges.
+
+use crate::op::{ImageIoOperation, ImageOperation, OutputOptions, RunOptions};
+use crate::units::color::Color;
+use crate::util::{ImageUtil, InputFile};
+use crate::OperationParametersError;
+use image::{DynamicImage, GenericImage, GenericImageView};
+use std::error::Error;
+use std::path::PathBuf;
+use structopt::StructOpt;
+
+/// Darken image edges with a radial vignette.
+#[derive(StructOpt, Debug)]
+pub struct VignetteImage {
+    #[structopt(flatten)]
+    pub output: OutputOptions,
+
+    /// Strength of the vignette in the image corners, in percent (0-100). Default: `50`.
+    #[structopt(long)]
+    pub amount: Option<f64>,
+
+    /// Radius of the unaffected center, in percent (0-100) of the half image diagonal.
+    /// Default: `50`.
+    #[structopt(long)]
+    pub radius: Option<f64>,
+
+    /// Vignette color. Default: `black`.
+    #[structopt(long, value_name = "color")]
+    pub color: Option<Color>,
+}
+
+impl VignetteImage {
+    fn check(&self) -> Result<(), Box<dyn Error>> {
+        for (name, value) in &[("amount", self.amount), ("radius", self.radius)] {
+            if let Some(v) = value {
+                if !(0.0..=100.0).contains(v) {
+                    return Err(Box::new(OperationParametersError(format!(
+                        "`--{}` must be in range 0-100, got {}!",
+                        name, v
+                    ))));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Opacity of the vignette at a relative distance from the center,
+    /// where 1 is the half image diagonal.
+    fn opacity(&self, distance: f64) -> f64 {
+        let amount = self.amount.unwrap_or(50.0) / 100.0;
+        let radius = self.radius.unwrap_or(50.0) / 100.0;
+        if distance <= radius {
+            return 0.0;
+        }
+        let t = ((distance - radius) / (1.0 - radius)).min(1.0);
+        // Smooth step for a soft transition
+        amount * t * t * (3.0 - 2.0 * t)
+    }
+}
+
+impl ImageOperation for VignetteImage {
+    fn execute(&self, files: &[InputFile], options: &RunOptions) -> Result<(), Box<dyn Error>> {
+        ImageIoOperation::execute(self, files, options)
+    }
+}
+
+impl ImageIoOperation for VignetteImage {
+    fn output(&self) -> &OutputOptions {
+        &self.output
+    }
+
+    fn output_size(&self, width: u32, height: u32) -> Result<(u32, u32), Box<dyn Error>> {
+        self.check()?;
+        Ok((width, height))
+    }
+
+    fn process_image(
+        &self,
+        image: &DynamicImage,
+        _file: &PathBuf,
+    ) -> Result<DynamicImage, Box<dyn Error>> {
+        self.check()?;
+
+        let color = self.color.clone().unwrap_or(Color::new(0, 0, 0, 255));
+        let (cx, cy) = (image.width() as f64 / 2.0, image.height() as f64 / 2.0);
+        let half_diagonal = (cx * cx + cy * cy).sqrt();
+
+        let mut result = image.clone();
+        for (x, y, pixel) in image.pixels() {
+            let (dx, dy) = (x as f64 + 0.5 - cx, y as f64 + 0.5 - cy);
+            let opacity = self.opacity((dx * dx + dy * dy).sqrt() / half_diagonal);
+            if opacity > 0.0 {
+                result.put_pixel(x, y, ImageUtil::blend(&pixel, color.channels(), opacity));
+            }
+        }
+
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::op::{ImageIoOperation, VignetteImage};
+    use crate::util::ImageUtil;
+    use image::{DynamicImage, GenericImageView};
+    use std::path::PathBuf;
+    use structopt::StructOpt;
+
+    #[test]
+    fn vignette() {
+        let op = VignetteImage::from_iter(&["vignette", "--output", "out.png", "--amount", "80"]);
+        for image in &mut [
+            DynamicImage::new_rgb8(120, 80),
+            DynamicImage::new_rgba8(80, 120),
+        ] {
+            ImageUtil::fill_image(image, &[200, 200, 200, 255]);
+            let result = op.process_image(image, &PathBuf::from("in.png")).unwrap();
+
+            assert_eq!(result.color(), image.color());
+            let (w, h) = (result.width(), result.height());
+            assert_eq!(result.get_pixel(w / 2, h / 2).0, [200, 200, 200, 255]);
+            let corner = result.get_pixel(0, 0).0;
+            assert!(corner[0] < 100);
+            assert_eq!(corner[3], 255);
+            assert_eq!(result.get_pixel(w - 1, h - 1).0, corner);
+        }
+    }
+
+    #[test]
+    fn check_range() {
+        let op = VignetteImage::from_iter(&["vignette", "--output", "out.png", "--amount", "120"]);
+        assert!(op.output_size(10, 10).is_err());
+    }
+}
@@ -0,0 +1,94 @@
+//! Aspect ratios.
+
+use crate::ParseStructError;
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+/// Aspect ratio of width to height.
+///
+/// Can be parsed from strings of format `width:height`, with positive numbers.
+/// Examples:
+/// <pre>
+/// 16:9
+/// 1:1
+/// 1.85:1
+/// </pre>
+#[derive(Debug, PartialEq, Clone)]
+pub struct AspectRatio {
+    width: f64,
+    height: f64,
+}
+
+impl AspectRatio {
+    /// The ratio `width / height`.
+    pub fn ratio(&self) -> f64 {
+        self.width / self.height
+    }
+    /// The smallest size with this aspect ratio that contains an image of the given size.
+    pub fn enclosing_size(&self, width: u32, height: u32) -> (u32, u32) {
+        let ratio = self.ratio();
+        if (width as f64) < height as f64 * ratio {
+            (((height as f64 * ratio).round() as u32).max(width), height)
+        } else {
+            (width, ((width as f64 / ratio).round() as u32).max(height))
+        }
+    }
+}
+
+impl FromStr for AspectRatio {
+    type Err = Box<dyn Error>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<_> = s.split(':').collect();
+        if parts.len() != 2 {
+            return Err(Box::new(ParseStructError(format!(
+                "Unexpected aspect ratio format in `{}`, expects `width:height`, e.g. `16:9`",
+                s
+            ))));
+        }
+        let (width, height): (f64, f64) = (parts[0].parse()?, parts[1].parse()?);
+        if !(width > 0.0 && height > 0.0 && width.is_finite() && height.is_finite()) {
+            return Err(Box::new(ParseStructError(format!(
+                "Aspect ratio requires positive width and height, got `{}`",
+                s
+            ))));
+        }
+        Ok(AspectRatio { width, height })
+    }
+}
+
+impl fmt::Display for AspectRatio {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.width, self.height)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::units::AspectRatio;
+
+    #[test]
+    fn parse_aspect() {
+        let aspect: AspectRatio = "16:9".parse().unwrap();
+        assert_eq!(aspect.to_string(), "16:9");
+        assert!((aspect.ratio() - 16.0 / 9.0).abs() < 1e-9);
+        assert_eq!("1.85:1".parse::<AspectRatio>().unwrap().ratio(), 1.85);
+
+        assert!("16/9".parse::<AspectRatio>().is_err());
+        assert!("16:0".parse::<AspectRatio>().is_err());
+        assert!("0:9".parse::<AspectRatio>().is_err());
+        assert!("-16:9".parse::<AspectRatio>().is_err());
+        assert!("16:9:1".parse::<AspectRatio>().is_err());
+    }
+
+    #[test]
+    fn enclosing_size() {
+        let aspect: AspectRatio = "16:9".parse().unwrap();
+        assert_eq!(aspect.enclosing_size(1200, 900), (1600, 900));
+        assert_eq!(aspect.enclosing_size(1920, 800), (1920, 1080));
+        assert_eq!(aspect.enclosing_size(1920, 1080), (1920, 1080));
+        let square: AspectRatio = "1:1".parse().unwrap();
+        assert_eq!(square.enclosing_size(300, 200), (300, 300));
+    }
+}
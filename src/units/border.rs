@@ -83,6 +83,30 @@ impl Borders {
             self.top.clone(),
         )
     }
+    /// Are any of these borders relative to a reference size?
+    pub fn is_relative(&self) -> bool {
+        self.top.is_relative()
+            || self.right.is_relative()
+            || self.bottom.is_relative()
+            || self.left.is_relative()
+    }
+    /// Resolves relative borders against a reference size in px.
+    /// Top and bottom are relative to `height`, left and right to `width`.
+    pub fn resolve(&self, width: f64, height: f64) -> Borders {
+        Borders {
+            top: self.top.resolve(height),
+            right: self.right.resolve(width),
+            bottom: self.bottom.resolve(height),
+            left: self.left.resolve(width),
+        }
+    }
+    /// Are all borders zero, independent of their units?
+    pub fn is_zero(&self) -> bool {
+        self.top.value() == 0.0
+            && self.right.value() == 0.0
+            && self.bottom.value() == 0.0
+            && self.left.value() == 0.0
+    }
     /// Do these borders require a dpi value for conversion to px?
     pub fn needs_dpi(&self) -> bool {
         self.top.needs_dpi()
@@ -159,6 +183,26 @@ mod test {
         assert_eq!(borders.to_string(), str);
     }
 
+    #[test]
+    fn parse_zero() {
+        let borders: Borders = "0".parse().unwrap();
+        assert_eq!(borders.to_string(), "0px/0px/0px/0px");
+        assert!(borders.is_zero());
+
+        let borders: Borders = "0/1cm".parse().unwrap();
+        assert_eq!(borders.to_string(), "0px/1cm/0px/1cm");
+        assert!(!borders.is_zero());
+    }
+
+    #[test]
+    fn parse_mixed() {
+        let borders: Borders = "1cm/5%/0/20".parse().unwrap();
+        assert_eq!(borders.to_string(), "1cm/5%/0px/20px");
+
+        let px = borders.resolve(1000.0, 2000.0).to_px(254.0);
+        assert_eq!(px.to_string(), "100px/50px/0px/20px");
+    }
+
     #[test]
     fn rotate() {
         let str = "1cm/2cm/3cm/4cm";
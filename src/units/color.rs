@@ -2,10 +2,11 @@
 use crate::ParseStructError;
 use std::collections::HashMap;
 use std::error::Error;
+use std::fmt;
 use std::str::FromStr;
 
 /// Color color data structure.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct Color {
     channels: [u8; 4],
 }
@@ -18,6 +19,46 @@ impl Color {
     pub fn channels(&self) -> &[u8; 4] {
         &self.channels
     }
+    /// Hex representation `#rrggbb`, or `#rrggbbaa` if not fully opaque.
+    pub fn to_hex(&self) -> String {
+        let [r, g, b, a] = self.channels;
+        if a == 255 {
+            format!("#{:02x}{:02x}{:02x}", r, g, b)
+        } else {
+            format!("#{:02x}{:02x}{:02x}{:02x}", r, g, b, a)
+        }
+    }
+    /// Name of the most similar opaque named color, by Euclidean distance in RGB.
+    /// Alpha is ignored.
+    pub fn nearest_named(&self) -> &'static str {
+        let [r, g, b, _] = self.channels;
+        COLORS
+            .iter()
+            .filter(|(_, color)| color.channels[3] == 255)
+            .min_by_key(|(name, color)| {
+                let [r2, g2, b2, _] = color.channels;
+                let dist = (r as i32 - r2 as i32).pow(2)
+                    + (g as i32 - g2 as i32).pow(2)
+                    + (b as i32 - b2 as i32).pow(2);
+                // Names for tie-breaking, as iteration order is arbitrary
+                (dist, **name)
+            })
+            .map_or("black", |(name, _)| name)
+    }
+    /// Parses a hex color `#rrggbb` or `#rrggbbaa`.
+    fn from_hex(hex: &str) -> Result<Self, Box<dyn Error>> {
+        if !(hex.len() == 6 || hex.len() == 8) || !hex.is_ascii() {
+            return Err(Box::new(ParseStructError(format!(
+                "Can't parse color from #{}, requires 6 or 8 hex digits",
+                hex
+            ))));
+        }
+        let mut channels = [255; 4];
+        for (i, channel) in channels.iter_mut().take(hex.len() / 2).enumerate() {
+            *channel = u8::from_str_radix(&hex[2 * i..2 * i + 2], 16)?;
+        }
+        Ok(Color { channels })
+    }
 }
 
 impl FromStr for Color {
@@ -26,6 +67,8 @@ impl FromStr for Color {
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         if COLORS.contains_key(s) {
             Ok(COLORS[s].clone())
+        } else if let Some(hex) = s.strip_prefix('#') {
+            Color::from_hex(hex)
         } else {
             let parts: Vec<_> = s.split("/").collect();
 
@@ -44,7 +87,7 @@ impl FromStr for Color {
                     parts[0].parse()?,
                     parts[1].parse()?,
                     parts[2].parse()?,
-                    parts[4].parse()?,
+                    parts[3].parse()?,
                 )),
                 _ => Err(Box::new(ParseStructError(format!(
                     "Can't parse color from {}, requires 1, 3 or 4 elements",
@@ -55,6 +98,32 @@ impl FromStr for Color {
     }
 }
 
+/// A color, or `auto` for detection from the image.
+#[derive(Clone, Debug, PartialEq)]
+pub enum AutoColor {
+    Auto,
+    Color(Color),
+}
+
+impl FromStr for AutoColor {
+    type Err = Box<dyn Error>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "auto" {
+            Ok(AutoColor::Auto)
+        } else {
+            Ok(AutoColor::Color(s.parse()?))
+        }
+    }
+}
+
+impl fmt::Display for Color {
+    /// Formats the color as `#rrggbb`, or as `#rrggbbaa` if not fully opaque.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.to_hex())
+    }
+}
+
 lazy_static! {
     /// Named color.
     ///
@@ -1807,3 +1876,50 @@ lazy_static! {
         m
     };
 }
+
+#[cfg(test)]
+mod test {
+    use crate::units::color::{Color, COLORS};
+
+    #[test]
+    fn parse_color() {
+        let color: Color = "10/20/30/40".parse().unwrap();
+        assert_eq!(color, Color::new(10, 20, 30, 40));
+
+        let color: Color = "128".parse().unwrap();
+        assert_eq!(color, Color::new(128, 128, 128, 255));
+
+        let color: Color = "#0a141E28".parse().unwrap();
+        assert_eq!(color, Color::new(10, 20, 30, 40));
+        assert!("#0a141".parse::<Color>().is_err());
+        assert!("#0a141g".parse::<Color>().is_err());
+    }
+
+    #[test]
+    fn nearest_named() {
+        assert_eq!(Color::new(255, 0, 0, 255).nearest_named(), "red");
+        assert_eq!(Color::new(255, 255, 255, 0).nearest_named(), "white");
+        assert_eq!(Color::new(1, 2, 1, 255).nearest_named(), "black");
+    }
+
+    #[test]
+    fn display_hex() {
+        let color: Color = "red".parse().unwrap();
+        assert_eq!(color.to_string(), "#ff0000");
+        assert_eq!(Color::new(10, 20, 30, 40).to_hex(), "#0a141e28");
+    }
+
+    #[test]
+    fn named_colors_round_trip() {
+        for (name, color) in COLORS.iter() {
+            let parsed: Color = color
+                .to_string()
+                .parse()
+                .unwrap_or_else(|e| panic!("Unable to re-parse color `{}`: {}", name, e));
+            assert_eq!(&parsed, color, "Color `{}` does not round-trip", name);
+        }
+
+        let transparent: Color = COLORS["transparent"].to_string().parse().unwrap();
+        assert_eq!(transparent.channels()[3], 0);
+    }
+}
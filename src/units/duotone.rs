@@ -0,0 +1,35 @@
+//! Duotone color presets.
+
+use crate::units::color::Color;
+use crate::ParseEnumError;
+use std::str::FromStr;
+
+/// Duotone presets
+#[derive(Debug, PartialEq, Clone)]
+pub enum DuotonePreset {
+    /// Dark brown shadows and cream highlights.
+    Sepia,
+}
+
+impl DuotonePreset {
+    /// The colors for shadows and highlights.
+    pub fn colors(&self) -> (Color, Color) {
+        match self {
+            DuotonePreset::Sepia => (Color::new(40, 26, 13, 255), Color::new(255, 240, 205, 255)),
+        }
+    }
+}
+
+impl FromStr for DuotonePreset {
+    type Err = ParseEnumError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "sepia" => Ok(DuotonePreset::Sepia),
+            _ => Err(ParseEnumError(format!(
+                "`{}` is not a valid duotone preset. Must be one of `(sepia)`",
+                s
+            ))),
+        }
+    }
+}
@@ -4,6 +4,49 @@ use crate::units::FixSize;
 use std::collections::HashMap;
 use std::error::Error;
 use std::fmt;
+use std::str::FromStr;
+
+/// A page format, either fixed or derived from the aspect ratio of each image.
+///
+/// Can be parsed from `auto` or a `width/height` size.
+#[derive(Debug, PartialEq, Clone)]
+pub enum PageFormat {
+    /// Derived from the image's aspect ratio.
+    Auto,
+    /// Fixed size `width/height`.
+    Fixed(FixSize),
+}
+
+impl PageFormat {
+    /// Is this format relative to a reference size?
+    pub fn is_relative(&self) -> bool {
+        match self {
+            PageFormat::Auto => false,
+            PageFormat::Fixed(size) => size.is_relative(),
+        }
+    }
+}
+
+impl FromStr for PageFormat {
+    type Err = Box<dyn Error>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "auto" {
+            Ok(PageFormat::Auto)
+        } else {
+            Ok(PageFormat::Fixed(s.parse()?))
+        }
+    }
+}
+
+impl fmt::Display for PageFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PageFormat::Auto => write!(f, "auto"),
+            PageFormat::Fixed(size) => write!(f, "{}", size),
+        }
+    }
+}
 
 /// Converts format in cm to exact print formats in inches.
 pub fn to_print_format(size: &FixSize) -> Result<FixSize, PrintFormatError> {
@@ -15,6 +58,44 @@ pub fn to_print_format(size: &FixSize) -> Result<FixSize, PrintFormatError> {
     }
 }
 
+/// All known print formats in cm, with their aspect ratio `width / height`, sorted by aspect ratio.
+pub fn formats_by_aspect() -> Vec<(f64, &'static str)> {
+    let mut formats: Vec<_> = FORMATS
+        .keys()
+        .map(|cm| {
+            let size: FixSize = cm.parse().unwrap();
+            (size.width().value() / size.height().value(), *cm)
+        })
+        .collect();
+    formats.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    formats
+}
+
+/// The known print format with the aspect ratio closest to an image of the given size.
+/// As formats exist in both orientations, the format has the orientation of the image.
+pub fn fit_format(width: u32, height: u32) -> FixSize {
+    let aspect = (width as f64 / height as f64).ln();
+    formats_by_aspect()
+        .into_iter()
+        .min_by(|a, b| {
+            let diff_a = (a.0.ln() - aspect).abs();
+            let diff_b = (b.0.ln() - aspect).abs();
+            diff_a.partial_cmp(&diff_b).unwrap()
+        })
+        .map(|(_, cm)| cm.parse().unwrap())
+        .unwrap()
+}
+
+/// Lists all known print formats as `<cm format> -> <inch format>`, sorted.
+pub fn list_formats() -> Vec<String> {
+    let mut formats: Vec<_> = FORMATS
+        .iter()
+        .map(|(cm, inch)| format!("{} -> {}", cm, inch))
+        .collect();
+    formats.sort();
+    formats
+}
+
 lazy_static! {
     pub static ref FORMATS: HashMap<&'static str, &'static str> = create_formats();
 }
@@ -64,9 +145,21 @@ fn create_formats() -> HashMap<&'static str, &'static str> {
 
 #[cfg(test)]
 mod test {
-    use crate::units::format::to_print_format;
+    use crate::units::format::{
+        fit_format, formats_by_aspect, list_formats, to_print_format, PageFormat,
+    };
     use crate::units::FixSize;
 
+    #[test]
+    fn page_format() {
+        assert_eq!("auto".parse::<PageFormat>().unwrap(), PageFormat::Auto);
+        let format: PageFormat = "6in/4in".parse().unwrap();
+        assert_eq!(format.to_string(), "6in/4in");
+        let format: PageFormat = "6in".parse().unwrap();
+        assert_eq!(format.to_string(), "6in/6in");
+        assert!("6in/4in/2in".parse::<PageFormat>().is_err());
+    }
+
     #[test]
     fn print_formats() {
         let size: FixSize = "15cm/10cm".parse().unwrap();
@@ -74,4 +167,24 @@ mod test {
 
         assert_eq!(format.to_string(), "6in/4in".to_string());
     }
+
+    #[test]
+    fn fit() {
+        let formats = formats_by_aspect();
+        assert_eq!(formats.len(), 10);
+        assert!(formats.windows(2).all(|w| w[0].0 <= w[1].0));
+
+        assert_eq!(fit_format(3000, 2000).to_string(), "15cm/10cm");
+        assert_eq!(fit_format(2000, 3000).to_string(), "10cm/15cm");
+        assert_eq!(fit_format(4000, 3000).to_string(), "24cm/18cm");
+        assert_eq!(fit_format(1414, 1000).to_string(), "21cm/15cm");
+    }
+
+    #[test]
+    fn list() {
+        let formats = list_formats();
+        assert_eq!(formats.len(), 10);
+        assert!(formats.contains(&"15cm/10cm -> 6in/4in".to_string()));
+        assert!(formats.windows(2).all(|w| w[0] <= w[1]));
+    }
 }
@@ -0,0 +1,160 @@
+//! Rectangles with position and size.
+
+use crate::units::length::{Length, LengthUnit};
+use crate::ParseStructError;
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+/// A rectangle, given by the position of its top left corner and its size.
+///
+/// Can be parsed from strings of format `x/y/width/height`.
+/// All components can be in units px, cm, mm, in, pt or %.
+/// Examples:
+/// <pre>
+/// 10px/20px/100px/50px
+/// 10%/10%/80%/80%
+/// </pre>
+///
+/// `.` can be used as placeholder for `x` and `y`, meaning `0`.
+/// Example:
+/// <pre>
+/// ././100px/50px
+/// </pre>
+#[derive(Debug, PartialEq, Clone)]
+pub struct Geometry {
+    x: Length,
+    y: Length,
+    w: Length,
+    h: Length,
+}
+
+impl Geometry {
+    pub fn new(x: Length, y: Length, w: Length, h: Length) -> Self {
+        Geometry { x, y, w, h }
+    }
+    /// Horizontal position of the top left corner.
+    pub fn x(&self) -> &Length {
+        &self.x
+    }
+    /// Vertical position of the top left corner.
+    pub fn y(&self) -> &Length {
+        &self.y
+    }
+    /// Width of the rectangle.
+    pub fn w(&self) -> &Length {
+        &self.w
+    }
+    /// Height of the rectangle.
+    pub fn h(&self) -> &Length {
+        &self.h
+    }
+    /// Converts this geometry to pixels.
+    pub fn to_px(&self, dpi: f64) -> Geometry {
+        self.to(&LengthUnit::Px, dpi)
+    }
+    /// Converts this geometry to another unit.
+    pub fn to(&self, unit: &LengthUnit, dpi: f64) -> Geometry {
+        Geometry {
+            x: self.x.to(unit, dpi),
+            y: self.y.to(unit, dpi),
+            w: self.w.to(unit, dpi),
+            h: self.h.to(unit, dpi),
+        }
+    }
+    /// Does this geometry require a dpi value for conversion to px?
+    pub fn needs_dpi(&self) -> bool {
+        [&self.x, &self.y, &self.w, &self.h]
+            .iter()
+            .any(|l| l.needs_dpi())
+    }
+    /// Is this geometry relative to a reference size?
+    pub fn is_relative(&self) -> bool {
+        [&self.x, &self.y, &self.w, &self.h]
+            .iter()
+            .any(|l| l.is_relative())
+    }
+    /// Resolves relative components against a reference size in px.
+    pub fn resolve(&self, width: f64, height: f64) -> Geometry {
+        Geometry {
+            x: self.x.resolve(width),
+            y: self.y.resolve(height),
+            w: self.w.resolve(width),
+            h: self.h.resolve(height),
+        }
+    }
+}
+
+impl FromStr for Geometry {
+    type Err = Box<dyn Error>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<_> = s.split('/').collect();
+        if parts.len() != 4 {
+            return Err(Box::new(ParseStructError(format!(
+                "Unexpected geometry format in {}, expects `x/y/width/height`",
+                s
+            ))));
+        }
+        let position = |p: &str| -> Result<Length, Box<dyn Error>> {
+            if p == "." {
+                Ok(Length::px(0))
+            } else {
+                p.parse()
+            }
+        };
+        Ok(Geometry {
+            x: position(parts[0])?,
+            y: position(parts[1])?,
+            w: parts[2].parse()?,
+            h: parts[3].parse()?,
+        })
+    }
+}
+
+impl fmt::Display for Geometry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}/{}/{}", self.x, self.y, self.w, self.h)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::units::{Geometry, Length, LengthUnit};
+
+    #[test]
+    fn parse_geometry() {
+        let geom: Geometry = "10px/20px/100px/50px".parse().unwrap();
+        assert_eq!(geom.x(), &Length::px(10));
+        assert_eq!(geom.y(), &Length::px(20));
+        assert_eq!(geom.w(), &Length::px(100));
+        assert_eq!(geom.h(), &Length::px(50));
+        assert_eq!(geom.to_string(), "10px/20px/100px/50px");
+    }
+
+    #[test]
+    fn parse_geometry_placeholders() {
+        let geom: Geometry = "././100px/50px".parse().unwrap();
+        assert_eq!(geom.x(), &Length::px(0));
+        assert_eq!(geom.y(), &Length::px(0));
+        assert_eq!(geom.w(), &Length::px(100));
+
+        assert!("10px/20px/100px".parse::<Geometry>().is_err());
+    }
+
+    #[test]
+    fn resolve_geometry() {
+        let geom: Geometry = "10%/10%/1in/80%".parse().unwrap();
+        let px = geom.resolve(1000.0, 500.0).to_px(100.0);
+        assert_eq!(
+            px,
+            Geometry::new(
+                Length::px(100),
+                Length::px(50),
+                Length::px(100),
+                Length::px(400)
+            )
+        );
+        assert_eq!(px.w().unit(), &LengthUnit::Px);
+    }
+}
@@ -0,0 +1,28 @@
+//! Perceptual image hashes.
+
+use crate::ParseEnumError;
+use std::str::FromStr;
+
+/// Perceptual hash algorithms
+#[derive(Debug, PartialEq, Clone)]
+pub enum ImageHash {
+    /// Difference hash: compares horizontally neighbouring pixels of a 9x8 thumbnail.
+    DHash,
+    /// Average hash: compares the pixels of an 8x8 thumbnail to their mean.
+    AHash,
+}
+
+impl FromStr for ImageHash {
+    type Err = ParseEnumError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "dhash" => Ok(ImageHash::DHash),
+            "ahash" => Ok(ImageHash::AHash),
+            _ => Err(ParseEnumError(format!(
+                "`{}` is not a valid image hash. Must be one of `(dhash|ahash)`",
+                s
+            ))),
+        }
+    }
+}
@@ -0,0 +1,65 @@
+//! ICC color profiles.
+
+use crate::ParseStructError;
+use std::error::Error;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+/// An ICC color profile to embed into output images.
+///
+/// Can be parsed from `srgb` for the bundled sRGB profile, or from a path to an ICC file.
+#[derive(Debug, PartialEq, Clone)]
+pub enum IccProfile {
+    /// The bundled sRGB profile.
+    Srgb,
+    /// A profile file.
+    File(PathBuf),
+}
+
+impl IccProfile {
+    /// Loads and validates the profile data.
+    pub fn data(&self) -> Result<Vec<u8>, Box<dyn Error>> {
+        let data = match self {
+            IccProfile::Srgb => crate::Assets::get("icc/sRGB.icc")
+                .expect("Bundled sRGB profile not found")
+                .into_owned(),
+            IccProfile::File(path) => std::fs::read(path)?,
+        };
+        if data.len() < 132 || &data[36..40] != b"acsp" {
+            return Err(Box::new(ParseStructError(format!(
+                "Not a valid ICC profile: {:?}",
+                self
+            ))));
+        }
+        Ok(data)
+    }
+}
+
+impl FromStr for IccProfile {
+    type Err = Box<dyn Error>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "srgb" {
+            Ok(IccProfile::Srgb)
+        } else {
+            Ok(IccProfile::File(PathBuf::from(s)))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::units::IccProfile;
+    use std::path::PathBuf;
+
+    #[test]
+    fn srgb() {
+        let data = IccProfile::Srgb.data().unwrap();
+        let size = u32::from_be_bytes([data[0], data[1], data[2], data[3]]);
+        assert_eq!(size as usize, data.len());
+        assert_eq!(&data[12..20], b"mntrRGB ");
+
+        let file = IccProfile::File(PathBuf::from("test_data/test-pattern.png"));
+        assert!(file.data().is_err());
+    }
+}
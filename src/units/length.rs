@@ -1,6 +1,7 @@
 //! Length units and conversions
 
 use crate::ParseEnumError;
+use std::cmp::Ordering;
 use std::error::Error;
 use std::fmt;
 use std::str::FromStr;
@@ -52,6 +53,13 @@ impl Length {
             unit: LengthUnit::Inch,
         }
     }
+    /// Creates a new length in points (1/72 inch).
+    pub fn pt(value: f64) -> Self {
+        Length {
+            value,
+            unit: LengthUnit::Pt,
+        }
+    }
     /// Creates a new length in pixels.
     pub fn px(value: i32) -> Self {
         Length {
@@ -78,16 +86,94 @@ impl Length {
     pub fn needs_dpi(&self) -> bool {
         self.unit.needs_dpi()
     }
+    /// Is this length relative to a reference size, i.e. given in percent?
+    pub fn is_relative(&self) -> bool {
+        self.unit == LengthUnit::Percent
+    }
+    /// Resolves a relative length against a reference size in px.
+    /// Absolute lengths are returned unchanged.
+    pub fn resolve(&self, reference: f64) -> Length {
+        if self.is_relative() {
+            Length::new(self.value * reference / 100.0, LengthUnit::Px)
+        } else {
+            self.clone()
+        }
+    }
+    /// Compares two lengths after converting them to a common metric base.
+    /// `dpi` is only used for lengths in px. Returns `None` for relative lengths in `%`,
+    /// unless both are relative.
+    pub fn cmp_with_dpi(&self, other: &Length, dpi: f64) -> Option<Ordering> {
+        if self.unit == other.unit {
+            return self.value.partial_cmp(&other.value);
+        }
+        if self.is_relative() || other.is_relative() {
+            return None;
+        }
+        let a = self.value * self.unit.metric_factor(dpi);
+        let b = other.value * other.unit.metric_factor(dpi);
+        // Ignore floating point noise from the unit conversion
+        if (a - b).abs() <= 1e-9 * a.abs().max(b.abs()) {
+            Some(Ordering::Equal)
+        } else {
+            a.partial_cmp(&b)
+        }
+    }
+    /// The smaller of two lengths, see [`cmp_with_dpi`](#method.cmp_with_dpi).
+    /// Returns `self` if the lengths are equal or can't be compared.
+    pub fn min(&self, other: &Length, dpi: f64) -> Length {
+        match self.cmp_with_dpi(other, dpi) {
+            Some(Ordering::Greater) => other.clone(),
+            _ => self.clone(),
+        }
+    }
+    /// The larger of two lengths, see [`cmp_with_dpi`](#method.cmp_with_dpi).
+    /// Returns `self` if the lengths are equal or can't be compared.
+    pub fn max(&self, other: &Length, dpi: f64) -> Length {
+        match self.cmp_with_dpi(other, dpi) {
+            Some(Ordering::Less) => other.clone(),
+            _ => self.clone(),
+        }
+    }
+    /// Restricts this length to the range `[min, max]`, see [`cmp_with_dpi`](#method.cmp_with_dpi).
+    pub fn clamp(&self, min: &Length, max: &Length, dpi: f64) -> Length {
+        self.max(min, dpi).min(max, dpi)
+    }
+}
+
+/// Compares lengths in units that don't need a dpi value for conversion.
+/// Lengths in px and in other units can only be compared with
+/// [`Length::cmp_with_dpi`](struct.Length.html#method.cmp_with_dpi).
+/// Equal lengths in different units (like `1cm` and `10mm`) are not `==`, and thus unordered.
+impl PartialOrd for Length {
+    fn partial_cmp(&self, other: &Length) -> Option<Ordering> {
+        if self.unit == other.unit {
+            return self.value.partial_cmp(&other.value);
+        }
+        if self.needs_dpi() != other.needs_dpi() {
+            return None;
+        }
+        match self.cmp_with_dpi(other, 1.0) {
+            Some(Ordering::Equal) => None,
+            ord => ord,
+        }
+    }
 }
 
 impl FromStr for Length {
     type Err = Box<dyn Error>;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let pos = s.len() - 2;
-        let unit_str = &s[pos..];
-        let (unit, val_str) = if unit_str.chars().all(|c| char::is_alphabetic(c)) {
-            (unit_str.parse()?, &s[..pos])
+        if let Some(val_str) = s.strip_suffix('%') {
+            let value = val_str.parse()?;
+            return Ok(Length {
+                value,
+                unit: LengthUnit::Percent,
+            });
+        }
+        // The unit is the trailing alphabetic part, px if there is none
+        let pos = s.trim_end_matches(char::is_alphabetic).len();
+        let (unit, val_str) = if pos < s.len() {
+            (s[pos..].parse()?, s[..pos].trim_end())
         } else {
             (LengthUnit::Px, s)
         };
@@ -118,6 +204,8 @@ pub trait ToLength {
     fn mm(&self) -> Length;
     /// Converts the number to a length in inches.
     fn inch(&self) -> Length;
+    /// Converts the number to a length in points.
+    fn pt(&self) -> Length;
     /// Converts the number to a length in pixels.
     fn px(&self) -> Length;
 }
@@ -131,6 +219,9 @@ impl ToLength for f64 {
     fn inch(&self) -> Length {
         Length::inch(*self)
     }
+    fn pt(&self) -> Length {
+        Length::pt(*self)
+    }
     fn px(&self) -> Length {
         Length::px(*self as i32)
     }
@@ -145,12 +236,16 @@ impl ToLength for i32 {
     fn inch(&self) -> Length {
         Length::inch(*self as f64)
     }
+    fn pt(&self) -> Length {
+        Length::pt(*self as f64)
+    }
     fn px(&self) -> Length {
         Length::px(*self)
     }
 }
 
 const INCH_TO_METERS: f64 = 0.0254;
+const POINTS_PER_INCH: f64 = 72.0;
 
 /// Length units.
 #[derive(Debug, PartialEq, Clone)]
@@ -163,6 +258,11 @@ pub enum LengthUnit {
     Mm,
     /// Inches.
     Inch,
+    /// Points (1/72 inch).
+    Pt,
+    /// Percent of a reference size. Can't be converted to other units directly,
+    /// see [`Length::resolve`](struct.Length.html#method.resolve).
+    Percent,
 }
 impl LengthUnit {
     /// Does this unit require a dpi value vor conversion to px?
@@ -174,11 +274,17 @@ impl LengthUnit {
     }
 
     /// Converts this length to another unit.
+    ///
+    /// Panics for relative units, which must be resolved against a reference size first.
     pub fn metric_factor(&self, dpi: f64) -> f64 {
         match self {
+            LengthUnit::Percent => {
+                panic!("Relative lengths in `%` can't be converted without a reference size")
+            }
             LengthUnit::Cm => 0.01,
             LengthUnit::Mm => 0.001,
             LengthUnit::Inch => 0.0254,
+            LengthUnit::Pt => INCH_TO_METERS / POINTS_PER_INCH,
             LengthUnit::Px => INCH_TO_METERS / dpi,
         }
     }
@@ -187,13 +293,15 @@ impl FromStr for LengthUnit {
     type Err = ParseEnumError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s {
-            "px" => Ok(LengthUnit::Px),
-            "cm" => Ok(LengthUnit::Cm),
-            "mm" => Ok(LengthUnit::Mm),
-            "in" => Ok(LengthUnit::Inch),
+        match s.to_lowercase().as_str() {
+            "px" | "pixel" | "pixels" => Ok(LengthUnit::Px),
+            "cm" | "centimeter" | "centimeters" => Ok(LengthUnit::Cm),
+            "mm" | "millimeter" | "millimeters" => Ok(LengthUnit::Mm),
+            "in" | "inch" | "inches" => Ok(LengthUnit::Inch),
+            "pt" | "point" | "points" => Ok(LengthUnit::Pt),
+            "%" => Ok(LengthUnit::Percent),
             _ => Err(ParseEnumError(format!(
-                "`{}` is not a valid length unit. Must be one of `(px|cm|mm|in)`",
+                "`{}` is not a valid length unit. Must be one of `(px|cm|mm|in|pt|%)`",
                 s
             ))),
         }
@@ -208,6 +316,8 @@ impl fmt::Display for LengthUnit {
                 LengthUnit::Cm => "cm",
                 LengthUnit::Mm => "mm",
                 LengthUnit::Inch => "in",
+                LengthUnit::Pt => "pt",
+                LengthUnit::Percent => "%",
                 LengthUnit::Px => "px",
             }
         )
@@ -218,6 +328,7 @@ impl fmt::Display for LengthUnit {
 mod test {
     use crate::units::length::{Length, LengthUnit, ToLength};
     use crate::units::size::Size;
+    use std::cmp::Ordering;
 
     #[test]
     fn parse_length() {
@@ -226,6 +337,11 @@ mod test {
         assert_eq!(len.value, 1024.0);
         assert_eq!(len.unit, LengthUnit::Px);
 
+        let str = "0";
+        let len: Length = str.parse().unwrap();
+        assert_eq!(len.value, 0.0);
+        assert_eq!(len.unit, LengthUnit::Px);
+
         let str = "5cm";
         let len: Length = str.parse().unwrap();
         assert_eq!(len.value, 5.0);
@@ -235,6 +351,30 @@ mod test {
         let len: Length = str.parse().unwrap();
         assert_eq!(len.value, 10.0);
         assert_eq!(len.unit, LengthUnit::Inch);
+
+        let str = "10pt";
+        let len: Length = str.parse().unwrap();
+        assert_eq!(len.value, 10.0);
+        assert_eq!(len.unit, LengthUnit::Pt);
+    }
+
+    #[test]
+    fn parse_unit_names() {
+        let len: Length = "5CM".parse().unwrap();
+        assert_eq!((len.value, len.unit), (5.0, LengthUnit::Cm));
+
+        let len: Length = "10Inches".parse().unwrap();
+        assert_eq!((len.value, len.unit), (10.0, LengthUnit::Inch));
+
+        let len: Length = "300Pixels".parse().unwrap();
+        assert_eq!((len.value, len.unit), (300.0, LengthUnit::Px));
+
+        let len: Length = "2.5 mm".parse().unwrap();
+        assert_eq!((len.value, len.unit), (2.5, LengthUnit::Mm));
+
+        assert!("5furlongs".parse::<Length>().is_err());
+        assert!("5".parse::<LengthUnit>().is_err());
+        assert!("cm".parse::<Length>().is_err());
     }
 
     #[test]
@@ -291,6 +431,63 @@ mod test {
         assert!((px.to(&LengthUnit::Inch, 300.0).value - inch.value).abs() < 0.000001);
     }
 
+    #[test]
+    fn point_conversion() {
+        let pt = 72.pt();
+        for dpi in &[72.0, 96.0, 300.0, 1200.0] {
+            assert!((pt.to(&LengthUnit::Inch, *dpi).value - 1.0).abs() < 1e-12);
+        }
+
+        let px = 1234.px();
+        assert_eq!(px.to(&LengthUnit::Pt, 72.0), 1234.pt());
+        assert_eq!(px.to(&LengthUnit::Pt, 72.0).to_px(72.0), px);
+        assert_eq!(36.pt().to_px(300.0), 150.px());
+    }
+
+    #[test]
+    fn compare() {
+        assert!(1.cm() > 5.mm());
+        assert!(5.mm() < 1.cm());
+        assert!(2.cm() >= 2.cm());
+        assert_eq!(1.cm().partial_cmp(&10.mm()), None);
+        assert_eq!(1.cm().partial_cmp(&10.px()), None);
+        assert!(10.px() < 20.px());
+
+        assert_eq!(1.cm().cmp_with_dpi(&5.mm(), 300.0), Some(Ordering::Greater));
+        assert_eq!(1.cm().cmp_with_dpi(&10.mm(), 300.0), Some(Ordering::Equal));
+        assert_eq!(
+            300.px().cmp_with_dpi(&1.inch(), 300.0),
+            Some(Ordering::Equal)
+        );
+        assert_eq!(
+            300.px().cmp_with_dpi(&1.inch(), 150.0),
+            Some(Ordering::Greater)
+        );
+        assert_eq!(
+            72.pt().cmp_with_dpi(&1.inch(), 300.0),
+            Some(Ordering::Equal)
+        );
+        let percent: Length = "5%".parse().unwrap();
+        assert_eq!(percent.cmp_with_dpi(&1.cm(), 300.0), None);
+
+        assert_eq!(1.cm().min(&5.mm(), 300.0), 5.mm());
+        assert_eq!(1.cm().max(&5.mm(), 300.0), 1.cm());
+        assert_eq!(100.px().max(&1.cm(), 300.0), 1.cm());
+        assert_eq!(5.cm().clamp(&1.cm(), &30.mm(), 300.0), 30.mm());
+        assert_eq!(5.mm().clamp(&1.cm(), &3.cm(), 300.0), 1.cm());
+        assert_eq!(2.cm().clamp(&1.cm(), &3.cm(), 300.0), 2.cm());
+    }
+
+    #[test]
+    fn relative_length() {
+        let len: Length = "5%".parse().unwrap();
+        assert_eq!(len.unit, LengthUnit::Percent);
+        assert!(len.is_relative());
+        assert_eq!(len.to_string(), "5%");
+        assert_eq!(len.resolve(3000.0), 150.px());
+        assert_eq!(2.cm().resolve(3000.0), 2.cm());
+    }
+
     #[test]
     fn display() {
         let cm = 254.cm();
@@ -300,5 +497,6 @@ mod test {
         assert_eq!(cm.to_string(), "254cm");
         assert_eq!(inch.to_string(), "100in");
         assert_eq!(px.to_string(), "30000px");
+        assert_eq!(12.pt().to_string(), "12pt");
     }
 }
@@ -0,0 +1,28 @@
+//! Tone correction modes.
+
+use crate::ParseEnumError;
+use std::str::FromStr;
+
+/// Auto levels modes
+#[derive(Debug, PartialEq, Clone)]
+pub enum LevelsMode {
+    /// Linear stretch of the minimum and maximum value to the full range.
+    Stretch,
+    /// Histogram equalization.
+    Equalize,
+}
+
+impl FromStr for LevelsMode {
+    type Err = ParseEnumError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "stretch" => Ok(LevelsMode::Stretch),
+            "equalize" => Ok(LevelsMode::Equalize),
+            _ => Err(ParseEnumError(format!(
+                "`{}` is not a valid levels mode. Must be one of `(stretch|equalize)`",
+                s
+            ))),
+        }
+    }
+}
@@ -0,0 +1,31 @@
+//! File list output formats.
+
+use crate::ParseEnumError;
+use std::str::FromStr;
+
+/// Output formats for file lists
+#[derive(Debug, PartialEq, Clone)]
+pub enum ListFormat {
+    /// One file per line.
+    Text,
+    /// A JSON array of objects.
+    Json,
+    /// Comma-separated values with a header line.
+    Csv,
+}
+
+impl FromStr for ListFormat {
+    type Err = ParseEnumError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(ListFormat::Text),
+            "json" => Ok(ListFormat::Json),
+            "csv" => Ok(ListFormat::Csv),
+            _ => Err(ParseEnumError(format!(
+                "`{}` is not a valid list format. Must be one of `(text|json|csv)`",
+                s
+            ))),
+        }
+    }
+}
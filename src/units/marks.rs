@@ -0,0 +1,88 @@
+//! Printer's crop marks.
+
+use crate::units::Length;
+use crate::ParseStructError;
+use std::error::Error;
+use std::str::FromStr;
+
+/// Corner crop marks, as used by print shops.
+///
+/// Can be parsed from strings of format `length/offset/gap`.
+/// Marks of `length` lie on the extensions of the trim box edges, starting `offset` outside the trim box.
+/// With a non-zero `gap`, each corner gets a second pair of marks, `gap` further out (e.g. for bleed).
+/// The `gap` is optional, and defaults to zero.
+/// Examples:
+/// <pre>
+/// 5mm/3mm
+/// 5mm/3mm/3mm
+/// </pre>
+#[derive(Debug, PartialEq, Clone)]
+pub struct CropMarks {
+    /// Length of the marks.
+    pub length: Length,
+    /// Distance of the marks from the trim box.
+    pub offset: Length,
+    /// Distance of the second pair of marks, or zero.
+    pub gap: Length,
+}
+
+impl CropMarks {
+    /// Converts these marks to pixels.
+    pub fn to_px(&self, dpi: f64) -> CropMarks {
+        CropMarks {
+            length: self.length.to_px(dpi),
+            offset: self.offset.to_px(dpi),
+            gap: self.gap.to_px(dpi),
+        }
+    }
+}
+
+impl FromStr for CropMarks {
+    type Err = Box<dyn Error>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<_> = s.split('/').collect();
+        let (length, offset, gap) = match parts[..] {
+            [length, offset] => (length, offset, None),
+            [length, offset, gap] => (length, offset, Some(gap)),
+            _ => {
+                return Err(Box::new(ParseStructError(format!(
+                    "Unexpected crop marks format in `{}`, expects `length/offset/gap`",
+                    s
+                ))))
+            }
+        };
+        let marks = CropMarks {
+            length: length.parse()?,
+            offset: offset.parse()?,
+            gap: gap.map_or(Ok(Length::px(0)), |g| g.parse())?,
+        };
+        let lengths = [&marks.length, &marks.offset, &marks.gap];
+        if lengths.iter().any(|l| l.is_relative() || l.value() < 0.0) {
+            return Err(Box::new(ParseStructError(format!(
+                "Crop marks require non-negative absolute lengths, got `{}`",
+                s
+            ))));
+        }
+        Ok(marks)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::units::{CropMarks, Length};
+
+    #[test]
+    fn parse_crop_marks() {
+        let marks: CropMarks = "5mm/3mm/2mm".parse().unwrap();
+        assert_eq!(marks.length, Length::mm(5.0));
+        assert_eq!(marks.offset, Length::mm(3.0));
+        assert_eq!(marks.gap, Length::mm(2.0));
+
+        let marks: CropMarks = "5mm/3mm".parse().unwrap();
+        assert_eq!(marks.gap.value(), 0.0);
+
+        assert!("5mm".parse::<CropMarks>().is_err());
+        assert!("5%/3mm".parse::<CropMarks>().is_err());
+    }
+}
@@ -1,12 +1,27 @@
 //! Measures and units
 
+mod aspect;
 mod border;
 pub mod color;
+mod duotone;
 pub mod exif;
 pub mod format;
+mod geometry;
+mod hash;
+mod icc;
 mod length;
+mod levels;
+mod list;
+mod marks;
+mod orientation;
+mod output;
+mod overlay;
+mod position;
 mod scale;
+mod shape;
 mod size;
+mod sort;
+mod stroke;
 
 pub use length::Length;
 pub use length::LengthUnit;
@@ -16,7 +31,42 @@ pub use size::FixSize;
 pub use size::FreeSize;
 pub use size::Size;
 
+pub use sort::SortOrder;
+
+pub use aspect::AspectRatio;
+
+pub use duotone::DuotonePreset;
+
+pub use geometry::Geometry;
+
+pub use hash::ImageHash;
+
+pub use icc::IccProfile;
+
+pub use levels::LevelsMode;
+
+pub use list::ListFormat;
+
+pub use marks::CropMarks;
+
+pub use orientation::Orientation;
+
+pub use output::OutputFormat;
+
+pub use overlay::Overlay;
+pub use overlay::OverlayPosition;
+
+pub use position::Corner;
+pub use position::TextPosition;
+
+pub use scale::Downscale;
 pub use scale::Scale;
 pub use scale::ScaleMode;
+pub use scale::ScaleOf;
+pub use scale::SmartCrop;
+
+pub use shape::MaskShape;
 
 pub use border::Borders;
+
+pub use stroke::Stroke;
@@ -0,0 +1,38 @@
+//! Image orientation.
+
+use crate::ParseEnumError;
+use std::str::FromStr;
+
+/// Image orientation
+#[derive(Debug, PartialEq, Clone)]
+pub enum Orientation {
+    /// Height larger than width.
+    Portrait,
+    /// Width larger than height.
+    Landscape,
+}
+
+impl Orientation {
+    /// Does an image size match this orientation? Square images match both.
+    pub fn matches(&self, width: u32, height: u32) -> bool {
+        match self {
+            Orientation::Portrait => height >= width,
+            Orientation::Landscape => width >= height,
+        }
+    }
+}
+
+impl FromStr for Orientation {
+    type Err = ParseEnumError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "portrait" => Ok(Orientation::Portrait),
+            "landscape" => Ok(Orientation::Landscape),
+            _ => Err(ParseEnumError(format!(
+                "`{}` is not a valid orientation. Must be one of `(portrait|landscape)`",
+                s
+            ))),
+        }
+    }
+}
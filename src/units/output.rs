@@ -0,0 +1,104 @@
+//! Output image formats.
+
+use crate::ParseEnumError;
+use image::ImageFormat;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+/// Output image formats, independent of the extension of output paths
+#[derive(Debug, PartialEq, Clone)]
+pub enum OutputFormat {
+    Jpg,
+    Png,
+    Webp,
+    Tiff,
+    Bmp,
+}
+
+impl OutputFormat {
+    /// The file extension of this format.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            OutputFormat::Jpg => "jpg",
+            OutputFormat::Png => "png",
+            OutputFormat::Webp => "webp",
+            OutputFormat::Tiff => "tiff",
+            OutputFormat::Bmp => "bmp",
+        }
+    }
+
+    /// Sets the extension of a path to this format.
+    /// Image extensions of other formats are replaced, other extensions are kept, like in `2024.01`.
+    pub fn apply(&self, path: &PathBuf) -> PathBuf {
+        let current = path
+            .extension()
+            .map(|ext| ext.to_string_lossy().to_lowercase());
+        match current {
+            Some(ext) if ext.parse::<OutputFormat>().as_ref() == Ok(self) => path.clone(),
+            Some(ext) if ImageFormat::from_path(path).is_ok() || ext == "webp" => {
+                log::debug!(
+                    "Output format `{}` overrides the extension of {:?}",
+                    self.extension(),
+                    path
+                );
+                path.with_extension(self.extension())
+            }
+            _ => {
+                let mut path = path.clone().into_os_string();
+                path.push(".");
+                path.push(self.extension());
+                PathBuf::from(path)
+            }
+        }
+    }
+}
+
+impl FromStr for OutputFormat {
+    type Err = ParseEnumError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match &s.to_lowercase()[..] {
+            "jpg" | "jpeg" => Ok(OutputFormat::Jpg),
+            "png" => Ok(OutputFormat::Png),
+            "webp" => Ok(OutputFormat::Webp),
+            "tiff" | "tif" => Ok(OutputFormat::Tiff),
+            "bmp" => Ok(OutputFormat::Bmp),
+            _ => Err(ParseEnumError(format!(
+                "`{}` is not a valid output format. Must be one of `(jpg|png|webp|tiff|bmp)`",
+                s
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::units::OutputFormat;
+    use std::path::PathBuf;
+
+    #[test]
+    fn apply() {
+        let jpg = OutputFormat::Jpg;
+        assert_eq!(
+            jpg.apply(&PathBuf::from("out/a")),
+            PathBuf::from("out/a.jpg")
+        );
+        assert_eq!(
+            jpg.apply(&PathBuf::from("out/a.png")),
+            PathBuf::from("out/a.jpg")
+        );
+        assert_eq!(
+            jpg.apply(&PathBuf::from("out/a.JPEG")),
+            PathBuf::from("out/a.JPEG")
+        );
+        assert_eq!(
+            jpg.apply(&PathBuf::from("out/2024.01")),
+            PathBuf::from("out/2024.01.jpg")
+        );
+        assert_eq!(
+            OutputFormat::Tiff.apply(&PathBuf::from("out/a.webp")),
+            PathBuf::from("out/a.tiff")
+        );
+        assert!("gif".parse::<OutputFormat>().is_err());
+    }
+}
@@ -0,0 +1,102 @@
+//! Image overlays.
+
+use crate::units::{Corner, FixSize};
+use crate::ParseStructError;
+use std::error::Error;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+/// An overlay image, with position and opacity.
+///
+/// Can be parsed from strings of format `path@position@opacity`, where `position` is
+/// a corner `(top-left|top-right|bottom-left|bottom-right)` or the upper left corner `x/y`,
+/// and `opacity` is in percent. The opacity is optional, and defaults to 100.
+/// Examples:
+/// <pre>
+/// logo.png@bottom-right@50
+/// signature.png@2cm/90%
+/// </pre>
+#[derive(Debug, PartialEq, Clone)]
+pub struct Overlay {
+    /// Path of the overlay image.
+    pub path: PathBuf,
+    /// Position of the overlay.
+    pub position: OverlayPosition,
+    /// Opacity in percent.
+    pub opacity: f64,
+}
+
+/// Position of an overlay image.
+#[derive(Debug, PartialEq, Clone)]
+pub enum OverlayPosition {
+    /// Placed in a corner.
+    Corner(Corner),
+    /// Upper left corner of the overlay.
+    At(FixSize),
+}
+
+impl FromStr for Overlay {
+    type Err = Box<dyn Error>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<_> = s.split('@').collect();
+        let (path, position, opacity) = match parts[..] {
+            [path, position] => (path, position, None),
+            [path, position, opacity] => (path, position, Some(opacity)),
+            _ => {
+                return Err(Box::new(ParseStructError(format!(
+                    "Unexpected overlay format in `{}`, expects `path@position@opacity`",
+                    s
+                ))))
+            }
+        };
+        let position = if position.contains('/') {
+            OverlayPosition::At(position.parse()?)
+        } else {
+            OverlayPosition::Corner(position.parse()?)
+        };
+        let opacity = match opacity {
+            Some(o) => o.trim_end_matches('%').parse()?,
+            None => 100.0,
+        };
+        if !(0.0..=100.0).contains(&opacity) {
+            return Err(Box::new(ParseStructError(format!(
+                "Overlay opacity must be in range 0-100, got {}",
+                opacity
+            ))));
+        }
+        Ok(Overlay {
+            path: PathBuf::from(path),
+            position,
+            opacity,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::units::{Corner, Overlay, OverlayPosition};
+    use std::path::PathBuf;
+
+    #[test]
+    fn parse_overlay() {
+        let overlay: Overlay = "logo.png@bottom-right@50".parse().unwrap();
+        assert_eq!(overlay.path, PathBuf::from("logo.png"));
+        assert_eq!(
+            overlay.position,
+            OverlayPosition::Corner(Corner::BottomRight)
+        );
+        assert_eq!(overlay.opacity, 50.0);
+
+        let overlay: Overlay = "sign.png@2cm/90%".parse().unwrap();
+        assert_eq!(overlay.opacity, 100.0);
+        match overlay.position {
+            OverlayPosition::At(pos) => assert_eq!(pos.to_string(), "2cm/90%"),
+            _ => panic!("Expected an absolute position"),
+        }
+
+        assert!("logo.png".parse::<Overlay>().is_err());
+        assert!("logo.png@center".parse::<Overlay>().is_err());
+        assert!("logo.png@top-left@150".parse::<Overlay>().is_err());
+    }
+}
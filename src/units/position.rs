@@ -0,0 +1,92 @@
+//! Positions within an image.
+
+use crate::ParseEnumError;
+use std::str::FromStr;
+
+/// Image corners
+#[derive(Debug, PartialEq, Clone)]
+pub enum Corner {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+impl FromStr for Corner {
+    type Err = ParseEnumError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "top-left" => Ok(Corner::TopLeft),
+            "top-right" => Ok(Corner::TopRight),
+            "bottom-left" => Ok(Corner::BottomLeft),
+            "bottom-right" => Ok(Corner::BottomRight),
+            _ => Err(ParseEnumError(format!(
+                "`{}` is not a valid corner. Must be one of `(top-left|top-right|bottom-left|bottom-right)`",
+                s
+            ))),
+        }
+    }
+}
+
+impl Corner {
+    /// Calculates the position of an element of size `width`/`height`
+    /// in this corner of an image, with the given margin.
+    pub fn place(
+        &self,
+        image_width: u32,
+        image_height: u32,
+        width: u32,
+        height: u32,
+        margin: u32,
+    ) -> (u32, u32) {
+        let right = image_width.saturating_sub(width + margin);
+        let bottom = image_height.saturating_sub(height + margin);
+        match self {
+            Corner::TopLeft => (margin, margin),
+            Corner::TopRight => (right, margin),
+            Corner::BottomLeft => (margin, bottom),
+            Corner::BottomRight => (right, bottom),
+        }
+    }
+}
+
+/// Position of text relative to an image
+#[derive(Debug, PartialEq, Clone)]
+pub enum TextPosition {
+    /// Below the image, outside of the padding.
+    Below,
+    /// Above the image, outside of the padding.
+    Above,
+    /// Inside the image, in the bottom left corner.
+    Corner,
+}
+
+impl FromStr for TextPosition {
+    type Err = ParseEnumError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "below" => Ok(TextPosition::Below),
+            "above" => Ok(TextPosition::Above),
+            "corner" => Ok(TextPosition::Corner),
+            _ => Err(ParseEnumError(format!(
+                "`{}` is not a valid text position. Must be one of `(below|above|corner)`",
+                s
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::units::Corner;
+
+    #[test]
+    fn place() {
+        let corner: Corner = "bottom-right".parse().unwrap();
+        assert_eq!(corner.place(100, 50, 20, 10, 5), (75, 35));
+        assert_eq!(Corner::TopLeft.place(100, 50, 20, 10, 5), (5, 5));
+        assert!("center".parse::<Corner>().is_err());
+    }
+}
@@ -2,6 +2,7 @@
 
 use crate::{ParseEnumError, ParseStructError};
 use std::error::Error;
+use std::fmt;
 use std::str::FromStr;
 
 /// Scaling modes
@@ -19,29 +20,149 @@ pub enum ScaleMode {
     /// Aspect ratio is changed.
     /// The resulting image has exactly the given size, and the image is stretched.
     Stretch,
+    /// Keeps the original aspect ratio.
+    /// The resulting image has exactly the given size, and additional space is filled
+    /// with a blurred, enlarged copy of the image. Holds the blur sigma in px.
+    Blur(f32),
+    /// Keeps the original aspect ratio.
+    /// The resulting image has exactly the given size, and additional space is filled
+    /// by repeating the nearest edge pixels, e.g. for borderless prints.
+    Extend,
 }
 
+/// Default blur sigma in px for `ScaleMode::Blur`.
+pub const DEFAULT_BLUR_SIGMA: f32 = 25.0;
+
 impl FromStr for ScaleMode {
     type Err = ParseEnumError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s {
-            "fill" => Ok(ScaleMode::Fill),
+            "fill" | "pad" => Ok(ScaleMode::Fill),
             "crop" => Ok(ScaleMode::Crop),
             "keep" => Ok(ScaleMode::Keep),
             "stretch" => Ok(ScaleMode::Stretch),
+            "blur" => Ok(ScaleMode::Blur(DEFAULT_BLUR_SIGMA)),
+            "extend" => Ok(ScaleMode::Extend),
+            _ => Err(ParseEnumError(format!(
+                "`{}` is not a valid scale mode. Must be one of `(keep|fill|pad|crop|stretch|blur|extend)`",
+                s
+            ))),
+        }
+    }
+}
+
+impl fmt::Display for ScaleMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ScaleMode::Keep => "keep",
+            ScaleMode::Fill => "fill",
+            ScaleMode::Crop => "crop",
+            ScaleMode::Stretch => "stretch",
+            ScaleMode::Blur(_) => "blur",
+            ScaleMode::Extend => "extend",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Reference for relative scaling
+#[derive(Debug, PartialEq)]
+pub enum ScaleOf {
+    /// Scale relative to the source image size.
+    Source,
+    /// Scale relative to a print format.
+    Format,
+}
+
+impl FromStr for ScaleOf {
+    type Err = ParseEnumError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "source" => Ok(ScaleOf::Source),
+            "format" => Ok(ScaleOf::Format),
+            _ => Err(ParseEnumError(format!(
+                "`{}` is not a valid scale reference. Must be one of `(source|format)`",
+                s
+            ))),
+        }
+    }
+}
+
+/// Half-size steps for incremental downscaling
+#[derive(Debug, PartialEq, Clone)]
+pub enum Downscale {
+    /// Averages over 2x2 pixels.
+    Box,
+    /// Resizes to half size using the selected filter type. Sharper than `Box`.
+    Filtered,
+}
+
+impl FromStr for Downscale {
+    type Err = ParseEnumError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "box" => Ok(Downscale::Box),
+            "filtered" => Ok(Downscale::Filtered),
+            _ => Err(ParseEnumError(format!(
+                "`{}` is not a valid downscale step. Must be one of `(box|filtered)`",
+                s
+            ))),
+        }
+    }
+}
+
+/// Heuristics for choosing the image region to keep when cropping
+#[derive(Debug, PartialEq, Clone)]
+pub enum SmartCrop {
+    /// Keeps the center of the image.
+    Center,
+    /// Keeps the upper-center part of the image, centered at the upper third line.
+    /// Suitable for portraits.
+    Upper,
+    /// Aligns the upper third line of the crop with the image's upper third line.
+    RuleOfThirds,
+}
+
+impl FromStr for SmartCrop {
+    type Err = ParseEnumError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "center" => Ok(SmartCrop::Center),
+            "upper" => Ok(SmartCrop::Upper),
+            "rule-of-thirds" => Ok(SmartCrop::RuleOfThirds),
             _ => Err(ParseEnumError(format!(
-                "`{}` is not a valid scale mode. Must be one of `(keep|fill|crop|stretch)`",
+                "`{}` is not a valid crop heuristic. Must be one of `(center|upper|rule-of-thirds)`",
                 s
             ))),
         }
     }
 }
 
+impl SmartCrop {
+    /// Offset of a crop of size `crop` from an image dimension of size `size`.
+    /// Horizontal crops are always centered.
+    pub fn offset(&self, size: u32, crop: u32, vertical: bool) -> u32 {
+        let surplus = size.saturating_sub(crop);
+        if !vertical {
+            return surplus / 2;
+        }
+        match self {
+            SmartCrop::Center => surplus / 2,
+            SmartCrop::Upper => (size / 3).saturating_sub(crop / 2).min(surplus),
+            SmartCrop::RuleOfThirds => surplus / 3,
+        }
+    }
+}
+
 /// Relative scaling parameters.
 ///
 /// Can be parsed from stings of format `width/height` or `scale`.
-/// `width` and `height´ can be fractions or percentages.
+/// `width` and `height´ can be fractions or percentages, and must be larger than zero.
+/// A single `scale` applies to both axes.
 /// Examples:
 /// <pre>
 /// 50%
@@ -49,7 +170,7 @@ impl FromStr for ScaleMode {
 /// 0.6/0.8
 /// </pre>
 ///
-/// `.` can be used as placeholder.
+/// `.` can be used as placeholder, taking the value of the other axis.
 /// Examples:
 /// <pre>
 /// ./20%
@@ -111,6 +232,15 @@ impl FromStr for Scale {
         if height.is_none() {
             height = Some(width.unwrap())
         }
+        for value in &[width, height] {
+            let value = value.unwrap();
+            if !(value > 0.0 && value.is_finite()) {
+                return Err(Box::new(ParseStructError(format!(
+                    "Unable to parse scale from {}, scale must be larger than zero",
+                    s
+                ))));
+            }
+        }
         Ok(Scale {
             width: width.unwrap(),
             height: height.unwrap(),
@@ -120,7 +250,7 @@ impl FromStr for Scale {
 
 #[cfg(test)]
 mod test {
-    use crate::units::scale::Scale;
+    use crate::units::scale::{Scale, ScaleMode};
 
     #[test]
     fn parse_scale() {
@@ -130,4 +260,32 @@ mod test {
         assert_eq!(scale.width, 0.5);
         assert_eq!(scale.height, 1.0);
     }
+
+    #[test]
+    fn parse_single() {
+        let scale = |s: &str| {
+            let scale: Scale = s.parse().unwrap();
+            (scale.width, scale.height)
+        };
+        assert_eq!(scale("2"), (2.0, 2.0));
+        assert_eq!(scale("200%"), (2.0, 2.0));
+        assert_eq!(scale("./50%"), (0.5, 0.5));
+        assert_eq!(scale("0.5/."), (0.5, 0.5));
+    }
+
+    #[test]
+    fn parse_non_positive() {
+        assert!("0".parse::<Scale>().is_err());
+        assert!("0%".parse::<Scale>().is_err());
+        assert!("-50%".parse::<Scale>().is_err());
+        assert!("50%/0".parse::<Scale>().is_err());
+        assert!("./.".parse::<Scale>().is_err());
+    }
+
+    #[test]
+    fn parse_mode() {
+        assert_eq!("pad".parse::<ScaleMode>().unwrap(), ScaleMode::Fill);
+        assert_eq!("extend".parse::<ScaleMode>().unwrap(), ScaleMode::Extend);
+        assert!("tile".parse::<ScaleMode>().is_err());
+    }
 }
@@ -0,0 +1,28 @@
+//! Mask shapes.
+
+use crate::ParseEnumError;
+use std::str::FromStr;
+
+/// Mask shapes
+#[derive(Debug, PartialEq, Clone)]
+pub enum MaskShape {
+    /// Circle, centered in the image.
+    Circle,
+    /// Rectangle with rounded corners.
+    Rounded,
+}
+
+impl FromStr for MaskShape {
+    type Err = ParseEnumError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "circle" => Ok(MaskShape::Circle),
+            "rounded" => Ok(MaskShape::Rounded),
+            _ => Err(ParseEnumError(format!(
+                "`{}` is not a valid mask shape. Must be one of `(circle|rounded)`",
+                s
+            ))),
+        }
+    }
+}
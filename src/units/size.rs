@@ -1,6 +1,7 @@
 //! Absolute scale.
 
 use crate::units::length::{Length, LengthUnit};
+use crate::units::Borders;
 use crate::ParseStructError;
 use std::error::Error;
 use std::fmt;
@@ -9,7 +10,7 @@ use std::str::FromStr;
 /// Absolute scaling parameters.
 ///
 /// Can be parsed from stings of format `width/height`.
-/// `width` and `height´ can be in units px, cm or inch.
+/// `width` and `height´ can be in units px, cm, mm, in or pt.
 /// Examples:
 /// <pre>
 /// 10cm/5cm
@@ -57,10 +58,19 @@ impl Size {
             height: self.height.as_ref().and_then(|w| Some(w.to(unit, dpi))),
         }
     }
+    /// Converts this size to cm, e.g. for display independent of the input unit.
+    pub fn to_cm(&self, dpi: f64) -> Size {
+        self.to(&LengthUnit::Cm, dpi)
+    }
     /// Rotates this size by 90° clockwise (i.e. swaps width and height).
     pub fn rotate_90(&self) -> Size {
         Size::new(self.height.clone(), self.width.clone()).unwrap()
     }
+    /// Is this size relative to a reference size?
+    pub fn is_relative(&self) -> bool {
+        self.width.as_ref().is_some_and(|w| w.is_relative())
+            || self.height.as_ref().is_some_and(|h| h.is_relative())
+    }
     /// Does this size require a dpi value for conversion to px?
     pub fn needs_dpi(&self) -> bool {
         let mut needs = false;
@@ -78,17 +88,24 @@ impl Size {
     }
 }
 
+/// Splits `width/height` into its parts. A single value is used for width and height.
+fn split_size(s: &str) -> Result<[&str; 2], Box<dyn Error>> {
+    let parts: Vec<_> = s.split('/').collect();
+    match parts.len() {
+        1 => Ok([parts[0], parts[0]]),
+        2 => Ok([parts[0], parts[1]]),
+        _ => Err(Box::new(ParseStructError(format!(
+            "Unexpected size format in {}, expects `width/height` or `size`",
+            s
+        )))),
+    }
+}
+
 impl FromStr for Size {
     type Err = Box<dyn Error>;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let parts: Vec<_> = s.split("/").collect();
-        if parts.len() != 2 {
-            return Err(Box::new(ParseStructError(format!(
-                "Unexpected size format in {}, expects `width/height`",
-                s
-            ))));
-        }
+        let parts = split_size(s)?;
         let width = if parts[0] == "." {
             None
         } else {
@@ -155,6 +172,11 @@ impl FreeSize {
     pub fn rotate_90(&self) -> Size {
         Size::new(self.height.clone(), self.width.clone()).unwrap()
     }
+    /// Is this size relative to a reference size?
+    pub fn is_relative(&self) -> bool {
+        self.width.as_ref().is_some_and(|w| w.is_relative())
+            || self.height.as_ref().is_some_and(|h| h.is_relative())
+    }
     /// Does this size require a dpi value for conversion to px?
     pub fn needs_dpi(&self) -> bool {
         let mut needs = false;
@@ -245,6 +267,15 @@ impl FixSize {
             height: self.height.to(unit, dpi),
         }
     }
+    /// Canonical form of this size as rounded pixel dimensions `(width, height)`,
+    /// for comparing sizes given in different units. Must not be relative.
+    pub fn normalized(&self, dpi: f64) -> (u32, u32) {
+        let px = self.to_px(dpi);
+        (
+            px.width.value().round().max(0.0) as u32,
+            px.height.value().round().max(0.0) as u32,
+        )
+    }
     /// Rotates this size by 90° clockwise (i.e. swaps width and height).
     pub fn rotate_90(&self) -> FixSize {
         FixSize::new(self.height.clone(), self.width.clone())
@@ -257,19 +288,68 @@ impl FixSize {
     pub fn needs_dpi(&self) -> bool {
         self.width.needs_dpi() || self.height.needs_dpi()
     }
+    /// Is this size relative to a reference size?
+    pub fn is_relative(&self) -> bool {
+        self.width.is_relative() || self.height.is_relative()
+    }
+    /// Resolves a relative size against a reference size in px.
+    pub fn resolve(&self, width: f64, height: f64) -> FixSize {
+        FixSize {
+            width: self.width.resolve(width),
+            height: self.height.resolve(height),
+        }
+    }
+    /// Aspect ratio `width / height`. Both must be in the same unit.
+    pub fn aspect_ratio(&self) -> f64 {
+        self.width.value() / self.height.value()
+    }
+    /// The largest size with the aspect ratio of this size that fits into `other`,
+    /// in the unit of `other`.
+    pub fn scaled_to_fit(&self, other: &FixSize) -> FixSize {
+        let aspect = self.aspect_ratio();
+        let other_aspect = other.aspect_ratio();
+        let (width, height) = if aspect >= other_aspect {
+            // wider
+            (
+                other.width.value(),
+                other.height.value() * other_aspect / aspect,
+            )
+        } else {
+            // higher
+            (
+                other.width.value() * aspect / other_aspect,
+                other.height.value(),
+            )
+        };
+        FixSize {
+            width: Length::new(width, other.width.unit().clone()),
+            height: Length::new(height, other.height.unit().clone()),
+        }
+    }
+    /// Adds borders on all sides. Borders must be in the same unit as this size.
+    pub fn add_borders(&self, borders: &Borders) -> FixSize {
+        self.grow(borders, 1.0)
+    }
+    /// Subtracts borders on all sides. Borders must be in the same unit as this size.
+    pub fn sub_borders(&self, borders: &Borders) -> FixSize {
+        self.grow(borders, -1.0)
+    }
+    fn grow(&self, borders: &Borders, sign: f64) -> FixSize {
+        let width = self.width.value() + sign * (borders.left().value() + borders.right().value());
+        let height =
+            self.height.value() + sign * (borders.top().value() + borders.bottom().value());
+        FixSize {
+            width: Length::new(width, self.width.unit().clone()),
+            height: Length::new(height, self.height.unit().clone()),
+        }
+    }
 }
 
 impl FromStr for FixSize {
     type Err = Box<dyn Error>;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let parts: Vec<_> = s.split("/").collect();
-        if parts.len() != 2 {
-            return Err(Box::new(ParseStructError(format!(
-                "Unexpected size format in {}, expects `width/height`",
-                s
-            ))));
-        }
+        let parts = split_size(s)?;
         let width = parts[0].parse()?;
         let height = parts[1].parse()?;
         Ok(FixSize { width, height })
@@ -283,8 +363,42 @@ impl fmt::Display for FixSize {
 
 #[cfg(test)]
 mod test {
-    use crate::units::length::LengthUnit;
-    use crate::units::size::Size;
+    use crate::units::length::{Length, LengthUnit};
+    use crate::units::size::{FixSize, Size};
+    use crate::units::{format, Borders};
+
+    #[test]
+    fn normalized() {
+        let inch: FixSize = "6in/4in".parse().unwrap();
+        let cm: FixSize = "15cm/10cm".parse().unwrap();
+        let cm = format::to_print_format(&cm).unwrap();
+        assert_eq!(inch.normalized(300.0), (1800, 1200));
+        assert_eq!(cm.normalized(300.0), inch.normalized(300.0));
+
+        let px: FixSize = "1800px/1200px".parse().unwrap();
+        assert_eq!(px.normalized(300.0), (1800, 1200));
+
+        let size: Size = "2in/.".parse().unwrap();
+        assert_eq!(size.to_cm(300.0).to_string(), "5.08cm/.");
+        let size: Size = "300px/150px".parse().unwrap();
+        assert_eq!(size.to_cm(300.0).to_string(), "2.54cm/1.27cm");
+    }
+
+    #[test]
+    fn parse_single() {
+        let size: FixSize = "500px".parse().unwrap();
+        assert_eq!(size, FixSize::new(Length::px(500), Length::px(500)));
+
+        let size: FixSize = "5cm".parse().unwrap();
+        assert_eq!(size.to_string(), "5cm/5cm");
+
+        let size: Size = "5cm".parse().unwrap();
+        assert_eq!(size.to_string(), "5cm/5cm");
+        let size: Size = "./5cm".parse().unwrap();
+        assert_eq!(size.to_string(), "./5cm");
+        assert!(".".parse::<Size>().is_err());
+        assert!("1cm/2cm/3cm".parse::<FixSize>().is_err());
+    }
 
     #[test]
     fn parse_size() {
@@ -323,4 +437,28 @@ mod test {
         let size: Size = str.parse().unwrap();
         assert_eq!(size.to_string(), str);
     }
+
+    #[test]
+    fn scaled_to_fit() {
+        let target = FixSize::px(600, 400);
+        // Wider than the target
+        let fit = FixSize::px(300, 100).scaled_to_fit(&target);
+        assert_eq!(fit, FixSize::px(600, 200));
+        // Taller than the target
+        let fit = FixSize::px(100, 200).scaled_to_fit(&target);
+        assert_eq!(fit, FixSize::px(200, 400));
+        // Same aspect ratio
+        let fit = FixSize::px(30, 20).scaled_to_fit(&target);
+        assert_eq!(fit, target);
+        assert_eq!(target.aspect_ratio(), 1.5);
+    }
+
+    #[test]
+    fn borders() {
+        let size = FixSize::px(600, 400);
+        let borders = Borders::px(10, 20, 30, 40);
+        assert_eq!(size.add_borders(&borders), FixSize::px(660, 440));
+        assert_eq!(size.sub_borders(&borders), FixSize::px(540, 360));
+        assert_eq!(size.add_borders(&borders).sub_borders(&borders), size);
+    }
 }
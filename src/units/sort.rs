@@ -0,0 +1,31 @@
+//! Input file ordering.
+
+use crate::ParseEnumError;
+use std::str::FromStr;
+
+/// Order of input files
+#[derive(Debug, PartialEq, Clone)]
+pub enum SortOrder {
+    /// By path.
+    Name,
+    /// By modification time, oldest first.
+    Mtime,
+    /// By file size, smallest first.
+    Size,
+}
+
+impl FromStr for SortOrder {
+    type Err = ParseEnumError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "name" => Ok(SortOrder::Name),
+            "mtime" => Ok(SortOrder::Mtime),
+            "size" => Ok(SortOrder::Size),
+            _ => Err(ParseEnumError(format!(
+                "`{}` is not a valid sort order. Must be one of `(name|mtime|size)`",
+                s
+            ))),
+        }
+    }
+}
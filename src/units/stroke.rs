@@ -0,0 +1,68 @@
+//! Colored bands, like mats.
+
+use crate::units::color::Color;
+use crate::units::Length;
+use crate::ParseStructError;
+use std::error::Error;
+use std::str::FromStr;
+
+/// A band of a given width and color.
+///
+/// Can be parsed from strings of format `width/color`, where `color` is any color format.
+/// Relative widths are not supported.
+/// Examples:
+/// <pre>
+/// 2cm/white
+/// 1mm/240/235/220
+/// </pre>
+#[derive(Debug, PartialEq, Clone)]
+pub struct Stroke {
+    /// Width of the band.
+    pub width: Length,
+    /// Color of the band.
+    pub color: Color,
+}
+
+impl FromStr for Stroke {
+    type Err = Box<dyn Error>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (width, color) = s.split_once('/').ok_or_else(|| {
+            ParseStructError(format!(
+                "Unexpected format in `{}`, expects `width/color`",
+                s
+            ))
+        })?;
+        let width: Length = width.parse()?;
+        if width.is_relative() || width.value() < 0.0 {
+            return Err(Box::new(ParseStructError(format!(
+                "Width must be a non-negative absolute length, got `{}`",
+                s
+            ))));
+        }
+        Ok(Stroke {
+            width,
+            color: color.parse()?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::units::color::Color;
+    use crate::units::{Length, Stroke};
+
+    #[test]
+    fn parse_stroke() {
+        let stroke: Stroke = "2cm/white".parse().unwrap();
+        assert_eq!(stroke.width, Length::cm(2.0));
+        assert_eq!(stroke.color, Color::new(255, 255, 255, 255));
+
+        let stroke: Stroke = "3px/10/20/30".parse().unwrap();
+        assert_eq!(stroke.width, Length::px(3));
+        assert_eq!(stroke.color, Color::new(10, 20, 30, 255));
+
+        assert!("2cm".parse::<Stroke>().is_err());
+        assert!("5%/white".parse::<Stroke>().is_err());
+    }
+}
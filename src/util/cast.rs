@@ -0,0 +1,66 @@
+//! Checked numeric conversions for image geometry.
+
+/// Checked numeric conversions for image geometry.
+///
+/// Values outside the valid range of the target type are clamped, and a warning is printed.
+pub struct CastUtil {}
+
+impl CastUtil {
+    /// Converts an `i32` to `u32`, clamping negative values to `0`.
+    pub fn u32_from_i32(value: i32, what: &str) -> u32 {
+        if value < 0 {
+            log::warn!("Negative {} ({}), clamped to 0.", what, value);
+            0
+        } else {
+            value as u32
+        }
+    }
+    /// Converts an `f64` to `u32` (truncating), clamping to the range of `u32`.
+    pub fn u32_from_f64(value: f64, what: &str) -> u32 {
+        if value.is_nan() || value < 0.0 {
+            log::warn!("Invalid {} ({}), clamped to 0.", what, value);
+            0
+        } else if value > u32::MAX as f64 {
+            log::warn!("{} too large ({}), clamped.", what, value);
+            u32::MAX
+        } else {
+            value as u32
+        }
+    }
+    /// Converts an `f64` to `i32` (truncating), clamping to the range of `i32`.
+    pub fn i32_from_f64(value: f64, what: &str) -> i32 {
+        if value.is_nan() {
+            log::warn!("Invalid {} ({}), clamped to 0.", what, value);
+            0
+        } else if value < i32::MIN as f64 || value > i32::MAX as f64 {
+            log::warn!("{} out of range ({}), clamped.", what, value);
+            value.max(i32::MIN as f64).min(i32::MAX as f64) as i32
+        } else {
+            value as i32
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::util::CastUtil;
+
+    #[test]
+    fn u32_from_i32() {
+        assert_eq!(CastUtil::u32_from_i32(12, "test"), 12);
+        assert_eq!(CastUtil::u32_from_i32(-12, "test"), 0);
+    }
+
+    #[test]
+    fn u32_from_f64() {
+        assert_eq!(CastUtil::u32_from_f64(12.7, "test"), 12);
+        assert_eq!(CastUtil::u32_from_f64(-12.0, "test"), 0);
+        assert_eq!(CastUtil::u32_from_f64(1e12, "test"), u32::MAX);
+    }
+
+    #[test]
+    fn i32_from_f64() {
+        assert_eq!(CastUtil::i32_from_f64(-12.7, "test"), -12);
+        assert_eq!(CastUtil::i32_from_f64(1e12, "test"), i32::MAX);
+    }
+}
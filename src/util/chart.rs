@@ -0,0 +1,291 @@
+//! Calibration chart utilities
+
+use crate::util::ImageUtil;
+use image::{DynamicImage, GenericImage, GenericImageView, Rgba};
+use imageproc::rect::Rect;
+
+/// Drawing of print control elements and calibration charts.
+pub struct ChartUtil {}
+
+impl ChartUtil {
+    /// Creates a print control element with CMY, RGB and grayscale patches,
+    /// line patterns and a crosshair, in a grid of 9x3 patches.
+    ///
+    /// `sx`/`sy` are the patch sizes, `gap_x`/`gap_y` the gaps between patches, all in px.
+    pub fn control_element(sx: u32, gap_x: u32, sy: u32, gap_y: u32) -> DynamicImage {
+        let off_x = gap_x as i32;
+        let off_y = gap_y as i32;
+        // The width is based on the patch height, like in the original layout of `prep`
+        let mut image = DynamicImage::new_rgb8(9 * sy + 10 * gap_x, 3 * sy + 4 * gap_y);
+        ImageUtil::fill_image(&mut image, &[255, 255, 255, 255]);
+
+        // CMY(K)
+        for i in 0..5 {
+            imageproc::drawing::draw_filled_rect_mut(
+                &mut image,
+                Rect::at(off_x + i * (sx as i32 + off_x), off_y).of_size(sx, sy),
+                Rgba([i as u8 * 51, 255, 255, 255]),
+            );
+            imageproc::drawing::draw_filled_rect_mut(
+                &mut image,
+                Rect::at(
+                    off_x + i * (sx as i32 + off_x),
+                    off_y + 1 * (sy as i32 + off_y),
+                )
+                .of_size(sx, sy),
+                Rgba([255, i as u8 * 51, 255, 255]),
+            );
+            imageproc::drawing::draw_filled_rect_mut(
+                &mut image,
+                Rect::at(
+                    off_x + i * (sx as i32 + off_x),
+                    off_y + 2 * (sy as i32 + off_y),
+                )
+                .of_size(sx, sy),
+                Rgba([255, 255, i as u8 * 51, 255]),
+            );
+        }
+        // RGB
+        imageproc::drawing::draw_filled_rect_mut(
+            &mut image,
+            Rect::at(off_x + 5 * (sx as i32 + off_x), off_y).of_size(sx, sy),
+            Rgba([255, 0, 0, 255]),
+        );
+        imageproc::drawing::draw_filled_rect_mut(
+            &mut image,
+            Rect::at(
+                off_x + 5 * (sx as i32 + off_x),
+                off_y + 1 * (sy as i32 + off_y),
+            )
+            .of_size(sx, sy),
+            Rgba([0, 255, 0, 255]),
+        );
+        imageproc::drawing::draw_filled_rect_mut(
+            &mut image,
+            Rect::at(
+                off_x + 5 * (sx as i32 + off_x),
+                off_y + 2 * (sy as i32 + off_y),
+            )
+            .of_size(sx, sy),
+            Rgba([0, 0, 255, 255]),
+        );
+        // Greyscale light
+        imageproc::drawing::draw_filled_rect_mut(
+            &mut image,
+            Rect::at(off_x + 6 * (sx as i32 + off_x), off_y).of_size(sx, sy),
+            Rgba([255, 255, 255, 255]),
+        );
+        imageproc::drawing::draw_filled_rect_mut(
+            &mut image,
+            Rect::at(
+                off_x + 6 * (sx as i32 + off_x),
+                off_y + 1 * (sy as i32 + off_y),
+            )
+            .of_size(sx, sy),
+            Rgba([204, 204, 204, 255]),
+        );
+        imageproc::drawing::draw_filled_rect_mut(
+            &mut image,
+            Rect::at(
+                off_x + 6 * (sx as i32 + off_x),
+                off_y + 2 * (sy as i32 + off_y),
+            )
+            .of_size(sx, sy),
+            Rgba([153, 153, 153, 255]),
+        );
+        // Greyscale dark
+        imageproc::drawing::draw_filled_rect_mut(
+            &mut image,
+            Rect::at(off_x + 7 * (sx as i32 + off_x), off_y).of_size(sx, sy),
+            Rgba([102, 102, 102, 255]),
+        );
+        imageproc::drawing::draw_filled_rect_mut(
+            &mut image,
+            Rect::at(
+                off_x + 7 * (sx as i32 + off_x),
+                off_y + 1 * (sy as i32 + off_y),
+            )
+            .of_size(sx, sy),
+            Rgba([51, 51, 51, 255]),
+        );
+        imageproc::drawing::draw_filled_rect_mut(
+            &mut image,
+            Rect::at(
+                off_x + 7 * (sx as i32 + off_x),
+                off_y + 2 * (sy as i32 + off_y),
+            )
+            .of_size(sx, sy),
+            Rgba([0, 0, 0, 255]),
+        );
+        // Vertical pattern
+        for i in 0..(sx / 2) {
+            let x = 2 * i as i32;
+            imageproc::drawing::draw_filled_rect_mut(
+                &mut image,
+                Rect::at(off_x + 8 * (sx as i32 + off_x) + x, off_y).of_size(1, sy),
+                Rgba([0, 0, 0, 255]),
+            );
+        }
+        // Horizontal pattern
+        for i in 0..(sy / 2) {
+            let y = 2 * i as i32;
+            imageproc::drawing::draw_filled_rect_mut(
+                &mut image,
+                Rect::at(
+                    off_x + 8 * (sx as i32 + off_x),
+                    off_y + 2 * (sy as i32 + off_y) + y,
+                )
+                .of_size(sx, 1),
+                Rgba([0, 0, 0, 255]),
+            );
+        }
+        // Crosshair
+        {
+            let x = off_x + 8 * (sx as i32 + off_x) + sx as i32 / 2;
+            let y = off_y + 1 * (sy as i32 + off_y) + sy as i32 / 2;
+            imageproc::drawing::draw_hollow_circle_mut(
+                &mut image,
+                (x, y),
+                std::cmp::min(sx, sy) as i32 / 3,
+                Rgba([0, 0, 0, 255]),
+            );
+            imageproc::drawing::draw_filled_rect_mut(
+                &mut image,
+                Rect::at(x - sx as i32 / 2, y).of_size(sx, 1),
+                Rgba([0, 0, 0, 255]),
+            );
+            imageproc::drawing::draw_filled_rect_mut(
+                &mut image,
+                Rect::at(x, y - sy as i32 / 2).of_size(1, sy),
+                Rgba([0, 0, 0, 255]),
+            );
+        }
+
+        image
+    }
+
+    /// Draws a horizontal gradient between two colors into the given rectangle.
+    pub fn draw_gradient(image: &mut DynamicImage, rect: Rect, from: &[u8; 4], to: &[u8; 4]) {
+        let steps = (rect.width() - 1).max(1) as f64;
+        for i in 0..rect.width() {
+            let color = ImageUtil::blend(&Rgba(*from), to, i as f64 / steps);
+            imageproc::drawing::draw_filled_rect_mut(
+                image,
+                Rect::at(rect.left() + i as i32, rect.top()).of_size(1, rect.height()),
+                color,
+            );
+        }
+    }
+
+    /// Draws black line pairs of the given line width into the given rectangle.
+    /// Lines are vertical if `vertical` is `true`, horizontal otherwise.
+    pub fn draw_line_pairs(image: &mut DynamicImage, rect: Rect, line_width: u32, vertical: bool) {
+        let extent = if vertical {
+            rect.width()
+        } else {
+            rect.height()
+        };
+        for i in 0..(extent / (2 * line_width)) {
+            let offset = (2 * i * line_width) as i32;
+            let line = if vertical {
+                Rect::at(rect.left() + offset, rect.top()).of_size(line_width, rect.height())
+            } else {
+                Rect::at(rect.left(), rect.top() + offset).of_size(rect.width(), line_width)
+            };
+            imageproc::drawing::draw_filled_rect_mut(image, line, Rgba([0, 0, 0, 255]));
+        }
+    }
+
+    /// Creates a full-page calibration chart with color and grayscale gradients,
+    /// the control element and resolution line pairs.
+    pub fn test_chart(width: u32, height: u32) -> DynamicImage {
+        let mut image = DynamicImage::new_rgb8(width, height);
+        ImageUtil::fill_image(&mut image, &[255, 255, 255, 255]);
+
+        let margin = width.min(height) / 20;
+        let (inner_width, inner_height) = (width - 2 * margin, height - 2 * margin);
+        let section = inner_height / 3;
+        let gap = margin / 2;
+        if inner_width < 16 || section < 16 {
+            return image;
+        }
+
+        // Gradients
+        let strip = (section - gap) / 4;
+        let black = [0, 0, 0, 255];
+        let gradients = [
+            [255, 255, 255, 255],
+            [255, 0, 0, 255],
+            [0, 255, 0, 255],
+            [0, 0, 255, 255],
+        ];
+        for (i, to) in gradients.iter().enumerate() {
+            let rect = Rect::at(margin as i32, (margin + i as u32 * strip) as i32)
+                .of_size(inner_width, strip);
+            Self::draw_gradient(&mut image, rect, &black, to);
+        }
+
+        // Control element, with gaps of 1/5 patch size
+        let patch = (inner_width / 11).min((section - gap) * 5 / 19);
+        let element = Self::control_element(patch, patch / 5, patch, patch / 5);
+        let x = margin + (inner_width - element.width().min(inner_width)) / 2;
+        image
+            .copy_from(&element, x, margin + section)
+            .expect("control element fits into chart");
+
+        // Line pairs, with line widths from 1 to 8 px
+        let widths = [1, 2, 3, 4, 6, 8];
+        let block =
+            ((inner_width / widths.len() as u32).min((section - gap) / 2)).saturating_sub(gap);
+        let step = inner_width / widths.len() as u32;
+        for (i, line_width) in widths.iter().enumerate() {
+            let x = (margin + i as u32 * step) as i32;
+            let y = (margin + 2 * section) as i32;
+            if block >= 2 * line_width {
+                Self::draw_line_pairs(
+                    &mut image,
+                    Rect::at(x, y).of_size(block, block),
+                    *line_width,
+                    true,
+                );
+                Self::draw_line_pairs(
+                    &mut image,
+                    Rect::at(x, y + (block + gap) as i32).of_size(block, block),
+                    *line_width,
+                    false,
+                );
+            }
+        }
+
+        image
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::util::ChartUtil;
+    use image::GenericImageView;
+
+    #[test]
+    fn control_element() {
+        let element = ChartUtil::control_element(10, 2, 8, 1);
+        // The width depends on the patch height, like in the original layout of `prep`
+        assert_eq!((element.width(), element.height()), (92, 28));
+        // First cyan patch, and white gap
+        assert_eq!(element.get_pixel(3, 2).0, [0, 255, 255, 255]);
+        assert_eq!(element.get_pixel(1, 2).0, [255, 255, 255, 255]);
+    }
+
+    #[test]
+    fn test_chart() {
+        let chart = ChartUtil::test_chart(1800, 1200);
+        assert_eq!((chart.width(), chart.height()), (1800, 1200));
+        // Gradient from black to white
+        assert_eq!(chart.get_pixel(60, 70).0, [0, 0, 0, 255]);
+        assert_eq!(chart.get_pixel(1739, 70).0, [255, 255, 255, 255]);
+        // 1px vertical line pairs
+        let y = 60 + 2 * 360 + 10;
+        assert_eq!(chart.get_pixel(60, y).0, [0, 0, 0, 255]);
+        assert_eq!(chart.get_pixel(61, y).0, [255, 255, 255, 255]);
+    }
+}
@@ -2,18 +2,82 @@
 
 use crate::units::color::Color;
 use crate::units::exif::FIELDS;
-use crate::units::ScaleMode;
+use crate::units::{Downscale, ScaleMode, SmartCrop};
 use crate::util::PathUtil;
-use exif::Exif;
+use exif::{Exif, Field, In, Tag, Value};
+use image::buffer::ConvertBuffer;
 use image::flat::SampleLayout;
 use image::imageops::FilterType;
-use image::{DynamicImage, GenericImage, GenericImageView, Rgba};
+use image::{
+    ColorType, DynamicImage, GenericImage, GenericImageView, ImageDecoder, ImageFormat, Rgba,
+    RgbaImage,
+};
 use path_absolutize::Absolutize;
 use std::collections::HashMap;
+use std::convert::TryFrom;
 use std::error::Error;
 use std::fmt;
-use std::fs::File;
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::io::{BufWriter, Write};
 use std::path::PathBuf;
+use std::sync::Arc;
+
+/// Options for saving images.
+#[derive(Debug, Clone)]
+pub struct SaveOptions {
+    /// Image quality for JPEG output in percent.
+    pub quality: u8,
+    /// Overwrite existing files. If `false`, existing files are skipped.
+    pub overwrite: bool,
+    /// Use lossless compression for WebP output.
+    pub lossless: bool,
+    /// Compression for PNG output.
+    pub png_compression: png::Compression,
+    /// Insert JPEG restart markers every n MCU rows.
+    pub jpeg_restart: Option<u16>,
+    /// Maximum file size for JPEG output. Reduces quality down to `min_quality` to meet it.
+    pub max_bytes: Option<u64>,
+    /// Lowest JPEG quality to try for `max_bytes`.
+    pub min_quality: u8,
+    /// Downscale the image if `max_bytes` can't be met at `min_quality`.
+    pub max_bytes_downscale: bool,
+    /// Estimate the quality for `max_bytes` on a downscaled probe,
+    /// and encode the full image with optimized Huffman tables.
+    pub two_pass_jpeg: bool,
+    /// EXIF data (TIFF structure) to embed into JPEG output. Ignored for other formats.
+    pub exif: Option<Vec<u8>>,
+    /// ICC color profile to embed. Only supported for JPEG, PNG and TIFF output.
+    pub icc: Option<Arc<Vec<u8>>>,
+    /// Original file name to embed, as JPEG comment or PNG text chunk, tagged with a keyword.
+    /// Only supported for JPEG and PNG output.
+    pub original_name: Option<String>,
+}
+
+impl Default for SaveOptions {
+    fn default() -> Self {
+        SaveOptions {
+            quality: 95,
+            overwrite: true,
+            lossless: false,
+            png_compression: png::Compression::Default,
+            jpeg_restart: None,
+            max_bytes: None,
+            min_quality: 30,
+            max_bytes_downscale: false,
+            two_pass_jpeg: false,
+            exif: None,
+            icc: None,
+            original_name: None,
+        }
+    }
+}
+
+/// Maximum size of an ICC profile chunk in a JPEG APP2 segment.
+const ICC_CHUNK_SIZE: usize = 65519;
+
+/// Keyword of the PNG text chunk or JPEG comment holding the original file name.
+const ORIGINAL_NAME_KEYWORD: &[u8] = b"OriginalName";
 
 /// Image utilities
 pub struct ImageUtil {}
@@ -40,6 +104,510 @@ impl ImageUtil {
         Ok(map)
     }
 
+    /// Reads the dimensions and color type of an image file.
+    /// For common formats, only the header is read instead of decoding the whole image.
+    pub fn image_header(path: &PathBuf) -> Result<(u32, u32, ColorType), Box<dyn Error>> {
+        let format = ImageFormat::from_path(path)?;
+        let reader = io::BufReader::new(File::open(path)?);
+        Ok(match format {
+            ImageFormat::Jpeg => Self::decoder_header(image::jpeg::JpegDecoder::new(reader)?),
+            ImageFormat::Png => Self::decoder_header(image::png::PngDecoder::new(reader)?),
+            ImageFormat::Gif => Self::decoder_header(image::gif::GifDecoder::new(reader)?),
+            ImageFormat::Tiff => Self::decoder_header(image::tiff::TiffDecoder::new(reader)?),
+            ImageFormat::Bmp => Self::decoder_header(image::bmp::BmpDecoder::new(reader)?),
+            _ => {
+                let image = image::open(path)?;
+                (image.width(), image.height(), image.color())
+            }
+        })
+    }
+
+    fn decoder_header<'a, D: ImageDecoder<'a>>(decoder: D) -> (u32, u32, ColorType) {
+        let (width, height) = decoder.dimensions();
+        (width, height, decoder.color_type())
+    }
+
+    /// Reads the EXIF orientation (1-8) of an image file. Returns `1` if not available.
+    pub fn exif_orientation(path: &PathBuf) -> u32 {
+        Self::get_exif(path)
+            .ok()
+            .and_then(|exif| {
+                exif.get_field(Tag::Orientation, In::PRIMARY)
+                    .and_then(|field| field.value.get_uint(0))
+            })
+            .filter(|o| (1..=8).contains(o))
+            .unwrap_or(1)
+    }
+
+    /// Reads the embedded JPEG thumbnail (EXIF IFD1) of an image file.
+    /// Returns `None` if the file has no EXIF data or no thumbnail.
+    pub fn exif_thumbnail(path: &PathBuf) -> Option<Vec<u8>> {
+        let exif = Self::get_exif(path).ok()?;
+        let offset = exif
+            .get_field(Tag::JPEGInterchangeFormat, In::THUMBNAIL)?
+            .value
+            .get_uint(0)? as usize;
+        let length = exif
+            .get_field(Tag::JPEGInterchangeFormatLength, In::THUMBNAIL)?
+            .value
+            .get_uint(0)? as usize;
+        exif.buf()
+            .get(offset..offset.checked_add(length)?)
+            .map(|data| data.to_vec())
+    }
+
+    /// Applies an EXIF orientation (1-8) to the pixels of an image,
+    /// so that it is displayed correctly without the orientation tag.
+    pub fn apply_orientation(image: DynamicImage, orientation: u32) -> DynamicImage {
+        match orientation {
+            2 => image.fliph(),
+            3 => image.rotate180(),
+            4 => image.flipv(),
+            5 => image.rotate90().fliph(),
+            6 => image.rotate90(),
+            7 => image.rotate270().fliph(),
+            8 => image.rotate270(),
+            _ => image,
+        }
+    }
+
+    /// Encodes the EXIF data of the primary image for embedding into another image,
+    /// with the orientation reset to `1` and the pixel dimensions set to `width`/`height`.
+    /// The thumbnail is dropped.
+    pub fn exif_reoriented(
+        exif: &Exif,
+        width: u32,
+        height: u32,
+    ) -> Result<Vec<u8>, Box<dyn Error>> {
+        let replaced = [
+            Field {
+                tag: Tag::Orientation,
+                ifd_num: In::PRIMARY,
+                value: Value::Short(vec![1]),
+            },
+            Field {
+                tag: Tag::PixelXDimension,
+                ifd_num: In::PRIMARY,
+                value: Value::Long(vec![width]),
+            },
+            Field {
+                tag: Tag::PixelYDimension,
+                ifd_num: In::PRIMARY,
+                value: Value::Long(vec![height]),
+            },
+        ];
+        let mut writer = exif::experimental::Writer::new();
+        for field in exif.fields() {
+            if field.ifd_num == In::PRIMARY && !replaced.iter().any(|r| r.tag == field.tag) {
+                writer.push_field(field);
+            }
+        }
+        for field in &replaced {
+            writer.push_field(field);
+        }
+        let mut data = io::Cursor::new(Vec::new());
+        writer.write(&mut data, exif.little_endian())?;
+        Ok(data.into_inner())
+    }
+
+    /// Inserts EXIF data (TIFF structure) as APP1 segment into JPEG data,
+    /// after the start of image marker and the JFIF APP0 segment, if any.
+    fn insert_exif(jpeg: &mut Vec<u8>, exif: &[u8]) -> Result<(), Box<dyn Error>> {
+        if exif.len() + 8 > usize::from(u16::MAX) {
+            return Err(Box::new(ImageFormatError(format!(
+                "EXIF data too large: {} bytes",
+                exif.len()
+            ))));
+        }
+        let mut payload = b"Exif\0\0".to_vec();
+        payload.extend_from_slice(exif);
+        Self::insert_jpeg_segments(jpeg, 0xE1, &[payload]);
+        Ok(())
+    }
+
+    /// Inserts an ICC profile as APP2 segments into JPEG data,
+    /// after the start of image marker and the JFIF APP0 segment, if any.
+    fn insert_icc(jpeg: &mut Vec<u8>, icc: &[u8]) -> Result<(), Box<dyn Error>> {
+        let chunks: Vec<_> = icc.chunks(ICC_CHUNK_SIZE).collect();
+        let count = u8::try_from(chunks.len())
+            .map_err(|_| ImageFormatError(format!("ICC profile too large: {} bytes", icc.len())))?;
+        let payloads: Vec<_> = chunks
+            .iter()
+            .enumerate()
+            .map(|(i, chunk)| {
+                let mut payload = b"ICC_PROFILE\0".to_vec();
+                payload.push(i as u8 + 1);
+                payload.push(count);
+                payload.extend_from_slice(chunk);
+                payload
+            })
+            .collect();
+        Self::insert_jpeg_segments(jpeg, 0xE2, &payloads);
+        Ok(())
+    }
+
+    /// Inserts a comment as COM segment into JPEG data,
+    /// after the start of image marker and the JFIF APP0 segment, if any.
+    fn insert_jpeg_comment(jpeg: &mut Vec<u8>, comment: &[u8]) -> Result<(), Box<dyn Error>> {
+        if comment.len() + 2 > usize::from(u16::MAX) {
+            return Err(Box::new(ImageFormatError(format!(
+                "Comment too large: {} bytes",
+                comment.len()
+            ))));
+        }
+        Self::insert_jpeg_segments(jpeg, 0xFE, &[comment.to_vec()]);
+        Ok(())
+    }
+
+    /// Reads the original file name embedded by `SaveOptions::original_name`, if any.
+    /// Supports JPEG comments and PNG text chunks starting with the keyword `OriginalName`.
+    pub fn original_name(path: &PathBuf) -> Result<Option<String>, Box<dyn Error>> {
+        let data = std::fs::read(path)?;
+        // Keyword, followed by a null separator
+        let tagged = |text: &[u8]| {
+            if text.starts_with(ORIGINAL_NAME_KEYWORD)
+                && text.get(ORIGINAL_NAME_KEYWORD.len()) == Some(&0)
+            {
+                Some(text[ORIGINAL_NAME_KEYWORD.len() + 1..].to_vec())
+            } else {
+                None
+            }
+        };
+        if data.starts_with(&[0xFF, 0xD8]) {
+            // Segments up to the start of scan. The length includes its own two bytes.
+            let mut pos = 2;
+            while pos + 4 <= data.len() && data[pos] == 0xFF && data[pos + 1] != 0xDA {
+                let len = usize::from(u16::from_be_bytes([data[pos + 2], data[pos + 3]]));
+                if len < 2 {
+                    break;
+                }
+                if data[pos + 1] == 0xFE {
+                    let end = (pos + 2 + len).min(data.len());
+                    if let Some(text) = tagged(&data[pos + 4..end]) {
+                        return Ok(Some(String::from_utf8_lossy(&text).to_string()));
+                    }
+                }
+                pos += 2 + len;
+            }
+        } else if data.starts_with(b"\x89PNG\r\n\x1a\n") {
+            // Chunks of length, type, data and CRC
+            let mut pos = 8;
+            while pos + 8 <= data.len() {
+                let len =
+                    u32::from_be_bytes([data[pos], data[pos + 1], data[pos + 2], data[pos + 3]])
+                        as usize;
+                let end = (pos + 8 + len).min(data.len());
+                let chunk = &data[pos + 8..end];
+                if &data[pos + 4..pos + 8] == b"tEXt" {
+                    if let Some(text) = tagged(chunk) {
+                        // tEXt is Latin-1
+                        return Ok(Some(text.iter().map(|b| *b as char).collect()));
+                    }
+                }
+                pos += 12 + len;
+            }
+        }
+        Ok(None)
+    }
+
+    /// Inserts marker segments into JPEG data, after the start of image marker
+    /// and the JFIF APP0 segment, if any. Payloads must fit into a segment.
+    fn insert_jpeg_segments(jpeg: &mut Vec<u8>, marker: u8, payloads: &[Vec<u8>]) {
+        let mut pos = 2;
+        if jpeg.len() > 6 && jpeg[2..4] == [0xFF, 0xE0] {
+            pos += 2 + usize::from(u16::from_be_bytes([jpeg[4], jpeg[5]]));
+        }
+        let mut segments = Vec::new();
+        for payload in payloads {
+            segments.extend_from_slice(&[0xFF, marker]);
+            segments.extend_from_slice(&(payload.len() as u16 + 2).to_be_bytes());
+            segments.extend_from_slice(payload);
+        }
+        jpeg.splice(pos..pos, segments);
+    }
+
+    /// Calculates the size in px of a text drawn with `imageproc::drawing::draw_text_mut`.
+    pub fn text_size(font: &rusttype::Font, scale: rusttype::Scale, text: &str) -> (u32, u32) {
+        let v_metrics = font.v_metrics(scale);
+        let width = font
+            .layout(text, scale, rusttype::point(0.0, v_metrics.ascent))
+            .filter_map(|g| g.pixel_bounding_box())
+            .map(|bb| bb.max.x)
+            .max()
+            .unwrap_or(0);
+        let height = (v_metrics.ascent - v_metrics.descent).ceil();
+        (width.max(0) as u32, height as u32)
+    }
+
+    /// Calculates the largest size with the aspect ratio of `width`/`height`
+    /// that fits into `max_width`/`max_height`. Same as used by `ScaleMode::Keep`.
+    /// Never returns a dimension of zero.
+    pub fn fit_size(width: u32, height: u32, max_width: u32, max_height: u32) -> (u32, u32) {
+        let ratio = u64::from(width) * u64::from(max_height);
+        let max_ratio = u64::from(max_width) * u64::from(height);
+        if max_ratio <= ratio {
+            let h = u64::from(height) * u64::from(max_width) / u64::from(width);
+            (max_width.max(1), (h as u32).max(1))
+        } else {
+            let w = u64::from(width) * u64::from(max_height) / u64::from(height);
+            ((w as u32).max(1), max_height.max(1))
+        }
+    }
+
+    /// Derives a palette of at most `colors` colors from an image, using median cut.
+    pub fn median_cut_palette(image: &DynamicImage, colors: usize) -> Vec<[u8; 3]> {
+        // Sub-sample large images to limit memory and time
+        let step = ((image.width() as u64 * image.height() as u64 / 250_000) as f64)
+            .sqrt()
+            .ceil()
+            .max(1.0) as usize;
+        let mut pixels: Vec<[u8; 3]> = vec![];
+        for y in (0..image.height()).step_by(step) {
+            for x in (0..image.width()).step_by(step) {
+                let p = image.get_pixel(x, y).0;
+                pixels.push([p[0], p[1], p[2]]);
+            }
+        }
+
+        let range = |bucket: &[[u8; 3]], c: usize| -> u8 {
+            let min = bucket.iter().map(|p| p[c]).min().unwrap_or(0);
+            let max = bucket.iter().map(|p| p[c]).max().unwrap_or(0);
+            max - min
+        };
+
+        let mut buckets = vec![pixels];
+        while buckets.len() < colors {
+            // Split the bucket with the largest range in any channel
+            let (index, channel, max_range) = buckets
+                .iter()
+                .enumerate()
+                .flat_map(|(i, b)| (0..3).map(move |c| (i, c, range(b, c))))
+                .max_by_key(|(_, _, r)| *r)
+                .unwrap_or((0, 0, 0));
+            if max_range == 0 {
+                break;
+            }
+            let mut bucket = buckets.swap_remove(index);
+            bucket.sort_unstable_by_key(|p| p[channel]);
+            let upper = bucket.split_off(bucket.len() / 2);
+            buckets.push(bucket);
+            buckets.push(upper);
+        }
+
+        buckets
+            .iter()
+            .filter(|b| !b.is_empty())
+            .map(|b| {
+                let mut sum = [0_u64; 3];
+                for p in b.iter() {
+                    for c in 0..3 {
+                        sum[c] += p[c] as u64;
+                    }
+                }
+                let n = b.len() as f64;
+                [
+                    (sum[0] as f64 / n).round() as u8,
+                    (sum[1] as f64 / n).round() as u8,
+                    (sum[2] as f64 / n).round() as u8,
+                ]
+            })
+            .collect()
+    }
+
+    /// Replaces each pixel's color by the nearest color of the palette. Alpha is preserved.
+    pub fn quantize(image: &DynamicImage, palette: &[[u8; 3]]) -> DynamicImage {
+        let mut result = image.clone();
+        for (x, y, pix) in image.pixels() {
+            let p = pix.0;
+            let nearest = Self::nearest_color(palette, [p[0], p[1], p[2]]);
+            result.put_pixel(x, y, Rgba([nearest[0], nearest[1], nearest[2], p[3]]));
+        }
+        result
+    }
+
+    /// The color of the palette nearest to `color`, or `color` itself for an empty palette.
+    pub fn nearest_color(palette: &[[u8; 3]], color: [u8; 3]) -> [u8; 3] {
+        *palette
+            .iter()
+            .min_by_key(|c| {
+                (0..3)
+                    .map(|i| (c[i] as i32 - color[i] as i32).pow(2))
+                    .sum::<i32>()
+            })
+            .unwrap_or(&color)
+    }
+
+    /// Reduces colors with Floyd-Steinberg error diffusion.
+    /// `quantizer` maps a desired color in range `[0, 255]` to an available color.
+    /// Alpha is preserved, the result is RGB or RGBA.
+    pub fn dither_floyd_steinberg<F>(image: &DynamicImage, quantizer: F) -> DynamicImage
+    where
+        F: Fn([f32; 3]) -> [u8; 3],
+    {
+        let rgba = image.to_rgba();
+        let (width, height) = (rgba.width() as usize, rgba.height() as usize);
+        let mut values: Vec<[f32; 3]> = rgba
+            .pixels()
+            .map(|p| [p.0[0] as f32, p.0[1] as f32, p.0[2] as f32])
+            .collect();
+        let mut result = rgba.clone();
+
+        for y in 0..height {
+            for x in 0..width {
+                let value = values[y * width + x];
+                let color = quantizer([
+                    value[0].clamp(0.0, 255.0),
+                    value[1].clamp(0.0, 255.0),
+                    value[2].clamp(0.0, 255.0),
+                ]);
+                let pixel = result.get_pixel_mut(x as u32, y as u32);
+                pixel.0[..3].copy_from_slice(&color);
+
+                let mut diffuse = |dx: isize, dy: usize, weight: f32| {
+                    let xx = x as isize + dx;
+                    if xx >= 0 && (xx as usize) < width && y + dy < height {
+                        let target = &mut values[(y + dy) * width + xx as usize];
+                        for c in 0..3 {
+                            target[c] += (value[c] - color[c] as f32) * weight;
+                        }
+                    }
+                };
+                diffuse(1, 0, 7.0 / 16.0);
+                diffuse(-1, 1, 3.0 / 16.0);
+                diffuse(0, 1, 5.0 / 16.0);
+                diffuse(1, 1, 1.0 / 16.0);
+            }
+        }
+        Self::rgba_like(result, image)
+    }
+
+    /// Reduces colors with ordered dithering, using a threshold matrix like [`bayer_matrix`](Self::bayer_matrix).
+    /// `quantizer` maps a desired color in range `[0, 255]` to an available color,
+    /// `spread` is the distance between available levels, e.g. `255` for 1 bit per channel.
+    /// Alpha is preserved, the result is RGB or RGBA.
+    pub fn dither_ordered<F>(
+        image: &DynamicImage,
+        quantizer: F,
+        matrix: &[Vec<u32>],
+        spread: f32,
+    ) -> DynamicImage
+    where
+        F: Fn([f32; 3]) -> [u8; 3],
+    {
+        let mut result = image.to_rgba();
+        let rows = matrix.len();
+        let cols = matrix.first().map_or(0, |row| row.len());
+        let count = (rows * cols) as f32;
+
+        for (x, y, pixel) in result.enumerate_pixels_mut() {
+            let offset = if count > 0.0 {
+                let t = matrix[y as usize % rows][x as usize % cols] as f32;
+                ((t + 0.5) / count - 0.5) * spread
+            } else {
+                0.0
+            };
+            let p = pixel.0;
+            let color = quantizer([
+                (p[0] as f32 + offset).clamp(0.0, 255.0),
+                (p[1] as f32 + offset).clamp(0.0, 255.0),
+                (p[2] as f32 + offset).clamp(0.0, 255.0),
+            ]);
+            pixel.0[..3].copy_from_slice(&color);
+        }
+        Self::rgba_like(result, image)
+    }
+
+    /// Creates a Bayer threshold matrix of size `2^order`, with values `0..4^order`.
+    pub fn bayer_matrix(order: u32) -> Vec<Vec<u32>> {
+        let mut matrix = vec![vec![0]];
+        for _ in 0..order {
+            let n = matrix.len();
+            let mut next = vec![vec![0; 2 * n]; 2 * n];
+            for (y, row) in matrix.iter().enumerate() {
+                for (x, v) in row.iter().enumerate() {
+                    next[y][x] = 4 * v;
+                    next[y][x + n] = 4 * v + 2;
+                    next[y + n][x] = 4 * v + 3;
+                    next[y + n][x + n] = 4 * v + 1;
+                }
+            }
+            matrix = next;
+        }
+        matrix
+    }
+
+    /// Converts an RGBA image to RGB, unless the reference image has an alpha channel.
+    fn rgba_like(image: RgbaImage, reference: &DynamicImage) -> DynamicImage {
+        let image = DynamicImage::ImageRgba8(image);
+        if reference.color().has_alpha() {
+            image
+        } else {
+            DynamicImage::ImageRgb8(image.to_rgb())
+        }
+    }
+
+    /// Alpha-composites `color` over a pixel, with the given opacity in `[0, 1]`,
+    /// multiplied by the color's alpha. The pixel's own alpha is preserved.
+    pub fn blend(pixel: &Rgba<u8>, color: &[u8; 4], opacity: f64) -> Rgba<u8> {
+        let alpha = opacity.clamp(0.0, 1.0) * color[3] as f64 / 255.0;
+        let mix =
+            |i: usize| (pixel.0[i] as f64 * (1.0 - alpha) + color[i] as f64 * alpha).round() as u8;
+        Rgba([mix(0), mix(1), mix(2), pixel.0[3]])
+    }
+
+    /// Alpha-composites `color` over a pixel (Porter-Duff "over"), with the given opacity
+    /// in `[0, 1]`, multiplied by the color's alpha. Unlike `blend`, the pixel's alpha
+    /// is taken into account, so that colors blend correctly over transparent pixels.
+    fn over(pixel: &Rgba<u8>, color: &[u8; 4], opacity: f64) -> Rgba<u8> {
+        let src_alpha = opacity.clamp(0.0, 1.0) * color[3] as f64 / 255.0;
+        let dst_alpha = pixel.0[3] as f64 / 255.0 * (1.0 - src_alpha);
+        let alpha = src_alpha + dst_alpha;
+        if alpha <= 0.0 {
+            return Rgba([0, 0, 0, 0]);
+        }
+        let mix = |i: usize| {
+            ((color[i] as f64 * src_alpha + pixel.0[i] as f64 * dst_alpha) / alpha).round() as u8
+        };
+        Rgba([mix(0), mix(1), mix(2), (alpha * 255.0).round() as u8])
+    }
+
+    /// Alpha-composites an image over another at position `x`/`y`, with the given opacity
+    /// in `[0, 1]`, multiplied by the image's alpha. Parts outside the target are clipped.
+    /// Transparent parts of the target are blended correctly.
+    pub fn composite_over<I: GenericImageView<Pixel = Rgba<u8>>>(
+        target: &mut DynamicImage,
+        image: &I,
+        x: i64,
+        y: i64,
+        opacity: f64,
+    ) {
+        for (px, py, pixel) in image.pixels() {
+            let (tx, ty) = (x + px as i64, y + py as i64);
+            if tx < 0 || ty < 0 || tx >= target.width() as i64 || ty >= target.height() as i64 {
+                continue;
+            }
+            let (tx, ty) = (tx as u32, ty as u32);
+            let base = target.get_pixel(tx, ty);
+            target.put_pixel(tx, ty, Self::over(&base, &pixel.0, opacity));
+        }
+    }
+
+    /// Converts an image to RGB or RGBA, keeping the bit depth (8 or 16 bit)
+    /// and the alpha channel, if any. Grayscale and BGR images are converted,
+    /// so that operations can rely on a consistent pixel format.
+    pub fn normalize_color(image: DynamicImage) -> DynamicImage {
+        match image {
+            DynamicImage::ImageRgb8(_)
+            | DynamicImage::ImageRgba8(_)
+            | DynamicImage::ImageRgb16(_)
+            | DynamicImage::ImageRgba16(_) => image,
+            DynamicImage::ImageLuma16(buffer) => DynamicImage::ImageRgb16(buffer.convert()),
+            DynamicImage::ImageLumaA16(buffer) => DynamicImage::ImageRgba16(buffer.convert()),
+            _ if image.color().has_alpha() => DynamicImage::ImageRgba8(image.to_rgba()),
+            _ => DynamicImage::ImageRgb8(image.to_rgb()),
+        }
+    }
+
     pub fn fill_image(image: &mut DynamicImage, color: &[u8; 4]) {
         let col = Rgba(*color);
         for y in 0..image.height() {
@@ -48,6 +616,57 @@ impl ImageUtil {
             }
         }
     }
+    /// Calculates the rectangle `(x, y, width, height)` of an image of size `img_width`/`img_height`
+    /// to keep when cropping to the aspect ratio of `width`/`height`.
+    pub fn crop_rect(
+        img_width: u32,
+        img_height: u32,
+        width: u32,
+        height: u32,
+        smart_crop: &SmartCrop,
+    ) -> (u32, u32, u32, u32) {
+        let (crop_width, crop_height) = if u64::from(img_width) * u64::from(height)
+            > u64::from(width) * u64::from(img_height)
+        {
+            let w = u64::from(img_height) * u64::from(width) / u64::from(height);
+            (w as u32, img_height)
+        } else {
+            let h = u64::from(img_width) * u64::from(height) / u64::from(width);
+            (img_width, h as u32)
+        };
+        let x = smart_crop.offset(img_width, crop_width, false);
+        let y = smart_crop.offset(img_height, crop_height, true);
+        (x, y, crop_width, crop_height)
+    }
+
+    /// Crops an image to the aspect ratio of `width`/`height`,
+    /// choosing the region to keep by the given heuristic.
+    pub fn crop_to_aspect(
+        image: &DynamicImage,
+        width: u32,
+        height: u32,
+        smart_crop: &SmartCrop,
+    ) -> DynamicImage {
+        let (x, y, crop_width, crop_height) =
+            Self::crop_rect(image.width(), image.height(), width, height, smart_crop);
+        image.crop_imm(x, y, crop_width, crop_height)
+    }
+
+    /// The half-size step for `scale_image`, if `incremental` scaling is enabled.
+    /// Default: `box`.
+    pub fn incremental_step(
+        incremental: bool,
+        downscale: Option<&Downscale>,
+    ) -> Option<&Downscale> {
+        if incremental {
+            Some(downscale.unwrap_or(&Downscale::Box))
+        } else {
+            None
+        }
+    }
+
+    /// Scales an image. With `incremental` given, large downscales are done in multiple
+    /// half-size steps of the given kind first.
     pub fn scale_image(
         image: &DynamicImage,
         width: u32,
@@ -55,12 +674,24 @@ impl ImageUtil {
         mode: &ScaleMode,
         filter: &FilterType,
         background: &Color,
-        incremental: bool,
+        incremental: Option<&Downscale>,
     ) -> Result<DynamicImage, Box<dyn Error>> {
-        if incremental && image.width() > 3 * width && image.height() > 3 * height {
-            let mut img = Self::scale_to_half(image)?;
+        if width == 0 || height == 0 {
+            return Err(Box::new(ImageFormatError(format!(
+                "Can't scale to an empty image of {}x{}px",
+                width, height
+            ))));
+        }
+        let half = |img: &DynamicImage| match incremental {
+            Some(Downscale::Filtered) => {
+                Ok(img.resize_exact(img.width() / 2, img.height() / 2, *filter))
+            }
+            _ => Self::scale_to_half(img),
+        };
+        if incremental.is_some() && image.width() > 3 * width && image.height() > 3 * height {
+            let mut img = half(image)?;
             while img.width() > 3 * width && img.height() > 3 * height {
-                img = Self::scale_to_half(&img)?;
+                img = half(&img)?;
             }
             Self::scale_image_simple(&img, width, height, mode, filter, background)
         } else {
@@ -77,7 +708,11 @@ impl ImageUtil {
         background: &Color,
     ) -> Result<DynamicImage, Box<dyn Error>> {
         let result = match mode {
-            ScaleMode::Keep => image.resize(width, height, *filter),
+            ScaleMode::Keep => {
+                // Unlike `resize`, never rounds the short edge of extreme aspect ratios to zero
+                let (w, h) = Self::fit_size(image.width(), image.height(), width, height);
+                image.resize_exact(w, h, *filter)
+            }
             ScaleMode::Stretch => image.resize_exact(width, height, *filter),
             ScaleMode::Crop => image.resize_to_fill(width, height, *filter),
             ScaleMode::Fill => {
@@ -91,13 +726,71 @@ impl ImageUtil {
 
                 let x = (result.width() - temp.width()) / 2;
                 let y = (result.height() - temp.height()) / 2;
-                result.copy_from(&temp, x, y)?;
+                if temp.color().has_alpha() {
+                    // Blend soft edges with the background instead of replacing it
+                    Self::composite_over(&mut result, &temp.to_rgba(), x as i64, y as i64, 1.0);
+                } else {
+                    result.copy_from(&temp, x, y)?;
+                }
+                result
+            }
+            ScaleMode::Blur(sigma) => {
+                let temp = image.resize(width, height, *filter);
+                if temp.width() == width && temp.height() == height {
+                    // No padding required
+                    temp
+                } else {
+                    let mut result = Self::blurred_cover(image, width, height, *sigma);
+                    let x = (result.width() - temp.width()) / 2;
+                    let y = (result.height() - temp.height()) / 2;
+                    result.copy_from(&temp, x, y)?;
+                    result
+                }
+            }
+            ScaleMode::Extend => {
+                let temp = image.resize(width, height, *filter);
+                let mut result = if temp.color().has_alpha() {
+                    DynamicImage::new_rgba8(width, height)
+                } else {
+                    DynamicImage::new_rgb8(width, height)
+                };
+                let x = (width - temp.width()) / 2;
+                let y = (height - temp.height()) / 2;
+                // Each pixel takes the color of the nearest pixel of the scaled image,
+                // so edge rows and columns are stretched into the padding
+                let (max_x, max_y) = (temp.width() - 1, temp.height() - 1);
+                for yy in 0..height {
+                    let sy = yy.saturating_sub(y).min(max_y);
+                    for xx in 0..width {
+                        let sx = xx.saturating_sub(x).min(max_x);
+                        result.put_pixel(xx, yy, temp.get_pixel(sx, sy));
+                    }
+                }
                 result
             }
         };
         Ok(result)
     }
 
+    /// Scales an image to cover `width`/`height` and blurs it.
+    /// For large sigmas, blurs at reduced resolution for speed.
+    pub(crate) fn blurred_cover(
+        image: &DynamicImage,
+        width: u32,
+        height: u32,
+        sigma: f32,
+    ) -> DynamicImage {
+        let factor = if sigma >= 8.0 { 4 } else { 1 };
+        let small = image.resize_to_fill(
+            (width / factor).max(1),
+            (height / factor).max(1),
+            FilterType::Triangle,
+        );
+        small
+            .blur(sigma / factor as f32)
+            .resize_exact(width, height, FilterType::Triangle)
+    }
+
     fn scale_to_half(image: &DynamicImage) -> Result<DynamicImage, Box<dyn Error>> {
         let width = image.width() / 2;
         let height = image.height() / 2;
@@ -137,10 +830,16 @@ impl ImageUtil {
     pub fn save_image(
         image: DynamicImage,
         out_path: &PathBuf,
-        quality: u8,
+        options: &SaveOptions,
     ) -> Result<(), Box<dyn Error>> {
+        let image = match options.max_bytes {
+            Some(max_bytes) if options.max_bytes_downscale => {
+                Self::downscale_to_bytes(image, out_path, max_bytes, options)?
+            }
+            _ => image,
+        };
         if let Some(samples) = image.as_flat_samples_u8() {
-            Self::save_buffer(samples.samples, &samples.layout, out_path, quality)?;
+            Self::save_buffer(samples.samples, &samples.layout, out_path, options)?;
             Ok(())
         } else {
             // TODO: implement for 16 bit images
@@ -155,15 +854,140 @@ impl ImageUtil {
         buffer: &[u8],
         layout: &SampleLayout,
         out_path: &PathBuf,
-        quality: u8,
+        options: &SaveOptions,
     ) -> Result<(), Box<dyn Error>> {
         let abs_path = out_path.absolutize()?;
         let ext = Self::prepare_save(&abs_path)?;
 
+        if !options.overwrite && abs_path.exists() {
+            log::info!("Skipping existing output file {:?}.", abs_path);
+            return Ok(());
+        }
+
         if ext == "jpg" || ext == "jpeg" {
-            let mut file = File::create(&abs_path)?;
-            let mut enc = image::jpeg::JPEGEncoder::new_with_quality(&mut file, quality);
-            enc.encode(
+            let mut data = match options.max_bytes {
+                Some(max_bytes) => {
+                    let max_bytes = max_bytes.saturating_sub(Self::jpeg_metadata_bytes(options));
+                    let (data, quality) = if options.two_pass_jpeg {
+                        let (data, quality, _) =
+                            Self::encode_jpeg_two_pass(buffer, layout, max_bytes, options)?;
+                        (data, quality)
+                    } else {
+                        Self::encode_jpeg_max_bytes(buffer, layout, max_bytes, options)?
+                    };
+                    if data.len() as u64 > max_bytes {
+                        return Err(Box::new(ImageFormatError(format!(
+                            "Unable to save {:?} with at most {} bytes. Smallest size is {} bytes at quality {}.",
+                            abs_path,
+                            max_bytes,
+                            data.len(),
+                            quality
+                        ))));
+                    }
+                    data
+                }
+                None => Self::encode_jpeg(buffer, layout, options.quality, options.jpeg_restart)?,
+            };
+            // Inserted in reverse order, resulting in APP1 (EXIF), APP2 (ICC), COM
+            if let Some(name) = &options.original_name {
+                let mut comment = ORIGINAL_NAME_KEYWORD.to_vec();
+                comment.push(0);
+                comment.extend_from_slice(name.as_bytes());
+                Self::insert_jpeg_comment(&mut data, &comment)?;
+            }
+            if let Some(icc) = &options.icc {
+                Self::insert_icc(&mut data, icc)?;
+            }
+            if let Some(exif) = &options.exif {
+                Self::insert_exif(&mut data, exif)?;
+            }
+            let mut file = if options.overwrite {
+                File::create(&abs_path)?
+            } else {
+                // Fails if the file was created in the meantime, e.g. by another thread.
+                match OpenOptions::new()
+                    .write(true)
+                    .create_new(true)
+                    .open(&abs_path)
+                {
+                    Ok(file) => file,
+                    Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                        log::info!("Skipping existing output file {:?}.", abs_path);
+                        return Ok(());
+                    }
+                    Err(e) => return Err(Box::new(e)),
+                }
+            };
+            file.write_all(&data)?;
+        } else if options.icc.is_some() && ext != "png" && ext != "tif" && ext != "tiff" {
+            return Err(Box::new(ImageFormatError(format!(
+                "ICC profiles can only be embedded into JPEG, PNG and TIFF output, got {:?}.",
+                abs_path
+            ))));
+        } else if options.original_name.is_some() && ext != "png" {
+            return Err(Box::new(ImageFormatError(format!(
+                "File names can only be embedded into JPEG and PNG output, got {:?}.",
+                abs_path
+            ))));
+        } else if options.max_bytes.is_some() {
+            return Err(Box::new(ImageFormatError(format!(
+                "A maximum file size is only supported for JPEG output, got {:?}.",
+                abs_path
+            ))));
+        } else if ext == "png" {
+            let file = BufWriter::new(File::create(&abs_path)?);
+            let mut encoder = png::Encoder::new(file, layout.width, layout.height);
+            encoder.set_color(if layout.width_stride == 4 {
+                png::ColorType::RGBA
+            } else {
+                png::ColorType::RGB
+            });
+            encoder.set_depth(png::BitDepth::Eight);
+            encoder.set_compression(options.png_compression.clone());
+            let mut writer = encoder.write_header()?;
+            if let Some(icc) = &options.icc {
+                let mut chunk = b"ICC Profile\0\0".to_vec();
+                chunk.extend(deflate::deflate_bytes_zlib(icc));
+                writer.write_chunk(*b"iCCP", &chunk)?;
+            }
+            if let Some(name) = &options.original_name {
+                // tEXt is Latin-1, other characters are replaced
+                let mut chunk = ORIGINAL_NAME_KEYWORD.to_vec();
+                chunk.push(0);
+                chunk.extend(name.chars().map(|c| u8::try_from(c).unwrap_or(b'?')));
+                writer.write_chunk(*b"tEXt", &chunk)?;
+            }
+            let mut stream = writer.stream_writer();
+            stream.write_all(buffer)?;
+            stream.finish()?;
+        } else if ext == "webp" {
+            let encoder = webp::Encoder::new(
+                buffer,
+                if layout.width_stride == 4 {
+                    webp::PixelLayout::Rgba
+                } else {
+                    webp::PixelLayout::Rgb
+                },
+                layout.width,
+                layout.height,
+            );
+            // Lossless mode uses the quality as compression effort, with the default of `webp`
+            let quality = if options.lossless {
+                75.0
+            } else {
+                options.quality as f32
+            };
+            let data = encoder
+                .encode_simple(options.lossless, quality)
+                .map_err(|e| {
+                    ImageFormatError(format!("Unable to encode WebP {:?}: {:?}", abs_path, e))
+                })?;
+            std::fs::write(&abs_path, &*data)?;
+        } else if let (Some(icc), true) = (&options.icc, ext == "tif" || ext == "tiff") {
+            Self::save_tiff_icc(buffer, layout, &abs_path, icc)?;
+        } else {
+            image::save_buffer(
+                &abs_path,
                 &buffer,
                 layout.width,
                 layout.height,
@@ -173,11 +997,92 @@ impl ImageUtil {
                     image::ColorType::Rgb8
                 },
             )
-            .expect(&format!("Unable to write output file {:?}.", &abs_path));
+            .expect(&format!("Unable to save output file {:?}", &abs_path));
+        }
+
+        Ok(())
+    }
+
+    /// Size of the metadata segments inserted into JPEG output after encoding,
+    /// to be reserved for `max_bytes`.
+    fn jpeg_metadata_bytes(options: &SaveOptions) -> u64 {
+        // Marker and length, plus the EXIF header
+        let exif_bytes = options
+            .exif
+            .as_ref()
+            .map_or(0, |exif| exif.len() as u64 + 10);
+        // Marker, length, `ICC_PROFILE\0`, sequence number and count per chunk
+        let icc_bytes = options.icc.as_ref().map_or(0, |icc| {
+            icc.len() as u64 + 18 * icc.len().div_ceil(ICC_CHUNK_SIZE) as u64
+        });
+        // Marker, length, keyword and null separator
+        let comment_bytes = options.original_name.as_ref().map_or(0, |name| {
+            name.len() as u64 + ORIGINAL_NAME_KEYWORD.len() as u64 + 5
+        });
+        exif_bytes + icc_bytes + comment_bytes
+    }
+
+    /// Saves an 8 bit RGB or RGBA TIFF with an embedded ICC profile (tag 34675).
+    fn save_tiff_icc(
+        buffer: &[u8],
+        layout: &SampleLayout,
+        path: &PathBuf,
+        icc: &[u8],
+    ) -> Result<(), Box<dyn Error>> {
+        let mut file = BufWriter::new(File::create(path)?);
+        let mut encoder = tiff::encoder::TiffEncoder::new(&mut file)?;
+        if layout.width_stride == 4 {
+            let mut image = encoder
+                .new_image::<tiff::encoder::colortype::RGBA8>(layout.width, layout.height)?;
+            image
+                .encoder()
+                .write_tag(tiff::decoder::ifd::Tag::Unknown(34675), icc);
+            Self::write_tiff_strips(&mut image, buffer)?;
+            image.finish()?;
         } else {
-            image::save_buffer(
-                &abs_path,
-                &buffer,
+            let mut image =
+                encoder.new_image::<tiff::encoder::colortype::RGB8>(layout.width, layout.height)?;
+            image
+                .encoder()
+                .write_tag(tiff::decoder::ifd::Tag::Unknown(34675), icc);
+            Self::write_tiff_strips(&mut image, buffer)?;
+            image.finish()?;
+        }
+        file.flush()?;
+        Ok(())
+    }
+
+    fn write_tiff_strips<W, C>(
+        image: &mut tiff::encoder::ImageEncoder<W, C>,
+        buffer: &[u8],
+    ) -> Result<(), Box<dyn Error>>
+    where
+        W: Write + io::Seek,
+        C: tiff::encoder::colortype::ColorType<Inner = u8>,
+    {
+        let mut idx = 0;
+        while image.next_strip_sample_count() > 0 {
+            let count = image.next_strip_sample_count() as usize;
+            image.write_strip(&buffer[idx..idx + count])?;
+            idx += count;
+        }
+        Ok(())
+    }
+
+    /// Encodes a JPEG into memory.
+    fn encode_jpeg(
+        buffer: &[u8],
+        layout: &SampleLayout,
+        quality: u8,
+        restart: Option<u16>,
+    ) -> Result<Vec<u8>, Box<dyn Error>> {
+        let mut data = Vec::new();
+        if let Some(rows) = restart {
+            Self::save_jpeg_restart(buffer, layout, &mut data, quality, rows)?;
+        } else {
+            let mut enc = image::jpeg::JPEGEncoder::new_with_quality(&mut data, quality);
+            enc.encode(
+                buffer,
                 layout.width,
                 layout.height,
                 if layout.width_stride == 4 {
@@ -185,10 +1090,228 @@ impl ImageUtil {
                 } else {
                     image::ColorType::Rgb8
                 },
-            )
-            .expect(&format!("Unable to save output file {:?}", &abs_path));
+            )?;
+        }
+        Ok(data)
+    }
+
+    /// Encodes a JPEG with the highest quality between `min_quality` and `quality`
+    /// that results in at most `max_bytes`, using binary search.
+    /// If even `min_quality` is too large, returns the encoding at `min_quality`.
+    fn encode_jpeg_max_bytes(
+        buffer: &[u8],
+        layout: &SampleLayout,
+        max_bytes: u64,
+        options: &SaveOptions,
+    ) -> Result<(Vec<u8>, u8), Box<dyn Error>> {
+        let restart = options.jpeg_restart;
+        let mut low = options.min_quality.clamp(1, 100).min(options.quality);
+        let mut high = options.quality;
+
+        let data = Self::encode_jpeg(buffer, layout, high, restart)?;
+        if data.len() as u64 <= max_bytes {
+            return Ok((data, high));
+        }
+        let mut best = (Self::encode_jpeg(buffer, layout, low, restart)?, low);
+        if best.0.len() as u64 > max_bytes {
+            return Ok(best);
+        }
+        // Invariant: `low` fits, `high` does not
+        while high - low > 1 {
+            let mid = low + (high - low) / 2;
+            let data = Self::encode_jpeg(buffer, layout, mid, restart)?;
+            if data.len() as u64 <= max_bytes {
+                best = (data, mid);
+                low = mid;
+            } else {
+                high = mid;
+            }
+        }
+        Ok(best)
+    }
+
+    /// Encodes a JPEG for `max_bytes` in two passes. The quality is estimated by a binary search
+    /// on a probe subsampled to 1/16 of the pixels, with the byte budget reduced accordingly.
+    /// Subsampling without filtering preserves the detail that determines the file size.
+    /// Then, the full image is encoded with optimized Huffman tables, lowering the quality
+    /// in further full encodes only if the estimate was too high.
+    /// Returns the encoding, its quality and the number of full encodes.
+    fn encode_jpeg_two_pass(
+        buffer: &[u8],
+        layout: &SampleLayout,
+        max_bytes: u64,
+        options: &SaveOptions,
+    ) -> Result<(Vec<u8>, u8, usize), Box<dyn Error>> {
+        const PROBE_FACTOR: u32 = 4;
+        const QUALITY_STEP: u8 = 3;
+        let restart = options.jpeg_restart;
+        let min_quality = options.min_quality.clamp(1, 100).min(options.quality);
+        let channels = layout.width_stride as u8;
+
+        // Fixed overhead of headers and tables, independent of the image size
+        let tiny = SampleLayout::row_major_packed(channels, 8, 8);
+        let overhead =
+            Self::encode_jpeg_optimized(&vec![0; 64 * channels as usize], &tiny, 50, None)?.len()
+                as u64;
+
+        // First pass: estimate the quality on the probe
+        let (width, height) = (layout.width, layout.height);
+        let (probe_width, probe_height) = (
+            (width / PROBE_FACTOR).max(8).min(width),
+            (height / PROBE_FACTOR).max(8).min(height),
+        );
+        let probe = Self::buffer_to_image(buffer, layout)?.resize_exact(
+            probe_width,
+            probe_height,
+            FilterType::Nearest,
+        );
+        let probe = if channels == 4 {
+            probe.to_rgba().into_raw()
+        } else {
+            probe.to_rgb().into_raw()
+        };
+        let probe_layout = SampleLayout::row_major_packed(channels, probe_width, probe_height);
+        let ratio = (probe_width as f64 * probe_height as f64) / (width as f64 * height as f64);
+        let probe_budget = (max_bytes.saturating_sub(overhead) as f64 * ratio) as u64 + overhead;
+        let probe_fits = |quality: u8| -> Result<bool, Box<dyn Error>> {
+            let data = Self::encode_jpeg_optimized(&probe, &probe_layout, quality, None)?;
+            Ok(data.len() as u64 <= probe_budget)
+        };
+        let mut quality = if probe_fits(options.quality)? {
+            options.quality
+        } else {
+            // Invariant: `low` fits or is the minimum, `high` does not fit
+            let (mut low, mut high) = (min_quality, options.quality);
+            while high - low > 1 {
+                let mid = low + (high - low) / 2;
+                if probe_fits(mid)? {
+                    low = mid;
+                } else {
+                    high = mid;
+                }
+            }
+            low
+        };
+
+        // Second pass: full encodes, only repeated if the estimate was too high
+        let mut encodes = 0;
+        loop {
+            let data = Self::encode_jpeg_optimized(buffer, layout, quality, restart)?;
+            encodes += 1;
+            if data.len() as u64 <= max_bytes || quality <= min_quality {
+                return Ok((data, quality, encodes));
+            }
+            quality = quality.saturating_sub(QUALITY_STEP).max(min_quality);
         }
+    }
+
+    /// Creates an image from an 8 bit RGB or RGBA buffer.
+    fn buffer_to_image(
+        buffer: &[u8],
+        layout: &SampleLayout,
+    ) -> Result<DynamicImage, Box<dyn Error>> {
+        let (width, height) = (layout.width, layout.height);
+        let image = if layout.width_stride == 4 {
+            RgbaImage::from_raw(width, height, buffer.to_vec()).map(DynamicImage::ImageRgba8)
+        } else {
+            image::RgbImage::from_raw(width, height, buffer.to_vec()).map(DynamicImage::ImageRgb8)
+        };
+        image.ok_or_else(|| {
+            Box::new(ImageFormatError("Image buffer too small".to_string())) as Box<dyn Error>
+        })
+    }
+
+    /// Downscales an image until its JPEG encoding at minimum quality fits into `max_bytes`.
+    /// Returns the image unchanged for other formats or if it already fits.
+    fn downscale_to_bytes(
+        image: DynamicImage,
+        out_path: &PathBuf,
+        max_bytes: u64,
+        options: &SaveOptions,
+    ) -> Result<DynamicImage, Box<dyn Error>> {
+        let ext = PathUtil::extension(out_path);
+        if ext.as_deref() != Some("jpg") && ext.as_deref() != Some("jpeg") {
+            return Ok(image);
+        }
+        let mut image = image;
+        loop {
+            let size = match image.as_flat_samples_u8() {
+                Some(samples) => {
+                    Self::encode_jpeg(
+                        samples.samples,
+                        &samples.layout,
+                        options.min_quality.clamp(1, 100).min(options.quality),
+                        options.jpeg_restart,
+                    )?
+                    .len() as u64
+                        + Self::jpeg_metadata_bytes(options)
+                }
+                None => return Ok(image),
+            };
+            if size <= max_bytes || image.width() <= 8 || image.height() <= 8 {
+                return Ok(image);
+            }
+            // File size scales roughly with the pixel count
+            let factor = ((max_bytes as f64 / size as f64).sqrt() * 0.95).min(0.95);
+            let width = ((image.width() as f64 * factor).round() as u32).max(8);
+            let height = ((image.height() as f64 * factor).round() as u32).max(8);
+            image = image.resize_exact(width, height, FilterType::Triangle);
+        }
+    }
+
+    /// Encodes a JPEG with restart markers every `rows` MCU rows.
+    /// Uses no chroma subsampling, i.e. MCUs of 8x8 px, like the default JPEG encoder.
+    fn save_jpeg_restart<W: Write>(
+        buffer: &[u8],
+        layout: &SampleLayout,
+        writer: W,
+        quality: u8,
+        rows: u16,
+    ) -> Result<(), Box<dyn Error>> {
+        Self::save_jpeg_custom(buffer, layout, writer, quality, Some(rows), false)
+    }
 
+    /// Encodes a JPEG into memory with optimized Huffman tables,
+    /// and optional restart markers every `restart` MCU rows.
+    fn encode_jpeg_optimized(
+        buffer: &[u8],
+        layout: &SampleLayout,
+        quality: u8,
+        restart: Option<u16>,
+    ) -> Result<Vec<u8>, Box<dyn Error>> {
+        let mut data = Vec::new();
+        Self::save_jpeg_custom(buffer, layout, &mut data, quality, restart, true)?;
+        Ok(data)
+    }
+
+    /// Encodes a JPEG with optional restart markers every `rows` MCU rows,
+    /// and optionally optimized Huffman tables.
+    /// Uses no chroma subsampling, i.e. MCUs of 8x8 px, like the default JPEG encoder.
+    fn save_jpeg_custom<W: Write>(
+        buffer: &[u8],
+        layout: &SampleLayout,
+        writer: W,
+        quality: u8,
+        rows: Option<u16>,
+        optimize_huffman: bool,
+    ) -> Result<(), Box<dyn Error>> {
+        let width = u16::try_from(layout.width)?;
+        let height = u16::try_from(layout.height)?;
+
+        let mut enc = jpeg_encoder::Encoder::new(writer, quality);
+        enc.set_sampling_factor(jpeg_encoder::SamplingFactor::F_1_1);
+        enc.set_optimized_huffman_tables(optimize_huffman);
+        if let Some(rows) = rows {
+            let mcus_per_row = u32::from(width).div_ceil(8);
+            let interval = (u32::from(rows) * mcus_per_row).min(u32::from(u16::MAX)) as u16;
+            enc.set_restart_interval(interval);
+        }
+        let color = if layout.width_stride == 4 {
+            jpeg_encoder::ColorType::Rgba
+        } else {
+            jpeg_encoder::ColorType::Rgb
+        };
+        enc.encode(buffer, width, height, color)?;
         Ok(())
     }
 
@@ -203,7 +1326,7 @@ impl ImageUtil {
         )))?;
 
         if !parent.is_dir() {
-            std::fs::create_dir(parent)?;
+            std::fs::create_dir_all(parent)?;
         }
 
         Ok(ext)
@@ -246,10 +1369,39 @@ impl fmt::Display for ImageFormatError {
 #[cfg(test)]
 mod test {
     use crate::units::color::Color;
-    use crate::units::ScaleMode;
-    use crate::util::ImageUtil;
+    use crate::units::IccProfile;
+    use crate::units::{Downscale, ScaleMode};
+    use crate::util::test_dir;
+    use crate::util::{ImageUtil, SaveOptions};
     use image::imageops::FilterType;
-    use image::{DynamicImage, GenericImageView};
+    use image::{DynamicImage, GenericImage, GenericImageView, Rgba};
+    use std::sync::Arc;
+
+    #[test]
+    fn dither_floyd_steinberg() {
+        let mut image = DynamicImage::new_luma8(64, 64);
+        ImageUtil::fill_image(&mut image, &[128, 128, 128, 255]);
+        let one_bit = |c: [f32; 3]| if c[0] < 128.0 { [0; 3] } else { [255; 3] };
+        let result = ImageUtil::dither_floyd_steinberg(&image, one_bit);
+
+        let pixels: Vec<_> = result.pixels().map(|(_, _, p)| p.0[0]).collect();
+        assert!(pixels.iter().all(|v| *v == 0 || *v == 255));
+        let mean = pixels.iter().map(|v| *v as f64).sum::<f64>() / pixels.len() as f64;
+        assert!((mean - 128.0).abs() < 2.0);
+    }
+
+    #[test]
+    fn dither_ordered() {
+        assert_eq!(ImageUtil::bayer_matrix(1), vec![vec![0, 2], vec![3, 1]]);
+
+        let mut image = DynamicImage::new_rgb8(16, 16);
+        ImageUtil::fill_image(&mut image, &[64, 64, 64, 255]);
+        let one_bit = |c: [f32; 3]| if c[0] < 128.0 { [0; 3] } else { [255; 3] };
+        let result = ImageUtil::dither_ordered(&image, one_bit, &ImageUtil::bayer_matrix(2), 255.0);
+
+        let white = result.pixels().filter(|(_, _, p)| p.0[0] == 255).count();
+        assert_eq!(white, 16 * 16 / 4);
+    }
 
     #[test]
     fn fill_image() {
@@ -260,6 +1412,28 @@ mod test {
         assert_eq!(image.get_pixel(0, 0).0, col);
     }
 
+    #[test]
+    fn fit_size() {
+        assert_eq!(ImageUtil::fit_size(600, 400, 300, 300), (300, 200));
+        assert_eq!(ImageUtil::fit_size(400, 600, 300, 300), (200, 300));
+    }
+
+    #[test]
+    fn median_cut_palette() {
+        let mut image = DynamicImage::new_rgb8(32, 32);
+        ImageUtil::fill_image(&mut image, &[255, 0, 0, 255]);
+        for y in 0..16 {
+            for x in 0..32 {
+                image.put_pixel(x, y, Rgba([0, 0, 255, 255]));
+            }
+        }
+        let palette = ImageUtil::median_cut_palette(&image, 4);
+
+        assert_eq!(palette.len(), 2);
+        assert!(palette.contains(&[255, 0, 0]));
+        assert!(palette.contains(&[0, 0, 255]));
+    }
+
     #[test]
     fn scale_image() {
         let image = DynamicImage::new_rgb8(256, 256);
@@ -270,7 +1444,7 @@ mod test {
             &ScaleMode::Keep,
             &FilterType::CatmullRom,
             &Color::new(255, 255, 255, 255),
-            false,
+            None,
         )
         .unwrap();
 
@@ -278,6 +1452,92 @@ mod test {
         assert_eq!(scaled.height(), 32);
     }
 
+    #[test]
+    fn scale_image_blur() {
+        let mut image = DynamicImage::new_rgb8(300, 200);
+        ImageUtil::fill_image(&mut image, &[200, 0, 0, 255]);
+        let scale = |image: &DynamicImage, width, height| {
+            ImageUtil::scale_image(
+                image,
+                width,
+                height,
+                &ScaleMode::Blur(4.0),
+                &FilterType::CatmullRom,
+                &Color::new(255, 255, 255, 255),
+                None,
+            )
+            .unwrap()
+        };
+
+        let scaled = scale(&image, 100, 100);
+        assert_eq!((scaled.width(), scaled.height()), (100, 100));
+        // Padding is filled with the blurred image, not with the background color
+        assert_eq!(scaled.get_pixel(50, 5).0, [200, 0, 0, 255]);
+
+        let scaled = scale(&image, 150, 100);
+        assert_eq!((scaled.width(), scaled.height()), (150, 100));
+    }
+
+    #[test]
+    fn scale_image_fill_alpha() {
+        let mut image = DynamicImage::new_rgba8(4, 2);
+        ImageUtil::fill_image(&mut image, &[255, 0, 0, 255]);
+        image.put_pixel(3, 0, Rgba([255, 0, 0, 128]));
+        image.put_pixel(3, 1, Rgba([255, 0, 0, 0]));
+        let scaled = ImageUtil::scale_image(
+            &image,
+            4,
+            4,
+            &ScaleMode::Fill,
+            &FilterType::Nearest,
+            &Color::new(0, 0, 255, 128),
+            None,
+        )
+        .unwrap();
+
+        assert_eq!((scaled.width(), scaled.height()), (4, 4));
+        // Padding and fully transparent pixels keep the background
+        assert_eq!(scaled.get_pixel(0, 0).0, [0, 0, 255, 128]);
+        assert_eq!(scaled.get_pixel(3, 2).0, [0, 0, 255, 128]);
+        // Opaque pixels replace the background
+        assert_eq!(scaled.get_pixel(0, 1).0, [255, 0, 0, 255]);
+        // Semi-transparent pixels blend with it
+        assert_eq!(scaled.get_pixel(3, 1).0, [170, 0, 85, 192]);
+    }
+
+    #[test]
+    fn scale_image_extend() {
+        let mut image = DynamicImage::new_rgb8(200, 100);
+        ImageUtil::fill_image(&mut image, &[0, 0, 255, 255]);
+        for y in 0..100 {
+            image.put_pixel(0, y, Rgba([255, 0, 0, 255]));
+            image.put_pixel(199, y, Rgba([0, 255, 0, 255]));
+        }
+        for x in 1..199 {
+            image.put_pixel(x, 0, Rgba([255, 255, 0, 255]));
+        }
+        let scaled = ImageUtil::scale_image(
+            &image,
+            200,
+            200,
+            &ScaleMode::Extend,
+            &FilterType::Nearest,
+            &Color::new(255, 255, 255, 255),
+            None,
+        )
+        .unwrap();
+
+        assert_eq!((scaled.width(), scaled.height()), (200, 200));
+        // Corners repeat the nearest corner of the source
+        assert_eq!(scaled.get_pixel(0, 0).0, [255, 0, 0, 255]);
+        assert_eq!(scaled.get_pixel(199, 0).0, [0, 255, 0, 255]);
+        assert_eq!(scaled.get_pixel(0, 199).0, [255, 0, 0, 255]);
+        assert_eq!(scaled.get_pixel(199, 199).0, [0, 255, 0, 255]);
+        // Top padding repeats the top edge, bottom padding the bottom edge
+        assert_eq!(scaled.get_pixel(100, 10).0, [255, 255, 0, 255]);
+        assert_eq!(scaled.get_pixel(100, 190).0, [0, 0, 255, 255]);
+    }
+
     #[test]
     fn scale_image_inc() {
         let image = DynamicImage::new_rgb8(256, 256);
@@ -288,7 +1548,7 @@ mod test {
             &ScaleMode::Keep,
             &FilterType::CatmullRom,
             &Color::new(255, 255, 255, 255),
-            true,
+            ImageUtil::incremental_step(true, None),
         )
         .unwrap();
 
@@ -296,6 +1556,267 @@ mod test {
         assert_eq!(scaled.height(), 32);
     }
 
+    #[test]
+    fn scale_image_inc_filtered() {
+        let image = DynamicImage::new_rgb8(301, 203);
+        for downscale in &[Downscale::Box, Downscale::Filtered] {
+            let scaled = ImageUtil::scale_image(
+                &image,
+                30,
+                20,
+                &ScaleMode::Stretch,
+                &FilterType::CatmullRom,
+                &Color::new(255, 255, 255, 255),
+                Some(downscale),
+            )
+            .unwrap();
+
+            assert_eq!(scaled.width(), 30);
+            assert_eq!(scaled.height(), 20);
+        }
+        assert!(ImageUtil::incremental_step(false, Some(&Downscale::Filtered)).is_none());
+    }
+
+    #[test]
+    fn save_png() {
+        let dir = test_dir("png");
+        let path = dir.join("image.png");
+        let mut image = DynamicImage::new_rgb8(32, 16);
+        ImageUtil::fill_image(&mut image, &[255, 0, 0, 255]);
+        let options = SaveOptions {
+            png_compression: png::Compression::Best,
+            ..SaveOptions::default()
+        };
+        ImageUtil::save_image(image, &path, &options).unwrap();
+
+        let reopened = image::open(&path).unwrap();
+
+        assert_eq!(reopened.width(), 32);
+        assert_eq!(reopened.get_pixel(5, 5).0, [255, 0, 0, 255]);
+    }
+
+    #[test]
+    fn save_jpeg_restart() {
+        let dir = test_dir("jpeg-restart");
+        let plain = dir.join("plain.jpg");
+        let restart = dir.join("restart.jpg");
+        let mut image = DynamicImage::new_rgb8(64, 64);
+        ImageUtil::fill_image(&mut image, &[255, 0, 0, 255]);
+        let options = SaveOptions {
+            jpeg_restart: Some(2),
+            ..SaveOptions::default()
+        };
+        ImageUtil::save_image(image.clone(), &plain, &SaveOptions::default()).unwrap();
+        ImageUtil::save_image(image, &restart, &options).unwrap();
+
+        let plain_data = std::fs::read(&plain).unwrap();
+        let restart_data = std::fs::read(&restart).unwrap();
+        let reopened = image::open(&restart).unwrap();
+
+        let has_marker = |data: &[u8], marker: u8| data.windows(2).any(|w| w == [0xFF, marker]);
+        // DRI segment, and RST0 to RST7 markers
+        assert!(has_marker(&restart_data, 0xDD));
+        assert!((0xD0..=0xD7).any(|m| has_marker(&restart_data, m)));
+        assert!(!has_marker(&plain_data, 0xDD));
+        assert_eq!(reopened.width(), 64);
+    }
+
+    #[test]
+    fn save_icc() {
+        let dir = test_dir("icc");
+        let icc = IccProfile::Srgb.data().unwrap();
+        let options = SaveOptions {
+            icc: Some(Arc::new(icc.clone())),
+            ..SaveOptions::default()
+        };
+        let image = DynamicImage::new_rgb8(16, 16);
+        let (jpg, png, webp) = (dir.join("a.jpg"), dir.join("a.png"), dir.join("a.webp"));
+        let tif = dir.join("a.tif");
+        ImageUtil::save_image(image.clone(), &jpg, &options).unwrap();
+        ImageUtil::save_image(image.clone(), &png, &options).unwrap();
+        ImageUtil::save_image(image.clone(), &tif, &options).unwrap();
+        let webp_result = ImageUtil::save_image(image, &webp, &options);
+
+        let jpg_data = std::fs::read(&jpg).unwrap();
+        let png_data = std::fs::read(&png).unwrap();
+        let tif_data = std::fs::read(&tif).unwrap();
+        let reopened = (image::open(&jpg), image::open(&png), image::open(&tif));
+
+        let find =
+            |data: &[u8], pattern: &[u8]| data.windows(pattern.len()).position(|w| w == pattern);
+        let icc_pos = find(&jpg_data, b"ICC_PROFILE\0\x01\x01").unwrap();
+        assert_eq!(&jpg_data[icc_pos + 14..icc_pos + 14 + icc.len()], &icc[..]);
+        assert!(find(&png_data, b"iCCPICC Profile").is_some());
+        // ICC tag 34675 in the IFD, and the profile data
+        assert!(find(&tif_data, &34675u16.to_ne_bytes()).is_some());
+        assert!(find(&tif_data, &icc).is_some());
+        assert!(reopened.0.is_ok() && reopened.1.is_ok());
+        assert_eq!(reopened.2.unwrap().dimensions(), (16, 16));
+        assert!(webp_result.is_err());
+    }
+
+    #[test]
+    fn save_original_name() {
+        let dir = test_dir("original-name");
+        let options = SaveOptions {
+            original_name: Some("IMG_0042.CR2".to_string()),
+            icc: Some(Arc::new(IccProfile::Srgb.data().unwrap())),
+            ..SaveOptions::default()
+        };
+        let image = DynamicImage::new_rgb8(16, 16);
+        let (jpg, png, bmp) = (dir.join("a.jpg"), dir.join("a.png"), dir.join("a.bmp"));
+        ImageUtil::save_image(image.clone(), &jpg, &options).unwrap();
+        ImageUtil::save_image(image.clone(), &png, &options).unwrap();
+        let bmp_result = ImageUtil::save_image(
+            image.clone(),
+            &bmp,
+            &SaveOptions {
+                icc: None,
+                ..options.clone()
+            },
+        );
+        let plain = dir.join("plain.png");
+        ImageUtil::save_image(image, &plain, &SaveOptions::default()).unwrap();
+
+        let names = (
+            ImageUtil::original_name(&jpg).unwrap(),
+            ImageUtil::original_name(&png).unwrap(),
+            ImageUtil::original_name(&plain).unwrap(),
+        );
+        let reopened = (image::open(&jpg), image::open(&png));
+
+        assert_eq!(names.0.as_deref(), Some("IMG_0042.CR2"));
+        assert_eq!(names.1.as_deref(), Some("IMG_0042.CR2"));
+        assert_eq!(names.2, None);
+        assert!(reopened.0.is_ok() && reopened.1.is_ok());
+        assert!(bmp_result.is_err());
+    }
+
+    #[test]
+    fn original_name_segments() {
+        let dir = test_dir("original-name-segments");
+        let jpeg = |segments: &[u8]| {
+            let path = dir.join("in.jpg");
+            let mut data = vec![0xFF, 0xD8];
+            data.extend_from_slice(segments);
+            data.extend_from_slice(&[0xFF, 0xD9]);
+            std::fs::write(&path, data).unwrap();
+            ImageUtil::original_name(&path).unwrap()
+        };
+
+        // Untagged comment, e.g. from a camera, followed by a tagged one
+        let mut segments = vec![0xFF, 0xFE, 0x00, 0x08];
+        segments.extend_from_slice(b"Camera");
+        segments.extend_from_slice(&[0xFF, 0xFE, 0x00, 0x14]);
+        segments.extend_from_slice(b"OriginalName\0a.jpg");
+        assert_eq!(jpeg(&segments[..10]), None);
+        assert_eq!(jpeg(&segments).as_deref(), Some("a.jpg"));
+        // Segment lengths below 2 are invalid
+        assert_eq!(jpeg(&[0xFF, 0xFE, 0x00, 0x01]), None);
+        assert_eq!(jpeg(&[0xFF, 0xFE, 0x00, 0x00]), None);
+    }
+
+    #[test]
+    fn save_jpeg_max_bytes() {
+        let dir = test_dir("jpeg-max-bytes");
+        let path = dir.join("out.jpg");
+        let mut image = DynamicImage::new_rgb8(200, 200);
+        for (x, y, _) in image.clone().pixels() {
+            let v = ((x * 7919 + y * 104729) % 251) as u8;
+            image.put_pixel(x, y, Rgba([v, v.wrapping_mul(3), 255 - v, 255]));
+        }
+        let options = SaveOptions {
+            max_bytes: Some(15_000),
+            min_quality: 10,
+            ..SaveOptions::default()
+        };
+        ImageUtil::save_image(image.clone(), &path, &options).unwrap();
+        let size = std::fs::metadata(&path).unwrap().len();
+
+        let impossible = SaveOptions {
+            max_bytes: Some(1000),
+            ..options.clone()
+        };
+        let error = ImageUtil::save_image(image.clone(), &path, &impossible).unwrap_err();
+
+        let downscale = SaveOptions {
+            max_bytes_downscale: true,
+            ..impossible
+        };
+        ImageUtil::save_image(image, &path, &downscale).unwrap();
+        let downscaled_size = std::fs::metadata(&path).unwrap().len();
+        let downscaled = image::open(&path).unwrap();
+
+        assert!(size <= 15_000);
+        assert!(size > 10_000);
+        assert!(error.to_string().contains("Smallest size is"));
+        assert!(downscaled_size <= 1000);
+        assert!(downscaled.width() < 200);
+    }
+
+    #[test]
+    fn save_jpeg_two_pass() {
+        let mut image = DynamicImage::new_rgb8(400, 300);
+        for (x, y, _) in image.clone().pixels() {
+            let v = ((x * 7919 + y * 104729) % 251) as u8;
+            let w = ((x / 4 + y / 8) % 64) as u8 * 3;
+            image.put_pixel(x, y, Rgba([v / 4 + w, w, 255 - w, 255]));
+        }
+        let options = SaveOptions {
+            max_bytes: Some(60_000),
+            min_quality: 10,
+            two_pass_jpeg: true,
+            ..SaveOptions::default()
+        };
+        let samples = image.as_flat_samples_u8().unwrap();
+        let (data, quality, encodes) =
+            ImageUtil::encode_jpeg_two_pass(samples.samples, &samples.layout, 60_000, &options)
+                .unwrap();
+        let (_, search_quality) =
+            ImageUtil::encode_jpeg_max_bytes(samples.samples, &samples.layout, 60_000, &options)
+                .unwrap();
+
+        assert!(data.len() <= 60_000);
+        // The binary search of `encode_jpeg_max_bytes` requires about 8 full encodes
+        assert!(encodes <= 2);
+        assert!(quality > 10 && quality < 95);
+        assert!(quality + 10 >= search_quality);
+
+        let dir = test_dir("jpeg-two-pass");
+        let path = dir.join("out.jpg");
+        ImageUtil::save_image(image, &path, &options).unwrap();
+        let size = std::fs::metadata(&path).unwrap().len();
+        let reopened = image::open(&path);
+
+        assert!(size <= 60_000);
+        assert!(reopened.is_ok());
+    }
+
+    #[test]
+    fn save_webp() {
+        let dir = test_dir("webp");
+        let path = dir.join("image.webp");
+        let mut image = DynamicImage::new_rgb8(32, 16);
+        ImageUtil::fill_image(&mut image, &[255, 0, 0, 255]);
+        ImageUtil::save_image(image.clone(), &path, &SaveOptions::default()).unwrap();
+
+        let reopened = image::open(&path).unwrap();
+
+        assert_eq!(reopened.width(), 32);
+        assert_eq!(reopened.height(), 16);
+
+        // An invalid quality is an error, not a panic of the encoder
+        let invalid = SaveOptions {
+            quality: 150,
+            ..SaveOptions::default()
+        };
+        let error = ImageUtil::save_image(image, &dir.join("invalid.webp"), &invalid);
+        assert!(error
+            .unwrap_err()
+            .to_string()
+            .contains("Unable to encode WebP"));
+    }
+
     #[test]
     fn scale_to_half() {
         let image = DynamicImage::new_rgb8(64, 64);
@@ -0,0 +1,45 @@
+//! Leveled logging to stderr, via the `log` crate
+
+use log::LevelFilter;
+use std::io::Write;
+
+/// Environment variable to override the log level, like `PPREP_LOG=debug`.
+pub const LOG_ENV: &str = "PPREP_LOG";
+
+/// Logging setup.
+pub struct LogUtil {}
+
+impl LogUtil {
+    /// Level for the number of `--verbose` flags: `info` by default, `debug` for `-vv`.
+    pub fn level(verbosity: u8) -> LevelFilter {
+        if verbosity >= 2 {
+            LevelFilter::Debug
+        } else {
+            LevelFilter::Info
+        }
+    }
+
+    /// Installs a logger writing to stderr, with the level from the number of `--verbose` flags,
+    /// unless overridden by the environment variable `PPREP_LOG`.
+    /// Safe to use from parallel processing.
+    pub fn init(verbosity: u8) {
+        env_logger::Builder::new()
+            .filter_level(Self::level(verbosity))
+            .parse_env(LOG_ENV)
+            .format(|buf, record| writeln!(buf, "[{}] {}", record.level(), record.args()))
+            .init();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::util::LogUtil;
+    use log::LevelFilter;
+
+    #[test]
+    fn levels() {
+        assert_eq!(LogUtil::level(0), LevelFilter::Info);
+        assert_eq!(LogUtil::level(1), LevelFilter::Info);
+        assert_eq!(LogUtil::level(3), LevelFilter::Debug);
+    }
+}
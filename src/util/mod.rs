@@ -1,8 +1,21 @@
 //! Utilities
 
+mod cast;
+mod chart;
 mod image;
+mod log;
 mod path;
+#[cfg(test)]
+mod testing;
 
+pub use self::cast::CastUtil;
+pub use self::chart::ChartUtil;
 pub use self::image::ImageFormatError;
 pub use self::image::ImageUtil;
+pub use self::image::SaveOptions;
+pub use self::log::LogUtil;
+pub use self::path::ExtMapping;
+pub use self::path::InputFile;
 pub use self::path::PathUtil;
+#[cfg(test)]
+pub use self::testing::test_dir;
@@ -1,6 +1,72 @@
 //! Path and file utilities.
 
+use crate::units::SortOrder;
+use crate::ParseStructError;
+use path_absolutize::Absolutize;
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::io::BufRead;
 use std::path::PathBuf;
+use std::str::FromStr;
+
+/// Mapping from an input file extension to an output file extension.
+///
+/// Can be parsed from strings of format `from=to`, e.g. `tiff=jpg`.
+#[derive(Debug, PartialEq, Clone)]
+pub struct ExtMapping {
+    from: String,
+    to: String,
+}
+
+impl ExtMapping {
+    /// Input extension, lowercase.
+    pub fn from(&self) -> &str {
+        &self.from
+    }
+    /// Output extension.
+    pub fn to(&self) -> &str {
+        &self.to
+    }
+}
+
+impl FromStr for ExtMapping {
+    type Err = ParseStructError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<_> = s.split('=').collect();
+        if parts.len() != 2 || parts[0].is_empty() || parts[1].is_empty() {
+            return Err(ParseStructError(format!(
+                "Unexpected extension mapping format in {}, expects `from=to`",
+                s
+            )));
+        }
+        Ok(ExtMapping {
+            from: parts[0].trim_start_matches('.').to_lowercase(),
+            to: parts[1].trim_start_matches('.').to_string(),
+        })
+    }
+}
+
+/// Input file, with the input root it was found in.
+#[derive(Debug, PartialEq, Clone)]
+pub struct InputFile {
+    /// Path of the file.
+    pub path: PathBuf,
+    /// Root directory of the input pattern the file was found by.
+    pub root: PathBuf,
+}
+
+impl InputFile {
+    /// Creates an input file with the given root.
+    pub fn new(path: PathBuf, root: PathBuf) -> Self {
+        InputFile { path, root }
+    }
+    /// Creates an input file with its parent directory as root.
+    pub fn from_path(path: PathBuf) -> Self {
+        let root = path.parent().map(|p| p.to_path_buf()).unwrap_or_default();
+        InputFile { path, root }
+    }
+}
 
 /// Path and file utilities.
 pub struct PathUtil {}
@@ -21,12 +87,161 @@ impl PathUtil {
         path.file_name()
             .and_then(|st| st.to_str().and_then(|st| Some(st.to_string())))
     }
-    /// Get the file's base name from a path (name without extension).
-    pub fn out_path(in_path: &PathBuf, out_pattern: &str) -> Option<PathBuf> {
-        let name = PathUtil::stem(in_path);
-        match name {
-            Some(name) => Some(PathBuf::from(out_pattern.replace("*", &name))),
-            None => None,
+    /// Creates an output path from an input path and an output pattern.
+    ///
+    /// Replaces `*` and `{stem}` by the input file's base name, and `{ext}` by its extension.
+    /// Further tokens in the form `{name}` are replaced by the entries of `tokens`.
+    /// Unknown tokens are left unchanged.
+    ///
+    /// If the input extension is contained in `ext_map`, the output extension is replaced accordingly.
+    ///
+    /// If `root` is given, the directory of the input file relative to `root`
+    /// is inserted before the output file name. If the input path is not below `root` as given,
+    /// e.g. for a relative path and an absolute root, both are compared as absolute paths.
+    pub fn out_path(
+        in_path: &PathBuf,
+        out_pattern: &str,
+        tokens: &HashMap<&str, String>,
+        ext_map: &[ExtMapping],
+        root: Option<&PathBuf>,
+    ) -> Option<PathBuf> {
+        let name = PathUtil::stem(in_path)?;
+        let ext = PathUtil::extension(in_path).unwrap_or_default();
+        let mut path = out_pattern
+            .replace("*", &name)
+            .replace("{stem}", &name)
+            .replace("{ext}", &ext);
+        for (key, value) in tokens.iter() {
+            path = path.replace(&format!("{{{}}}", key), value);
+        }
+        let mut path = PathBuf::from(path);
+        if let Some(root) = root {
+            let parent = in_path.parent()?;
+            let relative = match parent.strip_prefix(root) {
+                Ok(rel) => rel.to_path_buf(),
+                Err(_) => {
+                    let parent = parent.absolutize().ok()?;
+                    let root = root.absolutize().ok()?;
+                    parent.strip_prefix(&root).ok()?.to_path_buf()
+                }
+            };
+            let name = path.file_name()?.to_owned();
+            path = path.with_file_name(relative).join(name);
+        }
+        if let Some(mapping) = ext_map.iter().find(|m| m.from == ext) {
+            path.set_extension(&mapping.to);
+        }
+        Some(path)
+    }
+    /// Root directory of a glob pattern: all leading path components without wildcards.
+    /// For patterns without wildcards, this is the parent directory.
+    pub fn pattern_root(pattern: &str) -> PathBuf {
+        let path = PathBuf::from(pattern);
+        let mut root = PathBuf::new();
+        let mut components = path.components().peekable();
+        while let Some(comp) = components.next() {
+            let is_pattern = comp
+                .as_os_str()
+                .to_str()
+                .is_some_and(|c| c.contains(|ch| "*?[{".contains(ch)));
+            if is_pattern || components.peek().is_none() {
+                break;
+            }
+            root.push(comp);
+        }
+        root
+    }
+    /// Longest common directory of the given files, compared by path components.
+    pub fn common_root(paths: &[PathBuf]) -> PathBuf {
+        let mut iter = paths.iter().filter_map(|p| p.parent());
+        let first = match iter.next() {
+            Some(p) => p,
+            None => return PathBuf::new(),
+        };
+        let mut common: Vec<_> = first.components().collect();
+        for path in iter {
+            let len = common
+                .iter()
+                .zip(path.components())
+                .take_while(|(a, b)| *a == b)
+                .count();
+            common.truncate(len);
+        }
+        common.iter().collect()
+    }
+    /// Expands brace groups in a glob pattern, which are not supported by `glob`.
+    /// E.g. `*.{jpg,png}` results in `*.jpg` and `*.png`. Handles multiple and nested groups.
+    /// Braces without a comma are kept as they are.
+    pub fn expand_braces(pattern: &str) -> Vec<String> {
+        Self::expand_braces_from(pattern, 0)
+    }
+    fn expand_braces_from(pattern: &str, start: usize) -> Vec<String> {
+        let bytes = pattern.as_bytes();
+        let mut open = start;
+        while open < bytes.len() {
+            if bytes[open] != b'{' {
+                open += 1;
+                continue;
+            }
+            // Find the matching closing brace, and commas on the top level of the group
+            let mut depth = 0;
+            let mut commas = vec![];
+            let mut close = None;
+            for (i, b) in bytes.iter().enumerate().skip(open) {
+                match b {
+                    b'{' => depth += 1,
+                    b'}' => {
+                        depth -= 1;
+                        if depth == 0 {
+                            close = Some(i);
+                            break;
+                        }
+                    }
+                    b',' if depth == 1 => commas.push(i),
+                    _ => {}
+                }
+            }
+            let close = match close {
+                Some(close) => close,
+                None => break,
+            };
+            if commas.is_empty() {
+                open += 1;
+                continue;
+            }
+            let (prefix, suffix) = (&pattern[..open], &pattern[close + 1..]);
+            let mut bounds = vec![open];
+            bounds.extend(commas);
+            bounds.push(close);
+            return bounds
+                .windows(2)
+                .flat_map(|w| {
+                    let expanded = format!("{}{}{}", prefix, &pattern[w[0] + 1..w[1]], suffix);
+                    // Continue at the alternative, as it may contain nested groups
+                    Self::expand_braces_from(&expanded, open)
+                })
+                .collect();
+        }
+        vec![pattern.to_string()]
+    }
+    /// Removes files found multiple times, e.g. by overlapping patterns, keeping the first.
+    /// Paths are compared in canonical form, so `a/../b.jpg` and `b.jpg` are the same file.
+    pub fn dedup_files(files: &mut Vec<InputFile>) {
+        let mut seen = HashSet::new();
+        files.retain(|f| seen.insert(f.path.canonicalize().unwrap_or_else(|_| f.path.clone())));
+    }
+    /// Sorts input files. Ties, and files without metadata, are ordered by path.
+    pub fn sort_files(files: &mut [InputFile], order: &SortOrder) {
+        match order {
+            SortOrder::Name => files.sort_by(|a, b| a.path.cmp(&b.path)),
+            SortOrder::Mtime => files.sort_by_cached_key(|f| {
+                let time = std::fs::metadata(&f.path).and_then(|m| m.modified()).ok();
+                (time, f.path.clone())
+            }),
+            SortOrder::Size => files.sort_by_cached_key(|f| {
+                let size = std::fs::metadata(&f.path).map(|m| m.len()).ok();
+                (size, f.path.clone())
+            }),
         }
     }
     /// List all files for a pattern
@@ -47,6 +262,133 @@ impl PathUtil {
             .collect();
         Ok(vec)
     }
+    /// Reads a newline-separated list of file paths.
+    /// Blank lines are skipped, and surrounding whitespace is trimmed.
+    /// Paths that do not exist are skipped with a warning.
+    pub fn read_list<R: BufRead>(reader: R) -> Result<Vec<PathBuf>, std::io::Error> {
+        let mut vec = vec![];
+        for line in reader.lines() {
+            let line = line?;
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            let path = PathBuf::from(line);
+            if path.is_file() {
+                vec.push(path);
+            } else {
+                log::info!("Skipping input {:?}, file does not exist.", path);
+            }
+        }
+        Ok(vec)
+    }
+    /// Reads a CSV mapping of `source,target` file paths, one pair per line.
+    /// Blank lines and a leading `source,target` header are skipped.
+    /// Fields may be quoted, to contain commas. Quotes inside quoted fields are escaped as `""`.
+    /// Source paths are made absolute, for matching against input files.
+    pub fn read_path_map<R: BufRead>(
+        reader: R,
+    ) -> Result<HashMap<PathBuf, PathBuf>, Box<dyn Error>> {
+        let mut map = HashMap::new();
+        for (index, line) in reader.lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let fields = Self::csv_fields(&line).unwrap_or_default();
+            if fields.len() != 2 || fields[0].is_empty() || fields[1].is_empty() {
+                return Err(Box::new(ParseStructError(format!(
+                    "Unexpected mapping format in line {}: `{}`, expects `source,target`",
+                    index + 1,
+                    line
+                ))));
+            }
+            if index == 0
+                && fields[0].eq_ignore_ascii_case("source")
+                && fields[1].eq_ignore_ascii_case("target")
+            {
+                continue;
+            }
+            let source = PathBuf::from(&fields[0]).absolutize()?.to_path_buf();
+            map.insert(source, PathBuf::from(&fields[1]));
+        }
+        Ok(map)
+    }
+    /// Splits a CSV line into fields. Whitespace around fields is ignored.
+    /// Returns `None` for unterminated quotes, or text after the closing quote of a field.
+    fn csv_fields(line: &str) -> Option<Vec<String>> {
+        let mut fields = vec![];
+        let mut chars = line.chars().peekable();
+        loop {
+            while chars.peek().is_some_and(|c| c.is_whitespace()) {
+                chars.next();
+            }
+            let mut field = String::new();
+            if chars.peek() == Some(&'"') {
+                chars.next();
+                loop {
+                    match chars.next()? {
+                        '"' if chars.peek() == Some(&'"') => {
+                            chars.next();
+                            field.push('"');
+                        }
+                        '"' => break,
+                        c => field.push(c),
+                    }
+                }
+                while chars.peek().is_some_and(|c| c.is_whitespace()) {
+                    chars.next();
+                }
+                if chars.peek().is_some_and(|c| *c != ',') {
+                    return None;
+                }
+            } else {
+                while let Some(c) = chars.peek().filter(|c| **c != ',') {
+                    field.push(*c);
+                    chars.next();
+                }
+                field = field.trim_end().to_string();
+            }
+            fields.push(field);
+            if chars.next().is_none() {
+                return Some(fields);
+            }
+        }
+    }
+    /// Returns the modification time of a file as `YYYY-MM-DD HH:MM:SS`, in UTC.
+    pub fn modified_time(path: &PathBuf) -> Result<String, std::io::Error> {
+        let modified = std::fs::metadata(path)?.modified()?;
+        let secs = modified
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        Ok(Self::format_utc(secs))
+    }
+    /// Formats seconds since the Unix epoch as `YYYY-MM-DD HH:MM:SS`.
+    fn format_utc(secs: u64) -> String {
+        let days = (secs / 86_400) as i64;
+        let rem = secs % 86_400;
+
+        // Civil date from days since epoch, after H. Hinnant
+        let z = days + 719_468;
+        let era = z.div_euclid(146_097);
+        let doe = z.rem_euclid(146_097);
+        let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = doy - (153 * mp + 2) / 5 + 1;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 };
+        let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+        format!(
+            "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+            year,
+            month,
+            day,
+            rem / 3600,
+            (rem % 3600) / 60,
+            rem % 60
+        )
+    }
     /// List all files and directories for a pattern
     pub fn list_all(pattern: &str) -> Result<Vec<PathBuf>, glob::PatternError> {
         let paths: glob::Paths = glob::glob(pattern)?;
@@ -57,7 +399,11 @@ impl PathUtil {
 
 #[cfg(test)]
 mod test {
-    use crate::util::PathUtil;
+    use crate::units::SortOrder;
+    use crate::util::test_dir;
+    use crate::util::{ExtMapping, InputFile, PathUtil};
+    use path_absolutize::Absolutize;
+    use std::collections::HashMap;
     use std::path::PathBuf;
 
     #[test]
@@ -68,6 +414,90 @@ mod test {
         assert_eq!(ext.unwrap(), "jpg")
     }
 
+    #[test]
+    fn out_path() {
+        let path = PathBuf::from("a/b/abc.JPG");
+        let mut tokens = HashMap::new();
+        tokens.insert("n", "0012".to_string());
+        tokens.insert("w", "600".to_string());
+
+        let out = PathUtil::out_path(&path, "out/*-{n}_{w}.{ext}", &tokens, &[], None).unwrap();
+        assert_eq!(out, PathBuf::from("out/abc-0012_600.jpg"));
+
+        let out = PathUtil::out_path(&path, "out/{stem}-{x}.png", &tokens, &[], None).unwrap();
+        assert_eq!(out, PathBuf::from("out/abc-{x}.png"));
+    }
+
+    #[test]
+    fn out_path_ext_map() {
+        let tokens = HashMap::new();
+        let ext_map: Vec<ExtMapping> = vec!["tiff=jpg".parse().unwrap()];
+
+        let tiff = PathBuf::from("a/abc.tiff");
+        let out = PathUtil::out_path(&tiff, "out/*.png", &tokens, &ext_map, None).unwrap();
+        assert_eq!(out, PathBuf::from("out/abc.jpg"));
+
+        let png = PathBuf::from("a/abc.png");
+        let out = PathUtil::out_path(&png, "out/*.png", &tokens, &ext_map, None).unwrap();
+        assert_eq!(out, PathBuf::from("out/abc.png"));
+    }
+
+    #[test]
+    fn out_path_roots() {
+        let tokens = HashMap::new();
+        let files = [
+            InputFile::new(
+                PathBuf::from("a/x/1.jpg"),
+                PathUtil::pattern_root("a/**/*.jpg"),
+            ),
+            InputFile::new(
+                PathBuf::from("b/y/z/2.jpg"),
+                PathUtil::pattern_root("b/*/*/*.jpg"),
+            ),
+        ];
+        let out: Vec<_> = files
+            .iter()
+            .map(|f| PathUtil::out_path(&f.path, "out/*.png", &tokens, &[], Some(&f.root)).unwrap())
+            .collect();
+
+        assert_eq!(
+            out,
+            vec![PathBuf::from("out/x/1.png"), PathBuf::from("out/y/z/2.png")]
+        );
+    }
+
+    #[test]
+    fn out_path_absolute_root() {
+        let tokens = HashMap::new();
+        let root = PathBuf::from("a").absolutize().unwrap().to_path_buf();
+        let path = PathBuf::from("a/x/1.jpg");
+        let out = PathUtil::out_path(&path, "out/*.png", &tokens, &[], Some(&root)).unwrap();
+
+        assert_eq!(out, PathBuf::from("out/x/1.png"));
+    }
+
+    #[test]
+    fn common_root() {
+        let paths = [
+            PathBuf::from("photos/2020/a/1.jpg"),
+            PathBuf::from("photos/2020/b/c/2.jpg"),
+            PathBuf::from("photos/2020/3.jpg"),
+        ];
+        assert_eq!(PathUtil::common_root(&paths), PathBuf::from("photos/2020"));
+        assert_eq!(
+            PathUtil::common_root(&[PathBuf::from("a/1.jpg"), PathBuf::from("b/2.jpg")]),
+            PathBuf::new()
+        );
+    }
+
+    #[test]
+    fn pattern_root() {
+        assert_eq!(PathUtil::pattern_root("a/b/*.jpg"), PathBuf::from("a/b"));
+        assert_eq!(PathUtil::pattern_root("a/**/c/*.jpg"), PathBuf::from("a"));
+        assert_eq!(PathUtil::pattern_root("a/b/c.jpg"), PathBuf::from("a/b"));
+        assert_eq!(PathUtil::pattern_root("*.jpg"), PathBuf::new());
+    }
+
     #[test]
     fn list_files() {
         let pattern = "./*";
@@ -76,6 +506,112 @@ mod test {
         assert!(list.contains(&PathBuf::from("Cargo.toml")));
         assert!(!list.contains(&PathBuf::from("src")));
     }
+    #[test]
+    fn expand_braces() {
+        assert_eq!(PathUtil::expand_braces("a/*.jpg"), vec!["a/*.jpg"]);
+        assert_eq!(
+            PathUtil::expand_braces("*.{jpg,png}"),
+            vec!["*.jpg", "*.png"]
+        );
+        assert_eq!(
+            PathUtil::expand_braces("{a,b}/*.{jpg,j{pe,p}g}"),
+            vec!["a/*.jpg", "a/*.jpeg", "a/*.jpg", "b/*.jpg", "b/*.jpeg", "b/*.jpg"]
+        );
+        assert_eq!(PathUtil::expand_braces("{a}/{b,c"), vec!["{a}/{b,c"]);
+        assert_eq!(PathUtil::pattern_root("a/{b,c}/*.jpg"), PathBuf::from("a"));
+
+        let dir = test_dir("braces");
+        for name in &["a.jpg", "b.png", "c.gif"] {
+            std::fs::write(dir.join(name), "").unwrap();
+        }
+        let pattern = dir.join("*.{jpg,png}");
+        let mut list: Vec<_> = PathUtil::expand_braces(pattern.to_str().unwrap())
+            .iter()
+            .flat_map(|p| PathUtil::list_files(p).unwrap())
+            .collect();
+        list.sort();
+
+        assert_eq!(list, vec![dir.join("a.jpg"), dir.join("b.png")]);
+    }
+    #[test]
+    fn dedup_files() {
+        let dir = test_dir("dedup");
+        std::fs::create_dir_all(dir.join("sub")).unwrap();
+        for name in &["a.jpg", "b.jpg", "b.png"] {
+            std::fs::write(dir.join(name), []).unwrap();
+        }
+        // Overlapping patterns
+        let patterns = [dir.join("*.jpg"), dir.join("b.*"), dir.join("sub/../a.jpg")];
+        let mut files: Vec<_> = patterns
+            .iter()
+            .flat_map(|p| PathUtil::list_files(p.to_str().unwrap()).unwrap())
+            .map(InputFile::from_path)
+            .collect();
+        assert_eq!(files.len(), 5);
+        PathUtil::dedup_files(&mut files);
+
+        let names: Vec<_> = files.iter().map(|f| f.path.clone()).collect();
+        assert_eq!(
+            names,
+            vec![dir.join("a.jpg"), dir.join("b.jpg"), dir.join("b.png")]
+        );
+    }
+    #[test]
+    fn sort_files() {
+        let dir = test_dir("sort");
+        for (name, size) in &[("b.jpg", 3), ("c.jpg", 1), ("a.jpg", 2)] {
+            std::fs::write(dir.join(name), vec![0; *size]).unwrap();
+        }
+        let mut files: Vec<_> = ["b.jpg", "c.jpg", "a.jpg"]
+            .iter()
+            .map(|name| InputFile::from_path(dir.join(name)))
+            .collect();
+        let names = |files: &[InputFile]| -> Vec<String> {
+            files
+                .iter()
+                .map(|f| PathUtil::name(&f.path).unwrap())
+                .collect()
+        };
+
+        PathUtil::sort_files(&mut files, &SortOrder::Size);
+        assert_eq!(names(&files), vec!["c.jpg", "a.jpg", "b.jpg"]);
+        PathUtil::sort_files(&mut files, &SortOrder::Name);
+        assert_eq!(names(&files), vec!["a.jpg", "b.jpg", "c.jpg"]);
+    }
+    #[test]
+    fn read_list() {
+        let list = "Cargo.toml\n\n  src/lib.rs  \nnot/existing.jpg\n";
+        let list = PathUtil::read_list(list.as_bytes()).unwrap();
+
+        assert_eq!(
+            list,
+            vec![PathBuf::from("Cargo.toml"), PathBuf::from("src/lib.rs")]
+        );
+    }
+    #[test]
+    fn read_path_map() {
+        let csv = "source,target\n\
+            a.jpg, out/a.jpg\n\
+            \"b, 2.jpg\",\"out/b, \"\"2\"\".jpg\"\n";
+        let map = PathUtil::read_path_map(csv.as_bytes()).unwrap();
+        let target =
+            |source: &str| map.get(&PathBuf::from(source).absolutize().unwrap().to_path_buf());
+
+        assert_eq!(map.len(), 2);
+        assert_eq!(target("a.jpg"), Some(&PathBuf::from("out/a.jpg")));
+        assert_eq!(target("b, 2.jpg"), Some(&PathBuf::from("out/b, \"2\".jpg")));
+
+        assert!(PathUtil::read_path_map("a.jpg,b.jpg,c.jpg".as_bytes()).is_err());
+        assert!(PathUtil::read_path_map("\"a.jpg,b.jpg".as_bytes()).is_err());
+        assert!(PathUtil::read_path_map("\"a\".jpg,b.jpg".as_bytes()).is_err());
+    }
+    #[test]
+    fn format_utc() {
+        assert_eq!(PathUtil::format_utc(0), "1970-01-01 00:00:00");
+        assert_eq!(PathUtil::format_utc(951_827_696), "2000-02-29 12:34:56");
+        assert_eq!(PathUtil::format_utc(1_704_067_199), "2023-12-31 23:59:59");
+    }
+
     #[test]
     fn list_dirs() {
         let pattern = "./*";
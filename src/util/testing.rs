@@ -0,0 +1,38 @@
+//! Test fixtures.
+
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+
+/// A temporary directory for a test, removed when dropped, even if the test panics.
+pub struct TestDir {
+    path: PathBuf,
+}
+
+impl Deref for TestDir {
+    type Target = PathBuf;
+
+    fn deref(&self) -> &PathBuf {
+        &self.path
+    }
+}
+
+impl AsRef<Path> for TestDir {
+    fn as_ref(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for TestDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.path);
+    }
+}
+
+/// Creates an empty temporary directory `print-prep-test-<name>`.
+/// Leftovers of an earlier, aborted run are removed.
+pub fn test_dir(name: &str) -> TestDir {
+    let path = std::env::temp_dir().join(format!("print-prep-test-{}", name));
+    let _ = std::fs::remove_dir_all(&path);
+    std::fs::create_dir_all(&path).unwrap();
+    TestDir { path }
+}